@@ -0,0 +1,493 @@
+//! 纯色 / 渐变背景抠图
+//!
+//! 沿用文字去除里的背景分析：取图片四周的像素判断是纯色还是渐变背景，
+//! 估计每个像素位置的背景色，按颜色距离生成透明通道，
+//! 再做边缘羽化和去溢色，输出 RGBA PNG。
+
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use image::{DynamicImage, GrayImage, ImageFormat, Luma, RgbaImage};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::io::Cursor;
+
+use crate::image_ops::parse_color;
+use crate::image_utils::decode_base64_image;
+use crate::storage::{save_image, GenerationParams, ImageInfo, ImageType, InputImageInfo};
+use crate::text_removal::adaptive_inpainter::{
+    analyze_background, gaussian_blur_mask, BackgroundStrategy, Sample,
+};
+
+const DEFAULT_TOLERANCE: f32 = 40.0;
+const DEFAULT_SOFTNESS: f32 = 30.0;
+const DEFAULT_FEATHER: u32 = 1;
+
+// 前端调用的参数
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RemoveBackgroundParams {
+    pub image_data: Option<String>,    // base64，与 image_path 二选一
+    pub image_path: Option<String>,    // 本地图片路径，同时作为来源记录
+    pub key_color: Option<String>,     // 指定抠除的颜色（如绿幕 "#00ff00"），默认自动识别
+    pub tolerance: Option<f32>,        // 与背景色的距离在此之内完全透明（RGB 欧氏距离）
+    pub softness: Option<f32>,         // 过渡带宽度，距离超过 tolerance + softness 完全不透明
+    pub feather: Option<u32>,          // 边缘羽化半径（像素）
+    pub despill: Option<bool>,         // 去除边缘残留的背景色，默认开启
+    pub contiguous: Option<bool>,      // 只抠除与边缘相连的背景，默认开启
+    pub save_to_storage: Option<bool>, // 默认只返回结果不落盘
+    pub canvas_id: Option<String>,
+    pub node_id: Option<String>,
+}
+
+// 返回结果
+#[derive(Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RemoveBackgroundResult {
+    pub success: bool,
+    pub image_data: Option<String>, // RGBA PNG（base64）
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    pub background: Option<String>, // "solid" | "gradient" | "key"
+    pub background_color: Option<String>, // 识别到的背景色 "#rrggbb"
+    pub saved_image: Option<ImageInfo>,
+    pub error: Option<String>,
+}
+
+impl RemoveBackgroundResult {
+    fn failure(error: impl Into<String>) -> Self {
+        Self {
+            success: false,
+            error: Some(error.into()),
+            ..Self::default()
+        }
+    }
+}
+
+// 抠图选项
+#[derive(Debug, Clone)]
+pub struct KeyOptions {
+    pub key_color: Option<[u8; 3]>,
+    pub tolerance: f32,
+    pub softness: f32,
+    pub feather: u32,
+    pub despill: bool,
+    pub contiguous: bool,
+}
+
+impl Default for KeyOptions {
+    fn default() -> Self {
+        Self {
+            key_color: None,
+            tolerance: DEFAULT_TOLERANCE,
+            softness: DEFAULT_SOFTNESS,
+            feather: DEFAULT_FEATHER,
+            despill: true,
+            contiguous: true,
+        }
+    }
+}
+
+// 背景模型：纯色，或按行列插值的渐变
+enum BackgroundModel {
+    Solid([f32; 3]),
+    Gradient {
+        top: Vec<[f32; 3]>,
+        bottom: Vec<[f32; 3]>,
+        left: Vec<[f32; 3]>,
+        right: Vec<[f32; 3]>,
+    },
+}
+
+impl BackgroundModel {
+    fn color_at(&self, x: u32, y: u32, width: u32, height: u32) -> [f32; 3] {
+        match self {
+            BackgroundModel::Solid(color) => *color,
+            BackgroundModel::Gradient {
+                top,
+                bottom,
+                left,
+                right,
+            } => {
+                let ty = y as f32 / (height.max(2) - 1) as f32;
+                let tx = x as f32 / (width.max(2) - 1) as f32;
+                let (x, y) = (x as usize, y as usize);
+                let mut color = [0f32; 3];
+                for (channel, value) in color.iter_mut().enumerate() {
+                    let vertical = top[x][channel] * (1.0 - ty) + bottom[x][channel] * ty;
+                    let horizontal = left[y][channel] * (1.0 - tx) + right[y][channel] * tx;
+                    *value = (vertical + horizontal) / 2.0;
+                }
+                color
+            }
+        }
+    }
+}
+
+// 抠图结果
+pub struct KeyedImage {
+    pub image: RgbaImage,
+    pub background: &'static str,
+    pub background_color: [u8; 3],
+}
+
+fn border_width(width: u32, height: u32) -> u32 {
+    (width.min(height) / 50).clamp(1, 8)
+}
+
+fn collect_image_border(image: &RgbaImage, border: u32) -> Vec<Sample> {
+    let (width, height) = image.dimensions();
+    let mut samples = Vec::new();
+    for (x, y, pixel) in image.enumerate_pixels() {
+        let on_border = x < border || y < border || x >= width - border || y >= height - border;
+        // 已经透明的像素不参与背景判断
+        if on_border && pixel[3] > 0 {
+            samples.push(Sample {
+                color: [pixel[0], pixel[1], pixel[2]],
+            });
+        }
+    }
+    samples
+}
+
+/// 沿一条边取边框条带的平均色，并做平滑
+fn edge_profile(
+    image: &RgbaImage,
+    length: u32,
+    border: u32,
+    pixel_at: impl Fn(u32, u32) -> (u32, u32),
+) -> Vec<[f32; 3]> {
+    let raw: Vec<[f32; 3]> = (0..length)
+        .map(|i| {
+            let mut sum = [0f32; 3];
+            for depth in 0..border {
+                let (x, y) = pixel_at(i, depth);
+                let pixel = image.get_pixel(x, y);
+                for channel in 0..3 {
+                    sum[channel] += pixel[channel] as f32;
+                }
+            }
+            sum.map(|v| v / border as f32)
+        })
+        .collect();
+
+    let radius = (length / 20).max(1) as i64;
+    (0..length as i64)
+        .map(|i| {
+            let (start, end) = ((i - radius).max(0), (i + radius).min(length as i64 - 1));
+            let mut sum = [0f32; 3];
+            for color in &raw[start as usize..=end as usize] {
+                for channel in 0..3 {
+                    sum[channel] += color[channel];
+                }
+            }
+            sum.map(|v| v / (end - start + 1) as f32)
+        })
+        .collect()
+}
+
+fn color_distance(a: [f32; 3], b: [f32; 3]) -> f32 {
+    ((a[0] - b[0]).powi(2) + (a[1] - b[1]).powi(2) + (a[2] - b[2]).powi(2)).sqrt()
+}
+
+/// 从半透明像素中去掉背景色的贡献：observed = a * fg + (1 - a) * bg
+fn despill_pixel(observed: [f32; 3], background: [f32; 3], alpha: f32) -> [u8; 3] {
+    if alpha <= 0.05 {
+        return observed.map(|v| v.round().clamp(0.0, 255.0) as u8);
+    }
+    let mut color = [0u8; 3];
+    for channel in 0..3 {
+        let fg = (observed[channel] - (1.0 - alpha) * background[channel]) / alpha;
+        color[channel] = fg.round().clamp(0.0, 255.0) as u8;
+    }
+    color
+}
+
+/// 把背景抠成透明
+pub fn key_out_background(source: &DynamicImage, options: &KeyOptions) -> KeyedImage {
+    let mut image = source.to_rgba8();
+    let (width, height) = image.dimensions();
+    let border = border_width(width, height);
+
+    let samples = collect_image_border(&image, border);
+    let (model, background, background_color) = match options.key_color {
+        Some(color) => (
+            BackgroundModel::Solid(color.map(|v| v as f32)),
+            "key",
+            color,
+        ),
+        None => match analyze_background(&samples) {
+            BackgroundStrategy::Solid { color } => (
+                BackgroundModel::Solid(color.map(|v| v as f32)),
+                "solid",
+                color,
+            ),
+            BackgroundStrategy::Gradient { fallback_color } => {
+                let (w, h) = (width - 1, height - 1);
+                let model = BackgroundModel::Gradient {
+                    top: edge_profile(&image, width, border, |i, d| (i, d)),
+                    bottom: edge_profile(&image, width, border, |i, d| (i, h - d)),
+                    left: edge_profile(&image, height, border, |i, d| (d, i)),
+                    right: edge_profile(&image, height, border, |i, d| (w - d, i)),
+                };
+                (model, "gradient", fallback_color)
+            }
+        },
+    };
+
+    // 按与背景色的距离计算透明度
+    let softness = options.softness.max(0.001);
+    let index = |x: u32, y: u32| (y * width + x) as usize;
+    let mut alpha = vec![1f32; (width * height) as usize];
+    for (x, y, pixel) in image.enumerate_pixels() {
+        let observed = [pixel[0] as f32, pixel[1] as f32, pixel[2] as f32];
+        let distance = color_distance(observed, model.color_at(x, y, width, height));
+        alpha[index(x, y)] = ((distance - options.tolerance) / softness).clamp(0.0, 1.0);
+    }
+
+    // 只保留与边缘连通的背景区域，主体内部同色部分不被抠掉
+    if options.contiguous {
+        let mut reached = vec![false; alpha.len()];
+        let mut queue = VecDeque::new();
+        for y in 0..height {
+            for x in 0..width {
+                let on_edge = x == 0 || y == 0 || x == width - 1 || y == height - 1;
+                if on_edge && alpha[index(x, y)] < 1.0 {
+                    reached[index(x, y)] = true;
+                    queue.push_back((x, y));
+                }
+            }
+        }
+        while let Some((x, y)) = queue.pop_front() {
+            let neighbors = [
+                (x.wrapping_sub(1), y),
+                (x + 1, y),
+                (x, y.wrapping_sub(1)),
+                (x, y + 1),
+            ];
+            for (nx, ny) in neighbors {
+                if nx >= width || ny >= height {
+                    continue;
+                }
+                let i = index(nx, ny);
+                if !reached[i] && alpha[i] < 1.0 {
+                    reached[i] = true;
+                    queue.push_back((nx, ny));
+                }
+            }
+        }
+        for (value, reached) in alpha.iter_mut().zip(reached) {
+            if !reached {
+                *value = 1.0;
+            }
+        }
+    }
+
+    if options.feather > 0 {
+        let mask = GrayImage::from_fn(width, height, |x, y| {
+            Luma([(alpha[index(x, y)] * 255.0).round() as u8])
+        });
+        alpha = gaussian_blur_mask(&mask, options.feather);
+    }
+
+    for (x, y, pixel) in image.enumerate_pixels_mut() {
+        let a = alpha[index(x, y)];
+        if options.despill && a < 1.0 {
+            let observed = [pixel[0] as f32, pixel[1] as f32, pixel[2] as f32];
+            let color = despill_pixel(observed, model.color_at(x, y, width, height), a);
+            pixel[0] = color[0];
+            pixel[1] = color[1];
+            pixel[2] = color[2];
+        }
+        // 保留原图已有的透明度
+        pixel[3] = (pixel[3] as f32 * a).round() as u8;
+    }
+
+    KeyedImage {
+        image,
+        background,
+        background_color,
+    }
+}
+
+// Tauri 命令：去除纯色或渐变背景
+#[tauri::command]
+pub async fn remove_background(
+    app: tauri::AppHandle,
+    params: RemoveBackgroundParams,
+) -> RemoveBackgroundResult {
+    println!("[Rust] remove_background called");
+
+    let source_bytes = match (&params.image_data, &params.image_path) {
+        (Some(data), _) if !data.trim().is_empty() => decode_base64_image(data),
+        (_, Some(path)) => std::fs::read(path).map_err(|e| format!("读取图片失败: {}", e)),
+        _ => Err("需要提供图片数据或路径".to_string()),
+    };
+    let source_bytes = match source_bytes {
+        Ok(bytes) => bytes,
+        Err(e) => return RemoveBackgroundResult::failure(e),
+    };
+    let key_color = match params.key_color.as_deref().filter(|c| !c.trim().is_empty()) {
+        Some(color) => match parse_color(color) {
+            Ok(color) => Some([color[0], color[1], color[2]]),
+            Err(e) => return RemoveBackgroundResult::failure(e),
+        },
+        None => None,
+    };
+    let options = KeyOptions {
+        key_color,
+        tolerance: params.tolerance.unwrap_or(DEFAULT_TOLERANCE).max(0.0),
+        softness: params.softness.unwrap_or(DEFAULT_SOFTNESS).max(0.0),
+        feather: params.feather.unwrap_or(DEFAULT_FEATHER).min(32),
+        despill: params.despill.unwrap_or(true),
+        contiguous: params.contiguous.unwrap_or(true),
+    };
+
+    let processed = tokio::task::spawn_blocking(move || {
+        let image =
+            image::load_from_memory(&source_bytes).map_err(|e| format!("图片解码失败: {}", e))?;
+        let keyed = key_out_background(&image, &options);
+        let mut output = Vec::new();
+        keyed
+            .image
+            .write_to(&mut Cursor::new(&mut output), ImageFormat::Png)
+            .map_err(|e| format!("PNG 编码失败: {}", e))?;
+        Ok::<_, String>((output, keyed))
+    })
+    .await
+    .map_err(|e| format!("抠图任务失败: {}", e))
+    .and_then(|result| result);
+    let (bytes, keyed) = match processed {
+        Ok(processed) => processed,
+        Err(e) => return RemoveBackgroundResult::failure(e),
+    };
+    let (width, height) = keyed.image.dimensions();
+    let [r, g, b] = keyed.background_color;
+    let background_color = format!("#{:02x}{:02x}{:02x}", r, g, b);
+    let image_data = BASE64.encode(&bytes);
+
+    let mut saved_image = None;
+    if params.save_to_storage.unwrap_or(false) {
+        let input_images = params.image_path.clone().map(|path| {
+            vec![InputImageInfo {
+                path: Some(path),
+                label: "原图".to_string(),
+            }]
+        });
+        match save_image(
+            app,
+            image_data.clone(),
+            params.canvas_id.clone(),
+            params.node_id.clone(),
+            None,
+            input_images,
+            Some(ImageType::Generated),
+            Some(GenerationParams {
+                provider: Some("local".to_string()),
+                width: Some(width),
+                height: Some(height),
+                extra: Some(serde_json::json!({
+                    "removeBackground": {
+                        "background": keyed.background,
+                        "color": background_color,
+                    }
+                })),
+                ..GenerationParams::default()
+            }),
+        ) {
+            Ok(info) => saved_image = Some(info),
+            Err(e) => return RemoveBackgroundResult::failure(format!("保存图片失败: {}", e)),
+        }
+    }
+
+    println!(
+        "[Rust] remove_background finished: {} background {}",
+        keyed.background, background_color
+    );
+    RemoveBackgroundResult {
+        success: true,
+        image_data: Some(image_data),
+        width: Some(width),
+        height: Some(height),
+        background: Some(keyed.background.to_string()),
+        background_color: Some(background_color),
+        saved_image,
+        error: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::Rgba;
+
+    // 中间一块红色方块，背景为给定颜色
+    fn square_on(background: impl Fn(u32, u32) -> [u8; 3]) -> DynamicImage {
+        DynamicImage::ImageRgba8(RgbaImage::from_fn(60, 60, |x, y| {
+            if (20..40).contains(&x) && (20..40).contains(&y) {
+                Rgba([200, 30, 30, 255])
+            } else {
+                let [r, g, b] = background(x, y);
+                Rgba([r, g, b, 255])
+            }
+        }))
+    }
+
+    #[test]
+    fn test_solid_background_becomes_transparent() {
+        let keyed = key_out_background(&square_on(|_, _| [255, 255, 255]), &KeyOptions::default());
+        assert_eq!(keyed.background, "solid");
+        assert_eq!(keyed.background_color, [255, 255, 255]);
+        assert_eq!(keyed.image.get_pixel(2, 2)[3], 0);
+        assert_eq!(keyed.image.get_pixel(30, 30)[3], 255);
+    }
+
+    #[test]
+    fn test_gradient_background_is_keyed() {
+        let keyed = key_out_background(
+            &square_on(|_, y| [(y * 4) as u8, 80, 255 - (y * 4) as u8]),
+            &KeyOptions::default(),
+        );
+        assert_eq!(keyed.background, "gradient");
+        assert_eq!(keyed.image.get_pixel(5, 30)[3], 0);
+        assert_eq!(keyed.image.get_pixel(55, 50)[3], 0);
+        assert_eq!(keyed.image.get_pixel(30, 30)[3], 255);
+    }
+
+    #[test]
+    fn test_contiguous_keeps_enclosed_background_color() {
+        // 红色方块中间有一个白色小洞
+        let image = DynamicImage::ImageRgba8(RgbaImage::from_fn(60, 60, |x, y| {
+            if (28..32).contains(&x) && (28..32).contains(&y) {
+                Rgba([255, 255, 255, 255])
+            } else if (20..40).contains(&x) && (20..40).contains(&y) {
+                Rgba([200, 30, 30, 255])
+            } else {
+                Rgba([255, 255, 255, 255])
+            }
+        }));
+        let options = KeyOptions {
+            feather: 0,
+            ..KeyOptions::default()
+        };
+        assert_eq!(
+            key_out_background(&image, &options).image.get_pixel(30, 30)[3],
+            255
+        );
+        let options = KeyOptions {
+            contiguous: false,
+            ..options
+        };
+        assert_eq!(
+            key_out_background(&image, &options).image.get_pixel(30, 30)[3],
+            0
+        );
+    }
+
+    #[test]
+    fn test_despill_removes_background_contribution() {
+        // 50% 红色 + 50% 绿幕
+        assert_eq!(
+            despill_pixel([100.0, 127.5, 0.0], [0.0, 255.0, 0.0], 0.5),
+            [200, 0, 0]
+        );
+    }
+}
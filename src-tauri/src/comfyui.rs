@@ -0,0 +1,887 @@
+//! ComfyUI 本地服务
+//!
+//! 提交 API 格式的工作流（支持 `{{prompt}}`、`{{seed}}`、`{{image_1}}` 等占位符），
+//! 通过 websocket 跟踪执行进度，完成后从 /history 与 /view 取回输出，
+//! 需要时再交给 `save_image` 落盘。
+
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use futures_util::StreamExt;
+use reqwest::{multipart, Client};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+use tauri::Emitter;
+use tokio_tungstenite::tungstenite::Message;
+use uuid::Uuid;
+
+use crate::image_provider::random_seed;
+use crate::image_utils::{decode_base64_image, guess_image_mime};
+use crate::storage::{save_image, GenerationParams, ImageInfo, ImageType};
+
+const DEFAULT_TIMEOUT_SECS: u64 = 600;
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+// 前端调用的参数
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ComfyUIParams {
+    pub base_url: String,
+    pub workflow: Value, // API 格式的工作流 JSON
+    pub prompt: Option<String>,
+    pub negative_prompt: Option<String>,
+    pub seed: Option<i64>,
+    pub input_images: Option<Vec<String>>, // base64 图片，依次对应 {{image_1}}、{{image_2}}...
+    pub variables: Option<HashMap<String, Value>>, // 自定义占位符
+    pub request_id: Option<String>,        // 用于区分进度事件
+    pub timeout_secs: Option<u64>,
+    pub canvas_id: Option<String>,
+    pub node_id: Option<String>,
+    pub save_to_storage: Option<bool>, // 默认保存图片输出
+}
+
+// 单个输出文件
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ComfyUIOutputFile {
+    pub node_id: String,
+    pub filename: String,
+    pub subfolder: String,
+    pub media_type: String, // "image" 或 "video"
+    pub mime_type: String,
+    pub data: String, // base64
+}
+
+// 进度事件（comfyui-progress）
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ComfyUIProgressEvent {
+    pub request_id: Option<String>,
+    pub prompt_id: String,
+    pub status: String, // "queued" | "running" | "completed"
+    pub node: Option<String>,
+    pub value: u64,
+    pub max: u64,
+}
+
+// 返回结果
+#[derive(Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ComfyUIResult {
+    pub success: bool,
+    pub prompt_id: Option<String>,
+    pub seed: Option<i64>,
+    pub outputs: Vec<ComfyUIOutputFile>,
+    pub saved_images: Vec<ImageInfo>,
+    pub error: Option<String>,
+}
+
+// 一次工作流执行的输出
+#[derive(Debug)]
+pub struct ComfyUIRun {
+    pub prompt_id: String,
+    pub seed: i64,
+    pub outputs: Vec<ComfyUIOutputFile>,
+}
+
+/// 替换工作流中的占位符：整串等于 `{{key}}` 时按原类型替换，否则做字符串内插
+pub fn apply_workflow_variables(workflow: &mut Value, variables: &HashMap<String, Value>) {
+    match workflow {
+        Value::String(text) if text.contains("{{") => {
+            let trimmed = text.trim();
+            if let Some(key) = trimmed
+                .strip_prefix("{{")
+                .and_then(|rest| rest.strip_suffix("}}"))
+            {
+                if let Some(value) = variables.get(key.trim()) {
+                    *workflow = value.clone();
+                    return;
+                }
+            }
+            let mut replaced = text.clone();
+            for (key, value) in variables {
+                let placeholder = format!("{{{{{}}}}}", key);
+                if replaced.contains(&placeholder) {
+                    let value_text = match value {
+                        Value::String(s) => s.clone(),
+                        other => other.to_string(),
+                    };
+                    replaced = replaced.replace(&placeholder, &value_text);
+                }
+            }
+            *text = replaced;
+        }
+        Value::Array(items) => {
+            for item in items {
+                apply_workflow_variables(item, variables);
+            }
+        }
+        Value::Object(map) => {
+            for (_, value) in map.iter_mut() {
+                apply_workflow_variables(value, variables);
+            }
+        }
+        _ => {}
+    }
+}
+
+fn build_variables(
+    params: &ComfyUIParams,
+    seed: i64,
+    uploaded_images: &[String],
+) -> HashMap<String, Value> {
+    let mut variables = HashMap::new();
+    variables.insert(
+        "prompt".to_string(),
+        Value::String(params.prompt.clone().unwrap_or_default()),
+    );
+    variables.insert(
+        "negative_prompt".to_string(),
+        Value::String(params.negative_prompt.clone().unwrap_or_default()),
+    );
+    variables.insert("seed".to_string(), Value::from(seed));
+
+    for (index, name) in uploaded_images.iter().enumerate() {
+        variables.insert(format!("image_{}", index + 1), Value::String(name.clone()));
+    }
+    if let Some(first) = uploaded_images.first() {
+        variables.insert("image".to_string(), Value::String(first.clone()));
+    }
+
+    if let Some(custom) = &params.variables {
+        for (key, value) in custom {
+            variables.insert(key.clone(), value.clone());
+        }
+    }
+    variables
+}
+
+fn ws_url(base_url: &str, client_id: &str) -> Option<String> {
+    let base = base_url.trim_end_matches('/');
+    let ws_base = if let Some(rest) = base.strip_prefix("http://") {
+        format!("ws://{}", rest)
+    } else if let Some(rest) = base.strip_prefix("https://") {
+        format!("wss://{}", rest)
+    } else {
+        return None;
+    };
+    Some(format!("{}/ws?clientId={}", ws_base, client_id))
+}
+
+async fn upload_image(client: &Client, base_url: &str, image_data: &str) -> Result<String, String> {
+    let bytes = decode_base64_image(image_data)?;
+    let (mime, ext) = guess_image_mime(&bytes);
+    let part = multipart::Part::bytes(bytes)
+        .file_name(format!("nextcreator_{}.{}", Uuid::new_v4(), ext))
+        .mime_str(mime)
+        .map_err(|e| format!("设置图片 MIME 类型失败: {}", e))?;
+    let form = multipart::Form::new()
+        .part("image", part)
+        .text("type", "input")
+        .text("overwrite", "true");
+
+    let url = format!("{}/upload/image", base_url.trim_end_matches('/'));
+    let response = client
+        .post(&url)
+        .multipart(form)
+        .send()
+        .await
+        .map_err(|e| format!("上传图片失败: {}", e))?;
+    if !response.status().is_success() {
+        let status = response.status();
+        let text = response.text().await.unwrap_or_default();
+        return Err(format!("上传图片失败 ({}): {}", status, text));
+    }
+
+    let body: Value = response
+        .json()
+        .await
+        .map_err(|e| format!("解析上传结果失败: {}", e))?;
+    let name = body
+        .get("name")
+        .and_then(|v| v.as_str())
+        .ok_or_else(|| "上传结果缺少文件名".to_string())?;
+    let subfolder = body.get("subfolder").and_then(|v| v.as_str()).unwrap_or("");
+    Ok(if subfolder.is_empty() {
+        name.to_string()
+    } else {
+        format!("{}/{}", subfolder, name)
+    })
+}
+
+async fn queue_prompt(
+    client: &Client,
+    base_url: &str,
+    workflow: &Value,
+    client_id: &str,
+) -> Result<String, String> {
+    let url = format!("{}/prompt", base_url.trim_end_matches('/'));
+    let response = client
+        .post(&url)
+        .json(&serde_json::json!({ "prompt": workflow, "client_id": client_id }))
+        .send()
+        .await
+        .map_err(|e| {
+            if e.is_connect() {
+                "无法连接到 ComfyUI，请确认服务已启动".to_string()
+            } else {
+                format!("提交工作流失败: {}", e)
+            }
+        })?;
+
+    let status = response.status();
+    let body: Value = response
+        .json()
+        .await
+        .map_err(|e| format!("解析提交结果失败: {}", e))?;
+    if !status.is_success() {
+        let message = body
+            .pointer("/error/message")
+            .and_then(|v| v.as_str())
+            .map(|s| s.to_string())
+            .unwrap_or_else(|| body.to_string());
+        return Err(format!("工作流校验失败 ({}): {}", status, message));
+    }
+    if let Some(errors) = body.get("node_errors").and_then(|v| v.as_object()) {
+        if !errors.is_empty() {
+            return Err(format!("工作流节点错误: {}", Value::Object(errors.clone())));
+        }
+    }
+
+    body.get("prompt_id")
+        .and_then(|v| v.as_str())
+        .map(|s| s.to_string())
+        .ok_or_else(|| "提交结果缺少 prompt_id".to_string())
+}
+
+async fn fetch_history(
+    client: &Client,
+    base_url: &str,
+    prompt_id: &str,
+) -> Result<Option<Value>, String> {
+    let url = format!("{}/history/{}", base_url.trim_end_matches('/'), prompt_id);
+    let response = client
+        .get(&url)
+        .send()
+        .await
+        .map_err(|e| format!("查询执行结果失败: {}", e))?;
+    let body: Value = response
+        .json()
+        .await
+        .map_err(|e| format!("解析执行结果失败: {}", e))?;
+    Ok(body.get(prompt_id).cloned())
+}
+
+/// 从 history 记录中取出执行错误
+fn history_error(entry: &Value) -> Option<String> {
+    let status = entry.get("status")?;
+    if status.get("status_str").and_then(|v| v.as_str()) != Some("error") {
+        return None;
+    }
+    let message = status
+        .get("messages")
+        .and_then(|v| v.as_array())
+        .and_then(|messages| {
+            messages.iter().find_map(|m| {
+                (m.get(0).and_then(|v| v.as_str()) == Some("execution_error"))
+                    .then(|| m.pointer("/1/exception_message"))
+                    .flatten()
+                    .and_then(|v| v.as_str())
+                    .map(|s| s.to_string())
+            })
+        })
+        .unwrap_or_else(|| "工作流执行失败".to_string());
+    Some(message)
+}
+
+/// 列出 history 记录中的输出文件：(节点, 文件名, 子目录, 类型, 媒体类别)
+fn collect_output_refs(entry: &Value) -> Vec<(String, String, String, String, &'static str)> {
+    let mut refs = Vec::new();
+    let outputs = match entry.get("outputs").and_then(|v| v.as_object()) {
+        Some(o) => o,
+        None => return refs,
+    };
+
+    let mut node_ids: Vec<&String> = outputs.keys().collect();
+    node_ids.sort_by_key(|id| id.parse::<u64>().unwrap_or(u64::MAX));
+
+    for node_id in node_ids {
+        let node_output = &outputs[node_id];
+        // images 为图片；gifs/videos 为视频合成节点（如 VHS）的输出
+        for (key, media) in [("images", "image"), ("gifs", "video"), ("videos", "video")] {
+            let files = match node_output.get(key).and_then(|v| v.as_array()) {
+                Some(f) => f,
+                None => continue,
+            };
+            for file in files {
+                let file_type = file
+                    .get("type")
+                    .and_then(|v| v.as_str())
+                    .unwrap_or("output");
+                // 预览节点的临时文件不作为结果
+                if file_type == "temp" {
+                    continue;
+                }
+                if let Some(filename) = file.get("filename").and_then(|v| v.as_str()) {
+                    refs.push((
+                        node_id.clone(),
+                        filename.to_string(),
+                        file.get("subfolder")
+                            .and_then(|v| v.as_str())
+                            .unwrap_or("")
+                            .to_string(),
+                        file_type.to_string(),
+                        media,
+                    ));
+                }
+            }
+        }
+    }
+    refs
+}
+
+fn video_mime(filename: &str) -> &'static str {
+    match filename.rsplit('.').next().map(|e| e.to_ascii_lowercase()) {
+        Some(ext) if ext == "webm" => "video/webm",
+        Some(ext) if ext == "gif" => "image/gif",
+        Some(ext) if ext == "webp" => "image/webp",
+        _ => "video/mp4",
+    }
+}
+
+async fn download_output(
+    client: &Client,
+    base_url: &str,
+    filename: &str,
+    subfolder: &str,
+    file_type: &str,
+) -> Result<Vec<u8>, String> {
+    let url = format!("{}/view", base_url.trim_end_matches('/'));
+    let response = client
+        .get(&url)
+        .query(&[
+            ("filename", filename),
+            ("subfolder", subfolder),
+            ("type", file_type),
+        ])
+        .send()
+        .await
+        .map_err(|e| format!("下载输出失败: {}", e))?;
+    if !response.status().is_success() {
+        return Err(format!("下载输出失败，HTTP 状态码: {}", response.status()));
+    }
+    response
+        .bytes()
+        .await
+        .map(|b| b.to_vec())
+        .map_err(|e| format!("读取输出数据失败: {}", e))
+}
+
+type WsStream =
+    tokio_tungstenite::WebSocketStream<tokio_tungstenite::MaybeTlsStream<tokio::net::TcpStream>>;
+
+/// 通过 websocket 等待执行完成；返回 Ok(false) 表示连接中断，需要改用轮询
+async fn wait_via_websocket(
+    ws: &mut WsStream,
+    prompt_id: &str,
+    deadline: Instant,
+    on_progress: &(dyn Fn(ComfyUIProgressEvent) + Send + Sync),
+) -> Result<bool, String> {
+    loop {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            return Err("等待 ComfyUI 执行超时".to_string());
+        }
+
+        let message = match tokio::time::timeout(remaining, ws.next()).await {
+            Err(_) => return Err("等待 ComfyUI 执行超时".to_string()),
+            Ok(None) | Ok(Some(Err(_))) => return Ok(false),
+            Ok(Some(Ok(message))) => message,
+        };
+        let text = match message {
+            Message::Text(text) => text,
+            Message::Close(_) => return Ok(false),
+            // 二进制消息为预览图，忽略
+            _ => continue,
+        };
+        let event: Value = match serde_json::from_str(text.as_str()) {
+            Ok(v) => v,
+            Err(_) => continue,
+        };
+
+        let data = event.get("data").cloned().unwrap_or(Value::Null);
+        // 其他任务的消息忽略；部分版本的 progress 消息不带 prompt_id
+        if let Some(id) = data.get("prompt_id").and_then(|v| v.as_str()) {
+            if id != prompt_id {
+                continue;
+            }
+        }
+
+        match event.get("type").and_then(|v| v.as_str()).unwrap_or("") {
+            "progress" => on_progress(ComfyUIProgressEvent {
+                request_id: None,
+                prompt_id: prompt_id.to_string(),
+                status: "running".to_string(),
+                node: data.get("node").and_then(|v| v.as_str()).map(String::from),
+                value: data.get("value").and_then(|v| v.as_u64()).unwrap_or(0),
+                max: data.get("max").and_then(|v| v.as_u64()).unwrap_or(0),
+            }),
+            "executing" => {
+                if data.get("node").map(|v| v.is_null()).unwrap_or(false) {
+                    return Ok(true);
+                }
+                on_progress(ComfyUIProgressEvent {
+                    request_id: None,
+                    prompt_id: prompt_id.to_string(),
+                    status: "running".to_string(),
+                    node: data.get("node").and_then(|v| v.as_str()).map(String::from),
+                    value: 0,
+                    max: 0,
+                });
+            }
+            "execution_success" => return Ok(true),
+            "execution_error" => {
+                let message = data
+                    .get("exception_message")
+                    .and_then(|v| v.as_str())
+                    .unwrap_or("工作流执行失败");
+                return Err(format!("ComfyUI 执行失败: {}", message));
+            }
+            "execution_interrupted" => return Err("ComfyUI 执行已被中断".to_string()),
+            _ => {}
+        }
+    }
+}
+
+/// 轮询 /history 直到出现结果
+async fn wait_via_polling(
+    client: &Client,
+    base_url: &str,
+    prompt_id: &str,
+    deadline: Instant,
+) -> Result<Value, String> {
+    loop {
+        if let Some(entry) = fetch_history(client, base_url, prompt_id).await? {
+            let completed = entry
+                .pointer("/status/completed")
+                .and_then(|v| v.as_bool())
+                .unwrap_or(true);
+            if completed || history_error(&entry).is_some() {
+                return Ok(entry);
+            }
+        }
+        if Instant::now() >= deadline {
+            return Err("等待 ComfyUI 执行超时".to_string());
+        }
+        tokio::time::sleep(POLL_INTERVAL).await;
+    }
+}
+
+/// 执行工作流并取回全部输出
+pub async fn run_workflow(
+    params: &ComfyUIParams,
+    on_progress: &(dyn Fn(ComfyUIProgressEvent) + Send + Sync),
+) -> Result<ComfyUIRun, String> {
+    if !params.workflow.is_object() {
+        return Err("工作流必须是 ComfyUI API 格式的 JSON 对象".to_string());
+    }
+
+    let client = Client::builder()
+        .timeout(Duration::from_secs(120))
+        .build()
+        .map_err(|e| format!("创建 HTTP 客户端失败: {}", e))?;
+    let base_url = params.base_url.trim_end_matches('/');
+    let deadline =
+        Instant::now() + Duration::from_secs(params.timeout_secs.unwrap_or(DEFAULT_TIMEOUT_SECS));
+
+    // 上传输入图片并替换占位符
+    let mut uploaded = Vec::new();
+    for image in params.input_images.iter().flatten() {
+        if image.trim().is_empty() {
+            continue;
+        }
+        uploaded.push(upload_image(&client, base_url, image).await?);
+    }
+    // 未指定种子时随机生成，避免 ComfyUI 命中缓存直接返回旧结果
+    let seed = params.seed.unwrap_or_else(random_seed);
+    let mut workflow = params.workflow.clone();
+    apply_workflow_variables(&mut workflow, &build_variables(params, seed, &uploaded));
+
+    // 先建立 websocket 再提交，避免漏掉进度消息
+    let client_id = Uuid::new_v4().to_string();
+    let mut ws = match ws_url(base_url, &client_id) {
+        Some(url) => match tokio_tungstenite::connect_async(url.as_str()).await {
+            Ok((stream, _)) => Some(stream),
+            Err(e) => {
+                println!(
+                    "[Rust] ComfyUI websocket unavailable, falling back to polling: {}",
+                    e
+                );
+                None
+            }
+        },
+        None => None,
+    };
+
+    let prompt_id = queue_prompt(&client, base_url, &workflow, &client_id).await?;
+    println!("[Rust] ComfyUI prompt queued: {}", prompt_id);
+    on_progress(ComfyUIProgressEvent {
+        request_id: None,
+        prompt_id: prompt_id.clone(),
+        status: "queued".to_string(),
+        node: None,
+        value: 0,
+        max: 0,
+    });
+
+    if let Some(stream) = ws.as_mut() {
+        let finished = wait_via_websocket(stream, &prompt_id, deadline, on_progress).await?;
+        if !finished {
+            println!("[Rust] ComfyUI websocket closed, falling back to polling");
+        }
+        let _ = stream.close(None).await;
+    }
+
+    let entry = wait_via_polling(&client, base_url, &prompt_id, deadline).await?;
+    if let Some(error) = history_error(&entry) {
+        return Err(format!("ComfyUI 执行失败: {}", error));
+    }
+
+    let mut outputs = Vec::new();
+    for (node_id, filename, subfolder, file_type, media) in collect_output_refs(&entry) {
+        let bytes = download_output(&client, base_url, &filename, &subfolder, &file_type).await?;
+        let mime_type = if media == "image" {
+            guess_image_mime(&bytes).0
+        } else {
+            video_mime(&filename)
+        };
+        outputs.push(ComfyUIOutputFile {
+            node_id,
+            filename,
+            subfolder,
+            media_type: media.to_string(),
+            mime_type: mime_type.to_string(),
+            data: BASE64.encode(&bytes),
+        });
+    }
+
+    if outputs.is_empty() {
+        return Err("工作流没有产生输出，请确认包含 SaveImage 等输出节点".to_string());
+    }
+
+    on_progress(ComfyUIProgressEvent {
+        request_id: None,
+        prompt_id: prompt_id.clone(),
+        status: "completed".to_string(),
+        node: None,
+        value: 1,
+        max: 1,
+    });
+
+    Ok(ComfyUIRun {
+        prompt_id,
+        seed,
+        outputs,
+    })
+}
+
+// Tauri 命令：执行 ComfyUI 工作流，图片输出默认保存到本地
+#[tauri::command]
+pub async fn comfyui_generate(app: tauri::AppHandle, params: ComfyUIParams) -> ComfyUIResult {
+    println!("[Rust] comfyui_generate called");
+    println!("[Rust] base_url: {}", params.base_url);
+
+    let request_id = params.request_id.clone();
+    let emitter = app.clone();
+    let on_progress = move |mut event: ComfyUIProgressEvent| {
+        event.request_id = request_id.clone();
+        let _ = emitter.emit("comfyui-progress", event);
+    };
+
+    let run = match run_workflow(&params, &on_progress).await {
+        Ok(run) => run,
+        Err(e) => {
+            println!("[Rust] ComfyUI failed: {}", e);
+            return ComfyUIResult {
+                success: false,
+                error: Some(e),
+                ..ComfyUIResult::default()
+            };
+        }
+    };
+
+    let mut saved_images = Vec::new();
+    if params.save_to_storage.unwrap_or(true) {
+        for output in run.outputs.iter().filter(|o| o.media_type == "image") {
+            match save_image(
+                app.clone(),
+                output.data.clone(),
+                params.canvas_id.clone(),
+                params.node_id.clone(),
+                params.prompt.clone(),
+                None,
+                Some(ImageType::Generated),
+                Some(GenerationParams {
+                    provider: Some("comfyui".to_string()),
+                    negative_prompt: params.negative_prompt.clone(),
+                    seed: Some(run.seed),
+                    extra: Some(serde_json::json!({
+                        "promptId": run.prompt_id,
+                        "outputNode": output.node_id,
+                    })),
+                    ..GenerationParams::default()
+                }),
+            ) {
+                Ok(info) => saved_images.push(info),
+                Err(e) => println!("[Rust] Failed to save ComfyUI output: {}", e),
+            }
+        }
+    }
+
+    println!(
+        "[Rust] ComfyUI finished: {} outputs, {} saved",
+        run.outputs.len(),
+        saved_images.len()
+    );
+
+    ComfyUIResult {
+        success: true,
+        prompt_id: Some(run.prompt_id),
+        seed: Some(run.seed),
+        outputs: run.outputs,
+        saved_images,
+        error: None,
+    }
+}
+
+/// 队列条目格式为 [number, prompt_id, prompt, extra_data, outputs]
+fn queue_contains(queue: &Value, key: &str, prompt_id: &str) -> bool {
+    queue[key].as_array().is_some_and(|entries| {
+        entries
+            .iter()
+            .any(|entry| entry.get(1).and_then(|id| id.as_str()) == Some(prompt_id))
+    })
+}
+
+// Tauri 命令：取消任务。指定 prompt_id 时只在它正在执行时中断，排队中的只从队列移除，
+// 避免中断共享服务上其他人的工作流；未指定时中断当前执行
+#[tauri::command]
+pub async fn comfyui_cancel(base_url: String, prompt_id: Option<String>) -> Result<(), String> {
+    println!("[Rust] comfyui_cancel called, prompt_id: {:?}", prompt_id);
+    let client = Client::builder()
+        .timeout(Duration::from_secs(30))
+        .build()
+        .map_err(|e| format!("创建 HTTP 客户端失败: {}", e))?;
+    let base_url = base_url.trim_end_matches('/');
+
+    let interrupt = match &prompt_id {
+        Some(id) => {
+            let queue: Value = client
+                .get(format!("{}/queue", base_url))
+                .send()
+                .await
+                .map_err(|e| format!("读取队列失败: {}", e))?
+                .json()
+                .await
+                .map_err(|e| format!("解析队列失败: {}", e))?;
+            if queue_contains(&queue, "queue_pending", id) {
+                client
+                    .post(format!("{}/queue", base_url))
+                    .json(&serde_json::json!({ "delete": [id] }))
+                    .send()
+                    .await
+                    .map_err(|e| format!("移除队列任务失败: {}", e))?;
+            }
+            queue_contains(&queue, "queue_running", id)
+        }
+        None => true,
+    };
+    if interrupt {
+        // 新版 ComfyUI 只在 prompt_id 与当前执行的任务一致时中断
+        let body = match &prompt_id {
+            Some(id) => serde_json::json!({ "prompt_id": id }),
+            None => serde_json::json!({}),
+        };
+        client
+            .post(format!("{}/interrupt", base_url))
+            .json(&body)
+            .send()
+            .await
+            .map_err(|e| format!("中断执行失败: {}", e))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{mock_http, mock_http_with_ws, MockRequest, MockResponse};
+    use std::sync::{Arc, Mutex};
+    use tokio::net::TcpStream;
+    use tokio_tungstenite::WebSocketStream;
+
+    fn png_bytes() -> Vec<u8> {
+        let image = image::RgbaImage::from_pixel(2, 2, image::Rgba([255, 0, 0, 255]));
+        let mut bytes = Vec::new();
+        image
+            .write_to(
+                &mut std::io::Cursor::new(&mut bytes),
+                image::ImageFormat::Png,
+            )
+            .unwrap();
+        bytes
+    }
+
+    #[derive(Default)]
+    struct MockState {
+        queued_workflow: Option<Value>,
+    }
+
+    // 等待工作流提交后再推送进度，保持连接直到客户端关闭
+    async fn push_progress(mut ws: WebSocketStream<TcpStream>, state: Arc<Mutex<MockState>>) {
+        use futures_util::SinkExt;
+        while state.lock().unwrap().queued_workflow.is_none() {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        let messages = [
+            r#"{"type":"status","data":{"status":{"exec_info":{"queue_remaining":1}}}}"#,
+            r#"{"type":"progress","data":{"value":1,"max":2,"prompt_id":"p1","node":"3"}}"#,
+            r#"{"type":"progress","data":{"value":2,"max":2,"prompt_id":"p1","node":"3"}}"#,
+            r#"{"type":"executing","data":{"node":null,"prompt_id":"p1"}}"#,
+        ];
+        for message in messages {
+            ws.send(Message::text(message)).await.unwrap();
+        }
+        while let Some(Ok(_)) = ws.next().await {}
+    }
+
+    fn respond(request: MockRequest, state: &Mutex<MockState>) -> MockResponse {
+        let target = format!("{} {}", request.method, request.path);
+        if target.starts_with("POST /upload/image") {
+            MockResponse::json(br#"{"name":"upload.png","subfolder":"","type":"input"}"#.to_vec())
+        } else if target.starts_with("POST /prompt") {
+            state.lock().unwrap().queued_workflow = Some(request.json()["prompt"].clone());
+            MockResponse::json(br#"{"prompt_id":"p1","number":0,"node_errors":{}}"#.to_vec())
+        } else if target.starts_with("GET /history/p1") {
+            MockResponse::json(
+                br#"{"p1":{"outputs":{"9":{"images":[{"filename":"out.png","subfolder":"","type":"output"}]},"12":{"images":[{"filename":"preview.png","subfolder":"","type":"temp"}]}},"status":{"status_str":"success","completed":true}}}"#.to_vec(),
+            )
+        } else if target.starts_with("GET /view") {
+            assert!(target.contains("filename=out.png"));
+            MockResponse::new("200 OK", "image/png", png_bytes())
+        } else {
+            MockResponse::json(b"{}".to_vec())
+        }
+    }
+
+    async fn start_mock_server() -> (String, Arc<Mutex<MockState>>) {
+        let state = Arc::new(Mutex::new(MockState::default()));
+        let http_state = state.clone();
+        let ws_state = state.clone();
+        let base_url = mock_http_with_ws(
+            move |request| {
+                let response = respond(request, &http_state);
+                async move { response }
+            },
+            "/ws",
+            move |ws| push_progress(ws, ws_state.clone()),
+        )
+        .await;
+        (base_url, state)
+    }
+
+    #[test]
+    fn test_apply_workflow_variables() {
+        let mut workflow = serde_json::json!({
+            "3": { "inputs": { "seed": "{{seed}}", "steps": 20 } },
+            "6": { "inputs": { "text": "masterpiece, {{prompt}}" } },
+            "10": { "inputs": { "image": "{{ image_1 }}" } }
+        });
+        let mut variables = HashMap::new();
+        variables.insert("seed".to_string(), Value::from(42));
+        variables.insert("prompt".to_string(), Value::from("a cat"));
+        variables.insert("image_1".to_string(), Value::from("upload.png"));
+
+        apply_workflow_variables(&mut workflow, &variables);
+        assert_eq!(workflow["3"]["inputs"]["seed"], Value::from(42));
+        assert_eq!(workflow["6"]["inputs"]["text"], "masterpiece, a cat");
+        assert_eq!(workflow["10"]["inputs"]["image"], "upload.png");
+        assert_eq!(workflow["3"]["inputs"]["steps"], 20);
+    }
+
+    #[tokio::test]
+    async fn test_run_workflow_against_mock_server() {
+        let (base_url, state) = start_mock_server().await;
+        let params = ComfyUIParams {
+            base_url,
+            workflow: serde_json::json!({
+                "3": { "class_type": "KSampler", "inputs": { "seed": "{{seed}}" } },
+                "6": { "class_type": "CLIPTextEncode", "inputs": { "text": "{{prompt}}" } },
+                "10": { "class_type": "LoadImage", "inputs": { "image": "{{image_1}}" } }
+            }),
+            prompt: Some("a red fox".to_string()),
+            seed: Some(7),
+            input_images: Some(vec![BASE64.encode(png_bytes())]),
+            timeout_secs: Some(10),
+            ..ComfyUIParams::default()
+        };
+
+        let progress = Arc::new(Mutex::new(Vec::new()));
+        let recorder = progress.clone();
+        let on_progress = move |event: ComfyUIProgressEvent| {
+            recorder
+                .lock()
+                .unwrap()
+                .push((event.status, event.value, event.max));
+        };
+
+        let run = run_workflow(&params, &on_progress).await.unwrap();
+        assert_eq!(run.prompt_id, "p1");
+        assert_eq!(run.outputs.len(), 1);
+        assert_eq!(run.outputs[0].node_id, "9");
+        assert_eq!(run.outputs[0].mime_type, "image/png");
+        assert_eq!(BASE64.decode(&run.outputs[0].data).unwrap(), png_bytes());
+
+        let workflow = state.lock().unwrap().queued_workflow.clone().unwrap();
+        assert_eq!(workflow["3"]["inputs"]["seed"], Value::from(7));
+        assert_eq!(workflow["6"]["inputs"]["text"], "a red fox");
+        assert_eq!(workflow["10"]["inputs"]["image"], "upload.png");
+
+        let progress = progress.lock().unwrap();
+        assert!(progress.contains(&("running".to_string(), 2, 2)));
+        assert_eq!(progress.last().unwrap().0, "completed");
+    }
+
+    #[tokio::test]
+    async fn test_cancel_only_interrupts_running_prompt() {
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let recorder = requests.clone();
+        let base_url = mock_http(move |request: MockRequest| {
+            let target = format!("{} {}", request.method, request.path);
+            recorder.lock().unwrap().push(target.clone());
+            let response = if target == "GET /queue" {
+                MockResponse::json(
+                    br#"{"queue_running":[[3,"running",{},{},[]]],"queue_pending":[[4,"queued",{},{},[]]]}"#.to_vec(),
+                )
+            } else {
+                MockResponse::json(b"{}".to_vec())
+            };
+            async move { response }
+        })
+        .await;
+
+        let cancel = |prompt_id: &str| {
+            requests.lock().unwrap().clear();
+            comfyui_cancel(base_url.clone(), Some(prompt_id.to_string()))
+        };
+        cancel("queued").await.unwrap();
+        assert_eq!(*requests.lock().unwrap(), vec!["GET /queue", "POST /queue"]);
+        cancel("running").await.unwrap();
+        assert_eq!(
+            *requests.lock().unwrap(),
+            vec!["GET /queue", "POST /interrupt"]
+        );
+        cancel("finished").await.unwrap();
+        assert_eq!(*requests.lock().unwrap(), vec!["GET /queue"]);
+    }
+}
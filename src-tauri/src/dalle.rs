@@ -1,7 +1,16 @@
 use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use futures_util::StreamExt;
 use reqwest::{multipart, Client};
 use serde::{Deserialize, Serialize};
 use std::time::Duration;
+use tauri::Emitter;
+
+use crate::image_preprocess::{
+    preprocess_base64_image, resize_mask_to, ImageLimits, ImagePreprocessReport,
+};
+use crate::image_provider::{parse_size, random_seed};
+use crate::image_utils::decode_base64_image;
+use crate::storage::GenerationParams;
 
 /// 从 URL 下载图片并转换为 base64
 async fn download_image_as_base64(client: &Client, url: &str) -> Result<String, String> {
@@ -43,39 +52,14 @@ async fn download_image_as_base64(client: &Client, url: &str) -> Result<String,
     Ok(base64_data)
 }
 
-fn strip_data_url_prefix(base64_data: &str) -> &str {
-    base64_data
-        .split_once(',')
-        .map(|(_, data)| data)
-        .unwrap_or(base64_data)
-        .trim()
-}
-
-fn guess_image_mime(bytes: &[u8]) -> (&'static str, &'static str) {
-    if bytes.starts_with(b"\x89PNG\r\n\x1a\n") {
-        ("image/png", "png")
-    } else if bytes.starts_with(b"\xff\xd8\xff") {
-        ("image/jpeg", "jpg")
-    } else if bytes.len() >= 12 && &bytes[0..4] == b"RIFF" && &bytes[8..12] == b"WEBP" {
-        ("image/webp", "webp")
-    } else if bytes.starts_with(b"GIF87a") || bytes.starts_with(b"GIF89a") {
-        ("image/gif", "gif")
-    } else {
-        ("image/png", "png")
-    }
-}
-
-fn image_part_from_base64(
-    base64_data: &str,
+fn image_part(
+    bytes: Vec<u8>,
     filename_stem: &str,
+    extension: &str,
+    mime: &str,
 ) -> Result<multipart::Part, String> {
-    let bytes = BASE64
-        .decode(strip_data_url_prefix(base64_data))
-        .map_err(|e| format!("图片 base64 解码失败: {}", e))?;
-    let (mime, ext) = guess_image_mime(&bytes);
-
     multipart::Part::bytes(bytes)
-        .file_name(format!("{}.{}", filename_stem, ext))
+        .file_name(format!("{}.{}", filename_stem, extension))
         .mime_str(mime)
         .map_err(|e| format!("设置图片 MIME 类型失败: {}", e))
 }
@@ -107,6 +91,9 @@ pub struct DalleRequest {
     pub guidance_scale: Option<f32>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub watermark: Option<bool>,
+    // 随机种子（兼容服务支持，OpenAI 官方模型不接受）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub seed: Option<i64>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub background: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -115,6 +102,10 @@ pub struct DalleRequest {
     pub output_compression: Option<u8>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub moderation: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stream: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub partial_images: Option<u8>,
 }
 
 // DALL-E API 响应结构
@@ -124,6 +115,7 @@ pub struct DalleResponse {
     pub created: Option<i64>,
     pub data: Option<Vec<DalleImageData>>,
     pub error: Option<DalleError>,
+    pub usage: Option<serde_json::Value>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -165,6 +157,20 @@ pub struct DalleRequestParams {
     pub negative_prompt: Option<String>,
     pub guidance_scale: Option<f32>,
     pub watermark: Option<bool>,
+    pub seed: Option<i64>,
+    pub stream: Option<bool>,       // 流式返回中间预览图（gpt-image 系列）
+    pub partial_images: Option<u8>, // 中间预览图数量，0-3
+    pub request_id: Option<String>, // 预览事件中用于区分请求
+}
+
+// 流式生成的中间预览图（dalle-partial-image 事件）
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DallePartialImageEvent {
+    pub request_id: Option<String>,
+    pub partial_image_index: u32,
+    pub image_data: String, // base64
+    pub output_format: Option<String>,
 }
 
 // 前端返回的结果
@@ -176,7 +182,13 @@ pub struct DalleResult {
     pub image_data_list: Option<Vec<String>>,
     pub image_url: Option<String>,
     pub image_urls: Option<Vec<String>>,
+    #[serde(skip)]
+    pub image_sources: Vec<Option<String>>, // 与 image_data_list 逐项对应的原始地址
     pub revised_prompt: Option<String>,
+    pub usage: Option<serde_json::Value>,
+    pub preprocessing: Option<Vec<ImagePreprocessReport>>, // 被调整过的输入图片
+    pub seed: Option<i64>,                                 // 请求使用的种子，模型不接受种子时为空
+    pub generation: Option<GenerationParams>,              // 生成参数，保存图片时写入元数据
     pub error: Option<String>,
 }
 
@@ -205,10 +217,11 @@ impl DalleResult {
 
     fn success(
         image_data_list: Vec<String>,
-        image_urls: Vec<String>,
+        image_sources: Vec<Option<String>>,
         revised_prompt: Option<String>,
     ) -> Self {
         let image_data = image_data_list.first().cloned();
+        let image_urls: Vec<String> = image_sources.iter().flatten().cloned().collect();
         let image_url = image_urls.first().cloned();
 
         Self {
@@ -221,15 +234,185 @@ impl DalleResult {
             } else {
                 Some(image_urls)
             },
+            image_sources,
             revised_prompt,
+            usage: None,
+            preprocessing: None,
+            seed: None,
+            generation: None,
             error: None,
         }
     }
 }
 
-// Tauri 命令：发送 DALL-E API 请求
+/// 模型是否接受 seed 参数：OpenAI 官方的 dall-e / gpt-image 会拒绝未知参数
+pub(crate) fn accepts_seed(model: &str) -> bool {
+    let model = model.trim().to_ascii_lowercase();
+    !(model.starts_with("dall-e") || model.starts_with("gpt-image"))
+}
+
+/// 按 SSE 规则切分事件，返回每个事件的 data 内容
+#[derive(Default)]
+struct SseParser {
+    buffer: Vec<u8>,
+}
+
+impl SseParser {
+    fn push(&mut self, chunk: &[u8]) -> Vec<String> {
+        self.buffer.extend(chunk.iter().filter(|b| **b != b'\r'));
+        let mut events = Vec::new();
+        while let Some(pos) = self.buffer.windows(2).position(|w| w == b"\n\n") {
+            let raw: Vec<u8> = self.buffer.drain(..pos + 2).collect();
+            events.extend(Self::event_data(&raw));
+        }
+        events
+    }
+
+    /// 流结束时处理最后一个没有空行结尾的事件
+    fn finish(&mut self) -> Option<String> {
+        let raw = std::mem::take(&mut self.buffer);
+        Self::event_data(&raw)
+    }
+
+    fn event_data(raw: &[u8]) -> Option<String> {
+        let text = String::from_utf8_lossy(raw);
+        let data: Vec<&str> = text
+            .lines()
+            .filter_map(|line| line.strip_prefix("data:"))
+            .map(|line| line.trim_start())
+            .collect();
+        (!data.is_empty()).then(|| data.join("\n"))
+    }
+}
+
+/// 读取流式响应：中间预览图交给回调，完成事件作为最终结果
+async fn read_image_stream(
+    response: reqwest::Response,
+    params: &DalleRequestParams,
+    on_partial: &(dyn Fn(DallePartialImageEvent) + Send + Sync),
+) -> DalleResult {
+    let mut stream = response.bytes_stream();
+    let mut parser = SseParser::default();
+    let mut image_data_list = Vec::new();
+    let mut revised_prompt = None;
+    let mut usage = None;
+
+    let mut finished = false;
+    while !finished {
+        let events = match stream.next().await {
+            Some(Ok(chunk)) => parser.push(&chunk),
+            Some(Err(e)) => return DalleResult::failure(format!("读取流式响应失败: {}", e)),
+            None => {
+                finished = true;
+                parser.finish().into_iter().collect()
+            }
+        };
+        for data in events {
+            if data == "[DONE]" {
+                continue;
+            }
+            let event: serde_json::Value = match serde_json::from_str(&data) {
+                Ok(event) => event,
+                Err(e) => {
+                    println!("[Rust] Failed to parse stream event: {}", e);
+                    continue;
+                }
+            };
+            let event_type = event["type"].as_str().unwrap_or_default();
+            let b64 = event["b64_json"].as_str().map(|s| s.to_string());
+
+            if event_type.ends_with(".partial_image") {
+                let index = event["partial_image_index"].as_u64().unwrap_or(0) as u32;
+                println!("[Rust] Partial image #{} received", index);
+                if let Some(image_data) = b64 {
+                    on_partial(DallePartialImageEvent {
+                        request_id: params.request_id.clone(),
+                        partial_image_index: index,
+                        image_data,
+                        output_format: event["output_format"].as_str().map(|s| s.to_string()),
+                    });
+                }
+            } else if event_type.ends_with(".completed") {
+                if let Some(image_data) = b64 {
+                    image_data_list.push(image_data);
+                }
+                if revised_prompt.is_none() {
+                    revised_prompt = event["revised_prompt"].as_str().map(|s| s.to_string());
+                }
+                if !event["usage"].is_null() {
+                    usage = Some(event["usage"].clone());
+                }
+            } else if event_type == "error" || !event["error"].is_null() {
+                let message = event["error"]["message"]
+                    .as_str()
+                    .or_else(|| event["message"].as_str())
+                    .unwrap_or("未知错误");
+                return DalleResult::failure(format!("API 返回错误: {}", message));
+            }
+        }
+    }
+
+    if image_data_list.is_empty() {
+        return DalleResult::failure("流式响应中没有图片数据");
+    }
+    let mut result = DalleResult::success(image_data_list, Vec::new(), revised_prompt);
+    result.usage = usage;
+    result
+}
+
+// Tauri 命令：发送 DALL-E API 请求，开启 stream 时通过 dalle-partial-image 事件推送预览
 #[tauri::command]
-pub async fn dalle_generate_image(params: DalleRequestParams) -> DalleResult {
+pub async fn dalle_generate_image(
+    app: tauri::AppHandle,
+    params: DalleRequestParams,
+) -> DalleResult {
+    let on_partial = move |event: DallePartialImageEvent| {
+        let _ = app.emit("dalle-partial-image", event);
+    };
+    run_dalle_request(params, &on_partial).await
+}
+
+/// 记录到图片元数据的生成参数，生成变体时据此重建请求
+pub(crate) fn dalle_generation(params: &DalleRequestParams, seed: Option<i64>) -> GenerationParams {
+    let (width, height) = parse_size(params.size.as_deref());
+    GenerationParams {
+        provider: Some("openai".to_string()),
+        model: Some(params.model.clone()),
+        negative_prompt: params.negative_prompt.clone(),
+        seed,
+        cfg_scale: params.guidance_scale.map(f64::from),
+        width,
+        height,
+        extra: Some(serde_json::json!({
+            "aspectRatio": params.aspect_ratio,
+            "quality": params.quality,
+            "style": params.style,
+        })),
+        ..GenerationParams::default()
+    }
+}
+
+/// 执行一次 OpenAI Images 请求，成功时带回种子和生成参数
+pub async fn run_dalle_request(
+    params: DalleRequestParams,
+    on_partial: &(dyn Fn(DallePartialImageEvent) + Send + Sync),
+) -> DalleResult {
+    // 模型接受种子时，未指定就随机生成一个，结果才能复现
+    let seed = accepts_seed(&params.model).then(|| params.seed.unwrap_or_else(random_seed));
+    let generation = dalle_generation(&params, seed);
+    let mut result = send_dalle_request(params, seed, on_partial).await;
+    if result.success {
+        result.seed = seed;
+        result.generation = Some(generation);
+    }
+    result
+}
+
+async fn send_dalle_request(
+    params: DalleRequestParams,
+    seed: Option<i64>,
+    on_partial: &(dyn Fn(DallePartialImageEvent) + Send + Sync),
+) -> DalleResult {
     println!("[Rust] dalle_generate_image called");
     println!("[Rust] base_url: {}", params.base_url);
     println!("[Rust] model: {}", params.model);
@@ -253,6 +436,9 @@ pub async fn dalle_generate_image(params: DalleRequestParams) -> DalleResult {
             .map(|m| !m.trim().is_empty())
             .unwrap_or(false);
 
+    let streaming = params.stream.unwrap_or(false);
+    let partial_images = params.partial_images.map(|n| n.min(3));
+
     // 构建 URL
     let endpoint = if is_edit { "edits" } else { "generations" };
     let url = format!(
@@ -270,6 +456,7 @@ pub async fn dalle_generate_image(params: DalleRequestParams) -> DalleResult {
         .post(&url)
         .header("Authorization", format!("Bearer {}", params.api_key));
 
+    let mut preprocessing: Vec<ImagePreprocessReport> = Vec::new();
     let send_result = if is_edit {
         let images = params
             .input_images
@@ -315,6 +502,9 @@ pub async fn dalle_generate_image(params: DalleRequestParams) -> DalleResult {
         if let Some(n) = params.n {
             form = form.text("n", n.max(1).to_string());
         }
+        if let Some(seed) = seed {
+            form = form.text("seed", seed.to_string());
+        }
         if let Some(input_fidelity) = params
             .input_fidelity
             .as_ref()
@@ -322,9 +512,34 @@ pub async fn dalle_generate_image(params: DalleRequestParams) -> DalleResult {
         {
             form = form.text("input_fidelity", input_fidelity.clone());
         }
+        if streaming {
+            form = form.text("stream", "true");
+            if let Some(partial_images) = partial_images {
+                form = form.text("partial_images", partial_images.to_string());
+            }
+        }
 
+        // 输入图片按 OpenAI 限制预处理，蒙版跟随第一张图片的尺寸
+        let mut first_image_size: Option<(u32, u32)> = None;
         for (index, image) in images.iter().enumerate() {
-            let part = match image_part_from_base64(image, &format!("image-{}", index + 1)) {
+            let prepared = match preprocess_base64_image(
+                image.to_string(),
+                ImageLimits::for_provider("openai"),
+            )
+            .await
+            {
+                Ok(prepared) => prepared,
+                Err(e) => return DalleResult::failure(e),
+            };
+            if index == 0 {
+                first_image_size = Some((prepared.report.width, prepared.report.height));
+            }
+            let (mime, ext) = (prepared.mime_type(), prepared.extension());
+            if prepared.report.changed() {
+                preprocessing.push(prepared.report);
+            }
+            let part = match image_part(prepared.bytes, &format!("image-{}", index + 1), ext, mime)
+            {
                 Ok(part) => part,
                 Err(e) => return DalleResult::failure(e),
             };
@@ -332,7 +547,16 @@ pub async fn dalle_generate_image(params: DalleRequestParams) -> DalleResult {
         }
 
         if let Some(mask_image) = params.mask_image.as_ref().filter(|v| !v.trim().is_empty()) {
-            let part = match image_part_from_base64(mask_image, "mask") {
+            let mask_bytes = match decode_base64_image(mask_image).and_then(|bytes| {
+                match first_image_size.filter(|(w, h)| *w > 0 && *h > 0) {
+                    Some((width, height)) => resize_mask_to(&bytes, width, height),
+                    None => Ok(bytes),
+                }
+            }) {
+                Ok(bytes) => bytes,
+                Err(e) => return DalleResult::failure(format!("蒙版图片处理失败: {}", e)),
+            };
+            let part = match image_part(mask_bytes, "mask", "png", "image/png") {
                 Ok(part) => part,
                 Err(e) => return DalleResult::failure(format!("蒙版图片处理失败: {}", e)),
             };
@@ -358,10 +582,13 @@ pub async fn dalle_generate_image(params: DalleRequestParams) -> DalleResult {
             negative_prompt: params.negative_prompt.clone(),
             guidance_scale: params.guidance_scale,
             watermark: params.watermark,
+            seed,
             background: params.background.clone(),
             output_format: params.output_format.clone(),
             output_compression: params.output_compression,
             moderation: params.moderation.clone(),
+            stream: streaming.then_some(true),
+            partial_images: partial_images.filter(|_| streaming),
         };
 
         request_builder
@@ -399,6 +626,15 @@ pub async fn dalle_generate_image(params: DalleRequestParams) -> DalleResult {
         return DalleResult::failure(format!("API 返回错误 ({}): {}", status, error_text));
     }
 
+    if streaming {
+        let mut result = read_image_stream(response, &params, on_partial).await;
+        println!("[Rust] Stream finished in {:?}", start_time.elapsed());
+        if result.success && !preprocessing.is_empty() {
+            result.preprocessing = Some(preprocessing);
+        }
+        return result;
+    }
+
     // 解析响应
     let response_text = match response.text().await {
         Ok(t) => t,
@@ -424,56 +660,135 @@ pub async fn dalle_generate_image(params: DalleRequestParams) -> DalleResult {
 
     // 提取结果
     if let Some(data) = dalle_response.data {
-        let mut image_data_list = Vec::new();
-        let mut image_urls = Vec::new();
-        let mut revised_prompt = None;
+        let revised_prompt = data.iter().find_map(|item| item.revised_prompt.clone());
+        let (image_data_list, image_sources): (Vec<String>, Vec<Option<String>>) =
+            match collect_images(&client, &data).await {
+                Ok(images) => images.into_iter().unzip(),
+                Err(failed) => return failed,
+            };
 
-        for (index, image_data) in data.iter().enumerate() {
-            if revised_prompt.is_none() {
-                revised_prompt = image_data.revised_prompt.clone();
+        if !image_data_list.is_empty() {
+            let mut result = DalleResult::success(image_data_list, image_sources, revised_prompt);
+            result.usage = dalle_response.usage;
+            if !preprocessing.is_empty() {
+                result.preprocessing = Some(preprocessing);
             }
+            return result;
+        }
 
-            println!(
-                "[Rust] OpenAI Images result #{}: has_b64={}, has_url={}",
-                index + 1,
-                image_data.b64_json.is_some(),
-                image_data.url.is_some()
-            );
+        return DalleResult::failure("API 未返回图片数据或 URL");
+    }
 
-            if let Some(url) = &image_data.url {
-                image_urls.push(url.clone());
-            }
+    DalleResult::failure("API 未返回有效内容")
+}
 
-            if let Some(b64) = &image_data.b64_json {
-                image_data_list.push(b64.clone());
-                continue;
-            }
+/// 逐项取出结果图片及其地址，只有 URL 的项下载后保存，数据与地址始终对应同一项
+async fn collect_images(
+    client: &Client,
+    data: &[DalleImageData],
+) -> Result<Vec<(String, Option<String>)>, DalleResult> {
+    let mut images = Vec::new();
+    for (index, image_data) in data.iter().enumerate() {
+        println!(
+            "[Rust] OpenAI Images result #{}: has_b64={}, has_url={}",
+            index + 1,
+            image_data.b64_json.is_some(),
+            image_data.url.is_some()
+        );
+
+        if let Some(b64) = &image_data.b64_json {
+            images.push((b64.clone(), image_data.url.clone()));
+            continue;
+        }
 
-            if let Some(url) = &image_data.url {
-                println!(
-                    "[Rust] No base64 data for #{}; downloading from URL...",
-                    index + 1
-                );
-                match download_image_as_base64(&client, url).await {
-                    Ok(base64_data) => image_data_list.push(base64_data),
-                    Err(e) => {
-                        println!("[Rust] Failed to download image #{}: {}", index + 1, e);
-                        return DalleResult::failure_with_image_context(
-                            format!("图片生成成功但下载失败: {}", e),
-                            Some(url.clone()),
-                            image_data.revised_prompt.clone(),
-                        );
-                    }
+        if let Some(url) = &image_data.url {
+            println!(
+                "[Rust] No base64 data for #{}; downloading from URL...",
+                index + 1
+            );
+            match download_image_as_base64(client, url).await {
+                Ok(base64_data) => images.push((base64_data, Some(url.clone()))),
+                Err(e) => {
+                    println!("[Rust] Failed to download image #{}: {}", index + 1, e);
+                    return Err(DalleResult::failure_with_image_context(
+                        format!("图片生成成功但下载失败: {}", e),
+                        Some(url.clone()),
+                        image_data.revised_prompt.clone(),
+                    ));
                 }
             }
         }
+    }
+    Ok(images)
+}
 
-        if !image_data_list.is_empty() {
-            return DalleResult::success(image_data_list, image_urls, revised_prompt);
-        }
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{mock_http, MockResponse};
+
+    #[test]
+    fn test_sse_parser_handles_split_chunks() {
+        let mut parser = SseParser::default();
+        assert!(parser
+            .push(b"event: image_generation.partial_image\r\ndata: {\"a\":")
+            .is_empty());
+        let events = parser.push(b"1}\r\n\r\ndata: [DONE]\n\n: keep-alive\n\n");
+        assert_eq!(events, vec!["{\"a\":1}".to_string(), "[DONE]".to_string()]);
+        assert!(parser.buffer.is_empty());
+    }
 
-        return DalleResult::failure("API 未返回图片数据或 URL");
+    #[test]
+    fn test_sse_parser_flushes_last_event_at_eof() {
+        let mut parser = SseParser::default();
+        let events = parser.push(b"data: {\"a\":1}\n\ndata: {\"b\":");
+        assert_eq!(events, vec!["{\"a\":1}".to_string()]);
+        assert!(parser.push(b"2}\n").is_empty());
+        assert_eq!(parser.finish(), Some("{\"b\":2}".to_string()));
+        assert_eq!(parser.finish(), None);
+
+        let mut parser = SseParser::default();
+        parser.push(b": keep-alive\n");
+        assert_eq!(parser.finish(), None);
     }
 
-    DalleResult::failure("API 未返回有效内容")
+    #[test]
+    fn test_accepts_seed() {
+        assert!(!accepts_seed("gpt-image-1"));
+        assert!(!accepts_seed("dall-e-3"));
+        assert!(accepts_seed("doubao-seedream-4-0"));
+    }
+
+    #[tokio::test]
+    async fn test_collect_images_keeps_urls_with_their_entries() {
+        let base_url = mock_http(|_| async {
+            MockResponse::new("200 OK", "image/png", b"downloaded".to_vec())
+        })
+        .await;
+        let url = format!("{}/b.png", base_url);
+        let data: Vec<DalleImageData> = serde_json::from_value(serde_json::json!([
+            { "b64_json": "YQ==" },
+            { "url": url },
+            { "b64_json": "Yw==", "url": "https://cdn.example.com/c.png" },
+        ]))
+        .unwrap();
+
+        let images = collect_images(&Client::new(), &data).await.unwrap();
+        assert_eq!(
+            images,
+            vec![
+                ("YQ==".to_string(), None),
+                (BASE64.encode(b"downloaded"), Some(url)),
+                (
+                    "Yw==".to_string(),
+                    Some("https://cdn.example.com/c.png".to_string())
+                ),
+            ]
+        );
+
+        let (data_list, sources): (Vec<String>, Vec<Option<String>>) = images.into_iter().unzip();
+        let result = DalleResult::success(data_list, sources, None);
+        assert_eq!(result.image_sources[0], None);
+        assert_eq!(result.image_urls.map(|urls| urls.len()), Some(2));
+    }
 }
@@ -0,0 +1,289 @@
+//! 图片描述 / 反推提示词
+//!
+//! 把图片交给已配置的视觉模型（Gemini、OpenAI 兼容接口或 Claude），
+//! 得到结构化的画面描述、可复现的提示词、标签和替代文本，
+//! 可选写入图片的元数据文件，供搜索和导出时使用。
+
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::image_utils::{decode_base64_image, guess_image_mime, strip_data_url_prefix};
+use crate::storage::{write_image_description, ImageDescription};
+use crate::{gemini, llm};
+
+const MAX_TAGS: usize = 20;
+
+// 前端调用的参数
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DescribeImageParams {
+    pub provider: String, // "gemini" | "openai" | "claude"
+    pub base_url: String,
+    pub api_key: String,
+    pub model: String,
+    pub image_data: Option<String>, // base64，与 image_path 二选一
+    pub image_path: Option<String>, // 本地图片路径，保存描述时必填
+    pub language: Option<String>,   // 描述和替代文本的语言，默认中文；提示词固定为英文
+    pub temperature: Option<f64>,
+    pub max_tokens: Option<i32>,
+    pub save_to_metadata: Option<bool>, // 默认在提供 image_path 时写入元数据
+}
+
+// 返回结果
+#[derive(Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DescribeImageResult {
+    pub success: bool,
+    pub description: Option<ImageDescription>,
+    pub metadata_saved: bool,
+    pub error: Option<String>,
+}
+
+impl DescribeImageResult {
+    fn failure(error: impl Into<String>) -> Self {
+        Self {
+            success: false,
+            error: Some(error.into()),
+            ..Self::default()
+        }
+    }
+}
+
+// 模型返回的原始结构，兼容驼峰与下划线字段名
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct RawDescription {
+    caption: String,
+    prompt: String,
+    tags: Value,
+    #[serde(alias = "altText")]
+    alt_text: String,
+}
+
+fn system_prompt(language: &str) -> String {
+    format!(
+        "你是一名图像分析助手，负责为图片撰写描述并反推生成提示词。\
+         只输出一个 JSON 对象，不要输出其他内容，字段如下：\n\
+         - caption：用{language}详细描述画面的主体、构图、光照、色彩和风格；\n\
+         - prompt：用英文写一段可以让文生图模型复现这张图的提示词，包含主体、场景、风格、镜头和光照等关键词；\n\
+         - tags：5 到 15 个用于搜索的{language}关键词数组；\n\
+         - alt_text：用{language}写一句不超过 125 个字符的替代文本，适合放在演示文稿中。"
+    )
+}
+
+fn response_schema() -> Value {
+    json!({
+        "type": "object",
+        "properties": {
+            "caption": { "type": "string" },
+            "prompt": { "type": "string" },
+            "tags": { "type": "array", "items": { "type": "string" } },
+            "alt_text": { "type": "string" }
+        },
+        "required": ["caption", "prompt", "tags", "alt_text"]
+    })
+}
+
+/// 解析模型返回的 JSON（可能被 markdown 代码块包裹）
+fn parse_description(text: &str) -> Result<ImageDescription, String> {
+    let start = text.find('{');
+    let end = text.rfind('}');
+    let json_text = match (start, end) {
+        (Some(start), Some(end)) if start < end => &text[start..=end],
+        _ => return Err("模型没有返回 JSON 描述".to_string()),
+    };
+    let raw: RawDescription =
+        serde_json::from_str(json_text).map_err(|e| format!("解析描述失败: {}", e))?;
+
+    // 标签可能是数组，也可能是逗号分隔的字符串
+    let tags: Vec<String> = match raw.tags {
+        Value::Array(items) => items
+            .iter()
+            .filter_map(|tag| tag.as_str().map(|t| t.to_string()))
+            .collect(),
+        Value::String(text) => text
+            .split([',', '，', '、'])
+            .map(|t| t.to_string())
+            .collect(),
+        _ => vec![],
+    };
+    let mut unique: Vec<String> = Vec::new();
+    for tag in tags {
+        let tag = tag.trim().trim_start_matches('#').to_string();
+        if !tag.is_empty() && !unique.contains(&tag) && unique.len() < MAX_TAGS {
+            unique.push(tag);
+        }
+    }
+
+    let description = ImageDescription {
+        caption: raw.caption.trim().to_string(),
+        prompt: raw.prompt.trim().to_string(),
+        tags: unique,
+        alt_text: raw.alt_text.trim().to_string(),
+        model: None,
+        created_at: chrono::Utc::now().timestamp(),
+    };
+    if description.caption.is_empty() && description.prompt.is_empty() {
+        return Err("模型返回的描述为空".to_string());
+    }
+    Ok(description)
+}
+
+/// 调用视觉模型，返回原始文本
+async fn request_description(
+    params: &DescribeImageParams,
+    data: String,
+    mime_type: String,
+) -> Result<String, String> {
+    let language = params
+        .language
+        .clone()
+        .filter(|l| !l.trim().is_empty())
+        .unwrap_or_else(|| "中文".to_string());
+    let prompt = "请分析这张图片，按要求输出 JSON。".to_string();
+
+    // 两个模块的返回结构不同，统一为 (success, content, error)
+    let (success, content, error) = match params.provider.as_str() {
+        "gemini" => {
+            let result = gemini::gemini_generate_text(gemini::LLMRequestParams {
+                base_url: params.base_url.clone(),
+                api_key: params.api_key.clone(),
+                model: params.model.clone(),
+                prompt,
+                system_prompt: Some(system_prompt(&language)),
+                output_format: None,
+                temperature: params.temperature,
+                max_tokens: params.max_tokens,
+                files: Some(vec![gemini::FileData {
+                    data,
+                    mime_type,
+                    file_name: None,
+                }]),
+                response_format: Some("json_schema".to_string()),
+                response_json_schema: Some(response_schema()),
+            })
+            .await;
+            (result.success, result.content, result.error)
+        }
+        "openai" | "claude" => {
+            let request = llm::LLMRequestParams {
+                base_url: params.base_url.clone(),
+                api_key: params.api_key.clone(),
+                model: params.model.clone(),
+                prompt,
+                system_prompt: Some(system_prompt(&language)),
+                temperature: params.temperature,
+                max_tokens: params.max_tokens,
+                files: Some(vec![llm::FileData {
+                    data,
+                    mime_type,
+                    file_name: None,
+                }]),
+                response_format: Some("json_schema".to_string()),
+                response_json_schema: Some(response_schema()),
+            };
+            // Claude 接口不支持结构化输出，依赖系统提示词约束格式
+            let result = if params.provider == "openai" {
+                llm::openai_chat_completion(request).await
+            } else {
+                llm::claude_chat_completion(request).await
+            };
+            (result.success, result.content, result.error)
+        }
+        other => return Err(format!("不支持的视觉模型服务: {}", other)),
+    };
+
+    match (success, content) {
+        (true, Some(content)) => Ok(content),
+        _ => Err(error.unwrap_or_else(|| "模型没有返回内容".to_string())),
+    }
+}
+
+// Tauri 命令：描述图片并反推提示词
+#[tauri::command]
+pub async fn describe_image(params: DescribeImageParams) -> DescribeImageResult {
+    println!(
+        "[Rust] describe_image called, provider: {}, model: {}",
+        params.provider, params.model
+    );
+
+    let bytes = match (&params.image_data, &params.image_path) {
+        (Some(data), _) if !data.trim().is_empty() => decode_base64_image(data),
+        (_, Some(path)) => std::fs::read(path).map_err(|e| format!("读取图片失败: {}", e)),
+        _ => Err("需要提供图片数据或路径".to_string()),
+    };
+    let bytes = match bytes {
+        Ok(bytes) => bytes,
+        Err(e) => return DescribeImageResult::failure(e),
+    };
+    let (mime_type, _) = guess_image_mime(&bytes);
+    let data = match &params.image_data {
+        Some(data) if !data.trim().is_empty() => strip_data_url_prefix(data).to_string(),
+        _ => BASE64.encode(&bytes),
+    };
+
+    let mut description = match request_description(&params, data, mime_type.to_string()).await {
+        Ok(text) => match parse_description(&text) {
+            Ok(description) => description,
+            Err(e) => {
+                println!("[Rust] describe_image raw response: {}", text);
+                return DescribeImageResult::failure(e);
+            }
+        },
+        Err(e) => return DescribeImageResult::failure(e),
+    };
+    description.model = Some(params.model.clone());
+
+    // 有图片路径时默认写入元数据文件
+    let mut metadata_saved = false;
+    if let Some(path) = &params.image_path {
+        if params.save_to_metadata.unwrap_or(true) {
+            match write_image_description(path, description.clone()) {
+                Ok(_) => metadata_saved = true,
+                Err(e) => {
+                    return DescribeImageResult {
+                        success: false,
+                        description: Some(description),
+                        metadata_saved: false,
+                        error: Some(e),
+                    }
+                }
+            }
+        }
+    }
+
+    println!(
+        "[Rust] describe_image finished, {} tag(s), saved: {}",
+        description.tags.len(),
+        metadata_saved
+    );
+    DescribeImageResult {
+        success: true,
+        description: Some(description),
+        metadata_saved,
+        error: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_description_from_fenced_json() {
+        let text = "```json\n{\"caption\": \"一只橘猫趴在窗台上\", \"prompt\": \"an orange cat lying on a windowsill, soft morning light\", \"tags\": [\"猫\", \"窗台\", \"猫\"], \"altText\": \"窗台上的橘猫\"}\n```";
+        let description = parse_description(text).unwrap();
+        assert_eq!(description.caption, "一只橘猫趴在窗台上");
+        assert_eq!(description.tags, vec!["猫", "窗台"]);
+        assert_eq!(description.alt_text, "窗台上的橘猫");
+    }
+
+    #[test]
+    fn test_parse_description_accepts_string_tags() {
+        let text = r##"{"caption": "city at night", "prompt": "neon city", "tags": "#city, neon，night", "alt_text": "A neon city"}"##;
+        let description = parse_description(text).unwrap();
+        assert_eq!(description.tags, vec!["city", "neon", "night"]);
+        assert!(parse_description("抱歉，我无法描述这张图片").is_err());
+    }
+}
@@ -0,0 +1,796 @@
+// 图片内嵌元数据
+// 将提示词和生成参数写入图片文件本身（PNG iTXt、JPEG/WebP XMP），
+// 并能从任意导入的图片中读回 ImageMetadata，保证图片导出/拖出应用后仍保留来源信息
+
+use crate::storage::{GenerationParams, ImageMetadata};
+
+/// 内嵌 JSON 元数据使用的关键字（PNG iTXt keyword / XMP 属性名）
+const METADATA_KEYWORD: &str = "nextcreator:metadata";
+/// XMP 命名空间
+const XMP_NAMESPACE: &str = "https://nextcreator.app/ns/1.0/";
+/// JPEG APP1 XMP 段标识
+const JPEG_XMP_HEADER: &[u8] = b"http://ns.adobe.com/xap/1.0/\0";
+/// JPEG APP1 EXIF 段标识
+const JPEG_EXIF_HEADER: &[u8] = b"Exif\0\0";
+/// PNG 文件签名
+const PNG_SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+/// JPEG 单个段的最大负载长度（长度字段本身占 2 字节）
+const JPEG_MAX_SEGMENT_PAYLOAD: usize = 65533;
+
+/// 将元数据写入图片字节，返回新的图片字节
+/// 支持 PNG / JPEG / WebP，其他格式返回错误（调用方可回退为原始字节）
+pub fn embed_metadata(image_data: &[u8], metadata: &ImageMetadata) -> Result<Vec<u8>, String> {
+    let json = serde_json::to_string(metadata).map_err(|e| format!("序列化元数据失败: {}", e))?;
+    let prompt = metadata.prompt.as_deref().unwrap_or("");
+
+    if image_data.starts_with(PNG_SIGNATURE) {
+        embed_png(image_data, prompt, &json)
+    } else if image_data.starts_with(b"\xff\xd8\xff") {
+        embed_jpeg(image_data, &build_xmp_packet(prompt, &json))
+    } else if is_webp(image_data) {
+        embed_webp(image_data, &build_xmp_packet(prompt, &json))
+    } else {
+        Err("不支持写入元数据的图片格式".to_string())
+    }
+}
+
+/// 从图片字节中读取元数据
+/// 优先读取本应用写入的完整 JSON，其次回退到通用字段（PNG 文本、XMP dc:description、EXIF ImageDescription）
+pub fn extract_metadata(image_data: &[u8]) -> Option<ImageMetadata> {
+    if image_data.starts_with(PNG_SIGNATURE) {
+        extract_png(image_data)
+    } else if image_data.starts_with(b"\xff\xd8\xff") {
+        extract_jpeg(image_data)
+    } else if is_webp(image_data) {
+        extract_webp(image_data)
+    } else {
+        None
+    }
+}
+
+fn is_webp(data: &[u8]) -> bool {
+    data.len() >= 12 && &data[0..4] == b"RIFF" && &data[8..12] == b"WEBP"
+}
+
+/// 只有提示词时构造的元数据（用于外部软件写入的图片）
+fn metadata_from_prompt(prompt: String) -> Option<ImageMetadata> {
+    let prompt = prompt.trim().to_string();
+    if prompt.is_empty() {
+        return None;
+    }
+    Some(ImageMetadata {
+        prompt: Some(prompt),
+        input_images: vec![],
+        node_id: None,
+        canvas_id: None,
+        created_at: chrono::Utc::now().timestamp(),
+        generation: None,
+        description: None,
+    })
+}
+
+/// 拆分 `key: value, key: "value, with comma"` 形式的参数行
+fn split_infotext_params(line: &str) -> Vec<(String, String)> {
+    let mut pairs = Vec::new();
+    let mut current = String::new();
+    let mut in_quotes = false;
+    let mut fields = Vec::new();
+
+    for c in line.chars() {
+        match c {
+            '"' => {
+                in_quotes = !in_quotes;
+                current.push(c);
+            }
+            ',' if !in_quotes => fields.push(std::mem::take(&mut current)),
+            _ => current.push(c),
+        }
+    }
+    fields.push(current);
+
+    for field in fields {
+        if let Some((key, value)) = field.split_once(':') {
+            let value = value.trim().trim_matches('"').to_string();
+            pairs.push((key.trim().to_string(), value));
+        }
+    }
+    pairs
+}
+
+/// 解析 SD WebUI 的 infotext（parameters），返回 (正向提示词, 生成参数)
+pub fn parse_infotext(text: &str) -> (String, GenerationParams) {
+    let mut lines: Vec<&str> = text.trim().lines().collect();
+    let mut generation = GenerationParams {
+        provider: Some("sd-webui".to_string()),
+        ..GenerationParams::default()
+    };
+
+    // 最后一行是参数行（以 Steps: 开头）
+    let params_line = match lines.last() {
+        Some(last) if last.trim_start().starts_with("Steps:") => lines.pop(),
+        _ => None,
+    };
+
+    let mut prompt_lines = Vec::new();
+    let mut negative_lines = Vec::new();
+    let mut in_negative = false;
+    for line in lines {
+        if let Some(rest) = line.strip_prefix("Negative prompt:") {
+            in_negative = true;
+            negative_lines.push(rest.trim_start());
+        } else if in_negative {
+            negative_lines.push(line);
+        } else {
+            prompt_lines.push(line);
+        }
+    }
+    if !negative_lines.is_empty() {
+        generation.negative_prompt = Some(negative_lines.join("\n").trim().to_string());
+    }
+
+    let mut extra = serde_json::Map::new();
+    for (key, value) in params_line.map(split_infotext_params).unwrap_or_default() {
+        match key.as_str() {
+            "Steps" => generation.steps = value.parse().ok(),
+            "Sampler" => generation.sampler = Some(value),
+            "Schedule type" => generation.scheduler = Some(value),
+            "CFG scale" => generation.cfg_scale = value.parse().ok(),
+            "Seed" => generation.seed = value.parse().ok(),
+            "Model" => generation.model = Some(value),
+            "Denoising strength" => generation.denoising_strength = value.parse().ok(),
+            "Size" => {
+                if let Some((w, h)) = value.split_once('x') {
+                    generation.width = w.trim().parse().ok();
+                    generation.height = h.trim().parse().ok();
+                }
+            }
+            _ => {
+                extra.insert(key, serde_json::Value::String(value));
+            }
+        }
+    }
+    if !extra.is_empty() {
+        generation.extra = Some(serde_json::Value::Object(extra));
+    }
+
+    (prompt_lines.join("\n").trim().to_string(), generation)
+}
+
+// ==================== PNG ====================
+
+/// 遍历 PNG chunk，返回 (类型, 数据, chunk 起始偏移, chunk 总长度)
+fn png_chunks(data: &[u8]) -> Vec<([u8; 4], &[u8], usize, usize)> {
+    let mut chunks = Vec::new();
+    let mut offset = PNG_SIGNATURE.len();
+
+    while offset + 12 <= data.len() {
+        let length = u32::from_be_bytes([
+            data[offset],
+            data[offset + 1],
+            data[offset + 2],
+            data[offset + 3],
+        ]) as usize;
+        let total = 12 + length;
+        if offset + total > data.len() {
+            break;
+        }
+        let mut chunk_type = [0u8; 4];
+        chunk_type.copy_from_slice(&data[offset + 4..offset + 8]);
+        chunks.push((
+            chunk_type,
+            &data[offset + 8..offset + 8 + length],
+            offset,
+            total,
+        ));
+        offset += total;
+        if &chunk_type == b"IEND" {
+            break;
+        }
+    }
+
+    chunks
+}
+
+fn png_chunk(chunk_type: &[u8; 4], payload: &[u8]) -> Vec<u8> {
+    let mut chunk = Vec::with_capacity(payload.len() + 12);
+    chunk.extend_from_slice(&(payload.len() as u32).to_be_bytes());
+    chunk.extend_from_slice(chunk_type);
+    chunk.extend_from_slice(payload);
+
+    let mut hasher = crc32fast::Hasher::new();
+    hasher.update(chunk_type);
+    hasher.update(payload);
+    chunk.extend_from_slice(&hasher.finalize().to_be_bytes());
+    chunk
+}
+
+/// 构造未压缩的 iTXt chunk（UTF-8 文本）
+fn png_itxt_chunk(keyword: &str, text: &str) -> Vec<u8> {
+    let mut payload = Vec::with_capacity(keyword.len() + text.len() + 5);
+    payload.extend_from_slice(keyword.as_bytes());
+    payload.push(0); // keyword 结束
+    payload.push(0); // 压缩标志：未压缩
+    payload.push(0); // 压缩方法
+    payload.push(0); // 语言标签（空）
+    payload.push(0); // 翻译关键字（空）
+    payload.extend_from_slice(text.as_bytes());
+    png_chunk(b"iTXt", &payload)
+}
+
+/// 解析 tEXt / iTXt chunk，返回 (keyword, text)；压缩的 iTXt 忽略
+fn parse_png_text_chunk(chunk_type: &[u8; 4], payload: &[u8]) -> Option<(String, String)> {
+    let keyword_end = payload.iter().position(|b| *b == 0)?;
+    let keyword = String::from_utf8_lossy(&payload[..keyword_end]).to_string();
+    let rest = &payload[keyword_end + 1..];
+
+    match chunk_type {
+        b"tEXt" => {
+            // tEXt 为 Latin-1 编码
+            let text: String = rest.iter().map(|b| *b as char).collect();
+            Some((keyword, text))
+        }
+        b"iTXt" => {
+            if rest.len() < 2 || rest[0] != 0 {
+                return None;
+            }
+            let rest = &rest[2..];
+            let language_end = rest.iter().position(|b| *b == 0)?;
+            let rest = &rest[language_end + 1..];
+            let translated_end = rest.iter().position(|b| *b == 0)?;
+            let text = String::from_utf8_lossy(&rest[translated_end + 1..]).to_string();
+            Some((keyword, text))
+        }
+        _ => None,
+    }
+}
+
+fn embed_png(data: &[u8], prompt: &str, json: &str) -> Result<Vec<u8>, String> {
+    let chunks = png_chunks(data);
+    if chunks.last().map(|c| &c.0 != b"IEND").unwrap_or(true) {
+        return Err("PNG 文件结构不完整".to_string());
+    }
+
+    let mut output = Vec::with_capacity(data.len() + json.len() + prompt.len() + 64);
+    output.extend_from_slice(PNG_SIGNATURE);
+
+    for (chunk_type, payload, offset, total) in &chunks {
+        // 移除旧的同名文本 chunk，避免重复写入
+        if chunk_type == b"tEXt" || chunk_type == b"iTXt" {
+            if let Some((keyword, _)) = parse_png_text_chunk(chunk_type, payload) {
+                if keyword == METADATA_KEYWORD || keyword == "Description" || keyword == "Software"
+                {
+                    continue;
+                }
+            }
+        }
+
+        if chunk_type == b"IEND" {
+            output.extend_from_slice(&png_chunk(b"tEXt", b"Software\0NextCreator"));
+            if !prompt.is_empty() {
+                output.extend_from_slice(&png_itxt_chunk("Description", prompt));
+            }
+            output.extend_from_slice(&png_itxt_chunk(METADATA_KEYWORD, json));
+        }
+
+        output.extend_from_slice(&data[*offset..*offset + *total]);
+    }
+
+    Ok(output)
+}
+
+fn extract_png(data: &[u8]) -> Option<ImageMetadata> {
+    let mut fallback_prompt: Option<String> = None;
+    let mut fallback_generation: Option<GenerationParams> = None;
+
+    for (chunk_type, payload, _, _) in png_chunks(data) {
+        let Some((keyword, text)) = parse_png_text_chunk(&chunk_type, payload) else {
+            continue;
+        };
+
+        match keyword.as_str() {
+            METADATA_KEYWORD => {
+                if let Ok(metadata) = serde_json::from_str::<ImageMetadata>(&text) {
+                    return Some(metadata);
+                }
+            }
+            "XML:com.adobe.xmp" => {
+                if let Some(metadata) = parse_xmp_packet(&text) {
+                    return Some(metadata);
+                }
+            }
+            // SD WebUI 写入的 parameters 首行为正向提示词
+            "parameters" if fallback_prompt.is_none() => {
+                let (prompt, generation) = parse_infotext(&text);
+                fallback_prompt = Some(prompt);
+                fallback_generation = Some(generation);
+            }
+            "Description" | "prompt" | "Comment" if fallback_prompt.is_none() => {
+                fallback_prompt = Some(text);
+            }
+            _ => {}
+        }
+    }
+
+    fallback_prompt
+        .and_then(metadata_from_prompt)
+        .map(|mut metadata| {
+            metadata.generation = fallback_generation;
+            metadata
+        })
+}
+
+// ==================== XMP ====================
+
+fn xml_escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn xml_unescape(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+fn build_xmp_packet(prompt: &str, json: &str) -> String {
+    let description = if prompt.is_empty() {
+        String::new()
+    } else {
+        format!(
+            "<dc:description><rdf:Alt><rdf:li xml:lang=\"x-default\">{}</rdf:li></rdf:Alt></dc:description>",
+            xml_escape(prompt)
+        )
+    };
+
+    format!(
+        concat!(
+            "<?xpacket begin=\"\u{feff}\" id=\"W5M0MpCehiHzreSzNTczkc9d\"?>",
+            "<x:xmpmeta xmlns:x=\"adobe:ns:meta/\">",
+            "<rdf:RDF xmlns:rdf=\"http://www.w3.org/1999/02/22-rdf-syntax-ns#\">",
+            "<rdf:Description rdf:about=\"\" ",
+            "xmlns:dc=\"http://purl.org/dc/elements/1.1/\" ",
+            "xmlns:xmp=\"http://ns.adobe.com/xap/1.0/\" ",
+            "xmlns:nextcreator=\"{ns}\">",
+            "<xmp:CreatorTool>NextCreator</xmp:CreatorTool>",
+            "{description}",
+            "<{keyword}>{json}</{keyword}>",
+            "</rdf:Description>",
+            "</rdf:RDF>",
+            "</x:xmpmeta>",
+            "<?xpacket end=\"w\"?>"
+        ),
+        ns = XMP_NAMESPACE,
+        description = description,
+        keyword = METADATA_KEYWORD,
+        json = xml_escape(json),
+    )
+}
+
+/// 截取 XML 中 `<tag ...>内容</tag>` 的内容
+fn xml_element_text<'a>(xml: &'a str, tag: &str) -> Option<&'a str> {
+    let open = format!("<{}", tag);
+    let start = xml.find(&open)?;
+    let after_open = &xml[start + open.len()..];
+    // 确保匹配的是完整标签名
+    if !after_open.starts_with('>') && !after_open.starts_with(' ') {
+        return None;
+    }
+    let content_start = after_open.find('>')? + 1;
+    let content = &after_open[content_start..];
+    let close = format!("</{}>", tag);
+    let end = content.find(&close)?;
+    Some(&content[..end])
+}
+
+fn parse_xmp_packet(xmp: &str) -> Option<ImageMetadata> {
+    if let Some(json) = xml_element_text(xmp, METADATA_KEYWORD) {
+        if let Ok(metadata) = serde_json::from_str::<ImageMetadata>(&xml_unescape(json)) {
+            return Some(metadata);
+        }
+    }
+
+    let description = xml_element_text(xmp, "dc:description")?;
+    let prompt = xml_element_text(description, "rdf:li").unwrap_or(description);
+    metadata_from_prompt(xml_unescape(prompt))
+}
+
+// ==================== JPEG ====================
+
+/// 遍历 JPEG 头部段（SOS 之前），返回 (marker, 负载, 段起始偏移, 段总长度)
+fn jpeg_segments(data: &[u8]) -> Vec<(u8, &[u8], usize, usize)> {
+    let mut segments = Vec::new();
+    let mut offset = 2;
+
+    while offset + 4 <= data.len() && data[offset] == 0xFF {
+        let marker = data[offset + 1];
+        // SOS 之后是压缩数据，停止解析
+        if marker == 0xDA || marker == 0xD9 {
+            break;
+        }
+        let length = u16::from_be_bytes([data[offset + 2], data[offset + 3]]) as usize;
+        if length < 2 || offset + 2 + length > data.len() {
+            break;
+        }
+        segments.push((
+            marker,
+            &data[offset + 4..offset + 2 + length],
+            offset,
+            2 + length,
+        ));
+        offset += 2 + length;
+    }
+
+    segments
+}
+
+fn embed_jpeg(data: &[u8], xmp: &str) -> Result<Vec<u8>, String> {
+    let mut payload = Vec::with_capacity(JPEG_XMP_HEADER.len() + xmp.len());
+    payload.extend_from_slice(JPEG_XMP_HEADER);
+    payload.extend_from_slice(xmp.as_bytes());
+    if payload.len() > JPEG_MAX_SEGMENT_PAYLOAD {
+        return Err("元数据过大，无法写入 JPEG XMP 段".to_string());
+    }
+
+    let segments = jpeg_segments(data);
+    let header_end = segments
+        .last()
+        .map(|(_, _, offset, total)| offset + total)
+        .unwrap_or(2);
+    let is_xmp = |marker: u8, segment_payload: &[u8]| {
+        marker == 0xE1 && segment_payload.starts_with(JPEG_XMP_HEADER)
+    };
+
+    // XMP 段放在开头的 JFIF/EXIF 段之后
+    let leading = segments
+        .iter()
+        .take_while(|(marker, segment_payload, _, _)| {
+            *marker == 0xE0 || (*marker == 0xE1 && !is_xmp(*marker, segment_payload))
+        })
+        .count();
+
+    let mut output = Vec::with_capacity(data.len() + payload.len() + 4);
+    output.extend_from_slice(&data[..2]);
+    for (_, _, offset, total) in &segments[..leading] {
+        output.extend_from_slice(&data[*offset..*offset + *total]);
+    }
+
+    output.extend_from_slice(&[0xFF, 0xE1]);
+    output.extend_from_slice(&((payload.len() + 2) as u16).to_be_bytes());
+    output.extend_from_slice(&payload);
+
+    // 复制其余头部段，剔除旧的 XMP 段
+    for (marker, segment_payload, offset, total) in &segments[leading..] {
+        if !is_xmp(*marker, segment_payload) {
+            output.extend_from_slice(&data[*offset..*offset + *total]);
+        }
+    }
+    output.extend_from_slice(&data[header_end..]);
+
+    Ok(output)
+}
+
+fn extract_jpeg(data: &[u8]) -> Option<ImageMetadata> {
+    let mut exif_description: Option<String> = None;
+
+    for (marker, payload, _, _) in jpeg_segments(data) {
+        if marker != 0xE1 {
+            continue;
+        }
+        if let Some(xmp) = payload.strip_prefix(JPEG_XMP_HEADER) {
+            if let Some(metadata) = parse_xmp_packet(&String::from_utf8_lossy(xmp)) {
+                return Some(metadata);
+            }
+        } else if let Some(tiff) = payload.strip_prefix(JPEG_EXIF_HEADER) {
+            if exif_description.is_none() {
+                exif_description = exif_image_description(tiff);
+            }
+        }
+    }
+
+    exif_description.and_then(metadata_from_prompt)
+}
+
+/// 从 EXIF（TIFF 结构）IFD0 中读取 ImageDescription（0x010E）
+fn exif_image_description(tiff: &[u8]) -> Option<String> {
+    if tiff.len() < 8 {
+        return None;
+    }
+    let little_endian = match &tiff[0..2] {
+        b"II" => true,
+        b"MM" => false,
+        _ => return None,
+    };
+    let read_u16 = |offset: usize| -> Option<u16> {
+        let bytes = [*tiff.get(offset)?, *tiff.get(offset + 1)?];
+        Some(if little_endian {
+            u16::from_le_bytes(bytes)
+        } else {
+            u16::from_be_bytes(bytes)
+        })
+    };
+    let read_u32 = |offset: usize| -> Option<u32> {
+        let bytes = [
+            *tiff.get(offset)?,
+            *tiff.get(offset + 1)?,
+            *tiff.get(offset + 2)?,
+            *tiff.get(offset + 3)?,
+        ];
+        Some(if little_endian {
+            u32::from_le_bytes(bytes)
+        } else {
+            u32::from_be_bytes(bytes)
+        })
+    };
+
+    let ifd_offset = read_u32(4)? as usize;
+    let entry_count = read_u16(ifd_offset)? as usize;
+    for i in 0..entry_count {
+        let entry = ifd_offset + 2 + i * 12;
+        if read_u16(entry)? != 0x010E {
+            continue;
+        }
+        let count = read_u32(entry + 4)? as usize;
+        let value = if count <= 4 {
+            tiff.get(entry + 8..entry + 8 + count)?
+        } else {
+            let value_offset = read_u32(entry + 8)? as usize;
+            tiff.get(value_offset..value_offset + count)?
+        };
+        let text = String::from_utf8_lossy(value)
+            .trim_end_matches('\0')
+            .to_string();
+        return Some(text);
+    }
+
+    None
+}
+
+// ==================== WebP ====================
+
+/// 遍历 WebP RIFF chunk，返回 (FourCC, 负载, chunk 起始偏移, chunk 总长度（含填充）)
+fn webp_chunks(data: &[u8]) -> Vec<([u8; 4], &[u8], usize, usize)> {
+    let mut chunks = Vec::new();
+    let mut offset = 12;
+
+    while offset + 8 <= data.len() {
+        let mut fourcc = [0u8; 4];
+        fourcc.copy_from_slice(&data[offset..offset + 4]);
+        let size = u32::from_le_bytes([
+            data[offset + 4],
+            data[offset + 5],
+            data[offset + 6],
+            data[offset + 7],
+        ]) as usize;
+        let padded = size + (size & 1);
+        if offset + 8 + size > data.len() {
+            break;
+        }
+        let total = (8 + padded).min(data.len() - offset);
+        chunks.push((fourcc, &data[offset + 8..offset + 8 + size], offset, total));
+        offset += 8 + padded;
+    }
+
+    chunks
+}
+
+fn webp_chunk(fourcc: &[u8; 4], payload: &[u8]) -> Vec<u8> {
+    let mut chunk = Vec::with_capacity(payload.len() + 9);
+    chunk.extend_from_slice(fourcc);
+    chunk.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+    chunk.extend_from_slice(payload);
+    if payload.len() % 2 == 1 {
+        chunk.push(0);
+    }
+    chunk
+}
+
+fn embed_webp(data: &[u8], xmp: &str) -> Result<Vec<u8>, String> {
+    const XMP_FLAG: u8 = 0x04;
+    const ALPHA_FLAG: u8 = 0x10;
+
+    let chunks = webp_chunks(data);
+    if chunks.is_empty() {
+        return Err("WebP 文件结构不完整".to_string());
+    }
+
+    let mut body = Vec::with_capacity(data.len() + xmp.len() + 32);
+
+    if &chunks[0].0 == b"VP8X" {
+        // 扩展格式：设置 XMP 标志位
+        let mut vp8x = chunks[0].1.to_vec();
+        if vp8x.len() < 10 {
+            return Err("WebP VP8X 头不完整".to_string());
+        }
+        vp8x[0] |= XMP_FLAG;
+        body.extend_from_slice(&webp_chunk(b"VP8X", &vp8x));
+    } else {
+        // 简单格式：补充 VP8X 头才能携带 XMP
+        let (width, height) =
+            image::ImageReader::with_format(std::io::Cursor::new(data), image::ImageFormat::WebP)
+                .into_dimensions()
+                .map_err(|e| format!("读取 WebP 尺寸失败: {}", e))?;
+
+        let mut flags = XMP_FLAG;
+        // VP8L 头中第 29 位为 alpha_is_used
+        if &chunks[0].0 == b"VP8L" && chunks[0].1.len() >= 5 {
+            let bits = u32::from_le_bytes([
+                chunks[0].1[1],
+                chunks[0].1[2],
+                chunks[0].1[3],
+                chunks[0].1[4],
+            ]);
+            if (bits >> 28) & 1 == 1 {
+                flags |= ALPHA_FLAG;
+            }
+        }
+        if chunks.iter().any(|c| &c.0 == b"ALPH") {
+            flags |= ALPHA_FLAG;
+        }
+
+        let mut vp8x = vec![flags, 0, 0, 0];
+        vp8x.extend_from_slice(&(width - 1).to_le_bytes()[..3]);
+        vp8x.extend_from_slice(&(height - 1).to_le_bytes()[..3]);
+        body.extend_from_slice(&webp_chunk(b"VP8X", &vp8x));
+        body.extend_from_slice(&data[chunks[0].2..chunks[0].2 + chunks[0].3]);
+    }
+
+    for (fourcc, _, offset, total) in chunks.iter().skip(1) {
+        if fourcc == b"XMP " {
+            continue;
+        }
+        body.extend_from_slice(&data[*offset..*offset + *total]);
+    }
+    body.extend_from_slice(&webp_chunk(b"XMP ", xmp.as_bytes()));
+
+    let mut output = Vec::with_capacity(body.len() + 12);
+    output.extend_from_slice(b"RIFF");
+    output.extend_from_slice(&((body.len() + 4) as u32).to_le_bytes());
+    output.extend_from_slice(b"WEBP");
+    output.extend_from_slice(&body);
+    Ok(output)
+}
+
+fn extract_webp(data: &[u8]) -> Option<ImageMetadata> {
+    let mut exif_description: Option<String> = None;
+
+    for (fourcc, payload, _, _) in webp_chunks(data) {
+        match &fourcc {
+            b"XMP " => {
+                if let Some(metadata) = parse_xmp_packet(&String::from_utf8_lossy(payload)) {
+                    return Some(metadata);
+                }
+            }
+            b"EXIF" if exif_description.is_none() => {
+                let tiff = payload.strip_prefix(JPEG_EXIF_HEADER).unwrap_or(payload);
+                exif_description = exif_image_description(tiff);
+            }
+            _ => {}
+        }
+    }
+
+    exif_description.and_then(metadata_from_prompt)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::InputImageInfo;
+    use image::{DynamicImage, ImageFormat, RgbaImage};
+    use std::io::Cursor;
+
+    fn sample_metadata() -> ImageMetadata {
+        ImageMetadata {
+            prompt: Some("一只在 <雪地> 里奔跑的 \"柴犬\" & 夕阳".to_string()),
+            input_images: vec![InputImageInfo {
+                path: Some("/tmp/ref.png".to_string()),
+                label: "图片1".to_string(),
+            }],
+            node_id: Some("node-1".to_string()),
+            canvas_id: Some("canvas-1".to_string()),
+            created_at: 1_700_000_000,
+            generation: None,
+            description: None,
+        }
+    }
+
+    fn encode(format: ImageFormat) -> Vec<u8> {
+        let image =
+            DynamicImage::ImageRgba8(RgbaImage::from_pixel(7, 5, image::Rgba([10, 20, 30, 255])));
+        let image = if format == ImageFormat::Jpeg {
+            DynamicImage::ImageRgb8(image.to_rgb8())
+        } else {
+            image
+        };
+        let mut buffer = Cursor::new(Vec::new());
+        image.write_to(&mut buffer, format).unwrap();
+        buffer.into_inner()
+    }
+
+    fn assert_roundtrip(format: ImageFormat) {
+        let original = encode(format);
+        let metadata = sample_metadata();
+
+        let embedded = embed_metadata(&original, &metadata).unwrap();
+        // 重复写入不应产生重复的元数据块
+        let embedded = embed_metadata(&embedded, &metadata).unwrap();
+
+        let decoded = image::load_from_memory_with_format(&embedded, format).unwrap();
+        assert_eq!((decoded.width(), decoded.height()), (7, 5));
+
+        let extracted = extract_metadata(&embedded).unwrap();
+        assert_eq!(extracted.prompt, metadata.prompt);
+        assert_eq!(extracted.node_id, metadata.node_id);
+        assert_eq!(extracted.input_images.len(), 1);
+        assert_eq!(extracted.created_at, metadata.created_at);
+    }
+
+    #[test]
+    fn test_png_roundtrip() {
+        assert_roundtrip(ImageFormat::Png);
+    }
+
+    #[test]
+    fn test_jpeg_roundtrip() {
+        assert_roundtrip(ImageFormat::Jpeg);
+    }
+
+    #[test]
+    fn test_webp_roundtrip() {
+        assert_roundtrip(ImageFormat::WebP);
+    }
+
+    #[test]
+    fn test_png_parameters_fallback() {
+        let original = encode(ImageFormat::Png);
+        let chunks = png_chunks(&original);
+        let iend_offset = chunks.last().unwrap().2;
+
+        let mut data = original[..iend_offset].to_vec();
+        data.extend_from_slice(&png_chunk(
+            b"tEXt",
+            b"parameters\0a red fox\nNegative prompt: blurry\nSteps: 20",
+        ));
+        data.extend_from_slice(&original[iend_offset..]);
+
+        let extracted = extract_metadata(&data).unwrap();
+        assert_eq!(extracted.prompt.as_deref(), Some("a red fox"));
+        let generation = extracted.generation.unwrap();
+        assert_eq!(generation.negative_prompt.as_deref(), Some("blurry"));
+        assert_eq!(generation.steps, Some(20));
+    }
+
+    #[test]
+    fn test_parse_infotext() {
+        let (prompt, generation) = parse_infotext(
+            "a cat,\nsitting on a sofa\nNegative prompt: lowres\nSteps: 28, Sampler: DPM++ 2M, \
+             Schedule type: Karras, CFG scale: 6.5, Seed: 1234, Size: 832x1216, \
+             Model: sdxl_base, Lora hashes: \"a: 1, b: 2\", Version: v1.10.1",
+        );
+        assert_eq!(prompt, "a cat,\nsitting on a sofa");
+        assert_eq!(generation.negative_prompt.as_deref(), Some("lowres"));
+        assert_eq!(generation.steps, Some(28));
+        assert_eq!(generation.sampler.as_deref(), Some("DPM++ 2M"));
+        assert_eq!(generation.scheduler.as_deref(), Some("Karras"));
+        assert_eq!(generation.cfg_scale, Some(6.5));
+        assert_eq!(generation.seed, Some(1234));
+        assert_eq!(
+            (generation.width, generation.height),
+            (Some(832), Some(1216))
+        );
+        assert_eq!(generation.model.as_deref(), Some("sdxl_base"));
+        let extra = generation.extra.unwrap();
+        assert_eq!(extra["Lora hashes"], "a: 1, b: 2");
+    }
+}
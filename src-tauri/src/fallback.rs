@@ -0,0 +1,504 @@
+//! 服务降级链
+//!
+//! 图片生成或文本请求失败时，按配置的顺序换用下一个服务 / 模型重试，
+//! 例如 "gemini-3-pro-image → gpt-image-1 → flux"。只有错误类型在
+//! `retry_on` 中时才继续尝试；切换服务时沿用原请求的画幅，由 `framing` 模块
+//! 换算成目标服务的参数，并在结果中返回每次尝试的记录和最终生成结果的服务。
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::time::Instant;
+
+use crate::image_provider::{run_image_request, ImageGenerationRequest, ImageGenerationResult};
+use crate::{gemini, llm};
+
+// 错误类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorClass {
+    Safety,         // 内容安全拦截
+    NoOutput,       // 请求成功但没有返回图片 / 文本
+    RateLimit,      // 429、配额用尽
+    Server,         // 5xx、服务过载
+    Timeout,        // 请求超时
+    Network,        // 无法连接
+    Auth,           // 401 / 403
+    InvalidRequest, // 参数错误、模型不存在
+    Other,
+}
+
+// 默认在这些错误上切换服务；鉴权和参数错误通常需要用户处理
+const DEFAULT_RETRY_ON: [ErrorClass; 6] = [
+    ErrorClass::Safety,
+    ErrorClass::NoOutput,
+    ErrorClass::RateLimit,
+    ErrorClass::Server,
+    ErrorClass::Timeout,
+    ErrorClass::Network,
+];
+
+const SAFETY_KEYWORDS: [&str; 10] = [
+    "safety",
+    "blocked",
+    "blockreason",
+    "content_policy",
+    "content policy",
+    "moderation",
+    "prohibited",
+    "安全",
+    "敏感",
+    "违规",
+];
+
+/// 从 "API 返回错误 (503 Service Unavailable): ..." 中读取状态码
+fn status_code(message: &str) -> Option<u16> {
+    let start = message.find('(')? + 1;
+    message
+        .get(start..start + 3)
+        .and_then(|code| code.parse().ok())
+        .filter(|code| (100..600).contains(code))
+}
+
+/// 按错误信息判断错误类型
+pub fn classify_error(message: &str) -> ErrorClass {
+    let lower = message.to_lowercase();
+    let status = status_code(message);
+    // 安全拦截只会以 200（无输出）或 400 返回，其他状态码按状态码判断
+    if matches!(status, None | Some(200) | Some(400))
+        && SAFETY_KEYWORDS.iter().any(|k| lower.contains(k))
+    {
+        return ErrorClass::Safety;
+    }
+    if let Some(code) = status {
+        return match code {
+            429 => ErrorClass::RateLimit,
+            401 | 403 => ErrorClass::Auth,
+            408 | 504 => ErrorClass::Timeout,
+            500..=599 => ErrorClass::Server,
+            400..=499 => ErrorClass::InvalidRequest,
+            _ => ErrorClass::Other,
+        };
+    }
+    if lower.contains("resource_exhausted") || lower.contains("quota") {
+        ErrorClass::RateLimit
+    } else if lower.contains("overloaded") || lower.contains("unavailable") {
+        ErrorClass::Server
+    } else if message.contains("超时") || lower.contains("timeout") {
+        ErrorClass::Timeout
+    } else if message.contains("无法连接") || message.contains("请求失败") {
+        ErrorClass::Network
+    } else if message.contains("未返回") || message.contains("没有返回") {
+        ErrorClass::NoOutput
+    } else {
+        ErrorClass::Other
+    }
+}
+
+// 单次尝试的记录
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FallbackAttempt {
+    pub provider: String,
+    pub model: String,
+    pub success: bool,
+    pub error_class: Option<ErrorClass>,
+    pub error: Option<String>,
+    pub duration_ms: u64,
+}
+
+// 降级链中的一个服务 / 模型
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FallbackTarget {
+    pub provider: String,
+    pub base_url: String,
+    pub api_key: String,
+    pub model: String,
+    pub extras: Option<Value>, // 仅对该服务生效的参数，指定时替换原请求的 extras
+}
+
+/// 失败后是否继续尝试下一个服务
+fn should_fall_back(retry_on: &Option<Vec<ErrorClass>>, class: ErrorClass) -> bool {
+    match retry_on {
+        Some(classes) => classes.contains(&class),
+        None => DEFAULT_RETRY_ON.contains(&class),
+    }
+}
+
+// ==================== 图片生成 ====================
+
+// 前端调用的参数：原请求作为第一个服务，fallbacks 依次备用
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImageFallbackRequest {
+    #[serde(flatten)]
+    pub request: ImageGenerationRequest,
+    #[serde(default)]
+    pub fallbacks: Vec<FallbackTarget>,
+    pub retry_on: Option<Vec<ErrorClass>>,
+}
+
+// 返回结果：沿用统一的图片结果，provider / model 为实际生成的服务
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImageFallbackResult {
+    #[serde(flatten)]
+    pub result: ImageGenerationResult,
+    pub fallback_used: bool,
+    pub attempts: Vec<FallbackAttempt>,
+}
+
+/// 为降级目标改写请求：替换服务信息，按需替换 extras
+///
+/// 宽高比与尺寸由 `run_image_request` 按目标服务统一换算，这里保留原请求的画幅
+fn adapt_request(base: &ImageGenerationRequest, target: &FallbackTarget) -> ImageGenerationRequest {
+    let mut request = base.clone();
+    request.provider = target.provider.clone();
+    request.base_url = target.base_url.clone();
+    request.api_key = target.api_key.clone();
+    request.model = target.model.clone();
+
+    // 原请求的 extras 通常是上一个服务特有的参数，只在目标未指定时沿用
+    if target.extras.is_some() {
+        request.extras = target.extras.clone();
+    }
+    request
+}
+
+/// 沿降级链执行图片生成
+pub async fn run_image_with_fallback(params: &ImageFallbackRequest) -> ImageFallbackResult {
+    let primary = FallbackTarget {
+        provider: params.request.provider.clone(),
+        base_url: params.request.base_url.clone(),
+        api_key: params.request.api_key.clone(),
+        model: params.request.model.clone(),
+        extras: params.request.extras.clone(),
+    };
+    let chain: Vec<&FallbackTarget> = std::iter::once(&primary)
+        .chain(params.fallbacks.iter())
+        .collect();
+
+    let mut attempts = Vec::new();
+    let mut last_result = None;
+    for (index, target) in chain.iter().enumerate() {
+        let request = if index == 0 {
+            params.request.clone()
+        } else {
+            adapt_request(&params.request, target)
+        };
+        let start_time = Instant::now();
+        let result = run_image_request(&request).await;
+        let class =
+            (!result.success).then(|| classify_error(result.error.as_deref().unwrap_or_default()));
+        attempts.push(FallbackAttempt {
+            provider: request.provider.clone(),
+            model: request.model.clone(),
+            success: result.success,
+            error_class: class,
+            error: result.error.clone(),
+            duration_ms: start_time.elapsed().as_millis() as u64,
+        });
+
+        let Some(class) = class else {
+            return ImageFallbackResult {
+                result,
+                fallback_used: index > 0,
+                attempts,
+            };
+        };
+        println!(
+            "[Rust] Fallback: {} / {} failed ({:?})",
+            request.provider, request.model, class
+        );
+        last_result = Some(result);
+        if !should_fall_back(&params.retry_on, class) {
+            break;
+        }
+    }
+
+    ImageFallbackResult {
+        result: last_result.unwrap_or_else(|| ImageGenerationResult::failure("降级链为空")),
+        fallback_used: attempts.len() > 1,
+        attempts,
+    }
+}
+
+// Tauri 命令：带降级链的图片生成
+#[tauri::command]
+pub async fn generate_image_with_fallback(request: ImageFallbackRequest) -> ImageFallbackResult {
+    println!(
+        "[Rust] generate_image_with_fallback called, {} fallback(s)",
+        request.fallbacks.len()
+    );
+    let result = run_image_with_fallback(&request).await;
+    println!(
+        "[Rust] generate_image_with_fallback finished: success={}, provider={}, attempts={}",
+        result.result.success,
+        result.result.provider,
+        result.attempts.len()
+    );
+    result
+}
+
+// ==================== 文本生成 ====================
+
+// 前端调用的参数：provider 为 "openai" | "openai-responses" | "claude" | "gemini"
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LLMFallbackParams {
+    pub provider: String,
+    #[serde(flatten)]
+    pub request: llm::LLMRequestParams,
+    #[serde(default)]
+    pub fallbacks: Vec<FallbackTarget>,
+    pub retry_on: Option<Vec<ErrorClass>>,
+}
+
+// 返回结果
+#[derive(Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LLMFallbackResult {
+    pub success: bool,
+    pub content: Option<String>,
+    pub provider: Option<String>, // 实际返回结果的服务
+    pub model: Option<String>,
+    pub fallback_used: bool,
+    pub attempts: Vec<FallbackAttempt>,
+    pub error: Option<String>,
+}
+
+/// 调用单个文本服务，统一返回 (success, content, error)
+async fn run_llm_target(
+    provider: &str,
+    request: llm::LLMRequestParams,
+) -> (bool, Option<String>, Option<String>) {
+    let result = match provider {
+        "openai" => llm::openai_chat_completion(request).await,
+        "openai-responses" => llm::openai_responses(request).await,
+        "claude" => llm::claude_chat_completion(request).await,
+        "gemini" => {
+            let result = gemini::gemini_generate_text(gemini::LLMRequestParams {
+                base_url: request.base_url,
+                api_key: request.api_key,
+                model: request.model,
+                prompt: request.prompt,
+                system_prompt: request.system_prompt,
+                output_format: None,
+                temperature: request.temperature,
+                max_tokens: request.max_tokens,
+                files: request.files.map(|files| {
+                    files
+                        .into_iter()
+                        .map(|file| gemini::FileData {
+                            data: file.data,
+                            mime_type: file.mime_type,
+                            file_name: file.file_name,
+                        })
+                        .collect()
+                }),
+                response_format: request.response_format,
+                response_json_schema: request.response_json_schema,
+            })
+            .await;
+            return (result.success, result.content, result.error);
+        }
+        other => return (false, None, Some(format!("不支持的文本服务: {}", other))),
+    };
+    (result.success, result.content, result.error)
+}
+
+// Tauri 命令：带降级链的文本生成
+#[tauri::command]
+pub async fn llm_generate_with_fallback(params: LLMFallbackParams) -> LLMFallbackResult {
+    println!(
+        "[Rust] llm_generate_with_fallback called, provider: {}, {} fallback(s)",
+        params.provider,
+        params.fallbacks.len()
+    );
+
+    let primary = FallbackTarget {
+        provider: params.provider.clone(),
+        base_url: params.request.base_url.clone(),
+        api_key: params.request.api_key.clone(),
+        model: params.request.model.clone(),
+        extras: None,
+    };
+    let mut result = LLMFallbackResult::default();
+    for target in std::iter::once(&primary).chain(params.fallbacks.iter()) {
+        let mut request = params.request.clone();
+        request.base_url = target.base_url.clone();
+        request.api_key = target.api_key.clone();
+        request.model = target.model.clone();
+
+        let provider = target.provider.trim().to_ascii_lowercase();
+        let start_time = Instant::now();
+        let (success, content, error) = run_llm_target(&provider, request).await;
+        // 空内容同样视为失败
+        let content = content.filter(|c| !c.trim().is_empty());
+        let error = match (success, &content) {
+            (true, Some(_)) => None,
+            (true, None) => Some("模型没有返回内容".to_string()),
+            _ => Some(error.unwrap_or_else(|| "请求失败".to_string())),
+        };
+        let class = error.as_deref().map(classify_error);
+        result.attempts.push(FallbackAttempt {
+            provider: target.provider.clone(),
+            model: target.model.clone(),
+            success: error.is_none(),
+            error_class: class,
+            error: error.clone(),
+            duration_ms: start_time.elapsed().as_millis() as u64,
+        });
+
+        let Some(class) = class else {
+            result.success = true;
+            result.content = content;
+            result.provider = Some(target.provider.clone());
+            result.model = Some(target.model.clone());
+            result.error = None;
+            break;
+        };
+        println!(
+            "[Rust] Fallback: {} / {} failed ({:?})",
+            target.provider, target.model, class
+        );
+        result.error = error;
+        if !should_fall_back(&params.retry_on, class) {
+            break;
+        }
+    }
+    result.fallback_used = result.attempts.len() > 1;
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::framing::apply_to_request;
+
+    #[test]
+    fn test_classify_error() {
+        assert_eq!(
+            classify_error("API 返回错误 (503 Service Unavailable): overloaded"),
+            ErrorClass::Server
+        );
+        assert_eq!(
+            classify_error("API 返回错误 (429 Too Many Requests): slow down"),
+            ErrorClass::RateLimit
+        );
+        assert_eq!(
+            classify_error(
+                "API 返回错误 (400 Bad Request): Your request was rejected by the safety system"
+            ),
+            ErrorClass::Safety
+        );
+        assert_eq!(
+            classify_error("API 返回错误 (401 Unauthorized): invalid key"),
+            ErrorClass::Auth
+        );
+        // 带安全关键词的鉴权和服务端错误仍按状态码分类
+        assert_eq!(
+            classify_error("API 返回错误 (403 Forbidden): request blocked"),
+            ErrorClass::Auth
+        );
+        assert_eq!(
+            classify_error("API 返回错误 (502 Bad Gateway): blocked by upstream"),
+            ErrorClass::Server
+        );
+        assert_eq!(classify_error("模型未返回图片"), ErrorClass::NoOutput);
+        assert_eq!(classify_error("请求超时，请稍后重试"), ErrorClass::Timeout);
+        assert!(!should_fall_back(&None, ErrorClass::Auth));
+        assert!(should_fall_back(
+            &Some(vec![ErrorClass::Auth]),
+            ErrorClass::Auth
+        ));
+    }
+
+    #[test]
+    fn test_classify_gemini_safety_block() {
+        // Gemini 拦截时 HTTP 状态为 200，原因只在 promptFeedback / finishReason 中
+        let classify = |body: &str, message: &str| {
+            let response: gemini::GeminiResponse = serde_json::from_str(body).unwrap();
+            let reason = gemini::block_reason(&response);
+            classify_error(&gemini::empty_output_error(message, reason.as_deref()))
+        };
+        assert_eq!(
+            classify(
+                r#"{"promptFeedback":{"blockReason":"PROHIBITED_CONTENT"},"usageMetadata":{"promptTokenCount":12}}"#,
+                "API 未返回有效内容"
+            ),
+            ErrorClass::Safety
+        );
+        assert_eq!(
+            classify(
+                r#"{"candidates":[{"content":{"parts":[{"text":"I can't create that image."}],"role":"model"},"finishReason":"IMAGE_SAFETY","index":0}]}"#,
+                "模型未返回图片"
+            ),
+            ErrorClass::Safety
+        );
+        assert_eq!(
+            classify(
+                r#"{"candidates":[{"finishReason":"SAFETY","index":0}]}"#,
+                "API 未返回有效内容"
+            ),
+            ErrorClass::Safety
+        );
+        assert_eq!(
+            classify(
+                r#"{"candidates":[{"content":{"parts":[{"text":"ok"}]},"finishReason":"STOP"}]}"#,
+                "模型未返回图片"
+            ),
+            ErrorClass::NoOutput
+        );
+        assert_eq!(
+            classify(
+                r#"{"candidates":[{"finishReason":"MAX_TOKENS"}]}"#,
+                "API 未返回有效内容"
+            ),
+            ErrorClass::NoOutput
+        );
+    }
+
+    #[test]
+    fn test_adapt_request_translates_framing() {
+        let base = ImageGenerationRequest {
+            provider: "gemini".to_string(),
+            model: "gemini-3-pro-image-preview".to_string(),
+            aspect_ratio: Some("16:9".to_string()),
+            extras: Some(serde_json::json!({ "includeText": true })),
+            ..ImageGenerationRequest::default()
+        };
+        let target = FallbackTarget {
+            provider: "openai".to_string(),
+            model: "gpt-image-1".to_string(),
+            ..FallbackTarget::default()
+        };
+        let mut request = adapt_request(&base, &target);
+        assert_eq!(request.model, "gpt-image-1");
+        assert_eq!(request.extras, base.extras);
+        apply_to_request(&mut request);
+        assert_eq!(request.size.as_deref(), Some("1536x1024"));
+
+        let target = FallbackTarget {
+            provider: "sd-webui".to_string(),
+            ..FallbackTarget::default()
+        };
+        let mut request = adapt_request(&base, &target);
+        apply_to_request(&mut request);
+        assert_eq!(request.size.as_deref(), Some("1368x768"));
+
+        let base = ImageGenerationRequest {
+            provider: "openai".to_string(),
+            size: Some("1024x1536".to_string()),
+            ..ImageGenerationRequest::default()
+        };
+        let target = FallbackTarget {
+            provider: "gemini".to_string(),
+            ..FallbackTarget::default()
+        };
+        let mut request = adapt_request(&base, &target);
+        apply_to_request(&mut request);
+        assert_eq!(request.aspect_ratio.as_deref(), Some("2:3"));
+        assert_eq!(request.size, None);
+    }
+}
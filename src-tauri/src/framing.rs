@@ -0,0 +1,608 @@
+//! 宽高比与分辨率规范化
+//!
+//! 各服务接受的画面参数不同：Gemini 只接受固定的宽高比和 1K/2K/4K 档位，
+//! OpenAI 只接受几种固定尺寸，SD / ComfyUI / Flux 接受任意宽高但有倍数和范围限制，
+//! 视频服务也各有档位。这里把请求的宽高比与目标分辨率映射到目标服务 / 模型
+//! 最接近的合法值并报告调整情况；需要时把输出裁切或补边回精确的请求画幅。
+
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use image::imageops::FilterType;
+use image::{DynamicImage, GenericImageView, ImageFormat, RgbaImage};
+use serde::{Deserialize, Serialize};
+use std::io::Cursor;
+
+use crate::image_provider::{parse_size, GeneratedImage, ImageGenerationRequest};
+use crate::image_utils::decode_base64_image;
+use crate::video_provider::VideoGenerationRequest;
+
+const GEMINI_RATIOS: [&str; 10] = [
+    "1:1", "2:3", "3:2", "3:4", "4:3", "4:5", "5:4", "9:16", "16:9", "21:9",
+];
+const GEMINI_IMAGE_SIZES: [(&str, u32); 3] = [("1K", 1024), ("2K", 2048), ("4K", 4096)];
+const GPT_IMAGE_SIZES: [(u32, u32); 3] = [(1024, 1024), (1536, 1024), (1024, 1536)];
+const DALLE3_SIZES: [(u32, u32); 3] = [(1024, 1024), (1792, 1024), (1024, 1792)];
+const DALLE2_SIZES: [(u32, u32); 3] = [(256, 256), (512, 512), (1024, 1024)];
+const SORA_SIZES: [(u32, u32); 4] = [(1280, 720), (720, 1280), (1792, 1024), (1024, 1792)];
+const VEO_RATIOS: [&str; 2] = ["16:9", "9:16"];
+const VEO_RESOLUTIONS: [(&str, u32); 2] = [("720p", 1280), ("1080p", 1920)];
+const KLING_SIZES: [(u32, u32); 6] = [
+    (1280, 720),
+    (720, 1280),
+    (720, 720),
+    (1920, 1080),
+    (1080, 1920),
+    (1080, 1080),
+];
+
+// 服务 / 模型接受的画面参数
+#[derive(Debug, Clone, PartialEq)]
+pub enum FramingSpec {
+    // 只接受固定宽高比，分辨率按档位选择（档位名, 长边像素）
+    AspectRatios {
+        ratios: &'static [&'static str],
+        image_sizes: &'static [(&'static str, u32)],
+    },
+    // 只接受固定尺寸
+    Sizes(&'static [(u32, u32)]),
+    // 任意宽高，需为 multiple 的倍数并在 [min, max] 内
+    Free {
+        multiple: u32,
+        min: u32,
+        max: u32,
+        default_pixels: u32, // 只给宽高比时的目标像素数
+    },
+    // 由服务自行处理（如 Midjourney 的 --ar）
+    Passthrough,
+}
+
+/// 查找服务 / 模型的画面参数规格
+pub fn framing_spec(provider: &str, model: &str) -> FramingSpec {
+    let model = model.to_ascii_lowercase();
+    match provider.trim().to_ascii_lowercase().as_str() {
+        "gemini" | "google" => FramingSpec::AspectRatios {
+            ratios: &GEMINI_RATIOS,
+            // 只有 Gemini 3 Pro Image 支持分辨率档位
+            image_sizes: if model.contains("gemini-3") || model.contains("pro-image") {
+                &GEMINI_IMAGE_SIZES
+            } else {
+                &[]
+            },
+        },
+        // 兼容接口上的其他模型（如即梦、通义）尺寸规则不同，交给服务自行处理
+        "openai" | "dalle" | "gpt-image" if model.contains("dall-e-2") => {
+            FramingSpec::Sizes(&DALLE2_SIZES)
+        }
+        "openai" | "dalle" | "gpt-image" if model.contains("dall-e-3") => {
+            FramingSpec::Sizes(&DALLE3_SIZES)
+        }
+        "openai" | "dalle" | "gpt-image" if model.contains("gpt-image") => {
+            FramingSpec::Sizes(&GPT_IMAGE_SIZES)
+        }
+        "sd-webui" | "a1111" | "forge" => FramingSpec::Free {
+            multiple: 8,
+            min: 64,
+            max: 2048,
+            default_pixels: 1024 * 1024,
+        },
+        "comfyui" => FramingSpec::Free {
+            multiple: 8,
+            min: 64,
+            max: 4096,
+            default_pixels: 1024 * 1024,
+        },
+        "flux" | "bfl" => FramingSpec::Free {
+            multiple: 32,
+            min: 256,
+            max: 1440,
+            default_pixels: 1024 * 1024,
+        },
+        "sora" | "openai-video" => FramingSpec::Sizes(&SORA_SIZES),
+        "kling" => FramingSpec::Sizes(&KLING_SIZES),
+        "veo" => FramingSpec::AspectRatios {
+            ratios: &VEO_RATIOS,
+            // Veo 2 固定 720p，不接受 resolution 参数
+            image_sizes: if model.contains("veo-3") {
+                &VEO_RESOLUTIONS
+            } else {
+                &[]
+            },
+        },
+        "newapi-video" | "new-api" => FramingSpec::Free {
+            multiple: 16,
+            min: 256,
+            max: 1920,
+            default_pixels: 1280 * 720,
+        },
+        _ => FramingSpec::Passthrough,
+    }
+}
+
+// 请求的画面参数
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FramingTarget {
+    pub aspect_ratio: Option<String>, // "16:9"
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    pub image_size: Option<String>, // "1K" | "2K" | "4K"
+}
+
+// 规范化结果
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NormalizedFraming {
+    pub aspect_ratio: Option<String>,
+    pub image_size: Option<String>,
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    pub requested_aspect_ratio: Option<String>,
+    pub requested_width: Option<u32>,
+    pub requested_height: Option<u32>,
+    pub adjusted: bool,       // 与请求不一致
+    pub reframed: bool,       // 输出已裁切 / 补边回请求画幅
+    pub note: Option<String>, // 调整说明
+}
+
+/// 解析 "16:9" 形式的宽高比
+pub fn parse_ratio(ratio: &str) -> Option<f64> {
+    let (w, h) = ratio.split_once(':')?;
+    let (w, h): (f64, f64) = (w.trim().parse().ok()?, h.trim().parse().ok()?);
+    (w > 0.0 && h > 0.0).then_some(w / h)
+}
+
+fn ratio_distance(a: f64, b: f64) -> f64 {
+    (a.ln() - b.ln()).abs()
+}
+
+fn image_size_edge(label: &str) -> Option<u32> {
+    GEMINI_IMAGE_SIZES
+        .iter()
+        .chain(VEO_RESOLUTIONS.iter())
+        .find(|(name, _)| name.eq_ignore_ascii_case(label.trim()))
+        .map(|(_, edge)| *edge)
+}
+
+fn snap(value: f64, multiple: u32, min: u32, max: u32) -> u32 {
+    let multiple = multiple.max(1) as f64;
+    ((value / multiple).round() * multiple).clamp(min as f64, max as f64) as u32
+}
+
+/// 把请求的宽高比与分辨率映射到服务 / 模型的合法值；未指定画面参数时返回 None
+pub fn normalize(provider: &str, model: &str, target: &FramingTarget) -> Option<NormalizedFraming> {
+    let requested_dims = match (target.width, target.height) {
+        (Some(w), Some(h)) if w > 0 && h > 0 => Some((w, h)),
+        _ => None,
+    };
+    let ratio = target
+        .aspect_ratio
+        .as_deref()
+        .and_then(parse_ratio)
+        .or(requested_dims.map(|(w, h)| w as f64 / h as f64))?;
+    let long_edge = requested_dims
+        .map(|(w, h)| w.max(h))
+        .or(target.image_size.as_deref().and_then(image_size_edge));
+
+    let mut framing = NormalizedFraming {
+        requested_aspect_ratio: target.aspect_ratio.clone(),
+        requested_width: requested_dims.map(|(w, _)| w),
+        requested_height: requested_dims.map(|(_, h)| h),
+        ..NormalizedFraming::default()
+    };
+
+    match framing_spec(provider, model) {
+        FramingSpec::Passthrough => return None,
+        FramingSpec::AspectRatios {
+            ratios,
+            image_sizes,
+        } => {
+            let nearest = ratios
+                .iter()
+                .min_by(|a, b| {
+                    let distance = |r: &&str| ratio_distance(parse_ratio(r).unwrap_or(1.0), ratio);
+                    distance(a).total_cmp(&distance(b))
+                })
+                .copied()
+                .unwrap_or("1:1");
+            // 选能覆盖目标长边的最小档位
+            let image_size = if image_sizes.is_empty() {
+                None
+            } else {
+                long_edge.map(|edge| {
+                    image_sizes
+                        .iter()
+                        .find(|(_, size)| *size >= edge)
+                        .unwrap_or(&image_sizes[image_sizes.len() - 1])
+                        .0
+                        .to_string()
+                })
+            };
+            framing.adjusted = ratio_distance(parse_ratio(nearest).unwrap_or(1.0), ratio) > 0.01
+                || (target.image_size.is_some() && image_size != target.image_size);
+            framing.aspect_ratio = Some(nearest.to_string());
+            framing.image_size = image_size;
+        }
+        FramingSpec::Sizes(sizes) => {
+            let pixels = long_edge.map(|edge| {
+                let edge = edge as f64;
+                if ratio >= 1.0 {
+                    edge * edge / ratio
+                } else {
+                    edge * edge * ratio
+                }
+            });
+            // 先比宽高比，再比像素数
+            let (w, h) = sizes
+                .iter()
+                .min_by(|a, b| {
+                    let score = |(w, h): &&(u32, u32)| {
+                        let ratio_score = ratio_distance(*w as f64 / *h as f64, ratio);
+                        let pixel_score = pixels
+                            .map(|p| ratio_distance((*w * *h) as f64, p))
+                            .unwrap_or(0.0);
+                        ratio_score * 10.0 + pixel_score
+                    };
+                    score(a).total_cmp(&score(b))
+                })
+                .copied()
+                .unwrap_or((1024, 1024));
+            framing.width = Some(w);
+            framing.height = Some(h);
+        }
+        FramingSpec::Free {
+            multiple,
+            min,
+            max,
+            default_pixels,
+        } => {
+            let (w, h) = match requested_dims {
+                Some((w, h)) => {
+                    // 超出上限时等比缩小，保持宽高比
+                    let scale = (max as f64 / w.max(h) as f64).min(1.0);
+                    (
+                        snap(w as f64 * scale, multiple, min, max),
+                        snap(h as f64 * scale, multiple, min, max),
+                    )
+                }
+                None => {
+                    let pixels = long_edge
+                        .map(|edge| {
+                            let edge = edge as f64;
+                            edge * edge / ratio.max(1.0 / ratio)
+                        })
+                        .unwrap_or(default_pixels as f64);
+                    let width = (pixels * ratio).sqrt();
+                    (
+                        snap(width, multiple, min, max),
+                        snap(width / ratio, multiple, min, max),
+                    )
+                }
+            };
+            framing.width = Some(w);
+            framing.height = Some(h);
+        }
+    }
+
+    if let (Some(w), Some(h)) = (framing.width, framing.height) {
+        framing.adjusted = match requested_dims {
+            Some(dims) => dims != (w, h),
+            None => ratio_distance(w as f64 / h as f64, ratio) > 0.01,
+        };
+    }
+    if framing.adjusted {
+        framing.note = Some(format!(
+            "{} 不支持请求的画面参数，已调整为 {}",
+            provider,
+            match (&framing.aspect_ratio, framing.width, framing.height) {
+                (_, Some(w), Some(h)) => format!("{}x{}", w, h),
+                (Some(ratio), _, _) => match &framing.image_size {
+                    Some(size) => format!("{} {}", ratio, size),
+                    None => ratio.clone(),
+                },
+                _ => "默认值".to_string(),
+            }
+        ));
+    }
+    Some(framing)
+}
+
+/// 从统一图片请求中读取画面参数
+pub fn target_from_request(
+    aspect_ratio: Option<&str>,
+    size: Option<&str>,
+    image_size: Option<&str>,
+) -> FramingTarget {
+    let (width, height) = parse_size(size);
+    FramingTarget {
+        aspect_ratio: aspect_ratio
+            .filter(|r| !r.trim().is_empty())
+            .map(|r| r.to_string()),
+        width,
+        height,
+        image_size: image_size
+            .filter(|s| !s.trim().is_empty())
+            .map(|s| s.to_string()),
+    }
+}
+
+/// 计算裁切 / 补边后的精确尺寸：请求了宽高时用请求值，否则保持输出分辨率只修正宽高比
+fn reframe_dimensions(
+    output: (u32, u32),
+    framing: &NormalizedFraming,
+    mode: &str,
+) -> Option<(u32, u32)> {
+    if let (Some(w), Some(h)) = (framing.requested_width, framing.requested_height) {
+        return Some((w, h));
+    }
+    let ratio = framing
+        .requested_aspect_ratio
+        .as_deref()
+        .and_then(parse_ratio)?;
+    let (w, h) = (output.0 as f64, output.1 as f64);
+    let wider = w / h > ratio;
+    let dims = match (mode, wider) {
+        ("pad", true) | ("crop", false) => (w, w / ratio),
+        _ => (h * ratio, h),
+    };
+    Some((
+        dims.0.round().max(1.0) as u32,
+        dims.1.round().max(1.0) as u32,
+    ))
+}
+
+/// 把输出裁切（crop）或补透明边（pad）回请求的画幅，返回 PNG 数据
+pub fn reframe_image(
+    bytes: &[u8],
+    framing: &NormalizedFraming,
+    mode: &str,
+) -> Result<Vec<u8>, String> {
+    let image = image::load_from_memory(bytes).map_err(|e| format!("图片解码失败: {}", e))?;
+    let (tw, th) =
+        reframe_dimensions(image.dimensions(), framing, mode).ok_or("缺少请求的画面参数")?;
+    if (tw, th) == image.dimensions() {
+        return Ok(bytes.to_vec());
+    }
+    let output = match mode {
+        "crop" => image.resize_to_fill(tw, th, FilterType::Lanczos3),
+        "pad" => {
+            let fitted = image.resize(tw, th, FilterType::Lanczos3).to_rgba8();
+            let mut canvas = RgbaImage::new(tw, th);
+            let x = (tw - fitted.width()) / 2;
+            let y = (th - fitted.height()) / 2;
+            image::imageops::overlay(&mut canvas, &fitted, x as i64, y as i64);
+            DynamicImage::ImageRgba8(canvas)
+        }
+        other => return Err(format!("不支持的画幅调整方式: {}", other)),
+    };
+    let mut encoded = Vec::new();
+    output
+        .write_to(&mut Cursor::new(&mut encoded), ImageFormat::Png)
+        .map_err(|e| format!("PNG 编码失败: {}", e))?;
+    Ok(encoded)
+}
+
+/// 按服务规格改写统一图片请求，返回调整情况
+pub fn apply_to_request(request: &mut ImageGenerationRequest) -> Option<NormalizedFraming> {
+    let target = target_from_request(
+        request.aspect_ratio.as_deref(),
+        request.size.as_deref(),
+        request.image_size.as_deref(),
+    );
+    let framing = normalize(&request.provider, &request.model, &target)?;
+    match (framing.width, framing.height) {
+        // 按尺寸出图的服务只改 size，宽高比留给同时支持两者的服务（如 Flux Kontext）
+        (Some(w), Some(h)) => request.size = Some(format!("{}x{}", w, h)),
+        _ => {
+            request.aspect_ratio = framing.aspect_ratio.clone();
+            request.image_size = framing.image_size.clone();
+            request.size = None;
+        }
+    }
+    if let Some(note) = &framing.note {
+        println!("[Rust] Framing: {}", note);
+    }
+    Some(framing)
+}
+
+/// 按视频服务规格改写统一视频请求，provider_id 为适配器标识，返回调整情况
+pub fn apply_to_video_request(
+    request: &mut VideoGenerationRequest,
+    provider_id: &str,
+) -> Option<NormalizedFraming> {
+    // OpenAI 视频接口与图片接口的尺寸规则不同，按 Sora 查找规格
+    let provider = match provider_id {
+        "openai" => "sora",
+        "newapi" => "newapi-video",
+        id => id,
+    };
+    // "1280x720" 按宽高处理，"720p" 按档位处理
+    let resolution = request
+        .resolution
+        .as_deref()
+        .filter(|r| !r.trim().is_empty());
+    let (size, tier) = match resolution {
+        Some(r) if r.contains(['x', 'X']) => (Some(r), None),
+        other => (None, other),
+    };
+    let target = target_from_request(request.aspect_ratio.as_deref(), size, tier);
+    let framing = normalize(provider, &request.model, &target)?;
+    match (framing.width, framing.height) {
+        (Some(w), Some(h)) => request.resolution = Some(format!("{}x{}", w, h)),
+        _ => {
+            request.aspect_ratio = framing.aspect_ratio.clone();
+            request.resolution = framing.image_size.clone();
+        }
+    }
+    if let Some(note) = &framing.note {
+        println!("[Rust] Framing: {}", note);
+    }
+    Some(framing)
+}
+
+/// 把输出图片调整回请求的画幅，失败的图片保持原样
+pub fn reframe_outputs(images: &mut [GeneratedImage], framing: &mut NormalizedFraming, mode: &str) {
+    for image in images.iter_mut() {
+        let Some(data) = &image.data else {
+            continue;
+        };
+        let reframed = decode_base64_image(data)
+            .and_then(|bytes| Ok((reframe_image(&bytes, framing, mode)?, bytes)));
+        match reframed {
+            Ok((output, original)) if output != original => {
+                image.data = Some(BASE64.encode(&output));
+                image.mime_type = Some("image/png".to_string());
+                framing.reframed = true;
+            }
+            Ok(_) => {}
+            Err(e) => println!("[Rust] Reframe failed, keeping original: {}", e),
+        }
+    }
+}
+
+// 前端查询参数
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FramingQuery {
+    pub provider: String,
+    pub model: String,
+    #[serde(flatten)]
+    pub target: FramingTarget,
+}
+
+// Tauri 命令：查询服务 / 模型可接受的最接近画面参数（视频节点等直接使用）
+#[tauri::command]
+pub fn normalize_framing(query: FramingQuery) -> Option<NormalizedFraming> {
+    normalize(&query.provider, &query.model, &query.target)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn target(aspect_ratio: Option<&str>, size: Option<&str>) -> FramingTarget {
+        target_from_request(aspect_ratio, size, None)
+    }
+
+    #[test]
+    fn test_gemini_picks_nearest_ratio_and_tier() {
+        let framing = normalize(
+            "gemini",
+            "gemini-3-pro-image-preview",
+            &target(None, Some("1000x1500")),
+        )
+        .unwrap();
+        assert_eq!(framing.aspect_ratio.as_deref(), Some("2:3"));
+        assert_eq!(framing.image_size.as_deref(), Some("2K"));
+
+        let framing = normalize(
+            "gemini",
+            "gemini-2.5-flash-image",
+            &target(Some("2:1"), None),
+        )
+        .unwrap();
+        assert_eq!(framing.aspect_ratio.as_deref(), Some("16:9"));
+        assert_eq!(framing.image_size, None);
+        assert!(framing.adjusted);
+    }
+
+    #[test]
+    fn test_fixed_sizes_and_free_dimensions() {
+        let framing = normalize("openai", "gpt-image-1", &target(Some("16:9"), None)).unwrap();
+        assert_eq!((framing.width, framing.height), (Some(1536), Some(1024)));
+        assert!(framing.adjusted);
+
+        let framing = normalize("openai", "dall-e-3", &target(None, Some("1024x1024"))).unwrap();
+        assert!(!framing.adjusted);
+
+        let framing = normalize("sd-webui", "", &target(Some("16:9"), None)).unwrap();
+        assert_eq!((framing.width, framing.height), (Some(1368), Some(768)));
+
+        let framing = normalize("flux", "flux-pro-1.1", &target(None, Some("1000x3000"))).unwrap();
+        assert_eq!((framing.width, framing.height), (Some(480), Some(1440)));
+
+        assert!(normalize("midjourney", "", &target(Some("16:9"), None)).is_none());
+        assert!(normalize("openai", "gpt-image-1", &target(None, None)).is_none());
+    }
+
+    #[test]
+    fn test_reframe_to_requested_ratio() {
+        let framing = normalize("openai", "gpt-image-1", &target(Some("16:9"), None)).unwrap();
+        let mut source = Vec::new();
+        DynamicImage::ImageRgba8(RgbaImage::new(1536, 1024))
+            .write_to(&mut Cursor::new(&mut source), ImageFormat::Png)
+            .unwrap();
+
+        let cropped =
+            image::load_from_memory(&reframe_image(&source, &framing, "crop").unwrap()).unwrap();
+        assert_eq!(cropped.dimensions(), (1536, 864));
+        let padded =
+            image::load_from_memory(&reframe_image(&source, &framing, "pad").unwrap()).unwrap();
+        assert_eq!(padded.dimensions(), (1820, 1024));
+    }
+
+    fn video_request(
+        model: &str,
+        aspect_ratio: Option<&str>,
+        resolution: Option<&str>,
+    ) -> VideoGenerationRequest {
+        VideoGenerationRequest {
+            model: model.to_string(),
+            aspect_ratio: aspect_ratio.map(|r| r.to_string()),
+            resolution: resolution.map(|r| r.to_string()),
+            ..VideoGenerationRequest::default()
+        }
+    }
+
+    #[test]
+    fn test_sora_video_framing() {
+        let mut request = video_request("sora-2", Some("9:16"), None);
+        assert!(apply_to_video_request(&mut request, "openai").is_some());
+        assert_eq!(request.resolution.as_deref(), Some("720x1280"));
+
+        let mut request = video_request("sora-2-pro", None, Some("1920x1080"));
+        let framing = apply_to_video_request(&mut request, "openai").unwrap();
+        assert_eq!(request.resolution.as_deref(), Some("1792x1024"));
+        assert!(framing.adjusted);
+    }
+
+    #[test]
+    fn test_kling_video_framing() {
+        let mut request = video_request("kling-v2-master", None, Some("1000x1000"));
+        apply_to_video_request(&mut request, "kling");
+        assert_eq!(request.resolution.as_deref(), Some("1080x1080"));
+
+        let mut request = video_request("kling-v2-master", None, Some("1280x720"));
+        let framing = apply_to_video_request(&mut request, "kling").unwrap();
+        assert_eq!(request.resolution.as_deref(), Some("1280x720"));
+        assert!(!framing.adjusted);
+    }
+
+    #[test]
+    fn test_veo_video_framing() {
+        let mut request = video_request("veo-3.0-generate-preview", Some("4:3"), Some("1080p"));
+        let framing = apply_to_video_request(&mut request, "veo").unwrap();
+        assert_eq!(request.aspect_ratio.as_deref(), Some("16:9"));
+        assert_eq!(request.resolution.as_deref(), Some("1080p"));
+        assert!(framing.adjusted);
+
+        let mut request = video_request("veo-3.0-generate-preview", None, Some("720x1280"));
+        apply_to_video_request(&mut request, "veo");
+        assert_eq!(request.aspect_ratio.as_deref(), Some("9:16"));
+        assert_eq!(request.resolution.as_deref(), Some("720p"));
+
+        // Veo 2 不接受 resolution
+        let mut request = video_request("veo-2.0-generate-001", Some("16:9"), Some("720p"));
+        apply_to_video_request(&mut request, "veo");
+        assert_eq!(request.resolution, None);
+    }
+
+    #[test]
+    fn test_newapi_video_framing() {
+        let mut request = video_request("wan2.2", None, Some("1000x562"));
+        apply_to_video_request(&mut request, "newapi");
+        assert_eq!(request.resolution.as_deref(), Some("1008x560"));
+
+        let mut request = video_request("wan2.2", None, None);
+        assert!(apply_to_video_request(&mut request, "newapi").is_none());
+        assert_eq!(request.resolution, None);
+    }
+}
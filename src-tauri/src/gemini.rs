@@ -2,6 +2,10 @@ use reqwest::Client;
 use serde::{Deserialize, Serialize};
 use std::time::Duration;
 
+use crate::image_preprocess::{preprocess_base64_image, ImageLimits, ImagePreprocessReport};
+use crate::image_provider::random_seed;
+use crate::storage::GenerationParams;
+
 // Gemini API 请求结构
 #[derive(Debug, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -25,7 +29,7 @@ pub enum Part {
     InlineData { inline_data: InlineData },
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct InlineData {
     pub mime_type: String,
@@ -39,6 +43,10 @@ pub struct GenerationConfig {
     pub response_modalities: Option<Vec<String>>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub image_config: Option<ImageConfig>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub candidate_count: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub seed: Option<i64>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -55,11 +63,24 @@ pub struct ImageConfig {
 pub struct GeminiResponse {
     pub candidates: Option<Vec<Candidate>>,
     pub error: Option<GeminiError>,
+    #[serde(rename = "usageMetadata")]
+    pub usage_metadata: Option<serde_json::Value>,
+    #[serde(rename = "promptFeedback")]
+    pub prompt_feedback: Option<PromptFeedback>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Candidate {
     pub content: Option<CandidateContent>,
+    #[serde(rename = "finishReason")]
+    pub finish_reason: Option<String>,
+}
+
+// 提示词被拦截时返回 blockReason，此时没有候选结果
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PromptFeedback {
+    #[serde(rename = "blockReason")]
+    pub block_reason: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -72,6 +93,8 @@ pub struct CandidateContent {
 pub struct ResponsePart {
     pub text: Option<String>,
     pub inline_data: Option<InlineData>,
+    pub thought: Option<bool>,
+    pub thought_signature: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -91,18 +114,48 @@ pub struct GeminiRequestParams {
     pub input_images: Option<Vec<String>>, // base64 图片数据
     pub aspect_ratio: Option<String>,
     pub image_size: Option<String>,
+    pub seed: Option<i64>,
+    pub candidate_count: Option<i32>, // 候选数量，每个候选对应一组输出
+    pub include_text: Option<bool>,   // 是否允许图文交错输出
+}
+
+// 按返回顺序排列的输出片段
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GeminiOutputPart {
+    pub candidate_index: usize,
+    pub text: Option<String>,
+    pub image_data: Option<String>,
+    pub mime_type: Option<String>,
 }
 
 // 前端返回的结果
-#[derive(Debug, Serialize)]
+#[derive(Debug, Default, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct GeminiResult {
     pub success: bool,
     pub image_data: Option<String>,
+    pub image_data_list: Option<Vec<String>>,
     pub text: Option<String>,
+    pub parts: Option<Vec<GeminiOutputPart>>,
+    pub usage: Option<serde_json::Value>,
+    pub preprocessing: Option<Vec<ImagePreprocessReport>>, // 被调整过的输入图片
+    pub seed: Option<i64>,                                  // 请求使用的种子
+    pub generation: Option<GenerationParams>, // 生成参数，保存图片时写入元数据
+    pub block_reason: Option<String>, // 未正常结束的原因，如 "finishReason: IMAGE_SAFETY"
     pub error: Option<String>,
 }
 
+impl GeminiResult {
+    pub(crate) fn failure(error: impl Into<String>) -> Self {
+        Self {
+            success: false,
+            error: Some(error.into()),
+            ..Self::default()
+        }
+    }
+}
+
 // Tauri 命令：发送 Gemini API 请求
 #[tauri::command]
 pub async fn gemini_generate_content(params: GeminiRequestParams) -> GeminiResult {
@@ -114,21 +167,29 @@ pub async fn gemini_generate_content(params: GeminiRequestParams) -> GeminiResul
         params.input_images.as_ref().map(|v| v.len()).unwrap_or(0)
     );
 
+    // 未指定种子时随机生成一个，结果才能复现
+    let seed = params.seed.or_else(|| Some(random_seed()));
+    let generation = gemini_generation(&params, seed);
+
     // 构建请求体
     let mut parts: Vec<Part> = vec![Part::Text {
         text: params.prompt,
     }];
 
-    // 添加输入图片
+    // 添加输入图片（按 Gemini 限制预处理）
+    let mut preprocessing: Vec<ImagePreprocessReport> = Vec::new();
     if let Some(images) = params.input_images {
         println!("[Rust] Adding {} images to request", images.len());
-        for image_data in images {
-            parts.push(Part::InlineData {
-                inline_data: InlineData {
-                    mime_type: "image/png".to_string(),
-                    data: image_data,
-                },
-            });
+        for image_data in images.iter().filter(|image| !image.trim().is_empty()) {
+            match inline_image(image_data).await {
+                Ok((inline_data, report)) => {
+                    if report.changed() {
+                        preprocessing.push(report);
+                    }
+                    parts.push(Part::InlineData { inline_data });
+                }
+                Err(e) => return GeminiResult::failure(format!("输入图片处理失败: {}", e)),
+            }
         }
     }
 
@@ -136,25 +197,134 @@ pub async fn gemini_generate_content(params: GeminiRequestParams) -> GeminiResul
         contents: vec![Content { parts }],
         system_instruction: None,
         generation_config: Some(GenerationConfig {
-            response_modalities: Some(vec!["IMAGE".to_string()]),
+            response_modalities: Some(if params.include_text.unwrap_or(false) {
+                vec!["TEXT".to_string(), "IMAGE".to_string()]
+            } else {
+                vec!["IMAGE".to_string()]
+            }),
             image_config: Some(ImageConfig {
                 aspect_ratio: params.aspect_ratio,
                 image_size: params.image_size,
             }),
+            candidate_count: params.candidate_count.map(|n| n.max(1)),
+            seed,
         }),
     };
 
+    let gemini_response = match send_generate_content(
+        &params.base_url,
+        &params.api_key,
+        &params.model,
+        &request_body,
+    )
+    .await
+    {
+        Ok(r) => r,
+        Err(e) => return GeminiResult::failure(e),
+    };
+
+    // 提取结果：保留所有候选中的图片与文字，按返回顺序排列
+    let block_reason = block_reason(&gemini_response);
+    let output_parts = collect_output_parts(gemini_response.candidates.unwrap_or_default());
+    let image_data_list: Vec<String> = output_parts
+        .iter()
+        .filter_map(|part| part.image_data.clone())
+        .collect();
+    let texts: Vec<String> = output_parts
+        .iter()
+        .filter_map(|part| part.text.clone())
+        .collect();
+
+    println!(
+        "[Rust] Result: images={}, text_parts={}",
+        image_data_list.len(),
+        texts.len()
+    );
+
+    if image_data_list.is_empty() && texts.is_empty() {
+        return GeminiResult::failure(empty_output_error(
+            "API 未返回有效内容",
+            block_reason.as_deref(),
+        ));
+    }
+
+    GeminiResult {
+        success: true,
+        image_data: image_data_list.first().cloned(),
+        image_data_list: Some(image_data_list),
+        text: if texts.is_empty() {
+            None
+        } else {
+            Some(texts.join("\n\n"))
+        },
+        parts: Some(output_parts),
+        usage: gemini_response.usage_metadata,
+        preprocessing: if preprocessing.is_empty() {
+            None
+        } else {
+            Some(preprocessing)
+        },
+        seed,
+        generation: Some(generation),
+        block_reason,
+        error: None,
+    }
+}
+
+/// 记录到图片元数据的生成参数，生成变体时据此重建请求
+pub(crate) fn gemini_generation(params: &GeminiRequestParams, seed: Option<i64>) -> GenerationParams {
+    GenerationParams {
+        provider: Some("gemini".to_string()),
+        model: Some(params.model.clone()),
+        seed,
+        extra: Some(serde_json::json!({
+            "aspectRatio": params.aspect_ratio,
+            "imageSize": params.image_size,
+        })),
+        ..GenerationParams::default()
+    }
+}
+
+/// 将 base64 输入图片按 Gemini 的限制预处理后转换为 inlineData
+pub(crate) async fn inline_image(
+    image_data: &str,
+) -> Result<(InlineData, ImagePreprocessReport), String> {
+    let prepared =
+        preprocess_base64_image(image_data.to_string(), ImageLimits::for_provider("gemini"))
+            .await?;
+    let data = if prepared.report.changed() {
+        prepared.to_base64()
+    } else {
+        // 未改动时沿用原始数据，避免重复编码
+        crate::image_utils::strip_data_url_prefix(image_data).to_string()
+    };
+    Ok((
+        InlineData {
+            mime_type: prepared.mime_type().to_string(),
+            data,
+        },
+        prepared.report,
+    ))
+}
+
+/// 发送 generateContent 请求并解析响应，单轮生成与编辑会话共用
+pub(crate) async fn send_generate_content<T: Serialize>(
+    base_url: &str,
+    api_key: &str,
+    model: &str,
+    request_body: &T,
+) -> Result<GeminiResponse, String> {
     // 构建 URL
     let url = format!(
         "{}/v1beta/models/{}:generateContent?key={}",
-        params.base_url.trim_end_matches('/'),
-        params.model,
-        params.api_key
+        base_url.trim_end_matches('/'),
+        model,
+        api_key
     );
     println!(
         "[Rust] Request URL (without key): {}/v1beta/models/{}:generateContent",
-        params.base_url.trim_end_matches('/'),
-        params.model
+        base_url.trim_end_matches('/'),
+        model
     );
 
     // 创建 HTTP 客户端，设置较长的超时时间（10分钟）
@@ -163,12 +333,7 @@ pub async fn gemini_generate_content(params: GeminiRequestParams) -> GeminiResul
         Ok(c) => c,
         Err(e) => {
             println!("[Rust] Failed to create HTTP client: {}", e);
-            return GeminiResult {
-                success: false,
-                image_data: None,
-                text: None,
-                error: Some(format!("创建 HTTP 客户端失败: {}", e)),
-            };
+            return Err(format!("创建 HTTP 客户端失败: {}", e));
         }
     };
 
@@ -179,7 +344,7 @@ pub async fn gemini_generate_content(params: GeminiRequestParams) -> GeminiResul
     let response = match client
         .post(&url)
         .header("Content-Type", "application/json")
-        .json(&request_body)
+        .json(request_body)
         .send()
         .await
     {
@@ -200,12 +365,7 @@ pub async fn gemini_generate_content(params: GeminiRequestParams) -> GeminiResul
             } else {
                 format!("请求失败: {}", e)
             };
-            return GeminiResult {
-                success: false,
-                image_data: None,
-                text: None,
-                error: Some(error_msg),
-            };
+            return Err(error_msg);
         }
     };
 
@@ -215,12 +375,7 @@ pub async fn gemini_generate_content(params: GeminiRequestParams) -> GeminiResul
     if !status.is_success() {
         let error_text = response.text().await.unwrap_or_default();
         println!("[Rust] Error response: {}", error_text);
-        return GeminiResult {
-            success: false,
-            image_data: None,
-            text: None,
-            error: Some(format!("API 返回错误 ({}): {}", status, error_text)),
-        };
+        return Err(format!("API 返回错误 ({}): {}", status, error_text));
     }
 
     // 先获取响应文本，再解析 JSON
@@ -229,12 +384,7 @@ pub async fn gemini_generate_content(params: GeminiRequestParams) -> GeminiResul
         Ok(t) => t,
         Err(e) => {
             println!("[Rust] Failed to get response text: {}", e);
-            return GeminiResult {
-                success: false,
-                image_data: None,
-                text: None,
-                error: Some(format!("获取响应失败: {}", e)),
-            };
+            return Err(format!("获取响应失败: {}", e));
         }
     };
 
@@ -249,7 +399,7 @@ pub async fn gemini_generate_content(params: GeminiRequestParams) -> GeminiResul
 
     // 解析 JSON
     println!("[Rust] Parsing JSON...");
-    let gemini_response: GeminiResponse = match serde_json::from_str(&response_text) {
+    let mut gemini_response: GeminiResponse = match serde_json::from_str(&response_text) {
         Ok(r) => r,
         Err(e) => {
             println!("[Rust] Failed to parse JSON: {}", e);
@@ -258,68 +408,88 @@ pub async fn gemini_generate_content(params: GeminiRequestParams) -> GeminiResul
                 e.line(),
                 e.column()
             );
-            return GeminiResult {
-                success: false,
-                image_data: None,
-                text: None,
-                error: Some(format!("解析响应失败: {}", e)),
-            };
+            return Err(format!("解析响应失败: {}", e));
         }
     };
 
     // 检查 API 错误
-    if let Some(err) = gemini_response.error {
+    if let Some(err) = gemini_response.error.take() {
         println!("[Rust] API error: {}", err.message);
-        return GeminiResult {
-            success: false,
-            image_data: None,
-            text: None,
-            error: Some(err.message),
-        };
+        return Err(err.message);
     }
 
-    // 提取结果
-    let mut image_data: Option<String> = None;
-    let mut text: Option<String> = None;
+    Ok(gemini_response)
+}
 
-    if let Some(candidates) = gemini_response.candidates {
-        if let Some(candidate) = candidates.first() {
-            if let Some(content) = &candidate.content {
-                if let Some(parts) = &content.parts {
-                    for part in parts {
-                        if let Some(inline) = &part.inline_data {
-                            image_data = Some(inline.data.clone());
-                        }
-                        if let Some(t) = &part.text {
-                            text = Some(t.clone());
-                        }
-                    }
-                }
-            }
+// 属于内容安全拦截的 finishReason，IMAGE_ 前缀的变体同样以这些结尾
+const SAFETY_FINISH_REASONS: [&str; 4] = ["SAFETY", "PROHIBITED_CONTENT", "BLOCKLIST", "SPII"];
+
+/// 响应没有正常结束的原因：提示词的 blockReason，或候选的 finishReason
+pub(crate) fn block_reason(response: &GeminiResponse) -> Option<String> {
+    if let Some(reason) = response
+        .prompt_feedback
+        .as_ref()
+        .and_then(|feedback| feedback.block_reason.as_ref())
+    {
+        return Some(format!("blockReason: {}", reason));
+    }
+    response
+        .candidates
+        .iter()
+        .flatten()
+        .filter_map(|candidate| candidate.finish_reason.as_deref())
+        .find(|reason| !matches!(*reason, "STOP" | "FINISH_REASON_UNSPECIFIED"))
+        .map(|reason| format!("finishReason: {}", reason))
+}
+
+/// 没有返回内容时的错误信息，带上拦截原因，降级链据此判断是否为安全拦截
+pub(crate) fn empty_output_error(message: &str, reason: Option<&str>) -> String {
+    match reason {
+        Some(reason)
+            if reason.starts_with("blockReason")
+                || SAFETY_FINISH_REASONS.iter().any(|r| reason.ends_with(r)) =>
+        {
+            format!("{}，内容被安全策略拦截 ({})", message, reason)
         }
+        Some(reason) => format!("{} ({})", message, reason),
+        None => message.to_string(),
     }
+}
 
-    println!(
-        "[Rust] Result: has_image={}, has_text={}",
-        image_data.is_some(),
-        text.is_some()
-    );
+pub(crate) fn collect_output_parts(candidates: Vec<Candidate>) -> Vec<GeminiOutputPart> {
+    let mut output_parts = Vec::new();
 
-    if image_data.is_none() && text.is_none() {
-        return GeminiResult {
-            success: false,
-            image_data: None,
-            text: None,
-            error: Some("API 未返回有效内容".to_string()),
-        };
-    }
+    for (candidate_index, candidate) in candidates.into_iter().enumerate() {
+        let parts = candidate
+            .content
+            .and_then(|content| content.parts)
+            .unwrap_or_default();
 
-    GeminiResult {
-        success: true,
-        image_data,
-        text,
-        error: None,
+        for part in parts {
+            // 思考过程中的文字不作为输出
+            if part.thought.unwrap_or(false) {
+                continue;
+            }
+            if let Some(inline) = part.inline_data {
+                output_parts.push(GeminiOutputPart {
+                    candidate_index,
+                    text: None,
+                    image_data: Some(inline.data),
+                    mime_type: Some(inline.mime_type),
+                });
+            }
+            if let Some(text) = part.text.filter(|t| !t.trim().is_empty()) {
+                output_parts.push(GeminiOutputPart {
+                    candidate_index,
+                    text: Some(text),
+                    image_data: None,
+                    mime_type: None,
+                });
+            }
+        }
     }
+
+    output_parts
 }
 
 // 文件数据结构（用于LLM内容生成）
@@ -417,11 +587,17 @@ pub async fn gemini_generate_text(params: LLMRequestParams) -> LLMResult {
                 "[Rust] Adding file: mime_type={}, name={:?}",
                 file.mime_type, file.file_name
             );
+            // 图片按 Gemini 限制预处理，失败时沿用原始数据
+            let inline_data = if file.mime_type.starts_with("image/") {
+                inline_image(&file.data).await.map(|(data, _)| data).ok()
+            } else {
+                None
+            };
             parts.push(Part::InlineData {
-                inline_data: InlineData {
+                inline_data: inline_data.unwrap_or_else(|| InlineData {
                     mime_type: file.mime_type.clone(),
                     data: file.data.clone(),
-                },
+                }),
             });
         }
     }
@@ -547,6 +723,7 @@ pub async fn gemini_generate_text(params: LLMRequestParams) -> LLMResult {
     }
 
     // 提取文本内容
+    let reason = block_reason(&gemini_response);
     let mut content: Option<String> = None;
 
     if let Some(candidates) = gemini_response.candidates {
@@ -571,7 +748,7 @@ pub async fn gemini_generate_text(params: LLMRequestParams) -> LLMResult {
         return LLMResult {
             success: false,
             content: None,
-            error: Some("API 未返回有效内容".to_string()),
+            error: Some(empty_output_error("API 未返回有效内容", reason.as_deref())),
         };
     }
 
@@ -586,3 +763,73 @@ pub async fn gemini_generate_text(params: LLMRequestParams) -> LLMResult {
         error: None,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn candidates(json: serde_json::Value) -> Vec<Candidate> {
+        serde_json::from_value::<GeminiResponse>(json)
+            .unwrap()
+            .candidates
+            .unwrap_or_default()
+    }
+
+    #[test]
+    fn test_collect_output_parts_keeps_mixed_order() {
+        let parts = collect_output_parts(candidates(serde_json::json!({
+            "candidates": [
+                {
+                    "content": {
+                        "parts": [
+                            { "text": "构思中", "thought": true },
+                            { "text": "第一张" },
+                            { "inlineData": { "mimeType": "image/png", "data": "AAA" } },
+                            { "text": "  " },
+                            { "inlineData": { "mimeType": "image/jpeg", "data": "BBB" } }
+                        ]
+                    }
+                },
+                {
+                    "content": {
+                        "parts": [{ "inlineData": { "mimeType": "image/webp", "data": "CCC" } }]
+                    }
+                }
+            ]
+        })));
+
+        let summary: Vec<_> = parts
+            .iter()
+            .map(|p| {
+                (
+                    p.candidate_index,
+                    p.text.as_deref(),
+                    p.image_data.as_deref(),
+                    p.mime_type.as_deref(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                (0, Some("第一张"), None, None),
+                (0, None, Some("AAA"), Some("image/png")),
+                (0, None, Some("BBB"), Some("image/jpeg")),
+                (1, None, Some("CCC"), Some("image/webp")),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_collect_output_parts_handles_empty_candidates() {
+        assert!(collect_output_parts(Vec::new()).is_empty());
+        let parts = collect_output_parts(candidates(serde_json::json!({
+            "candidates": [
+                { "finishReason": "SAFETY" },
+                { "content": {} },
+                { "content": { "parts": [] } }
+            ]
+        })));
+        assert!(parts.is_empty());
+    }
+}
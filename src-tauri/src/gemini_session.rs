@@ -0,0 +1,641 @@
+//! Gemini 多轮图片编辑会话
+//!
+//! 会话历史保存在后端：用户轮次、模型返回的图片轮次以及 thought signature，
+//! 后续编辑（"把天空调暗一些"）只需发送新的指令，由后端拼接完整上下文。
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use uuid::Uuid;
+
+use crate::gemini::{
+    block_reason, collect_output_parts, empty_output_error, inline_image, send_generate_content,
+    GeminiResult, GenerationConfig, ImageConfig, InlineData, ResponsePart,
+};
+
+// 每个会话单独加锁，一轮编辑从读取历史到写回结果期间持有
+type SessionHandle = Arc<tokio::sync::Mutex<EditSession>>;
+
+// 会话历史带有完整图片数据，前端未结束的会话闲置过久后回收
+const SESSION_IDLE_TTL: Duration = Duration::from_secs(2 * 60 * 60);
+const MAX_SESSIONS: usize = 20;
+
+struct SessionEntry {
+    handle: SessionHandle,
+    last_used: Instant,
+}
+
+lazy_static::lazy_static! {
+    static ref SESSIONS: Mutex<HashMap<String, SessionEntry>> = Mutex::new(HashMap::new());
+}
+
+// 会话中的单个片段，原样回传模型返回的 thought 与 thoughtSignature
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SessionPart {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    text: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    inline_data: Option<InlineData>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    thought: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    thought_signature: Option<String>,
+}
+
+impl From<&ResponsePart> for SessionPart {
+    fn from(part: &ResponsePart) -> Self {
+        Self {
+            text: part.text.clone(),
+            inline_data: part.inline_data.clone(),
+            thought: part.thought,
+            thought_signature: part.thought_signature.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct SessionContent {
+    role: String, // "user" 或 "model"
+    parts: Vec<SessionPart>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SessionRequest {
+    contents: Vec<SessionContent>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    system_instruction: Option<SessionContent>,
+    generation_config: GenerationConfig,
+}
+
+#[derive(Debug, Clone)]
+struct EditSession {
+    base_url: String,
+    api_key: String,
+    model: String,
+    aspect_ratio: Option<String>,
+    image_size: Option<String>,
+    system_instruction: Option<String>,
+    history: Vec<SessionContent>,
+}
+
+impl EditSession {
+    /// 已完成的编辑轮数（一问一答算一轮）
+    fn round_count(&self) -> usize {
+        self.history.len() / 2
+    }
+
+    /// 只保留前 keep_rounds 轮，默认保留全部，返回保留的轮数
+    fn keep_rounds(&mut self, keep_rounds: Option<usize>) -> usize {
+        let rounds = keep_rounds
+            .unwrap_or(self.round_count())
+            .min(self.round_count());
+        self.history.truncate(rounds * 2);
+        rounds
+    }
+
+    /// 在历史后追加本轮输入构建请求，单轮的图片配置优先于会话配置
+    fn request(
+        &self,
+        user_content: SessionContent,
+        aspect_ratio: Option<String>,
+        image_size: Option<String>,
+    ) -> SessionRequest {
+        let mut contents = self.history.clone();
+        contents.push(user_content);
+        SessionRequest {
+            contents,
+            system_instruction: self.system_instruction.clone().map(|text| SessionContent {
+                role: "user".to_string(),
+                parts: vec![SessionPart {
+                    text: Some(text),
+                    inline_data: None,
+                    thought: None,
+                    thought_signature: None,
+                }],
+            }),
+            generation_config: GenerationConfig {
+                response_modalities: Some(vec!["TEXT".to_string(), "IMAGE".to_string()]),
+                image_config: Some(ImageConfig {
+                    aspect_ratio: aspect_ratio.or_else(|| self.aspect_ratio.clone()),
+                    image_size: image_size.or_else(|| self.image_size.clone()),
+                }),
+                candidate_count: None,
+                seed: None,
+            },
+        }
+    }
+}
+
+// 创建会话参数
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GeminiSessionCreateParams {
+    pub base_url: String,
+    pub api_key: String,
+    pub model: String,
+    pub aspect_ratio: Option<String>,
+    pub image_size: Option<String>,
+    pub system_instruction: Option<String>,
+}
+
+// 继续会话参数
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GeminiSessionContinueParams {
+    pub session_id: String,
+    pub prompt: String,
+    pub input_images: Option<Vec<String>>, // base64 图片数据
+    // 单轮覆盖会话的图片配置
+    pub aspect_ratio: Option<String>,
+    pub image_size: Option<String>,
+}
+
+// 分支会话参数
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GeminiSessionBranchParams {
+    pub session_id: String,
+    pub keep_rounds: Option<usize>, // 保留前几轮，默认保留全部
+}
+
+// 会话命令返回结果
+#[derive(Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GeminiSessionResult {
+    pub session_id: Option<String>,
+    pub round_count: usize,
+    #[serde(flatten)]
+    pub output: GeminiResult,
+}
+
+impl GeminiSessionResult {
+    fn failure(session_id: Option<String>, error: impl Into<String>) -> Self {
+        Self {
+            session_id,
+            output: GeminiResult::failure(error),
+            ..Self::default()
+        }
+    }
+
+    fn created(session_id: String, round_count: usize) -> Self {
+        Self {
+            session_id: Some(session_id),
+            round_count,
+            output: GeminiResult {
+                success: true,
+                ..GeminiResult::default()
+            },
+        }
+    }
+}
+
+fn get_session(session_id: &str) -> Option<SessionHandle> {
+    let mut sessions = SESSIONS.lock().ok()?;
+    let entry = sessions.get_mut(session_id)?;
+    entry.last_used = Instant::now();
+    Some(entry.handle.clone())
+}
+
+/// 回收闲置超时的会话，数量仍达到上限时按最近使用时间淘汰；进行中的会话不回收
+fn evict_sessions(sessions: &mut HashMap<String, SessionEntry>, now: Instant) {
+    let in_use = |entry: &SessionEntry| Arc::strong_count(&entry.handle) > 1;
+    sessions.retain(|_, entry| {
+        in_use(entry) || now.saturating_duration_since(entry.last_used) < SESSION_IDLE_TTL
+    });
+    while sessions.len() >= MAX_SESSIONS {
+        let oldest = sessions
+            .iter()
+            .filter(|(_, entry)| !in_use(entry))
+            .min_by_key(|(_, entry)| entry.last_used)
+            .map(|(id, _)| id.clone());
+        match oldest {
+            Some(id) => {
+                println!("[Rust] Session evicted: {}", id);
+                sessions.remove(&id);
+            }
+            None => break,
+        }
+    }
+}
+
+fn insert_session(session: EditSession) -> Result<String, String> {
+    let session_id = Uuid::new_v4().to_string();
+    let mut sessions = SESSIONS
+        .lock()
+        .map_err(|e| format!("会话状态异常: {}", e))?;
+    let now = Instant::now();
+    evict_sessions(&mut sessions, now);
+    sessions.insert(
+        session_id.clone(),
+        SessionEntry {
+            handle: Arc::new(tokio::sync::Mutex::new(session)),
+            last_used: now,
+        },
+    );
+    Ok(session_id)
+}
+
+// Tauri 命令：创建编辑会话
+#[tauri::command]
+pub fn gemini_session_create(params: GeminiSessionCreateParams) -> GeminiSessionResult {
+    println!(
+        "[Rust] gemini_session_create called, model: {}",
+        params.model
+    );
+
+    let session = EditSession {
+        base_url: params.base_url,
+        api_key: params.api_key,
+        model: params.model,
+        aspect_ratio: params.aspect_ratio,
+        image_size: params.image_size,
+        system_instruction: params
+            .system_instruction
+            .filter(|instruction| !instruction.trim().is_empty()),
+        history: Vec::new(),
+    };
+
+    match insert_session(session) {
+        Ok(session_id) => {
+            println!("[Rust] Session created: {}", session_id);
+            GeminiSessionResult::created(session_id, 0)
+        }
+        Err(e) => GeminiSessionResult::failure(None, e),
+    }
+}
+
+// Tauri 命令：在会话中发送一轮编辑指令
+#[tauri::command]
+pub async fn gemini_session_continue(params: GeminiSessionContinueParams) -> GeminiSessionResult {
+    let session_id = params.session_id;
+    println!(
+        "[Rust] gemini_session_continue called, session: {}",
+        session_id
+    );
+
+    let handle = match get_session(&session_id) {
+        Some(s) => s,
+        None => return GeminiSessionResult::failure(Some(session_id), "会话不存在或已结束"),
+    };
+
+    // 构建本轮用户输入
+    let mut user_parts = vec![SessionPart {
+        text: Some(params.prompt),
+        inline_data: None,
+        thought: None,
+        thought_signature: None,
+    }];
+    let mut preprocessing = Vec::new();
+    for image_data in params.input_images.unwrap_or_default() {
+        if image_data.trim().is_empty() {
+            continue;
+        }
+        let inline_data = match inline_image(&image_data).await {
+            Ok((data, report)) => {
+                if report.changed() {
+                    preprocessing.push(report);
+                }
+                data
+            }
+            Err(e) => {
+                return GeminiSessionResult::failure(
+                    Some(session_id),
+                    format!("输入图片处理失败: {}", e),
+                )
+            }
+        };
+        user_parts.push(SessionPart {
+            text: None,
+            inline_data: Some(inline_data),
+            thought: None,
+            thought_signature: None,
+        });
+    }
+    let user_content = SessionContent {
+        role: "user".to_string(),
+        parts: user_parts,
+    };
+
+    // 整轮持有会话锁：同一会话的并发指令依次执行，每轮都基于上一轮的结果
+    let mut session = handle.lock().await;
+    let request_body =
+        session.request(user_content.clone(), params.aspect_ratio, params.image_size);
+    println!(
+        "[Rust] Session history: {} rounds, sending {} contents",
+        session.round_count(),
+        request_body.contents.len()
+    );
+
+    let gemini_response = match send_generate_content(
+        &session.base_url,
+        &session.api_key,
+        &session.model,
+        &request_body,
+    )
+    .await
+    {
+        Ok(r) => r,
+        Err(e) => return GeminiSessionResult::failure(Some(session_id), e),
+    };
+
+    // 只有第一个候选会写入历史
+    let reason = block_reason(&gemini_response);
+    let candidates = gemini_response.candidates.unwrap_or_default();
+    let model_parts: Vec<SessionPart> = candidates
+        .first()
+        .and_then(|candidate| candidate.content.as_ref())
+        .and_then(|content| content.parts.as_ref())
+        .map(|parts| parts.iter().map(SessionPart::from).collect())
+        .unwrap_or_default();
+    if model_parts.is_empty() {
+        return GeminiSessionResult::failure(
+            Some(session_id),
+            empty_output_error("API 未返回有效内容", reason.as_deref()),
+        );
+    }
+
+    let output_parts = collect_output_parts(candidates.into_iter().take(1).collect());
+    let image_data_list: Vec<String> = output_parts
+        .iter()
+        .filter_map(|part| part.image_data.clone())
+        .collect();
+    let texts: Vec<String> = output_parts
+        .iter()
+        .filter_map(|part| part.text.clone())
+        .collect();
+
+    // 请求成功后才写入历史，失败的轮次不会污染上下文
+    session.history.push(user_content);
+    session.history.push(SessionContent {
+        role: "model".to_string(),
+        parts: model_parts,
+    });
+    let round_count = session.round_count();
+    drop(session);
+
+    println!(
+        "[Rust] Session round {} done: images={}, text_parts={}",
+        round_count,
+        image_data_list.len(),
+        texts.len()
+    );
+
+    GeminiSessionResult {
+        session_id: Some(session_id),
+        round_count,
+        output: GeminiResult {
+            success: true,
+            image_data: image_data_list.first().cloned(),
+            image_data_list: Some(image_data_list),
+            text: if texts.is_empty() {
+                None
+            } else {
+                Some(texts.join("\n\n"))
+            },
+            parts: Some(output_parts),
+            usage: gemini_response.usage_metadata,
+            preprocessing: if preprocessing.is_empty() {
+                None
+            } else {
+                Some(preprocessing)
+            },
+            // 会话轮次依赖上下文，无法单独复现，不记录种子和生成参数
+            seed: None,
+            generation: None,
+            block_reason: reason,
+            error: None,
+        },
+    }
+}
+
+// Tauri 命令：从已有会话分出新会话，可只保留前几轮
+#[tauri::command]
+pub async fn gemini_session_branch(params: GeminiSessionBranchParams) -> GeminiSessionResult {
+    println!(
+        "[Rust] gemini_session_branch called, session: {}, keep_rounds: {:?}",
+        params.session_id, params.keep_rounds
+    );
+
+    // 等待进行中的一轮写回后再复制历史
+    let mut session = match get_session(&params.session_id) {
+        Some(handle) => handle.lock().await.clone(),
+        None => return GeminiSessionResult::failure(Some(params.session_id), "会话不存在或已结束"),
+    };
+
+    let keep_rounds = session.keep_rounds(params.keep_rounds);
+
+    match insert_session(session) {
+        Ok(session_id) => {
+            println!(
+                "[Rust] Session branched: {} ({} rounds)",
+                session_id, keep_rounds
+            );
+            GeminiSessionResult::created(session_id, keep_rounds)
+        }
+        Err(e) => GeminiSessionResult::failure(None, e),
+    }
+}
+
+// Tauri 命令：结束并释放会话
+#[tauri::command]
+pub fn gemini_session_discard(session_id: String) -> bool {
+    println!(
+        "[Rust] gemini_session_discard called, session: {}",
+        session_id
+    );
+    SESSIONS
+        .lock()
+        .map(|mut sessions| sessions.remove(&session_id).is_some())
+        .unwrap_or(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{mock_http, MockRequest, MockResponse};
+
+    fn text_part(text: &str) -> SessionPart {
+        SessionPart {
+            text: Some(text.to_string()),
+            inline_data: None,
+            thought: None,
+            thought_signature: None,
+        }
+    }
+
+    fn session(rounds: usize) -> EditSession {
+        let mut history = Vec::new();
+        for round in 0..rounds {
+            history.push(SessionContent {
+                role: "user".to_string(),
+                parts: vec![text_part(&format!("edit {}", round))],
+            });
+            history.push(SessionContent {
+                role: "model".to_string(),
+                parts: vec![SessionPart {
+                    text: None,
+                    inline_data: Some(InlineData {
+                        mime_type: "image/png".to_string(),
+                        data: format!("image-{}", round),
+                    }),
+                    thought: None,
+                    thought_signature: Some(format!("sig-{}", round)),
+                }],
+            });
+        }
+        EditSession {
+            base_url: String::new(),
+            api_key: String::new(),
+            model: "gemini-2.5-flash-image".to_string(),
+            aspect_ratio: Some("1:1".to_string()),
+            image_size: None,
+            system_instruction: Some("keep the style".to_string()),
+            history,
+        }
+    }
+
+    #[test]
+    fn test_keep_rounds_trims_history() {
+        let mut branched = session(3);
+        assert_eq!(branched.keep_rounds(Some(1)), 1);
+        assert_eq!(branched.history.len(), 2);
+        assert_eq!(branched.history[1].role, "model");
+
+        let mut branched = session(3);
+        assert_eq!(branched.keep_rounds(Some(10)), 3);
+        assert_eq!(branched.history.len(), 6);
+        assert_eq!(session(2).keep_rounds(None), 2);
+        assert_eq!(session(2).keep_rounds(Some(0)), 0);
+    }
+
+    #[test]
+    fn test_request_serializes_image_parts_and_signatures() {
+        let user_content = SessionContent {
+            role: "user".to_string(),
+            parts: vec![
+                text_part("make the sky darker"),
+                SessionPart {
+                    text: None,
+                    inline_data: Some(InlineData {
+                        mime_type: "image/jpeg".to_string(),
+                        data: "input".to_string(),
+                    }),
+                    thought: None,
+                    thought_signature: None,
+                },
+            ],
+        };
+        let request = session(1).request(user_content, Some("16:9".to_string()), None);
+        let body = serde_json::to_value(&request).unwrap();
+
+        let contents = body["contents"].as_array().unwrap();
+        assert_eq!(contents.len(), 3);
+        let model_part = &contents[1]["parts"][0];
+        assert_eq!(model_part["inlineData"]["mimeType"], "image/png");
+        assert_eq!(model_part["thoughtSignature"], "sig-0");
+        assert!(model_part.get("text").is_none());
+        assert_eq!(contents[2]["parts"][0]["text"], "make the sky darker");
+        assert_eq!(contents[2]["parts"][1]["inlineData"]["data"], "input");
+        assert!(contents[2]["parts"][1].get("thoughtSignature").is_none());
+
+        assert_eq!(
+            body["systemInstruction"]["parts"][0]["text"],
+            "keep the style"
+        );
+        let config = &body["generationConfig"];
+        assert_eq!(
+            config["responseModalities"],
+            serde_json::json!(["TEXT", "IMAGE"])
+        );
+        assert_eq!(config["imageConfig"]["aspectRatio"], "16:9");
+    }
+
+    // 记录每次请求携带的历史条数，第一次请求延迟返回以制造并发
+    async fn start_mock_server() -> (String, Arc<Mutex<Vec<usize>>>) {
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let recorder = received.clone();
+        let base_url = mock_http(move |request: MockRequest| {
+            let first = {
+                let mut received = recorder.lock().unwrap();
+                received.push(request.json()["contents"].as_array().unwrap().len());
+                received.len() == 1
+            };
+            async move {
+                if first {
+                    tokio::time::sleep(Duration::from_millis(200)).await;
+                }
+                MockResponse::json(
+                    br#"{"candidates":[{"content":{"role":"model","parts":[{"inlineData":{"mimeType":"image/png","data":"b3V0"},"thoughtSignature":"sig"}]},"finishReason":"STOP"}]}"#.to_vec(),
+                )
+            }
+        })
+        .await;
+        (base_url, received)
+    }
+
+    #[test]
+    fn test_idle_and_excess_sessions_are_evicted() {
+        let now = Instant::now();
+        let entry = |idle_secs: u64| SessionEntry {
+            handle: Arc::new(tokio::sync::Mutex::new(session(1))),
+            last_used: now - Duration::from_secs(idle_secs),
+        };
+        let later = now + SESSION_IDLE_TTL;
+
+        // 闲置超时的会话被回收，仍被持有的会话保留
+        let mut sessions = HashMap::new();
+        sessions.insert("idle".to_string(), entry(10));
+        sessions.insert("recent".to_string(), entry(0));
+        let running = entry(20);
+        let _held = running.handle.clone();
+        sessions.insert("running".to_string(), running);
+        evict_sessions(&mut sessions, later - Duration::from_secs(5));
+        let mut ids: Vec<_> = sessions.keys().cloned().collect();
+        ids.sort();
+        assert_eq!(ids, vec!["recent", "running"]);
+
+        // 达到上限时淘汰最久未使用的会话，为新会话留出位置
+        let mut sessions: HashMap<String, SessionEntry> = (0..MAX_SESSIONS)
+            .map(|index| (format!("s{}", index), entry(index as u64)))
+            .collect();
+        evict_sessions(&mut sessions, now);
+        assert_eq!(sessions.len(), MAX_SESSIONS - 1);
+        assert!(!sessions.contains_key(&format!("s{}", MAX_SESSIONS - 1)));
+    }
+
+    fn continue_params(session_id: &str, prompt: &str) -> GeminiSessionContinueParams {
+        GeminiSessionContinueParams {
+            session_id: session_id.to_string(),
+            prompt: prompt.to_string(),
+            input_images: None,
+            aspect_ratio: None,
+            image_size: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_rounds_keep_every_turn() {
+        let (base_url, received) = start_mock_server().await;
+        let mut edit_session = session(0);
+        edit_session.base_url = base_url;
+        let session_id = insert_session(edit_session).unwrap();
+
+        let (first, second) = tokio::join!(
+            gemini_session_continue(continue_params(&session_id, "add a moon")),
+            gemini_session_continue(continue_params(&session_id, "make it snow")),
+        );
+        assert!(first.output.success && second.output.success);
+        let mut rounds = vec![first.round_count, second.round_count];
+        rounds.sort();
+        assert_eq!(rounds, vec![1, 2]);
+        // 第二轮在第一轮写回后才发出，带上了完整的上一轮
+        assert_eq!(*received.lock().unwrap(), vec![1, 3]);
+
+        let stored = get_session(&session_id).unwrap();
+        assert_eq!(stored.lock().await.history.len(), 4);
+        assert!(gemini_session_discard(session_id));
+    }
+}
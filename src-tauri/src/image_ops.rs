@@ -0,0 +1,534 @@
+//! 本地图片编辑
+//!
+//! 裁剪、缩放、旋转、翻转、扩边、亮度/对比度/饱和度、锐化与格式转换，
+//! 可按顺序串联多个操作。处理在阻塞线程中执行，结果可通过 `save_image`
+//! 保存并在元数据中记录来源图片。
+
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use image::codecs::jpeg::JpegEncoder;
+use image::imageops::FilterType;
+use image::{DynamicImage, GenericImageView, ImageFormat, Rgba, RgbaImage};
+use serde::{Deserialize, Serialize};
+use std::io::Cursor;
+
+use crate::image_utils::{decode_base64_image, ImageKind};
+use crate::storage::{save_image, GenerationParams, ImageInfo, ImageType, InputImageInfo};
+
+const MAX_OUTPUT_DIMENSION: u32 = 16384;
+const DEFAULT_JPEG_QUALITY: u8 = 90;
+
+// 单个编辑操作，按 op 字段区分
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "camelCase", rename_all_fields = "camelCase")]
+pub enum ImageOp {
+    Crop {
+        x: u32,
+        y: u32,
+        width: u32,
+        height: u32,
+    },
+    Resize {
+        width: Option<u32>,
+        height: Option<u32>,
+        mode: Option<String>, // "fit"（默认，保持比例）| "fill"（裁切填满）| "exact"
+    },
+    Rotate {
+        degrees: f32, // 顺时针；非 90 倍数时扩展画布，空白透明
+    },
+    Flip {
+        direction: String, // "horizontal" | "vertical"
+    },
+    Pad {
+        top: u32,
+        right: u32,
+        bottom: u32,
+        left: u32,
+        color: Option<String>, // "#rrggbb" 或 "#rrggbbaa"，默认透明
+    },
+    Brightness {
+        value: i32, // -255 到 255
+    },
+    Contrast {
+        value: f32, // 百分比，正数增强
+    },
+    Saturation {
+        value: f32, // 倍数，1.0 不变，0 为灰度
+    },
+    Sharpen {
+        sigma: Option<f32>,
+        threshold: Option<i32>,
+    },
+}
+
+// 前端调用的参数
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImageOpsParams {
+    pub image_data: Option<String>, // base64，与 image_path 二选一
+    pub image_path: Option<String>, // 本地图片路径，同时作为来源记录
+    #[serde(default)]
+    pub operations: Vec<ImageOp>,
+    pub output_format: Option<String>, // "png"（默认）| "jpeg" | "webp"
+    pub quality: Option<u8>,           // JPEG 质量
+    pub save_to_storage: Option<bool>, // 默认只返回结果不落盘
+    pub canvas_id: Option<String>,
+    pub node_id: Option<String>,
+}
+
+// 返回结果
+#[derive(Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImageOpsResult {
+    pub success: bool,
+    pub image_data: Option<String>,
+    pub mime_type: Option<String>,
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    pub saved_image: Option<ImageInfo>,
+    pub error: Option<String>,
+}
+
+impl ImageOpsResult {
+    fn failure(error: impl Into<String>) -> Self {
+        Self {
+            success: false,
+            error: Some(error.into()),
+            ..Self::default()
+        }
+    }
+}
+
+/// 解析 "#rrggbb" / "#rrggbbaa" 颜色
+pub(crate) fn parse_color(text: &str) -> Result<Rgba<u8>, String> {
+    let hex = text.trim().trim_start_matches('#');
+    if !hex.is_ascii() {
+        return Err(format!("无效的颜色: {}", text));
+    }
+    let channel = |index: usize| {
+        u8::from_str_radix(&hex[index * 2..index * 2 + 2], 16)
+            .map_err(|_| format!("无效的颜色: {}", text))
+    };
+    match hex.len() {
+        6 => Ok(Rgba([channel(0)?, channel(1)?, channel(2)?, 255])),
+        8 => Ok(Rgba([channel(0)?, channel(1)?, channel(2)?, channel(3)?])),
+        _ => Err(format!("无效的颜色: {}", text)),
+    }
+}
+
+/// 任意角度旋转（双线性插值），画布扩展到能容纳整张图
+fn rotate_any(image: &DynamicImage, degrees: f32) -> RgbaImage {
+    let source = image.to_rgba8();
+    let (w, h) = source.dimensions();
+    let radians = degrees.to_radians();
+    let (sin, cos) = radians.sin_cos();
+    let out_w = (w as f32 * cos.abs() + h as f32 * sin.abs()).ceil() as u32;
+    let out_h = (w as f32 * sin.abs() + h as f32 * cos.abs()).ceil() as u32;
+    let (cx, cy) = (w as f32 / 2.0, h as f32 / 2.0);
+    let (ocx, ocy) = (out_w as f32 / 2.0, out_h as f32 / 2.0);
+
+    RgbaImage::from_fn(out_w, out_h, |x, y| {
+        // 反向映射回原图坐标
+        let dx = x as f32 + 0.5 - ocx;
+        let dy = y as f32 + 0.5 - ocy;
+        let sx = dx * cos + dy * sin + cx - 0.5;
+        let sy = -dx * sin + dy * cos + cy - 0.5;
+        if sx < -0.5 || sy < -0.5 || sx > w as f32 - 0.5 || sy > h as f32 - 0.5 {
+            return Rgba([0, 0, 0, 0]);
+        }
+        let (x0, y0) = (sx.floor().max(0.0) as u32, sy.floor().max(0.0) as u32);
+        let (x1, y1) = ((x0 + 1).min(w - 1), (y0 + 1).min(h - 1));
+        let (fx, fy) = (
+            (sx - x0 as f32).clamp(0.0, 1.0),
+            (sy - y0 as f32).clamp(0.0, 1.0),
+        );
+        let mut pixel = [0u8; 4];
+        for (channel, value) in pixel.iter_mut().enumerate() {
+            let top = source.get_pixel(x0, y0)[channel] as f32 * (1.0 - fx)
+                + source.get_pixel(x1, y0)[channel] as f32 * fx;
+            let bottom = source.get_pixel(x0, y1)[channel] as f32 * (1.0 - fx)
+                + source.get_pixel(x1, y1)[channel] as f32 * fx;
+            *value = (top * (1.0 - fy) + bottom * fy).round() as u8;
+        }
+        Rgba(pixel)
+    })
+}
+
+/// 调整饱和度：按亮度插值
+fn adjust_saturation(image: &DynamicImage, factor: f32) -> RgbaImage {
+    let mut output = image.to_rgba8();
+    for pixel in output.pixels_mut() {
+        let [r, g, b, a] = pixel.0;
+        let luma = 0.299 * r as f32 + 0.587 * g as f32 + 0.114 * b as f32;
+        let mix = |c: u8| {
+            (luma + (c as f32 - luma) * factor)
+                .clamp(0.0, 255.0)
+                .round() as u8
+        };
+        *pixel = Rgba([mix(r), mix(g), mix(b), a]);
+    }
+    output
+}
+
+/// 执行单个操作
+pub fn apply_op(image: DynamicImage, op: &ImageOp) -> Result<DynamicImage, String> {
+    let (w, h) = image.dimensions();
+    let output = match op {
+        ImageOp::Crop {
+            x,
+            y,
+            width,
+            height,
+        } => {
+            // 参数来自前端，相加可能溢出
+            let exceeds = |start: u32, len: u32, limit: u32| {
+                start.checked_add(len).is_none_or(|end| end > limit)
+            };
+            if *width == 0 || *height == 0 || exceeds(*x, *width, w) || exceeds(*y, *height, h) {
+                return Err(format!(
+                    "裁剪区域 ({}, {}, {}x{}) 超出图片范围 {}x{}",
+                    x, y, width, height, w, h
+                ));
+            }
+            image.crop_imm(*x, *y, *width, *height)
+        }
+        ImageOp::Resize {
+            width,
+            height,
+            mode,
+        } => {
+            // 只给一边时按比例计算另一边
+            let (tw, th) = match (width, height) {
+                (Some(tw), Some(th)) => (*tw, *th),
+                (Some(tw), None) => (*tw, ((*tw as f64) * h as f64 / w as f64).round() as u32),
+                (None, Some(th)) => (((*th as f64) * w as f64 / h as f64).round() as u32, *th),
+                (None, None) => return Err("缩放需要指定宽度或高度".to_string()),
+            };
+            if tw == 0 || th == 0 || tw > MAX_OUTPUT_DIMENSION || th > MAX_OUTPUT_DIMENSION {
+                return Err(format!("无效的缩放尺寸: {}x{}", tw, th));
+            }
+            match mode.as_deref().unwrap_or("fit") {
+                "fit" => image.resize(tw, th, FilterType::Lanczos3),
+                "fill" => image.resize_to_fill(tw, th, FilterType::Lanczos3),
+                "exact" => image.resize_exact(tw, th, FilterType::Lanczos3),
+                other => return Err(format!("不支持的缩放模式: {}", other)),
+            }
+        }
+        ImageOp::Rotate { degrees } => {
+            let normalized = degrees.rem_euclid(360.0);
+            if normalized.abs() < f32::EPSILON {
+                image
+            } else if (normalized - 90.0).abs() < f32::EPSILON {
+                image.rotate90()
+            } else if (normalized - 180.0).abs() < f32::EPSILON {
+                image.rotate180()
+            } else if (normalized - 270.0).abs() < f32::EPSILON {
+                image.rotate270()
+            } else {
+                DynamicImage::ImageRgba8(rotate_any(&image, normalized))
+            }
+        }
+        ImageOp::Flip { direction } => match direction.as_str() {
+            "horizontal" => image.fliph(),
+            "vertical" => image.flipv(),
+            other => return Err(format!("不支持的翻转方向: {}", other)),
+        },
+        ImageOp::Pad {
+            top,
+            right,
+            bottom,
+            left,
+            color,
+        } => {
+            let padded = |size: u32, a: u32, b: u32| {
+                size.checked_add(a)
+                    .and_then(|s| s.checked_add(b))
+                    .filter(|s| *s <= MAX_OUTPUT_DIMENSION)
+            };
+            let (Some(pw), Some(ph)) = (padded(w, *left, *right), padded(h, *top, *bottom)) else {
+                return Err(format!(
+                    "扩边后尺寸过大: 上 {} 右 {} 下 {} 左 {}",
+                    top, right, bottom, left
+                ));
+            };
+            let fill = match color {
+                Some(color) => parse_color(color)?,
+                None => Rgba([0, 0, 0, 0]),
+            };
+            let mut canvas = RgbaImage::from_pixel(pw, ph, fill);
+            image::imageops::overlay(&mut canvas, &image.to_rgba8(), *left as i64, *top as i64);
+            DynamicImage::ImageRgba8(canvas)
+        }
+        ImageOp::Brightness { value } => image.brighten((*value).clamp(-255, 255)),
+        ImageOp::Contrast { value } => image.adjust_contrast(*value),
+        ImageOp::Saturation { value } => {
+            DynamicImage::ImageRgba8(adjust_saturation(&image, value.max(0.0)))
+        }
+        ImageOp::Sharpen { sigma, threshold } => {
+            image.unsharpen(sigma.unwrap_or(1.0), threshold.unwrap_or(2))
+        }
+    };
+    Ok(output)
+}
+
+/// 依次执行全部操作
+pub fn apply_ops(image: DynamicImage, ops: &[ImageOp]) -> Result<DynamicImage, String> {
+    ops.iter()
+        .enumerate()
+        .try_fold(image, |image, (index, op)| {
+            apply_op(image, op).map_err(|e| format!("第 {} 个操作失败: {}", index + 1, e))
+        })
+}
+
+/// 按指定格式编码
+pub fn encode_image(
+    image: &DynamicImage,
+    format: &str,
+    quality: Option<u8>,
+) -> Result<(Vec<u8>, ImageKind), String> {
+    let mut output = Vec::new();
+    let kind = match format.trim().to_ascii_lowercase().as_str() {
+        "png" => {
+            image
+                .write_to(&mut Cursor::new(&mut output), ImageFormat::Png)
+                .map_err(|e| format!("PNG 编码失败: {}", e))?;
+            ImageKind::Png
+        }
+        "jpeg" | "jpg" => {
+            // JPEG 不支持透明，先铺白底
+            let mut background =
+                RgbaImage::from_pixel(image.width(), image.height(), Rgba([255, 255, 255, 255]));
+            image::imageops::overlay(&mut background, &image.to_rgba8(), 0, 0);
+            let rgb = DynamicImage::ImageRgba8(background).to_rgb8();
+            let quality = quality.unwrap_or(DEFAULT_JPEG_QUALITY).clamp(1, 100);
+            JpegEncoder::new_with_quality(&mut output, quality)
+                .encode_image(&rgb)
+                .map_err(|e| format!("JPEG 编码失败: {}", e))?;
+            ImageKind::Jpeg
+        }
+        "webp" => {
+            image
+                .write_to(&mut Cursor::new(&mut output), ImageFormat::WebP)
+                .map_err(|e| format!("WebP 编码失败: {}", e))?;
+            ImageKind::Webp
+        }
+        other => return Err(format!("不支持的输出格式: {}", other)),
+    };
+    Ok((output, kind))
+}
+
+// Tauri 命令：执行本地图片编辑
+#[tauri::command]
+pub async fn apply_image_ops(app: tauri::AppHandle, params: ImageOpsParams) -> ImageOpsResult {
+    println!(
+        "[Rust] apply_image_ops called, {} operation(s)",
+        params.operations.len()
+    );
+
+    let source_bytes = match (&params.image_data, &params.image_path) {
+        (Some(data), _) if !data.trim().is_empty() => decode_base64_image(data),
+        (_, Some(path)) => std::fs::read(path).map_err(|e| format!("读取图片失败: {}", e)),
+        _ => Err("需要提供图片数据或路径".to_string()),
+    };
+    let source_bytes = match source_bytes {
+        Ok(bytes) => bytes,
+        Err(e) => return ImageOpsResult::failure(e),
+    };
+
+    let operations = params.operations.clone();
+    let format = params
+        .output_format
+        .clone()
+        .unwrap_or_else(|| "png".to_string());
+    let quality = params.quality;
+    let processed = tokio::task::spawn_blocking(move || {
+        let image =
+            image::load_from_memory(&source_bytes).map_err(|e| format!("图片解码失败: {}", e))?;
+        let output = apply_ops(image, &operations)?;
+        let (bytes, kind) = encode_image(&output, &format, quality)?;
+        Ok::<_, String>((bytes, kind, output.dimensions()))
+    })
+    .await
+    .map_err(|e| format!("图片处理任务失败: {}", e))
+    .and_then(|result| result);
+    let (bytes, kind, (width, height)) = match processed {
+        Ok(processed) => processed,
+        Err(e) => return ImageOpsResult::failure(e),
+    };
+    let image_data = BASE64.encode(&bytes);
+
+    let mut saved_image = None;
+    if params.save_to_storage.unwrap_or(false) {
+        let input_images = params.image_path.clone().map(|path| {
+            vec![InputImageInfo {
+                path: Some(path),
+                label: "原图".to_string(),
+            }]
+        });
+        match save_image(
+            app,
+            image_data.clone(),
+            params.canvas_id.clone(),
+            params.node_id.clone(),
+            None,
+            input_images,
+            Some(ImageType::Generated),
+            Some(GenerationParams {
+                provider: Some("local".to_string()),
+                width: Some(width),
+                height: Some(height),
+                extra: Some(serde_json::json!({ "operations": params.operations })),
+                ..GenerationParams::default()
+            }),
+        ) {
+            Ok(info) => saved_image = Some(info),
+            Err(e) => return ImageOpsResult::failure(format!("保存图片失败: {}", e)),
+        }
+    }
+
+    println!("[Rust] apply_image_ops finished: {}x{}", width, height);
+    ImageOpsResult {
+        success: true,
+        image_data: Some(image_data),
+        mime_type: Some(kind.mime_type().to_string()),
+        width: Some(width),
+        height: Some(height),
+        saved_image,
+        error: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample() -> DynamicImage {
+        DynamicImage::ImageRgba8(RgbaImage::from_fn(40, 20, |x, _| {
+            Rgba([(x * 6) as u8, 100, 50, 255])
+        }))
+    }
+
+    #[test]
+    fn test_ops_deserialize_from_tagged_json() {
+        let ops: Vec<ImageOp> = serde_json::from_str(
+            r#"[{"op":"crop","x":1,"y":2,"width":3,"height":4},
+                {"op":"resize","width":100},
+                {"op":"flip","direction":"vertical"}]"#,
+        )
+        .unwrap();
+        assert_eq!(
+            ops[0],
+            ImageOp::Crop {
+                x: 1,
+                y: 2,
+                width: 3,
+                height: 4
+            }
+        );
+        assert!(matches!(
+            ops[1],
+            ImageOp::Resize {
+                width: Some(100),
+                height: None,
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn test_chain_changes_dimensions() {
+        let ops = vec![
+            ImageOp::Crop {
+                x: 0,
+                y: 0,
+                width: 20,
+                height: 20,
+            },
+            ImageOp::Rotate { degrees: 90.0 },
+            ImageOp::Pad {
+                top: 1,
+                right: 2,
+                bottom: 3,
+                left: 4,
+                color: Some("#ffffff".to_string()),
+            },
+            ImageOp::Resize {
+                width: Some(52),
+                height: None,
+                mode: None,
+            },
+        ];
+        let output = apply_ops(sample(), &ops).unwrap();
+        assert_eq!(output.dimensions(), (52, 48));
+    }
+
+    #[test]
+    fn test_invalid_crop_reports_step() {
+        let ops = vec![
+            ImageOp::Flip {
+                direction: "horizontal".to_string(),
+            },
+            ImageOp::Crop {
+                x: 30,
+                y: 0,
+                width: 20,
+                height: 10,
+            },
+        ];
+        let error = apply_ops(sample(), &ops).unwrap_err();
+        assert!(error.starts_with("第 2 个操作失败"));
+    }
+
+    #[test]
+    fn test_oversized_crop_and_pad_do_not_overflow() {
+        let crop = ImageOp::Crop {
+            x: u32::MAX,
+            y: 0,
+            width: 10,
+            height: 10,
+        };
+        assert!(apply_op(sample(), &crop).is_err());
+        let crop = ImageOp::Crop {
+            x: 0,
+            y: 1,
+            width: 10,
+            height: u32::MAX,
+        };
+        assert!(apply_op(sample(), &crop).is_err());
+
+        let pad = ImageOp::Pad {
+            top: 0,
+            right: u32::MAX,
+            bottom: 0,
+            left: 1,
+            color: None,
+        };
+        assert!(apply_op(sample(), &pad).is_err());
+        let pad = ImageOp::Pad {
+            top: 2,
+            right: 0,
+            bottom: 3,
+            left: 0,
+            color: None,
+        };
+        assert_eq!(apply_op(sample(), &pad).unwrap().dimensions(), (40, 25));
+    }
+
+    #[test]
+    fn test_rotate_any_expands_canvas_and_saturation_zero_is_gray() {
+        let rotated = apply_op(sample(), &ImageOp::Rotate { degrees: 45.0 }).unwrap();
+        assert_eq!(rotated.dimensions(), (43, 43));
+        assert_eq!(rotated.to_rgba8().get_pixel(0, 0)[3], 0);
+
+        let gray = adjust_saturation(&sample(), 0.0);
+        let pixel = gray.get_pixel(10, 10);
+        assert_eq!(pixel[0], pixel[1]);
+        assert_eq!(pixel[1], pixel[2]);
+    }
+
+    #[test]
+    fn test_encode_jpeg_flattens_alpha() {
+        let (bytes, kind) = encode_image(&sample(), "jpg", Some(80)).unwrap();
+        assert_eq!(kind, ImageKind::Jpeg);
+        assert_eq!(&bytes[..2], &[0xFF, 0xD8]);
+    }
+}
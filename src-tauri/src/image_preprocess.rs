@@ -0,0 +1,444 @@
+//! 按服务限制预处理输入图片
+//!
+//! 上传前修正 EXIF 方向、按最大边长缩放、转成服务接受的格式，
+//! 超出体积限制时逐步降低 JPEG 质量或继续缩小，并记录做了哪些调整。
+
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use image::codecs::jpeg::JpegEncoder;
+use image::imageops::FilterType;
+use image::metadata::Orientation;
+use image::{DynamicImage, ImageDecoder, ImageReader};
+use serde::Serialize;
+use std::io::Cursor;
+
+use crate::image_utils::{
+    decode_base64_image, sniff_image_kind, strip_data_url_prefix, ImageKind, CLAUDE_INPUT_FORMATS,
+    GEMINI_INPUT_FORMATS, OPENAI_INPUT_FORMATS, VIDEO_INPUT_FORMATS,
+};
+
+// 重新编码时的 JPEG 质量，超出体积时依次降低
+const JPEG_QUALITY_STEPS: &[u8] = &[90, 82, 74, 66];
+// 体积仍超限时每次缩小的比例
+const SHRINK_FACTOR: f32 = 0.8;
+const MAX_SHRINK_ROUNDS: usize = 6;
+
+// 服务对输入图片的限制
+#[derive(Debug, Clone, Copy)]
+pub struct ImageLimits {
+    pub max_dimension: u32,
+    pub max_bytes: usize,
+    pub accepted: &'static [ImageKind],
+}
+
+impl ImageLimits {
+    pub fn for_provider(provider: &str) -> Self {
+        match provider.trim().to_ascii_lowercase().as_str() {
+            "gemini" | "google" => Self {
+                max_dimension: 3072,
+                max_bytes: 7 * 1024 * 1024,
+                accepted: GEMINI_INPUT_FORMATS,
+            },
+            "openai" | "dalle" | "gpt-image" => Self {
+                max_dimension: 2048,
+                max_bytes: 20 * 1024 * 1024,
+                accepted: OPENAI_INPUT_FORMATS,
+            },
+            "claude" | "anthropic" => Self {
+                max_dimension: 1568,
+                max_bytes: 5 * 1024 * 1024,
+                accepted: CLAUDE_INPUT_FORMATS,
+            },
+            "video" | "kling" | "veo" | "sora" => Self {
+                max_dimension: 4096,
+                max_bytes: 10 * 1024 * 1024,
+                accepted: VIDEO_INPUT_FORMATS,
+            },
+            // 未知服务按较保守的通用限制处理
+            _ => Self {
+                max_dimension: 2048,
+                max_bytes: 10 * 1024 * 1024,
+                accepted: VIDEO_INPUT_FORMATS,
+            },
+        }
+    }
+}
+
+// 预处理报告
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImagePreprocessReport {
+    pub original_mime_type: String,
+    pub mime_type: String,
+    pub original_width: u32,
+    pub original_height: u32,
+    pub width: u32,
+    pub height: u32,
+    pub original_bytes: usize,
+    pub bytes: usize,
+    pub orientation_fixed: bool,
+    pub resized: bool,
+    pub reencoded: bool,
+    pub jpeg_quality: Option<u8>,
+}
+
+impl ImagePreprocessReport {
+    pub fn changed(&self) -> bool {
+        self.orientation_fixed || self.resized || self.reencoded
+    }
+
+    /// 简短描述，用于日志
+    pub fn summary(&self) -> String {
+        format!(
+            "{} {}x{} {}B -> {} {}x{} {}B",
+            self.original_mime_type,
+            self.original_width,
+            self.original_height,
+            self.original_bytes,
+            self.mime_type,
+            self.width,
+            self.height,
+            self.bytes
+        )
+    }
+}
+
+// 预处理后的图片
+#[derive(Debug, Clone)]
+pub struct PreparedImage {
+    pub bytes: Vec<u8>,
+    pub kind: ImageKind,
+    pub report: ImagePreprocessReport,
+}
+
+impl PreparedImage {
+    pub fn mime_type(&self) -> &'static str {
+        self.kind.mime_type()
+    }
+
+    pub fn extension(&self) -> &'static str {
+        self.kind.extension()
+    }
+
+    pub fn to_base64(&self) -> String {
+        BASE64.encode(&self.bytes)
+    }
+}
+
+fn read_orientation(bytes: &[u8]) -> Orientation {
+    ImageReader::new(Cursor::new(bytes))
+        .with_guessed_format()
+        .ok()
+        .and_then(|reader| reader.into_decoder().ok())
+        .and_then(|mut decoder| decoder.orientation().ok())
+        .unwrap_or(Orientation::NoTransforms)
+}
+
+pub(crate) fn read_dimensions(bytes: &[u8]) -> Option<(u32, u32)> {
+    ImageReader::new(Cursor::new(bytes))
+        .with_guessed_format()
+        .ok()
+        .and_then(|reader| reader.into_dimensions().ok())
+}
+
+fn encode_png(image: &DynamicImage) -> Result<Vec<u8>, String> {
+    let mut output = Vec::new();
+    image
+        .write_to(&mut Cursor::new(&mut output), image::ImageFormat::Png)
+        .map_err(|e| format!("PNG 编码失败: {}", e))?;
+    Ok(output)
+}
+
+fn encode_jpeg(image: &DynamicImage, quality: u8) -> Result<Vec<u8>, String> {
+    let mut output = Vec::new();
+    JpegEncoder::new_with_quality(&mut output, quality)
+        .encode_image(&image.to_rgb8())
+        .map_err(|e| format!("JPEG 编码失败: {}", e))?;
+    Ok(output)
+}
+
+fn fit_within(width: u32, height: u32, max_dimension: u32) -> (u32, u32) {
+    let longest = width.max(height);
+    if longest <= max_dimension {
+        return (width, height);
+    }
+    let scale = max_dimension as f64 / longest as f64;
+    (
+        ((width as f64 * scale).round() as u32).max(1),
+        ((height as f64 * scale).round() as u32).max(1),
+    )
+}
+
+/// 按服务限制预处理图片；已满足限制的图片原样返回
+pub fn preprocess_image(bytes: Vec<u8>, limits: &ImageLimits) -> Result<PreparedImage, String> {
+    let original_kind = sniff_image_kind(&bytes);
+    let original_mime_type = original_kind
+        .map(|k| k.mime_type())
+        .unwrap_or("application/octet-stream")
+        .to_string();
+    let orientation = read_orientation(&bytes);
+    let (original_width, original_height) = read_dimensions(&bytes).unwrap_or((0, 0));
+
+    let mut report = ImagePreprocessReport {
+        original_mime_type: original_mime_type.clone(),
+        mime_type: original_mime_type,
+        original_width,
+        original_height,
+        width: original_width,
+        height: original_height,
+        original_bytes: bytes.len(),
+        bytes: bytes.len(),
+        ..ImagePreprocessReport::default()
+    };
+
+    // 快速路径：格式、尺寸、体积均满足且无需旋转
+    if let Some(kind) = original_kind.filter(|k| limits.accepted.contains(k)) {
+        if orientation == Orientation::NoTransforms
+            && original_width.max(original_height) <= limits.max_dimension
+            && bytes.len() <= limits.max_bytes
+        {
+            return Ok(PreparedImage {
+                bytes,
+                kind,
+                report,
+            });
+        }
+    }
+
+    let mut image = image::load_from_memory(&bytes).map_err(|e| match original_kind {
+        Some(kind @ (ImageKind::Heic | ImageKind::Avif)) => format!(
+            "暂不支持 {} 格式图片，请先转换为 PNG 或 JPEG",
+            kind.extension().to_uppercase()
+        ),
+        _ => format!("无法识别的图片格式: {}", e),
+    })?;
+
+    if orientation != Orientation::NoTransforms {
+        image.apply_orientation(orientation);
+        report.orientation_fixed = true;
+    }
+
+    let (target_width, target_height) =
+        fit_within(image.width(), image.height(), limits.max_dimension);
+    if (target_width, target_height) != (image.width(), image.height()) {
+        image = image.resize_exact(target_width, target_height, FilterType::Lanczos3);
+        report.resized = true;
+    }
+
+    // 有透明通道时保留 PNG，否则优先 JPEG（原图为 PNG 且体积允许时仍保留 PNG）
+    let has_alpha = image.color().has_alpha();
+    let png_allowed = limits.accepted.contains(&ImageKind::Png);
+    let jpeg_allowed = limits.accepted.contains(&ImageKind::Jpeg);
+    let prefer_png =
+        png_allowed && (has_alpha || !jpeg_allowed || original_kind == Some(ImageKind::Png));
+
+    let mut output: Option<(Vec<u8>, ImageKind, Option<u8>)> = None;
+    for _ in 0..=MAX_SHRINK_ROUNDS {
+        if prefer_png {
+            let png = encode_png(&image)?;
+            if png.len() <= limits.max_bytes || !jpeg_allowed || has_alpha {
+                output = Some((png, ImageKind::Png, None));
+            }
+        }
+        if output.is_none() && jpeg_allowed {
+            for &quality in JPEG_QUALITY_STEPS {
+                let jpeg = encode_jpeg(&image, quality)?;
+                let fits = jpeg.len() <= limits.max_bytes;
+                output = Some((jpeg, ImageKind::Jpeg, Some(quality)));
+                if fits {
+                    break;
+                }
+            }
+        }
+        if output.is_none() {
+            output = Some((encode_png(&image)?, ImageKind::Png, None));
+        }
+
+        let fits = output
+            .as_ref()
+            .map(|(data, _, _)| data.len() <= limits.max_bytes)
+            .unwrap_or(false);
+        if fits {
+            break;
+        }
+
+        // 仍超出体积限制，继续缩小
+        let width = ((image.width() as f32 * SHRINK_FACTOR) as u32).max(1);
+        let height = ((image.height() as f32 * SHRINK_FACTOR) as u32).max(1);
+        image = image.resize_exact(width, height, FilterType::Lanczos3);
+        report.resized = true;
+        output = None;
+    }
+
+    let (data, kind, quality) = match output {
+        Some(o) => o,
+        None => return Err(format!("图片压缩后仍超过 {} 字节限制", limits.max_bytes)),
+    };
+
+    report.reencoded = true;
+    report.mime_type = kind.mime_type().to_string();
+    report.width = image.width();
+    report.height = image.height();
+    report.bytes = data.len();
+    report.jpeg_quality = quality;
+
+    println!("[Rust] Preprocessed input image: {}", report.summary());
+
+    Ok(PreparedImage {
+        bytes: data,
+        kind,
+        report,
+    })
+}
+
+/// 在阻塞线程中预处理 base64 图片，避免大图解码阻塞异步运行时
+pub async fn preprocess_base64_image(
+    base64_data: String,
+    limits: ImageLimits,
+) -> Result<PreparedImage, String> {
+    tokio::task::spawn_blocking(move || {
+        let bytes = decode_base64_image(&base64_data)?;
+        preprocess_image(bytes, &limits)
+    })
+    .await
+    .map_err(|e| format!("图片预处理任务失败: {}", e))?
+}
+
+/// 预处理 JSON 接口中的图片字段：URL 原样保留，data URL 保留前缀格式
+pub async fn preprocess_image_field(
+    value: String,
+    limits: ImageLimits,
+) -> Result<(String, Option<ImagePreprocessReport>), String> {
+    let trimmed = value.trim();
+    if trimmed.starts_with("http://") || trimmed.starts_with("https://") || trimmed.is_empty() {
+        return Ok((value, None));
+    }
+
+    let is_data_url = trimmed.starts_with("data:");
+    let prepared = preprocess_base64_image(value.clone(), limits).await?;
+    if !prepared.report.changed() {
+        return Ok((value, Some(prepared.report)));
+    }
+
+    let data = if is_data_url {
+        format!(
+            "data:{};base64,{}",
+            prepared.mime_type(),
+            prepared.to_base64()
+        )
+    } else {
+        prepared.to_base64()
+    };
+    Ok((data, Some(prepared.report)))
+}
+
+/// 将蒙版缩放到与原图一致的尺寸（PNG）
+pub fn resize_mask_to(bytes: &[u8], width: u32, height: u32) -> Result<Vec<u8>, String> {
+    let mask = image::load_from_memory(bytes).map_err(|e| format!("蒙版图片解码失败: {}", e))?;
+    if mask.width() == width
+        && mask.height() == height
+        && sniff_image_kind(bytes) == Some(ImageKind::Png)
+    {
+        return Ok(bytes.to_vec());
+    }
+    encode_png(&mask.resize_exact(width, height, FilterType::Nearest))
+}
+
+// 前端手动预处理的结果
+#[derive(Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PreprocessImageResult {
+    pub success: bool,
+    pub image_data: Option<String>,
+    pub mime_type: Option<String>,
+    pub report: Option<ImagePreprocessReport>,
+    pub error: Option<String>,
+}
+
+// Tauri 命令：按指定服务的限制预处理图片
+#[tauri::command]
+pub async fn preprocess_image_for_provider(
+    image_data: String,
+    provider: String,
+) -> PreprocessImageResult {
+    println!(
+        "[Rust] preprocess_image_for_provider called, provider: {}",
+        provider
+    );
+
+    let limits = ImageLimits::for_provider(&provider);
+    match preprocess_base64_image(strip_data_url_prefix(&image_data).to_string(), limits).await {
+        Ok(prepared) => PreprocessImageResult {
+            success: true,
+            image_data: Some(prepared.to_base64()),
+            mime_type: Some(prepared.mime_type().to_string()),
+            report: Some(prepared.report),
+            error: None,
+        },
+        Err(e) => PreprocessImageResult {
+            success: false,
+            error: Some(e),
+            ..PreprocessImageResult::default()
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{Rgb, RgbImage, Rgba, RgbaImage};
+
+    fn jpeg_bytes(width: u32, height: u32) -> Vec<u8> {
+        let image = RgbImage::from_fn(width, height, |x, y| {
+            Rgb([(x % 256) as u8, (y % 256) as u8, 128])
+        });
+        encode_jpeg(&DynamicImage::ImageRgb8(image), 90).unwrap()
+    }
+
+    #[test]
+    fn test_small_image_passes_through() {
+        let bytes = jpeg_bytes(64, 32);
+        let limits = ImageLimits::for_provider("gemini");
+        let prepared = preprocess_image(bytes.clone(), &limits).unwrap();
+        assert!(!prepared.report.changed());
+        assert_eq!(prepared.bytes, bytes);
+    }
+
+    #[test]
+    fn test_large_image_is_downscaled() {
+        let limits = ImageLimits {
+            max_dimension: 100,
+            max_bytes: 1024 * 1024,
+            accepted: VIDEO_INPUT_FORMATS,
+        };
+        let prepared = preprocess_image(jpeg_bytes(400, 200), &limits).unwrap();
+        assert!(prepared.report.resized);
+        assert_eq!((prepared.report.width, prepared.report.height), (100, 50));
+        assert_eq!(prepared.kind, ImageKind::Jpeg);
+    }
+
+    #[test]
+    fn test_alpha_image_stays_png() {
+        let image = RgbaImage::from_pixel(300, 300, Rgba([10, 20, 30, 128]));
+        let bytes = encode_png(&DynamicImage::ImageRgba8(image)).unwrap();
+        let limits = ImageLimits {
+            max_dimension: 150,
+            max_bytes: 1024 * 1024,
+            accepted: OPENAI_INPUT_FORMATS,
+        };
+        let prepared = preprocess_image(bytes, &limits).unwrap();
+        assert_eq!(prepared.kind, ImageKind::Png);
+        assert_eq!((prepared.report.width, prepared.report.height), (150, 150));
+    }
+
+    #[test]
+    fn test_byte_limit_forces_smaller_output() {
+        let limits = ImageLimits {
+            max_dimension: 4096,
+            max_bytes: 4 * 1024,
+            accepted: VIDEO_INPUT_FORMATS,
+        };
+        let prepared = preprocess_image(jpeg_bytes(300, 300), &limits).unwrap();
+        assert!(prepared.bytes.len() <= limits.max_bytes);
+        assert!(prepared.report.reencoded);
+    }
+}
@@ -0,0 +1,65 @@
+use async_trait::async_trait;
+
+use super::{GeneratedImage, ImageGenerationRequest, ImageGenerationResult, ImageProvider};
+use crate::comfyui::{run_workflow, ComfyUIParams};
+
+// ComfyUI 本地服务，工作流通过 extras.workflow 传入
+pub struct ComfyUIImageProvider;
+
+#[async_trait]
+impl ImageProvider for ComfyUIImageProvider {
+    fn id(&self) -> &'static str {
+        "comfyui"
+    }
+
+    async fn generate(&self, request: &ImageGenerationRequest) -> ImageGenerationResult {
+        let extras = request.extras.clone().unwrap_or_default();
+        let workflow = match extras.get("workflow") {
+            Some(w) if w.is_object() => w.clone(),
+            _ => {
+                return ImageGenerationResult::failure(
+                    "ComfyUI 需要在 extras.workflow 中提供工作流",
+                )
+            }
+        };
+
+        let params = ComfyUIParams {
+            base_url: request.base_url.clone(),
+            workflow,
+            prompt: Some(request.prompt.clone()),
+            negative_prompt: request.negative_prompt.clone(),
+            seed: request.seed,
+            input_images: Some(request.non_empty_input_images()),
+            variables: extras
+                .get("variables")
+                .and_then(|v| serde_json::from_value(v.clone()).ok()),
+            timeout_secs: extras.get("timeoutSecs").and_then(|v| v.as_u64()),
+            ..ComfyUIParams::default()
+        };
+
+        match run_workflow(&params, &|_| {}).await {
+            Ok(run) => {
+                let images: Vec<GeneratedImage> = run
+                    .outputs
+                    .into_iter()
+                    .filter(|output| output.media_type == "image")
+                    .map(|output| GeneratedImage {
+                        data: Some(output.data),
+                        mime_type: Some(output.mime_type),
+                        url: None,
+                        seed: Some(run.seed),
+                    })
+                    .collect();
+                if images.is_empty() {
+                    return ImageGenerationResult::failure("工作流没有产生图片输出");
+                }
+                ImageGenerationResult {
+                    success: true,
+                    images,
+                    ..ImageGenerationResult::default()
+                }
+            }
+            Err(e) => ImageGenerationResult::failure(e),
+        }
+    }
+}
@@ -0,0 +1,427 @@
+//! 多服务对比生成
+//!
+//! 同一提示词并发交给多个服务 / 模型，每个结果完成时通过 compare-generate-result
+//! 事件推送，并附上耗时和按单价估算的费用；保存时把结果记为一个对比分组。
+
+use futures_util::StreamExt;
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::time::Duration;
+use tauri::Emitter;
+use uuid::Uuid;
+
+use super::{run_image_request, GeneratedImage, ImageGenerationRequest, ImageGenerationResult};
+use crate::image_task::download_image;
+use crate::storage::{
+    save_comparison_group, save_image, validate_comparison_id, ComparisonEntry, ComparisonGroup,
+    GenerationParams, ImageInfo, ImageType,
+};
+
+const DEFAULT_CONCURRENCY: usize = 3;
+const MAX_CONCURRENCY: usize = 8;
+const DOWNLOAD_TIMEOUT_SECS: u64 = 60;
+
+// 参与对比的一个服务/模型配置
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CompareConfig {
+    pub label: Option<String>, // 显示名称，默认 "provider / model"
+    pub provider: String,
+    pub base_url: String,
+    pub api_key: String,
+    pub model: String,
+    pub size: Option<String>, // 覆盖公共参数
+    pub aspect_ratio: Option<String>,
+    pub image_size: Option<String>,
+    pub extras: Option<Value>,       // 与公共 extras 合并，同名字段以此为准
+    pub cost_per_image: Option<f64>, // 按张计费的单价
+    pub input_token_price: Option<f64>, // 每百万输入 token 的价格
+    pub output_token_price: Option<f64>, // 每百万输出 token 的价格
+}
+
+// 前端调用的参数
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CompareGenerateParams {
+    pub comparison_id: Option<String>,
+    pub prompt: String,
+    #[serde(default)]
+    pub input_images: Vec<String>,
+    pub mask_image: Option<String>,
+    pub size: Option<String>,
+    pub aspect_ratio: Option<String>,
+    pub image_size: Option<String>,
+    pub n: Option<u32>,
+    pub seed: Option<i64>,
+    pub negative_prompt: Option<String>,
+    pub extras: Option<Value>,
+    #[serde(default)]
+    pub configs: Vec<CompareConfig>,
+    pub max_concurrency: Option<usize>, // 同时请求的数量，默认 3
+    pub save_to_storage: Option<bool>,  // 默认保存
+    pub canvas_id: Option<String>,
+    pub node_id: Option<String>,
+}
+
+// 单个配置的生成结果
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CompareItem {
+    pub index: usize,
+    pub label: String,
+    pub provider: String,
+    pub model: String,
+    pub success: bool,
+    pub images: Vec<GeneratedImage>,
+    pub saved_images: Vec<ImageInfo>,
+    pub latency_ms: u64,
+    pub cost: Option<f64>,
+    pub usage: Option<Value>,
+    pub error: Option<String>,
+}
+
+// 单个结果完成时推送的事件（compare-generate-result）
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CompareResultEvent {
+    pub comparison_id: String,
+    pub completed: usize,
+    pub total: usize,
+    pub item: CompareItem,
+}
+
+// 返回结果（按配置顺序排列）
+#[derive(Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CompareGenerateResult {
+    pub success: bool,
+    pub comparison_id: String,
+    pub items: Vec<CompareItem>,
+    pub error: Option<String>,
+}
+
+fn config_label(config: &CompareConfig) -> String {
+    config
+        .label
+        .clone()
+        .filter(|label| !label.trim().is_empty())
+        .unwrap_or_else(|| format!("{} / {}", config.provider, config.model))
+}
+
+/// 合并 extras，后者覆盖前者的同名字段
+pub(super) fn merge_extras(base: Option<&Value>, overrides: Option<&Value>) -> Option<Value> {
+    match (base, overrides) {
+        (Some(Value::Object(base)), Some(Value::Object(overrides))) => {
+            let mut merged = base.clone();
+            for (key, value) in overrides {
+                merged.insert(key.clone(), value.clone());
+            }
+            Some(Value::Object(merged))
+        }
+        (base, overrides) => overrides.or(base).cloned(),
+    }
+}
+
+fn build_request(params: &CompareGenerateParams, config: &CompareConfig) -> ImageGenerationRequest {
+    ImageGenerationRequest {
+        provider: config.provider.clone(),
+        base_url: config.base_url.clone(),
+        api_key: config.api_key.clone(),
+        model: config.model.clone(),
+        prompt: params.prompt.clone(),
+        input_images: params.input_images.clone(),
+        mask_image: params.mask_image.clone(),
+        size: config.size.clone().or_else(|| params.size.clone()),
+        aspect_ratio: config
+            .aspect_ratio
+            .clone()
+            .or_else(|| params.aspect_ratio.clone()),
+        image_size: config
+            .image_size
+            .clone()
+            .or_else(|| params.image_size.clone()),
+        n: params.n,
+        seed: params.seed,
+        negative_prompt: params.negative_prompt.clone(),
+        reframe: None,
+        extras: merge_extras(params.extras.as_ref(), config.extras.as_ref()),
+    }
+}
+
+/// 从各家 usage 结构中读取输入 / 输出 token 数
+fn token_counts(usage: &Value) -> (Option<u64>, Option<u64>) {
+    let first = |keys: &[&str]| keys.iter().find_map(|key| usage.get(key)?.as_u64());
+    (
+        first(&["input_tokens", "prompt_tokens", "promptTokenCount"]),
+        first(&["output_tokens", "completion_tokens", "candidatesTokenCount"]),
+    )
+}
+
+/// 按配置的单价估算费用，未配置单价时返回 None
+fn estimate_cost(config: &CompareConfig, result: &ImageGenerationResult) -> Option<f64> {
+    let mut cost = None;
+    if let Some(price) = config.cost_per_image {
+        cost = Some(price * result.images.len() as f64);
+    }
+    if let Some(usage) = &result.usage {
+        let (input, output) = token_counts(usage);
+        for (tokens, price) in [
+            (input, config.input_token_price),
+            (output, config.output_token_price),
+        ] {
+            if let (Some(tokens), Some(price)) = (tokens, price) {
+                cost = Some(cost.unwrap_or(0.0) + tokens as f64 * price / 1_000_000.0);
+            }
+        }
+    }
+    cost
+}
+
+/// 生成一个配置的结果，需要时保存图片
+async fn run_config(
+    app: &tauri::AppHandle,
+    client: &Client,
+    params: &CompareGenerateParams,
+    comparison_id: &str,
+    index: usize,
+    request: ImageGenerationRequest,
+) -> CompareItem {
+    let result = run_image_request(&request).await;
+    let config = &params.configs[index];
+    let label = config_label(config);
+
+    let mut saved_images = Vec::new();
+    if params.save_to_storage.unwrap_or(true) && result.success {
+        for image in &result.images {
+            // 只返回地址的结果立即取回保存，地址通常有时效，分组中不能只留链接
+            let data = match (&image.data, &image.url) {
+                (Some(data), _) => data.clone(),
+                (None, Some(url)) => match download_image(client, url).await {
+                    Ok(downloaded) => downloaded.data.unwrap_or_default(),
+                    Err(e) => {
+                        println!("[Rust] Failed to download comparison image: {}", e);
+                        continue;
+                    }
+                },
+                (None, None) => continue,
+            };
+            let generation = GenerationParams {
+                provider: Some(result.provider.clone()),
+                model: Some(result.model.clone()),
+                negative_prompt: params.negative_prompt.clone(),
+                seed: image.seed.or(result.seed),
+                extra: Some(serde_json::json!({
+                    "comparison": {
+                        "id": comparison_id,
+                        "index": index,
+                        "label": label,
+                    }
+                })),
+                ..GenerationParams::default()
+            };
+            let app = app.clone();
+            let canvas_id = params.canvas_id.clone();
+            let node_id = params.node_id.clone();
+            let prompt = params.prompt.clone();
+            let saved = tokio::task::spawn_blocking(move || {
+                save_image(
+                    app,
+                    data,
+                    canvas_id,
+                    node_id,
+                    Some(prompt),
+                    None,
+                    Some(ImageType::Generated),
+                    Some(generation),
+                )
+            })
+            .await
+            .unwrap_or_else(|e| Err(format!("保存任务失败: {}", e)));
+            match saved {
+                Ok(info) => saved_images.push(info),
+                Err(e) => println!("[Rust] Failed to save comparison image: {}", e),
+            }
+        }
+    }
+
+    CompareItem {
+        index,
+        label,
+        provider: result.provider.clone(),
+        model: result.model.clone(),
+        success: result.success,
+        latency_ms: result.timings.as_ref().map(|t| t.total_ms).unwrap_or(0),
+        cost: estimate_cost(config, &result),
+        usage: result.usage,
+        images: result.images,
+        saved_images,
+        error: result.error,
+    }
+}
+
+// Tauri 命令：同一提示词并发交给多个服务/模型，结果逐个推送
+#[tauri::command]
+pub async fn compare_generate(
+    app: tauri::AppHandle,
+    params: CompareGenerateParams,
+) -> CompareGenerateResult {
+    let comparison_id = params
+        .comparison_id
+        .clone()
+        .unwrap_or_else(|| Uuid::new_v4().to_string());
+    println!(
+        "[Rust] compare_generate called, {} config(s), id: {}",
+        params.configs.len(),
+        comparison_id
+    );
+
+    // 分组 ID 会用作文件名，前端传入的需要校验
+    if let Err(e) = validate_comparison_id(&comparison_id) {
+        return CompareGenerateResult {
+            success: false,
+            comparison_id,
+            error: Some(e),
+            ..CompareGenerateResult::default()
+        };
+    }
+    if params.configs.is_empty() {
+        return CompareGenerateResult {
+            success: false,
+            comparison_id,
+            error: Some("至少需要一个对比配置".to_string()),
+            ..CompareGenerateResult::default()
+        };
+    }
+
+    let total = params.configs.len();
+    let concurrency = params
+        .max_concurrency
+        .unwrap_or(DEFAULT_CONCURRENCY)
+        .clamp(1, MAX_CONCURRENCY);
+    let save = params.save_to_storage.unwrap_or(true);
+    let client = Client::builder()
+        .timeout(Duration::from_secs(DOWNLOAD_TIMEOUT_SECS))
+        .build()
+        .unwrap_or_default();
+
+    let requests: Vec<(usize, ImageGenerationRequest)> = params
+        .configs
+        .iter()
+        .map(|config| build_request(&params, config))
+        .enumerate()
+        .collect();
+    let mut results = futures_util::stream::iter(requests.into_iter().map(|(index, request)| {
+        run_config(&app, &client, &params, &comparison_id, index, request)
+    }))
+    .buffer_unordered(concurrency);
+
+    // 下载和保存在各自的任务中完成，这里只负责推送事件
+    let mut items = Vec::with_capacity(total);
+    while let Some(item) = results.next().await {
+        println!(
+            "[Rust] compare_generate item {} ({}) finished: success={}, {}ms",
+            item.index, item.label, item.success, item.latency_ms
+        );
+        let _ = app.emit(
+            "compare-generate-result",
+            CompareResultEvent {
+                comparison_id: comparison_id.clone(),
+                completed: items.len() + 1,
+                total,
+                item: item.clone(),
+            },
+        );
+        items.push(item);
+    }
+    drop(results);
+    items.sort_by_key(|item| item.index);
+
+    if save {
+        let group = ComparisonGroup {
+            id: comparison_id.clone(),
+            prompt: params.prompt.clone(),
+            canvas_id: params.canvas_id.clone(),
+            node_id: params.node_id.clone(),
+            created_at: chrono::Utc::now().timestamp(),
+            entries: items
+                .iter()
+                .map(|item| ComparisonEntry {
+                    label: item.label.clone(),
+                    provider: item.provider.clone(),
+                    model: item.model.clone(),
+                    success: item.success,
+                    image_paths: item.saved_images.iter().map(|i| i.path.clone()).collect(),
+                    latency_ms: item.latency_ms,
+                    cost: item.cost,
+                    error: item.error.clone(),
+                })
+                .collect(),
+        };
+        if let Err(e) = save_comparison_group(&app, &group) {
+            println!("[Rust] Failed to save comparison group: {}", e);
+        }
+    }
+
+    let succeeded = items.iter().filter(|item| item.success).count();
+    CompareGenerateResult {
+        success: succeeded > 0,
+        comparison_id,
+        error: if succeeded == 0 {
+            Some("所有对比配置均生成失败".to_string())
+        } else {
+            None
+        },
+        items,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_build_request_merges_overrides() {
+        let params = CompareGenerateParams {
+            prompt: "a cat".to_string(),
+            aspect_ratio: Some("1:1".to_string()),
+            extras: Some(json!({ "quality": "high", "steps": 20 })),
+            ..CompareGenerateParams::default()
+        };
+        let config = CompareConfig {
+            provider: "flux".to_string(),
+            model: "flux-pro-1.1".to_string(),
+            aspect_ratio: Some("16:9".to_string()),
+            extras: Some(json!({ "steps": 30 })),
+            ..CompareConfig::default()
+        };
+        let request = build_request(&params, &config);
+        assert_eq!(request.aspect_ratio.as_deref(), Some("16:9"));
+        assert_eq!(
+            request.extras,
+            Some(json!({ "quality": "high", "steps": 30 }))
+        );
+        assert_eq!(config_label(&config), "flux / flux-pro-1.1");
+    }
+
+    #[test]
+    fn test_estimate_cost() {
+        let config = CompareConfig {
+            cost_per_image: Some(0.04),
+            output_token_price: Some(30.0),
+            ..CompareConfig::default()
+        };
+        let result = ImageGenerationResult {
+            images: vec![GeneratedImage::default(), GeneratedImage::default()],
+            usage: Some(json!({ "promptTokenCount": 100, "candidatesTokenCount": 1000 })),
+            ..ImageGenerationResult::default()
+        };
+        let cost = estimate_cost(&config, &result).unwrap();
+        assert!((cost - 0.11).abs() < 1e-9);
+        assert_eq!(
+            estimate_cost(&CompareConfig::default(), &ImageGenerationResult::default()),
+            None
+        );
+    }
+}
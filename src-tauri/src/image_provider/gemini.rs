@@ -0,0 +1,86 @@
+use async_trait::async_trait;
+
+use super::{GeneratedImage, ImageGenerationRequest, ImageGenerationResult, ImageProvider};
+use crate::gemini::{empty_output_error, gemini_generate_content, GeminiRequestParams};
+
+// Gemini 图片生成（generateContent）
+pub struct GeminiImageProvider;
+
+#[async_trait]
+impl ImageProvider for GeminiImageProvider {
+    fn id(&self) -> &'static str {
+        "gemini"
+    }
+
+    async fn generate(&self, request: &ImageGenerationRequest) -> ImageGenerationResult {
+        let mut prompt = request.prompt.clone();
+        let mut input_images = request.non_empty_input_images();
+
+        // Gemini 没有独立的蒙版参数，把蒙版作为最后一张参考图并在提示词中说明
+        if let Some(mask) = request.non_empty_mask() {
+            input_images.push(mask);
+            prompt.push_str("\n\n最后一张图片是蒙版：白色区域为需要修改的部分，黑色区域保持不变。");
+        }
+        if let Some(negative) = request
+            .negative_prompt
+            .as_ref()
+            .filter(|v| !v.trim().is_empty())
+        {
+            prompt.push_str(&format!("\n\n避免出现：{}", negative));
+        }
+
+        let params = GeminiRequestParams {
+            base_url: request.base_url.clone(),
+            api_key: request.api_key.clone(),
+            model: request.model.clone(),
+            prompt,
+            input_images: if input_images.is_empty() {
+                None
+            } else {
+                Some(input_images)
+            },
+            aspect_ratio: request.aspect_ratio.clone(),
+            image_size: request.image_size.clone(),
+            seed: request.seed,
+            candidate_count: request.n.map(|n| n.max(1) as i32),
+            include_text: request.extra_bool("includeText"),
+        };
+
+        let result = gemini_generate_content(params).await;
+        if !result.success {
+            return ImageGenerationResult::failure(result.error.unwrap_or_default());
+        }
+
+        let images: Vec<GeneratedImage> = result
+            .parts
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|part| {
+                part.image_data.map(|data| GeneratedImage {
+                    data: Some(data),
+                    mime_type: part.mime_type,
+                    url: None,
+                    seed: None,
+                })
+            })
+            .collect();
+
+        let images_missing = images.is_empty();
+        ImageGenerationResult {
+            success: !images_missing,
+            images,
+            error: if images_missing {
+                Some(empty_output_error(
+                    "模型未返回图片",
+                    result.block_reason.as_deref(),
+                ))
+            } else {
+                None
+            },
+            text: result.text,
+            usage: result.usage,
+            preprocessing: result.preprocessing,
+            ..ImageGenerationResult::default()
+        }
+    }
+}
@@ -0,0 +1,262 @@
+//! 统一的图片生成抽象
+//!
+//! 各家图片服务（Gemini、OpenAI Images、ComfyUI、SD WebUI、Flux 等）通过 `ImageProvider` 接入，
+//! 前端只需调用 `generate_image`，按 `provider` 字段选择后端。
+
+mod comfyui;
+mod compare;
+mod gemini;
+mod openai;
+mod sd_webui;
+mod task;
+mod variations;
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::time::Instant;
+use uuid::Uuid;
+
+use crate::framing::{apply_to_request, reframe_outputs, NormalizedFraming};
+use crate::image_preprocess::ImagePreprocessReport;
+use crate::image_task::get_task_adapter;
+
+pub use comfyui::ComfyUIImageProvider;
+pub use compare::compare_generate;
+pub use gemini::GeminiImageProvider;
+pub use openai::OpenAIImageProvider;
+pub use sd_webui::SdWebUIImageProvider;
+pub use task::ImageTaskProvider;
+pub use variations::generate_variations;
+
+// 统一的图片生成请求
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImageGenerationRequest {
+    pub provider: String,
+    pub base_url: String,
+    pub api_key: String,
+    pub model: String,
+    pub prompt: String,
+    #[serde(default)]
+    pub input_images: Vec<String>, // base64 图片数据
+    pub mask_image: Option<String>,
+    pub size: Option<String>,
+    pub aspect_ratio: Option<String>,
+    pub image_size: Option<String>,
+    pub n: Option<u32>,
+    pub seed: Option<i64>,
+    pub negative_prompt: Option<String>,
+    pub reframe: Option<String>, // 服务不支持请求的画幅时，把输出 "crop" 裁切或 "pad" 补边回去
+    // 各服务特有的参数，原样交给对应的 provider 解析
+    pub extras: Option<serde_json::Value>,
+}
+
+impl ImageGenerationRequest {
+    /// 读取 extras 中的字符串参数
+    pub fn extra_str(&self, key: &str) -> Option<String> {
+        self.extras
+            .as_ref()
+            .and_then(|extras| extras.get(key))
+            .and_then(|value| value.as_str())
+            .map(|value| value.to_string())
+    }
+
+    /// 读取 extras 中的数值参数
+    pub fn extra_f64(&self, key: &str) -> Option<f64> {
+        self.extras
+            .as_ref()
+            .and_then(|extras| extras.get(key))
+            .and_then(|value| value.as_f64())
+    }
+
+    /// 读取 extras 中的布尔参数
+    pub fn extra_bool(&self, key: &str) -> Option<bool> {
+        self.extras
+            .as_ref()
+            .and_then(|extras| extras.get(key))
+            .and_then(|value| value.as_bool())
+    }
+
+    /// 过滤掉空白的输入图片
+    pub fn non_empty_input_images(&self) -> Vec<String> {
+        self.input_images
+            .iter()
+            .filter(|image| !image.trim().is_empty())
+            .cloned()
+            .collect()
+    }
+
+    /// 非空的蒙版图片
+    pub fn non_empty_mask(&self) -> Option<String> {
+        self.mask_image
+            .as_ref()
+            .filter(|mask| !mask.trim().is_empty())
+            .cloned()
+    }
+}
+
+/// 解析 "1024x768" 形式的尺寸
+pub(crate) fn parse_size(size: Option<&str>) -> (Option<u32>, Option<u32>) {
+    size.and_then(|s| s.split_once('x'))
+        .map(|(w, h)| (w.trim().parse().ok(), h.trim().parse().ok()))
+        .unwrap_or((None, None))
+}
+
+// 单张生成结果
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GeneratedImage {
+    pub data: Option<String>, // base64 图片数据
+    pub mime_type: Option<String>,
+    pub url: Option<String>,
+    pub seed: Option<i64>, // 该图片实际使用的种子
+}
+
+// 耗时统计
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImageTimings {
+    pub started_at: i64, // 毫秒时间戳
+    pub total_ms: u64,
+}
+
+// 统一的图片生成结果
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImageGenerationResult {
+    pub success: bool,
+    pub provider: String,
+    pub model: String,
+    pub images: Vec<GeneratedImage>,
+    pub text: Option<String>,
+    pub revised_prompt: Option<String>,
+    pub seed: Option<i64>, // 请求使用的种子，服务不接受种子时为空
+    pub usage: Option<serde_json::Value>,
+    pub preprocessing: Option<Vec<ImagePreprocessReport>>,
+    pub timings: Option<ImageTimings>,
+    pub framing: Option<NormalizedFraming>, // 画面参数调整情况
+    pub error: Option<String>,
+}
+
+impl ImageGenerationResult {
+    pub fn failure(error: impl Into<String>) -> Self {
+        Self {
+            success: false,
+            error: Some(error.into()),
+            ..Self::default()
+        }
+    }
+}
+
+#[async_trait]
+pub trait ImageProvider: Send + Sync {
+    /// provider 标识，与请求中的 `provider` 字段对应
+    fn id(&self) -> &'static str;
+
+    /// 是否接受种子参数，不接受时统一入口不会传入也不会返回种子
+    fn accepts_seed(&self, _model: &str) -> bool {
+        true
+    }
+
+    /// 执行一次生成；provider/model/timings 由调用方统一填充
+    async fn generate(&self, request: &ImageGenerationRequest) -> ImageGenerationResult;
+}
+
+/// 随机种子，限制在 int32 范围内以兼容 Gemini 等服务
+pub(crate) fn random_seed() -> i64 {
+    (Uuid::new_v4().as_u128() as u32 >> 1) as i64
+}
+
+/// 按标识查找 provider
+pub fn get_provider(id: &str) -> Option<Box<dyn ImageProvider>> {
+    match id.trim().to_ascii_lowercase().as_str() {
+        "gemini" | "google" => Some(Box::new(GeminiImageProvider)),
+        "openai" | "dalle" | "gpt-image" => Some(Box::new(OpenAIImageProvider)),
+        "comfyui" => Some(Box::new(ComfyUIImageProvider)),
+        "sd-webui" | "a1111" | "forge" => Some(Box::new(SdWebUIImageProvider)),
+        other => get_task_adapter(other)
+            .map(|adapter| Box::new(ImageTaskProvider(adapter)) as Box<dyn ImageProvider>),
+    }
+}
+
+/// 执行请求并补全 provider、model 与耗时信息
+pub async fn run_image_request(request: &ImageGenerationRequest) -> ImageGenerationResult {
+    let provider = match get_provider(&request.provider) {
+        Some(p) => p,
+        None => {
+            let mut result =
+                ImageGenerationResult::failure(format!("不支持的图片服务: {}", request.provider));
+            result.provider = request.provider.clone();
+            result.model = request.model.clone();
+            return result;
+        }
+    };
+
+    // 宽高比与分辨率换算成该服务可接受的值
+    let mut request = request.clone();
+    let mut framing = apply_to_request(&mut request);
+    // 未指定种子时随机生成一个，结果才能复现；服务不接受种子时清空，避免返回未生效的值
+    request.seed = if provider.accepts_seed(&request.model) {
+        request.seed.or_else(|| Some(random_seed()))
+    } else {
+        None
+    };
+
+    let started_at = chrono::Utc::now().timestamp_millis();
+    let start_time = Instant::now();
+    let mut result = provider.generate(&request).await;
+
+    // 服务不支持请求的画幅时按需裁切 / 补边，图片编解码放到阻塞线程
+    if let (Some(current), Some(mode), true) =
+        (framing.clone(), request.reframe.clone(), result.success)
+    {
+        let images = result.images.clone();
+        let reframed = tokio::task::spawn_blocking(move || {
+            let (mut images, mut current) = (images, current);
+            reframe_outputs(&mut images, &mut current, &mode);
+            (images, current)
+        })
+        .await;
+        if let Ok((images, current)) = reframed {
+            result.images = images;
+            framing = Some(current);
+        }
+    }
+    result.framing = framing;
+
+    // 服务没有返回单张图片的种子时，记为请求的种子
+    if result.success {
+        for image in result.images.iter_mut() {
+            image.seed = image.seed.or(request.seed);
+        }
+        result.seed = request.seed;
+    }
+
+    result.provider = provider.id().to_string();
+    result.model = request.model.clone();
+    result.timings = Some(ImageTimings {
+        started_at,
+        total_ms: start_time.elapsed().as_millis() as u64,
+    });
+    result
+}
+
+// Tauri 命令：统一的图片生成入口
+#[tauri::command]
+pub async fn generate_image(request: ImageGenerationRequest) -> ImageGenerationResult {
+    println!(
+        "[Rust] generate_image called, provider: {}, model: {}",
+        request.provider, request.model
+    );
+
+    let result = run_image_request(&request).await;
+    if let Some(timings) = &result.timings {
+        println!(
+            "[Rust] generate_image finished: success={}, images={}, {}ms",
+            result.success,
+            result.images.len(),
+            timings.total_ms
+        );
+    }
+    result
+}
@@ -0,0 +1,94 @@
+use async_trait::async_trait;
+
+use super::{GeneratedImage, ImageGenerationRequest, ImageGenerationResult, ImageProvider};
+use crate::dalle::{accepts_seed, run_dalle_request, DalleRequestParams};
+
+// OpenAI Images 接口（DALL-E / gpt-image 及兼容服务）
+pub struct OpenAIImageProvider;
+
+#[async_trait]
+impl ImageProvider for OpenAIImageProvider {
+    fn id(&self) -> &'static str {
+        "openai"
+    }
+
+    fn accepts_seed(&self, model: &str) -> bool {
+        accepts_seed(model)
+    }
+
+    async fn generate(&self, request: &ImageGenerationRequest) -> ImageGenerationResult {
+        let input_images = request.non_empty_input_images();
+        let output_format = request.extra_str("outputFormat");
+        let mime_type = output_format
+            .as_deref()
+            .map(|format| match format {
+                "jpeg" | "jpg" => "image/jpeg",
+                "webp" => "image/webp",
+                _ => "image/png",
+            })
+            .unwrap_or("image/png")
+            .to_string();
+
+        let params = DalleRequestParams {
+            base_url: request.base_url.clone(),
+            api_key: request.api_key.clone(),
+            model: request.model.clone(),
+            prompt: request.prompt.clone(),
+            operation: request.extra_str("operation"),
+            input_images: if input_images.is_empty() {
+                None
+            } else {
+                Some(input_images)
+            },
+            mask_image: request.non_empty_mask(),
+            size: request.size.clone(),
+            aspect_ratio: request.aspect_ratio.clone(),
+            quality: request.extra_str("quality"),
+            style: request.extra_str("style"),
+            background: request.extra_str("background"),
+            output_format,
+            output_compression: request
+                .extra_f64("outputCompression")
+                .map(|v| v.clamp(0.0, 100.0) as u8),
+            moderation: request.extra_str("moderation"),
+            n: request.n.map(|n| n.max(1) as i32),
+            input_fidelity: request.extra_str("inputFidelity"),
+            negative_prompt: request.negative_prompt.clone(),
+            guidance_scale: request.extra_f64("guidanceScale").map(|v| v as f32),
+            seed: request.seed,
+            watermark: request.extra_bool("watermark"),
+            stream: None,
+            partial_images: None,
+            request_id: None,
+        };
+
+        let result = run_dalle_request(params, &|_| {}).await;
+        if !result.success {
+            let mut failed = ImageGenerationResult::failure(result.error.unwrap_or_default());
+            failed.revised_prompt = result.revised_prompt;
+            return failed;
+        }
+
+        let data_list = result.image_data_list.unwrap_or_default();
+        let sources = result.image_sources;
+        let images = data_list
+            .into_iter()
+            .enumerate()
+            .map(|(index, data)| GeneratedImage {
+                data: Some(data),
+                mime_type: Some(mime_type.clone()),
+                url: sources.get(index).cloned().flatten(),
+                seed: None,
+            })
+            .collect();
+
+        ImageGenerationResult {
+            success: true,
+            images,
+            revised_prompt: result.revised_prompt,
+            usage: result.usage,
+            preprocessing: result.preprocessing,
+            ..ImageGenerationResult::default()
+        }
+    }
+}
@@ -0,0 +1,58 @@
+use async_trait::async_trait;
+
+use super::{
+    parse_size, GeneratedImage, ImageGenerationRequest, ImageGenerationResult, ImageProvider,
+};
+use crate::sd_webui::{run_sd_webui, SdWebUIParams};
+
+// Stable Diffusion WebUI（A1111 / Forge），采样参数通过 extras 传入
+pub struct SdWebUIImageProvider;
+
+#[async_trait]
+impl ImageProvider for SdWebUIImageProvider {
+    fn id(&self) -> &'static str {
+        "sd-webui"
+    }
+
+    async fn generate(&self, request: &ImageGenerationRequest) -> ImageGenerationResult {
+        let (width, height) = parse_size(request.size.as_deref());
+        let params = SdWebUIParams {
+            base_url: request.base_url.clone(),
+            api_key: Some(request.api_key.clone()),
+            model: Some(request.model.clone()),
+            prompt: request.prompt.clone(),
+            negative_prompt: request.negative_prompt.clone(),
+            sampler_name: request.extra_str("sampler"),
+            scheduler: request.extra_str("scheduler"),
+            steps: request.extra_f64("steps").map(|v| v as u32),
+            cfg_scale: request.extra_f64("cfgScale"),
+            seed: request.seed,
+            width,
+            height,
+            batch_size: request.n,
+            denoising_strength: request.extra_f64("denoisingStrength"),
+            init_images: Some(request.non_empty_input_images()),
+            mask_image: request.non_empty_mask(),
+            mask_blur: request.extra_f64("maskBlur").map(|v| v as u32),
+            inpaint_full_res: request.extra_bool("inpaintOnlyMasked"),
+            ..SdWebUIParams::default()
+        };
+
+        match run_sd_webui(&params).await {
+            Ok((images, _)) => ImageGenerationResult {
+                success: true,
+                images: images
+                    .into_iter()
+                    .map(|image| GeneratedImage {
+                        data: Some(image.data),
+                        mime_type: Some(image.mime_type),
+                        url: None,
+                        seed: image.generation.seed,
+                    })
+                    .collect(),
+                ..ImageGenerationResult::default()
+            },
+            Err(e) => ImageGenerationResult::failure(e),
+        }
+    }
+}
@@ -0,0 +1,51 @@
+use async_trait::async_trait;
+use std::sync::atomic::AtomicBool;
+
+use super::{
+    parse_size, GeneratedImage, ImageGenerationRequest, ImageGenerationResult, ImageProvider,
+};
+use crate::image_task::{run_image_task, ImageTaskAdapter, ImageTaskParams};
+
+// 提交 → 轮询式的图片服务（Flux、Midjourney-proxy），在统一接口中同步等待结果
+pub struct ImageTaskProvider(pub Box<dyn ImageTaskAdapter>);
+
+#[async_trait]
+impl ImageProvider for ImageTaskProvider {
+    fn id(&self) -> &'static str {
+        self.0.id()
+    }
+
+    async fn generate(&self, request: &ImageGenerationRequest) -> ImageGenerationResult {
+        let (width, height) = parse_size(request.size.as_deref());
+        let params = ImageTaskParams {
+            provider: self.0.id().to_string(),
+            base_url: request.base_url.clone(),
+            api_key: request.api_key.clone(),
+            model: Some(request.model.clone()),
+            prompt: request.prompt.clone(),
+            input_images: request.non_empty_input_images(),
+            seed: request.seed,
+            width,
+            height,
+            aspect_ratio: request.aspect_ratio.clone(),
+            extras: request.extras.clone(),
+            ..ImageTaskParams::default()
+        };
+
+        match run_image_task(self.0.as_ref(), &params, &AtomicBool::new(false), &|_| {}).await {
+            Ok(run) => ImageGenerationResult {
+                success: true,
+                images: run
+                    .images
+                    .into_iter()
+                    .map(|image| GeneratedImage {
+                        seed: run.seed,
+                        ..image
+                    })
+                    .collect(),
+                ..ImageGenerationResult::default()
+            },
+            Err(e) => ImageGenerationResult::failure(e),
+        }
+    }
+}
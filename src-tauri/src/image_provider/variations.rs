@@ -0,0 +1,463 @@
+//! 变体生成
+//!
+//! 读取已保存图片的元数据，沿用原请求的提示词、服务和画幅重新生成：
+//! 未指定强度时只扰动种子；指定强度时把原图作为输入做图生图，
+//! 强度越大与原图差别越大。
+
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use futures_util::StreamExt;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+
+use super::compare::merge_extras;
+use super::{random_seed, run_image_request, GeneratedImage, ImageGenerationRequest};
+use crate::storage::{
+    find_image_by_id, read_image_metadata, save_image, GenerationParams, ImageInfo, ImageMetadata,
+    ImageType, InputImageInfo,
+};
+
+const DEFAULT_VARIATIONS: u32 = 4;
+const MAX_VARIATIONS: u32 = 8;
+const CONCURRENCY: usize = 2;
+const SEED_MODULUS: i64 = 1 << 31; // 与随机种子相同的 int32 范围
+
+// 前端调用的参数
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GenerateVariationsParams {
+    pub image_id: String,
+    pub n: Option<u32>,           // 变体数量，默认 4，最多 8
+    pub strength: Option<f64>,    // 0-1，为空或 0 时只扰动种子，否则以原图做图生图
+    pub provider: Option<String>, // 覆盖原图记录的服务 / 模型
+    pub model: Option<String>,
+    pub base_url: String,
+    pub api_key: String,
+    pub extras: Option<Value>, // 与按原图参数生成的 extras 合并，同名字段以此为准
+    pub canvas_id: Option<String>, // 默认沿用原图所在画布
+    pub node_id: Option<String>,
+    pub save_to_storage: Option<bool>, // 默认保存
+}
+
+// 单个变体的结果
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VariationItem {
+    pub index: usize,
+    pub seed: Option<i64>,
+    pub success: bool,
+    pub image: Option<GeneratedImage>,
+    pub saved_image: Option<ImageInfo>,
+    pub error: Option<String>,
+}
+
+// 返回结果（按序号排列）
+#[derive(Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GenerateVariationsResult {
+    pub success: bool,
+    pub source_id: String,
+    pub source_seed: Option<i64>,
+    pub items: Vec<VariationItem>,
+    pub error: Option<String>,
+}
+
+impl GenerateVariationsResult {
+    fn failure(source_id: &str, error: impl Into<String>) -> Self {
+        Self {
+            success: false,
+            source_id: source_id.to_string(),
+            error: Some(error.into()),
+            ..Self::default()
+        }
+    }
+}
+
+/// 变体使用的种子：原图有种子时依次加一，否则随机
+fn variation_seeds(base: Option<i64>, n: u32) -> Vec<i64> {
+    (1..=n as i64)
+        .map(|offset| match base {
+            Some(seed) => (seed + offset).rem_euclid(SEED_MODULUS),
+            None => random_seed(),
+        })
+        .collect()
+}
+
+/// 有效的图生图强度
+fn effective_strength(strength: Option<f64>) -> Option<f64> {
+    strength
+        .filter(|s| s.is_finite() && *s > 0.0)
+        .map(|s| s.min(1.0))
+}
+
+/// 读取原图的参考图片，找不到的文件直接跳过
+fn read_input_images(metadata: &ImageMetadata) -> Vec<String> {
+    metadata
+        .input_images
+        .iter()
+        .filter_map(|input| input.path.as_deref())
+        .filter_map(|path| std::fs::read(path).ok())
+        .map(|bytes| BASE64.encode(bytes))
+        .collect()
+}
+
+/// 变体实际发送的参考图：图生图时是原图，否则沿用原请求的参考图
+fn variation_input_images(
+    metadata: &ImageMetadata,
+    source_path: &str,
+    strength: Option<f64>,
+) -> Vec<InputImageInfo> {
+    match strength {
+        Some(_) => vec![InputImageInfo {
+            path: Some(source_path.to_string()),
+            label: "原图".to_string(),
+        }],
+        None => metadata.input_images.clone(),
+    }
+}
+
+/// 按原图元数据重建请求；`inputs` 为图生图的原图或原请求的参考图
+fn variation_request(
+    metadata: &ImageMetadata,
+    inputs: Vec<String>,
+    params: &GenerateVariationsParams,
+) -> Result<ImageGenerationRequest, String> {
+    let generation = metadata.generation.clone().unwrap_or_default();
+    let pick = |value: &Option<String>| value.clone().filter(|v| !v.trim().is_empty());
+    let provider = pick(&params.provider)
+        .or(generation.provider.clone())
+        .ok_or("原图没有记录生成服务，请指定 provider")?;
+    let prompt = metadata.prompt.clone().unwrap_or_default();
+    let strength = effective_strength(params.strength);
+    if prompt.trim().is_empty() && strength.is_none() {
+        return Err("原图没有记录提示词，只能指定强度做图生图".to_string());
+    }
+
+    // 原图记录的采样参数，供 SD WebUI 等服务复用
+    let mut extras = Map::new();
+    if let Some(sampler) = &generation.sampler {
+        extras.insert("sampler".to_string(), json!(sampler));
+    }
+    if let Some(scheduler) = &generation.scheduler {
+        extras.insert("scheduler".to_string(), json!(scheduler));
+    }
+    if let Some(steps) = generation.steps {
+        extras.insert("steps".to_string(), json!(steps));
+    }
+    if let Some(cfg_scale) = generation.cfg_scale {
+        extras.insert("cfgScale".to_string(), json!(cfg_scale));
+    }
+    if let Some(strength) = strength {
+        extras.insert("denoisingStrength".to_string(), json!(strength));
+        extras.insert("strength".to_string(), json!(strength));
+    }
+
+    let recorded = |key: &str| {
+        generation
+            .extra
+            .as_ref()
+            .and_then(|extra| extra.get(key))
+            .and_then(|value| value.as_str())
+            .map(|value| value.to_string())
+    };
+    Ok(ImageGenerationRequest {
+        provider,
+        base_url: params.base_url.clone(),
+        api_key: params.api_key.clone(),
+        model: pick(&params.model)
+            .or(generation.model.clone())
+            .unwrap_or_default(),
+        prompt,
+        input_images: inputs,
+        size: match (generation.width, generation.height) {
+            (Some(w), Some(h)) => Some(format!("{}x{}", w, h)),
+            _ => None,
+        },
+        aspect_ratio: recorded("aspectRatio"),
+        image_size: recorded("imageSize"),
+        n: Some(1),
+        negative_prompt: generation.negative_prompt.clone(),
+        extras: merge_extras(Some(&Value::Object(extras)), params.extras.as_ref()),
+        ..ImageGenerationRequest::default()
+    })
+}
+
+// Tauri 命令：沿用原图的请求生成变体，扰动种子或按强度做图生图
+#[tauri::command]
+pub async fn generate_variations(
+    app: tauri::AppHandle,
+    params: GenerateVariationsParams,
+) -> GenerateVariationsResult {
+    let n = params
+        .n
+        .unwrap_or(DEFAULT_VARIATIONS)
+        .clamp(1, MAX_VARIATIONS);
+    let strength = effective_strength(params.strength);
+    println!(
+        "[Rust] generate_variations called, image: {}, n: {}, strength: {:?}",
+        params.image_id, n, strength
+    );
+
+    let source_path = match find_image_by_id(&app, &params.image_id) {
+        Ok(path) => path,
+        Err(e) => return GenerateVariationsResult::failure(&params.image_id, e),
+    };
+    let source_path_str = source_path.to_string_lossy().to_string();
+    let metadata = match read_image_metadata(source_path_str.clone()) {
+        Ok(Some(metadata)) => metadata,
+        Ok(None) => return GenerateVariationsResult::failure(&params.image_id, "原图没有生成参数"),
+        Err(e) => return GenerateVariationsResult::failure(&params.image_id, e),
+    };
+
+    let inputs = if strength.is_some() {
+        match std::fs::read(&source_path) {
+            Ok(bytes) => vec![BASE64.encode(bytes)],
+            Err(e) => {
+                return GenerateVariationsResult::failure(
+                    &params.image_id,
+                    format!("读取原图失败: {}", e),
+                )
+            }
+        }
+    } else {
+        read_input_images(&metadata)
+    };
+    let base = match variation_request(&metadata, inputs, &params) {
+        Ok(request) => request,
+        Err(e) => return GenerateVariationsResult::failure(&params.image_id, e),
+    };
+    let source_seed = metadata.generation.as_ref().and_then(|g| g.seed);
+    let input_images = variation_input_images(&metadata, &source_path_str, strength);
+
+    let requests: Vec<(usize, ImageGenerationRequest)> = variation_seeds(source_seed, n)
+        .into_iter()
+        .map(|seed| ImageGenerationRequest {
+            seed: Some(seed),
+            ..base.clone()
+        })
+        .enumerate()
+        .collect();
+    let mut results = futures_util::stream::iter(
+        requests
+            .into_iter()
+            .map(|(index, request)| async move { (index, run_image_request(&request).await) }),
+    )
+    .buffer_unordered(CONCURRENCY);
+
+    let save = params.save_to_storage.unwrap_or(true);
+    let canvas_id = params.canvas_id.clone().or(metadata.canvas_id.clone());
+    let generation = metadata.generation.clone().unwrap_or_default();
+    let mut items = Vec::with_capacity(n as usize);
+    while let Some((index, result)) = results.next().await {
+        let image = result.images.into_iter().next();
+        let seed = image.as_ref().and_then(|image| image.seed).or(result.seed);
+
+        let mut saved_image = None;
+        if let (true, Some(data)) = (
+            save && result.success,
+            image.as_ref().and_then(|i| i.data.clone()),
+        ) {
+            match save_image(
+                app.clone(),
+                data,
+                canvas_id.clone(),
+                params.node_id.clone(),
+                Some(base.prompt.clone()),
+                Some(input_images.clone()),
+                Some(ImageType::Generated),
+                Some(GenerationParams {
+                    provider: Some(result.provider.clone()),
+                    model: Some(result.model.clone()),
+                    negative_prompt: base.negative_prompt.clone(),
+                    seed,
+                    denoising_strength: strength,
+                    extra: Some(json!({
+                        "variation": {
+                            "sourceId": params.image_id,
+                            "index": index,
+                            "strength": strength,
+                        },
+                        "aspectRatio": base.aspect_ratio,
+                        "imageSize": base.image_size,
+                    })),
+                    ..generation.clone()
+                }),
+            ) {
+                Ok(info) => saved_image = Some(info),
+                Err(e) => println!("[Rust] Failed to save variation: {}", e),
+            }
+        }
+
+        items.push(VariationItem {
+            index,
+            seed,
+            success: result.success,
+            image,
+            saved_image,
+            error: result.error,
+        });
+    }
+    items.sort_by_key(|item| item.index);
+
+    let succeeded = items.iter().filter(|item| item.success).count();
+    println!(
+        "[Rust] generate_variations finished: {}/{} succeeded",
+        succeeded,
+        items.len()
+    );
+    GenerateVariationsResult {
+        success: succeeded > 0,
+        source_id: params.image_id.clone(),
+        source_seed,
+        error: if succeeded == 0 {
+            items
+                .iter()
+                .find_map(|item| item.error.clone())
+                .or_else(|| Some("变体生成失败".to_string()))
+        } else {
+            None
+        },
+        items,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn metadata(generation: GenerationParams) -> ImageMetadata {
+        ImageMetadata {
+            prompt: Some("a lighthouse at dusk".to_string()),
+            input_images: vec![],
+            node_id: None,
+            canvas_id: None,
+            created_at: 0,
+            generation: Some(generation),
+            description: None,
+        }
+    }
+
+    #[test]
+    fn test_variation_seeds_perturb_source_seed() {
+        assert_eq!(variation_seeds(Some(41), 3), vec![42, 43, 44]);
+        assert_eq!(variation_seeds(Some(SEED_MODULUS - 1), 2), vec![0, 1]);
+        assert!(variation_seeds(None, 4)
+            .iter()
+            .all(|seed| (0..SEED_MODULUS).contains(seed)));
+    }
+
+    #[test]
+    fn test_variation_request_reuses_original_params() {
+        let metadata = metadata(GenerationParams {
+            provider: Some("sd-webui".to_string()),
+            model: Some("sdxl".to_string()),
+            steps: Some(30),
+            width: Some(1024),
+            height: Some(768),
+            seed: Some(7),
+            extra: Some(json!({ "aspectRatio": "4:3" })),
+            ..GenerationParams::default()
+        });
+        let params = GenerateVariationsParams {
+            image_id: "abc".to_string(),
+            strength: Some(1.5),
+            extras: Some(json!({ "steps": 20 })),
+            ..GenerateVariationsParams::default()
+        };
+        let request = variation_request(&metadata, vec!["data".to_string()], &params).unwrap();
+        assert_eq!(request.provider, "sd-webui");
+        assert_eq!(request.size.as_deref(), Some("1024x768"));
+        assert_eq!(request.aspect_ratio.as_deref(), Some("4:3"));
+        assert_eq!(request.input_images.len(), 1);
+        let extras = request.extras.unwrap();
+        assert_eq!(extras["steps"], 20);
+        assert_eq!(extras["denoisingStrength"], 1.0);
+
+        let mut without_provider = metadata.clone();
+        without_provider.generation = None;
+        assert!(variation_request(&without_provider, vec![], &params).is_err());
+    }
+
+    // 模拟保存链路：生成结果经前端原样传回 save_image，写入图片后再读出
+    fn saved_metadata(generation: Option<GenerationParams>) -> ImageMetadata {
+        let generation: GenerationParams =
+            serde_json::from_value(serde_json::to_value(generation).unwrap()).unwrap();
+        let mut png = Vec::new();
+        image::DynamicImage::ImageRgba8(image::RgbaImage::new(4, 4))
+            .write_to(&mut std::io::Cursor::new(&mut png), image::ImageFormat::Png)
+            .unwrap();
+        let embedded =
+            crate::embedded_metadata::embed_metadata(&png, &metadata(generation)).unwrap();
+        crate::embedded_metadata::extract_metadata(&embedded).unwrap()
+    }
+
+    #[test]
+    fn test_saved_gemini_and_dalle_images_round_trip_into_variations() {
+        let params = GenerateVariationsParams {
+            image_id: "abc".to_string(),
+            ..GenerateVariationsParams::default()
+        };
+
+        let gemini: crate::gemini::GeminiRequestParams = serde_json::from_value(json!({
+            "baseUrl": "https://example.com",
+            "apiKey": "key",
+            "model": "gemini-2.5-flash-image",
+            "prompt": "a lighthouse at dusk",
+            "aspectRatio": "16:9",
+            "imageSize": "2K",
+        }))
+        .unwrap();
+        let saved = saved_metadata(Some(crate::gemini::gemini_generation(&gemini, Some(1234))));
+        let request = variation_request(&saved, vec![], &params).unwrap();
+        assert_eq!(request.provider, "gemini");
+        assert_eq!(request.model, "gemini-2.5-flash-image");
+        assert_eq!(request.aspect_ratio.as_deref(), Some("16:9"));
+        assert_eq!(request.image_size.as_deref(), Some("2K"));
+        let seed = saved.generation.and_then(|generation| generation.seed);
+        assert_eq!(variation_seeds(seed, 1), vec![1235]);
+
+        let dalle: crate::dalle::DalleRequestParams = serde_json::from_value(json!({
+            "baseUrl": "https://example.com",
+            "apiKey": "key",
+            "model": "dall-e-3",
+            "prompt": "a lighthouse at dusk",
+            "size": "1792x1024",
+        }))
+        .unwrap();
+        let saved = saved_metadata(Some(crate::dalle::dalle_generation(&dalle, None)));
+        let request = variation_request(&saved, vec![], &params).unwrap();
+        assert_eq!(request.provider, "openai");
+        assert_eq!(request.size.as_deref(), Some("1792x1024"));
+    }
+
+    #[test]
+    fn test_variation_of_variation_keeps_actual_inputs() {
+        let original = metadata(GenerationParams {
+            provider: Some("sd-webui".to_string()),
+            seed: Some(7),
+            ..GenerationParams::default()
+        });
+        let variation = |source: &ImageMetadata, path: &str, strength: Option<f64>| ImageMetadata {
+            input_images: variation_input_images(source, path, strength),
+            ..source.clone()
+        };
+
+        // 只扰动种子：文生图的变体再做变体仍然是文生图
+        let first = variation(&original, "/images/original.png", None);
+        assert!(first.input_images.is_empty());
+        let second = variation(&first, "/images/first.png", None);
+        assert!(second.input_images.is_empty());
+        assert!(read_input_images(&second).is_empty());
+
+        // 图生图记录原图，其种子变体沿用同一张参考图
+        let img2img = variation(&original, "/images/original.png", Some(0.5));
+        assert_eq!(
+            img2img.input_images[0].path.as_deref(),
+            Some("/images/original.png")
+        );
+        let reseeded = variation(&img2img, "/images/img2img.png", None);
+        assert_eq!(
+            reseeded.input_images[0].path.as_deref(),
+            Some("/images/original.png")
+        );
+    }
+}
@@ -0,0 +1,227 @@
+use async_trait::async_trait;
+use reqwest::Client;
+use serde_json::{json, Map, Value};
+use std::time::Duration;
+
+use super::{
+    read_response_text, request_error, ImageTaskAdapter, ImageTaskParams, SubmittedTask, TaskPoll,
+};
+use crate::image_utils::strip_data_url_prefix;
+
+const DEFAULT_MODEL: &str = "flux-pro-1.1";
+
+// Black Forest Labs（Flux）风格接口：POST /v1/{model}，GET polling_url 查询
+pub struct FluxTaskAdapter;
+
+fn build_submit_body(params: &ImageTaskParams) -> Value {
+    let mut body = Map::new();
+    body.insert("prompt".to_string(), json!(params.prompt));
+    if let Some(seed) = params.seed {
+        body.insert("seed".to_string(), json!(seed));
+    }
+    if let Some(width) = params.width {
+        body.insert("width".to_string(), json!(width));
+    }
+    if let Some(height) = params.height {
+        body.insert("height".to_string(), json!(height));
+    }
+    if let Some(aspect_ratio) = &params.aspect_ratio {
+        body.insert("aspect_ratio".to_string(), json!(aspect_ratio));
+    }
+    // Kontext 用 input_image 编辑，其余模型作为 image_prompt 参考
+    if let Some(image) = params.non_empty_input_images().first() {
+        let key = if params
+            .model
+            .as_deref()
+            .is_some_and(|model| model.contains("kontext"))
+        {
+            "input_image"
+        } else {
+            "image_prompt"
+        };
+        body.insert(key.to_string(), json!(strip_data_url_prefix(image)));
+    }
+    if let Some(Value::Object(extras)) = &params.extras {
+        for (key, value) in extras {
+            body.insert(key.clone(), value.clone());
+        }
+    }
+    Value::Object(body)
+}
+
+/// 解析 get_result 响应
+fn parse_poll_response(value: &Value) -> TaskPoll {
+    let status = value
+        .get("status")
+        .and_then(|s| s.as_str())
+        .unwrap_or("Pending");
+    match status {
+        "Ready" => {
+            let result = value.get("result");
+            let image_urls = result
+                .and_then(|r| r.get("sample"))
+                .and_then(|s| s.as_str())
+                .map(|url| vec![url.to_string()])
+                .unwrap_or_default();
+            TaskPoll::Succeeded {
+                image_urls,
+                seed: result.and_then(|r| r.get("seed")).and_then(|s| s.as_i64()),
+            }
+        }
+        "Request Moderated" | "Content Moderated" => {
+            TaskPoll::Failed(format!("内容审核未通过（{}）", status))
+        }
+        "Error" | "Failed" => {
+            let detail = value
+                .get("details")
+                .filter(|d| !d.is_null())
+                .map(|d| d.to_string())
+                .unwrap_or_default();
+            TaskPoll::Failed(
+                format!("任务失败: {} {}", status, detail)
+                    .trim()
+                    .to_string(),
+            )
+        }
+        // 刚提交时可能返回 "Task not found"，其他未知状态同样继续轮询直到超时
+        _ => TaskPoll::Pending {
+            status: status.to_lowercase(),
+            progress: value
+                .get("progress")
+                .and_then(|p| p.as_f64())
+                .map(|p| (p.clamp(0.0, 1.0) * 100.0).round() as u32),
+        },
+    }
+}
+
+#[async_trait]
+impl ImageTaskAdapter for FluxTaskAdapter {
+    fn id(&self) -> &'static str {
+        "flux"
+    }
+
+    fn poll_interval(&self) -> Duration {
+        Duration::from_millis(1500)
+    }
+
+    async fn submit(
+        &self,
+        client: &Client,
+        params: &ImageTaskParams,
+    ) -> Result<SubmittedTask, String> {
+        let model = params
+            .model
+            .as_deref()
+            .filter(|m| !m.trim().is_empty())
+            .unwrap_or(DEFAULT_MODEL);
+        let url = format!("{}/v1/{}", params.base_url.trim_end_matches('/'), model);
+        println!("[Rust] Flux submit: {}", url);
+
+        let response = client
+            .post(&url)
+            .header("x-key", &params.api_key)
+            .json(&build_submit_body(params))
+            .send()
+            .await
+            .map_err(request_error)?;
+        let text = read_response_text(response).await?;
+        let value: Value =
+            serde_json::from_str(&text).map_err(|e| format!("解析响应失败: {}", e))?;
+
+        let task_id = value
+            .get("id")
+            .and_then(|id| id.as_str())
+            .ok_or_else(|| format!("响应中没有任务 ID: {}", text))?;
+        Ok(SubmittedTask {
+            task_id: task_id.to_string(),
+            polling_url: value
+                .get("polling_url")
+                .and_then(|u| u.as_str())
+                .map(|u| u.to_string()),
+        })
+    }
+
+    async fn poll(
+        &self,
+        client: &Client,
+        params: &ImageTaskParams,
+        task: &SubmittedTask,
+    ) -> Result<TaskPoll, String> {
+        let url = task.polling_url.clone().unwrap_or_else(|| {
+            format!(
+                "{}/v1/get_result?id={}",
+                params.base_url.trim_end_matches('/'),
+                task.task_id
+            )
+        });
+        let response = client
+            .get(&url)
+            .header("x-key", &params.api_key)
+            .send()
+            .await
+            .map_err(request_error)?;
+        let text = read_response_text(response).await?;
+        let value: Value =
+            serde_json::from_str(&text).map_err(|e| format!("解析响应失败: {}", e))?;
+        Ok(parse_poll_response(&value))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_poll_response() {
+        assert_eq!(
+            parse_poll_response(&json!({ "status": "Pending", "progress": 0.42 })),
+            TaskPoll::Pending {
+                status: "pending".to_string(),
+                progress: Some(42),
+            }
+        );
+        assert_eq!(
+            parse_poll_response(&json!({
+                "status": "Ready",
+                "result": { "sample": "https://example.com/a.jpg", "seed": 7 }
+            })),
+            TaskPoll::Succeeded {
+                image_urls: vec!["https://example.com/a.jpg".to_string()],
+                seed: Some(7),
+            }
+        );
+        assert!(matches!(
+            parse_poll_response(&json!({ "status": "Content Moderated" })),
+            TaskPoll::Failed(_)
+        ));
+        assert_eq!(
+            parse_poll_response(&json!({ "status": "Error", "details": "boom" })),
+            TaskPoll::Failed("任务失败: Error \"boom\"".to_string())
+        );
+    }
+
+    #[test]
+    fn test_unknown_status_keeps_polling() {
+        for status in ["Task not found", "Warming Up"] {
+            assert_eq!(
+                parse_poll_response(&json!({ "status": status })),
+                TaskPoll::Pending {
+                    status: status.to_lowercase(),
+                    progress: None,
+                }
+            );
+        }
+    }
+
+    #[test]
+    fn test_kontext_uses_input_image() {
+        let params = ImageTaskParams {
+            model: Some("flux-kontext-pro".to_string()),
+            input_images: vec!["data:image/png;base64,AAAA".to_string()],
+            ..ImageTaskParams::default()
+        };
+        let body = build_submit_body(&params);
+        assert_eq!(body["input_image"], "AAAA");
+        assert!(body.get("image_prompt").is_none());
+    }
+}
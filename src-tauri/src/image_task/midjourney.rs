@@ -0,0 +1,250 @@
+use async_trait::async_trait;
+use reqwest::{Client, RequestBuilder};
+use serde_json::{json, Map, Value};
+use std::time::Duration;
+
+use super::{
+    read_response_text, request_error, ImageTaskAdapter, ImageTaskParams, SubmittedTask, TaskPoll,
+};
+use crate::image_utils::{decode_base64_image, guess_image_mime};
+
+// Midjourney-proxy 风格接口：POST /mj/submit/imagine，GET /mj/task/{id}/fetch 查询
+pub struct MidjourneyTaskAdapter;
+
+// 提交成功的返回码：1 已提交，21 任务已存在，22 排队中
+const ACCEPTED_CODES: [i64; 3] = [1, 21, 22];
+
+fn base_url(params: &ImageTaskParams) -> String {
+    // 兼容填写了 /mj 后缀的地址
+    params
+        .base_url
+        .trim_end_matches('/')
+        .trim_end_matches("/mj")
+        .to_string()
+}
+
+fn with_auth(builder: RequestBuilder, api_key: &str) -> RequestBuilder {
+    if api_key.trim().is_empty() {
+        return builder;
+    }
+    // 原版 proxy 用 mj-api-secret，中转站通常用 Bearer
+    builder
+        .header("mj-api-secret", api_key)
+        .bearer_auth(api_key)
+}
+
+fn build_submit_body(params: &ImageTaskParams) -> Value {
+    let mut body = Map::new();
+    let mut prompt = params.prompt.clone();
+    if let Some(aspect_ratio) = params
+        .aspect_ratio
+        .as_deref()
+        .filter(|_| !prompt.contains("--ar"))
+    {
+        prompt.push_str(&format!(" --ar {}", aspect_ratio));
+    }
+    if let Some(seed) = params.seed.filter(|_| !prompt.contains("--seed")) {
+        prompt.push_str(&format!(" --seed {}", seed));
+    }
+    body.insert("prompt".to_string(), json!(prompt));
+
+    // base64Array 需要 data URL 形式
+    let images: Vec<String> = params
+        .non_empty_input_images()
+        .iter()
+        .filter_map(|image| {
+            if image.starts_with("data:") {
+                return Some(image.clone());
+            }
+            let bytes = decode_base64_image(image).ok()?;
+            let (mime, _) = guess_image_mime(&bytes);
+            Some(format!("data:{};base64,{}", mime, image))
+        })
+        .collect();
+    if !images.is_empty() {
+        body.insert("base64Array".to_string(), json!(images));
+    }
+    if let Some(Value::Object(extras)) = &params.extras {
+        for (key, value) in extras {
+            body.insert(key.clone(), value.clone());
+        }
+    }
+    Value::Object(body)
+}
+
+/// 解析 "45%" 形式的进度
+fn parse_progress(value: &Value) -> Option<u32> {
+    match value {
+        Value::String(s) => s.trim().trim_end_matches('%').parse::<u32>().ok(),
+        Value::Number(n) => n.as_u64().map(|n| n as u32),
+        _ => None,
+    }
+    .map(|p| p.min(100))
+}
+
+/// 解析 fetch 响应
+fn parse_fetch_response(value: &Value) -> TaskPoll {
+    let status = value
+        .get("status")
+        .and_then(|s| s.as_str())
+        .unwrap_or("SUBMITTED");
+    match status {
+        "SUCCESS" => {
+            let mut image_urls: Vec<String> = value
+                .get("imageUrls")
+                .and_then(|urls| urls.as_array())
+                .map(|urls| {
+                    urls.iter()
+                        .filter_map(|u| u.get("url").or(Some(u)).and_then(|u| u.as_str()))
+                        .map(|u| u.to_string())
+                        .collect()
+                })
+                .unwrap_or_default();
+            if image_urls.is_empty() {
+                if let Some(url) = value.get("imageUrl").and_then(|u| u.as_str()) {
+                    image_urls.push(url.to_string());
+                }
+            }
+            TaskPoll::Succeeded {
+                image_urls,
+                seed: None,
+            }
+        }
+        "FAILURE" | "CANCEL" => TaskPoll::Failed(
+            value
+                .get("failReason")
+                .and_then(|r| r.as_str())
+                .filter(|r| !r.is_empty())
+                .map(|r| format!("任务失败: {}", r))
+                .unwrap_or_else(|| format!("任务失败（{}）", status)),
+        ),
+        "MODAL" => TaskPoll::Failed("任务需要在 Midjourney 中进一步确认".to_string()),
+        other => TaskPoll::Pending {
+            status: other.to_lowercase(),
+            progress: value.get("progress").and_then(parse_progress),
+        },
+    }
+}
+
+#[async_trait]
+impl ImageTaskAdapter for MidjourneyTaskAdapter {
+    fn id(&self) -> &'static str {
+        "midjourney"
+    }
+
+    fn poll_interval(&self) -> Duration {
+        Duration::from_secs(3)
+    }
+
+    async fn submit(
+        &self,
+        client: &Client,
+        params: &ImageTaskParams,
+    ) -> Result<SubmittedTask, String> {
+        let url = format!("{}/mj/submit/imagine", base_url(params));
+        println!("[Rust] Midjourney submit: {}", url);
+
+        let response = with_auth(client.post(&url), &params.api_key)
+            .json(&build_submit_body(params))
+            .send()
+            .await
+            .map_err(request_error)?;
+        let text = read_response_text(response).await?;
+        let value: Value =
+            serde_json::from_str(&text).map_err(|e| format!("解析响应失败: {}", e))?;
+
+        let code = value.get("code").and_then(|c| c.as_i64()).unwrap_or(0);
+        if !ACCEPTED_CODES.contains(&code) {
+            let description = value
+                .get("description")
+                .and_then(|d| d.as_str())
+                .unwrap_or("未知错误");
+            return Err(format!("提交失败 ({}): {}", code, description));
+        }
+        let task_id = match value.get("result") {
+            Some(Value::String(id)) => id.clone(),
+            Some(Value::Number(id)) => id.to_string(),
+            _ => return Err(format!("响应中没有任务 ID: {}", text)),
+        };
+        Ok(SubmittedTask {
+            task_id,
+            polling_url: None,
+        })
+    }
+
+    async fn poll(
+        &self,
+        client: &Client,
+        params: &ImageTaskParams,
+        task: &SubmittedTask,
+    ) -> Result<TaskPoll, String> {
+        let url = format!("{}/mj/task/{}/fetch", base_url(params), task.task_id);
+        let response = with_auth(client.get(&url), &params.api_key)
+            .send()
+            .await
+            .map_err(request_error)?;
+        let text = read_response_text(response).await?;
+        let value: Value =
+            serde_json::from_str(&text).map_err(|e| format!("解析响应失败: {}", e))?;
+        Ok(parse_fetch_response(&value))
+    }
+
+    async fn cancel(
+        &self,
+        client: &Client,
+        params: &ImageTaskParams,
+        task: &SubmittedTask,
+    ) -> Result<(), String> {
+        let url = format!("{}/mj/task/{}/cancel", base_url(params), task.task_id);
+        let response = with_auth(client.post(&url), &params.api_key)
+            .send()
+            .await
+            .map_err(request_error)?;
+        read_response_text(response).await.map(|_| ())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_fetch_response() {
+        assert_eq!(
+            parse_fetch_response(&json!({ "status": "IN_PROGRESS", "progress": "45%" })),
+            TaskPoll::Pending {
+                status: "in_progress".to_string(),
+                progress: Some(45),
+            }
+        );
+        assert_eq!(
+            parse_fetch_response(&json!({
+                "status": "SUCCESS",
+                "progress": "100%",
+                "imageUrl": "https://cdn.example.com/grid.png"
+            })),
+            TaskPoll::Succeeded {
+                image_urls: vec!["https://cdn.example.com/grid.png".to_string()],
+                seed: None,
+            }
+        );
+        assert_eq!(
+            parse_fetch_response(&json!({ "status": "FAILURE", "failReason": "banned prompt" })),
+            TaskPoll::Failed("任务失败: banned prompt".to_string())
+        );
+    }
+
+    #[test]
+    fn test_submit_body_appends_parameters() {
+        let params = ImageTaskParams {
+            prompt: "a cat".to_string(),
+            aspect_ratio: Some("16:9".to_string()),
+            seed: Some(42),
+            ..ImageTaskParams::default()
+        };
+        assert_eq!(
+            build_submit_body(&params)["prompt"],
+            "a cat --ar 16:9 --seed 42"
+        );
+    }
+}
@@ -0,0 +1,535 @@
+//! 异步图片任务（提交 → 轮询 → 取回）
+//!
+//! 部分图片服务（Flux/BFL、Midjourney-proxy 等）不直接返回图片，
+//! 而是像视频接口一样先返回任务 ID。这里在后端完成轮询，
+//! 通过 `image-task-progress` 事件上报进度，完成后下载结果并按需保存。
+
+mod flux;
+mod midjourney;
+
+use async_trait::async_trait;
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+use tauri::Emitter;
+use uuid::Uuid;
+
+use crate::image_provider::GeneratedImage;
+use crate::image_utils::guess_image_mime;
+use crate::storage::{save_image, GenerationParams, ImageInfo, ImageType};
+
+pub use flux::FluxTaskAdapter;
+pub use midjourney::MidjourneyTaskAdapter;
+
+const DEFAULT_TIMEOUT_SECS: u64 = 600;
+const MIN_POLL_INTERVAL_MS: u64 = 500;
+
+// 前端调用的参数
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImageTaskParams {
+    pub provider: String, // "flux" | "midjourney"
+    pub base_url: String,
+    pub api_key: String,
+    pub model: Option<String>,
+    pub prompt: String,
+    #[serde(default)]
+    pub input_images: Vec<String>, // base64 参考图
+    pub seed: Option<i64>,
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    pub aspect_ratio: Option<String>,
+    pub extras: Option<Value>,      // 各服务特有参数，原样合并进提交请求
+    pub request_id: Option<String>, // 用于区分进度事件和取消任务
+    pub poll_interval_ms: Option<u64>,
+    pub timeout_secs: Option<u64>,
+    pub canvas_id: Option<String>,
+    pub node_id: Option<String>,
+    pub save_to_storage: Option<bool>, // 默认保存
+}
+
+impl ImageTaskParams {
+    /// 过滤掉空白的输入图片
+    pub fn non_empty_input_images(&self) -> Vec<String> {
+        self.input_images
+            .iter()
+            .filter(|image| !image.trim().is_empty())
+            .cloned()
+            .collect()
+    }
+}
+
+// 已提交的任务
+#[derive(Debug, Clone)]
+pub struct SubmittedTask {
+    pub task_id: String,
+    pub polling_url: Option<String>, // 服务返回的轮询地址（BFL 多区域）
+}
+
+// 单次轮询的结果
+#[derive(Debug, Clone, PartialEq)]
+pub enum TaskPoll {
+    Pending {
+        status: String,
+        progress: Option<u32>, // 0-100
+    },
+    Succeeded {
+        image_urls: Vec<String>,
+        seed: Option<i64>,
+    },
+    Failed(String),
+}
+
+// 进度事件（image-task-progress）
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImageTaskProgressEvent {
+    pub request_id: String,
+    pub provider: String,
+    pub task_id: Option<String>,
+    pub status: String, // "submitted" | 服务返回的状态 | "downloading" | "completed"
+    pub progress: Option<u32>,
+}
+
+// 返回结果
+#[derive(Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImageTaskResult {
+    pub success: bool,
+    pub request_id: Option<String>,
+    pub task_id: Option<String>,
+    pub seed: Option<i64>,
+    pub images: Vec<GeneratedImage>,
+    pub saved_images: Vec<ImageInfo>,
+    pub error: Option<String>,
+}
+
+// 一次任务执行的输出
+#[derive(Debug)]
+pub struct ImageTaskRun {
+    pub task_id: String,
+    pub seed: Option<i64>,
+    pub images: Vec<GeneratedImage>,
+}
+
+#[async_trait]
+pub trait ImageTaskAdapter: Send + Sync {
+    /// 服务标识，与参数中的 `provider` 字段对应
+    fn id(&self) -> &'static str;
+
+    /// 默认轮询间隔
+    fn poll_interval(&self) -> Duration {
+        Duration::from_secs(2)
+    }
+
+    /// 提交任务
+    async fn submit(
+        &self,
+        client: &Client,
+        params: &ImageTaskParams,
+    ) -> Result<SubmittedTask, String>;
+
+    /// 查询一次任务状态
+    async fn poll(
+        &self,
+        client: &Client,
+        params: &ImageTaskParams,
+        task: &SubmittedTask,
+    ) -> Result<TaskPoll, String>;
+
+    /// 取消任务，服务不支持时什么也不做
+    async fn cancel(
+        &self,
+        _client: &Client,
+        _params: &ImageTaskParams,
+        _task: &SubmittedTask,
+    ) -> Result<(), String> {
+        Ok(())
+    }
+}
+
+/// 按标识查找适配器
+pub fn get_task_adapter(id: &str) -> Option<Box<dyn ImageTaskAdapter>> {
+    match id.trim().to_ascii_lowercase().as_str() {
+        "flux" | "bfl" => Some(Box::new(FluxTaskAdapter)),
+        "midjourney" | "mj" | "midjourney-proxy" => Some(Box::new(MidjourneyTaskAdapter)),
+        _ => None,
+    }
+}
+
+// ==================== 取消信号 ====================
+
+lazy_static::lazy_static! {
+    static ref CANCEL_FLAGS: Mutex<HashMap<String, Arc<AtomicBool>>> = Mutex::new(HashMap::new());
+}
+
+// 持锁期间只做表操作，中毒后内容仍然可用
+fn cancel_flags() -> MutexGuard<'static, HashMap<String, Arc<AtomicBool>>> {
+    CANCEL_FLAGS.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// 注册取消信号；同一 request_id 的任务仍在运行时拒绝，避免覆盖其信号
+pub(crate) fn register_cancel_flag(request_id: &str) -> Result<Arc<AtomicBool>, String> {
+    match cancel_flags().entry(request_id.to_string()) {
+        Entry::Occupied(_) => Err(format!("任务 {} 正在运行", request_id)),
+        Entry::Vacant(entry) => Ok(entry.insert(Arc::new(AtomicBool::new(false))).clone()),
+    }
+}
+
+pub(crate) fn remove_cancel_flag(request_id: &str) {
+    cancel_flags().remove(request_id);
+}
+
+/// 请求取消任务，返回任务是否存在
+pub fn request_cancel(request_id: &str) -> bool {
+    match cancel_flags().get(request_id) {
+        Some(flag) => {
+            flag.store(true, Ordering::SeqCst);
+            true
+        }
+        None => false,
+    }
+}
+
+/// 等待指定时长，期间被取消则提前返回 false
+pub(crate) async fn sleep_unless_cancelled(duration: Duration, cancel: &AtomicBool) -> bool {
+    let deadline = Instant::now() + duration;
+    while Instant::now() < deadline {
+        if cancel.load(Ordering::SeqCst) {
+            return false;
+        }
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        tokio::time::sleep(remaining.min(Duration::from_millis(200))).await;
+    }
+    !cancel.load(Ordering::SeqCst)
+}
+
+// ==================== 公共工具 ====================
+
+/// 读取响应文本，非 2xx 时返回带状态码的错误
+pub(crate) async fn read_response_text(response: reqwest::Response) -> Result<String, String> {
+    let status = response.status();
+    let text = response
+        .text()
+        .await
+        .map_err(|e| format!("读取响应失败: {}", e))?;
+    if !status.is_success() {
+        return Err(format!("API 错误 ({}): {}", status, text));
+    }
+    Ok(text)
+}
+
+pub(crate) fn request_error(e: reqwest::Error) -> String {
+    if e.is_timeout() {
+        "请求超时".to_string()
+    } else if e.is_connect() {
+        "无法连接到服务器".to_string()
+    } else {
+        format!("请求失败: {}", e)
+    }
+}
+
+/// 下载结果图片（结果地址通常有时效，需要立即取回）
+pub(crate) async fn download_image(client: &Client, url: &str) -> Result<GeneratedImage, String> {
+    let response = client.get(url).send().await.map_err(request_error)?;
+    if !response.status().is_success() {
+        return Err(format!("下载图片失败 ({})", response.status()));
+    }
+    let bytes = response
+        .bytes()
+        .await
+        .map_err(|e| format!("读取图片数据失败: {}", e))?;
+    let (mime_type, _) = guess_image_mime(&bytes);
+    Ok(GeneratedImage {
+        data: Some(BASE64.encode(&bytes)),
+        mime_type: Some(mime_type.to_string()),
+        url: Some(url.to_string()),
+        seed: None,
+    })
+}
+
+/// 提交任务并轮询到结束，下载全部结果图片
+pub async fn run_image_task(
+    adapter: &dyn ImageTaskAdapter,
+    params: &ImageTaskParams,
+    cancel: &AtomicBool,
+    on_progress: &(dyn Fn(ImageTaskProgressEvent) + Send + Sync),
+) -> Result<ImageTaskRun, String> {
+    let client = Client::builder()
+        .timeout(Duration::from_secs(60))
+        .build()
+        .map_err(|e| format!("创建 HTTP 客户端失败: {}", e))?;
+    let deadline =
+        Instant::now() + Duration::from_secs(params.timeout_secs.unwrap_or(DEFAULT_TIMEOUT_SECS));
+    let interval = params
+        .poll_interval_ms
+        .map(|ms| Duration::from_millis(ms.max(MIN_POLL_INTERVAL_MS)))
+        .unwrap_or_else(|| adapter.poll_interval());
+    let request_id = params.request_id.clone().unwrap_or_default();
+    let event = |task_id: Option<&str>, status: &str, progress: Option<u32>| {
+        on_progress(ImageTaskProgressEvent {
+            request_id: request_id.clone(),
+            provider: adapter.id().to_string(),
+            task_id: task_id.map(|id| id.to_string()),
+            status: status.to_string(),
+            progress,
+        });
+    };
+
+    let task = adapter.submit(&client, params).await?;
+    println!(
+        "[Rust] Image task submitted: {} ({})",
+        task.task_id,
+        adapter.id()
+    );
+    event(Some(&task.task_id), "submitted", Some(0));
+
+    let (image_urls, seed) = loop {
+        if !sleep_unless_cancelled(interval, cancel).await {
+            if let Err(e) = adapter.cancel(&client, params, &task).await {
+                println!("[Rust] Failed to cancel image task remotely: {}", e);
+            }
+            return Err("任务已取消".to_string());
+        }
+        if Instant::now() >= deadline {
+            return Err(format!("任务超时（{}）", task.task_id));
+        }
+
+        match adapter.poll(&client, params, &task).await {
+            Ok(TaskPoll::Pending { status, progress }) => {
+                event(Some(&task.task_id), &status, progress);
+            }
+            Ok(TaskPoll::Succeeded { image_urls, seed }) => break (image_urls, seed),
+            Ok(TaskPoll::Failed(reason)) => return Err(reason),
+            // 单次查询失败不终止任务，等待下一轮
+            Err(e) => println!("[Rust] Image task poll failed: {}", e),
+        }
+    };
+
+    if image_urls.is_empty() {
+        return Err("任务完成但没有返回图片".to_string());
+    }
+    event(Some(&task.task_id), "downloading", Some(100));
+    let mut images = Vec::new();
+    for url in &image_urls {
+        images.push(download_image(&client, url).await?);
+    }
+    event(Some(&task.task_id), "completed", Some(100));
+
+    Ok(ImageTaskRun {
+        task_id: task.task_id,
+        seed: seed.or(params.seed),
+        images,
+    })
+}
+
+// Tauri 命令：执行异步图片任务，图片默认保存到本地
+#[tauri::command]
+pub async fn image_task_run(app: tauri::AppHandle, mut params: ImageTaskParams) -> ImageTaskResult {
+    println!(
+        "[Rust] image_task_run called, provider: {}",
+        params.provider
+    );
+
+    let adapter = match get_task_adapter(&params.provider) {
+        Some(adapter) => adapter,
+        None => {
+            return ImageTaskResult {
+                success: false,
+                error: Some(format!("不支持的图片任务服务: {}", params.provider)),
+                ..ImageTaskResult::default()
+            }
+        }
+    };
+
+    let request_id = params
+        .request_id
+        .get_or_insert_with(|| Uuid::new_v4().to_string())
+        .clone();
+    let cancel = match register_cancel_flag(&request_id) {
+        Ok(cancel) => cancel,
+        Err(e) => {
+            return ImageTaskResult {
+                success: false,
+                request_id: Some(request_id),
+                error: Some(e),
+                ..ImageTaskResult::default()
+            }
+        }
+    };
+    let emitter = app.clone();
+    let on_progress = move |event: ImageTaskProgressEvent| {
+        let _ = emitter.emit("image-task-progress", event);
+    };
+
+    let result = run_image_task(adapter.as_ref(), &params, &cancel, &on_progress).await;
+    remove_cancel_flag(&request_id);
+
+    let run = match result {
+        Ok(run) => run,
+        Err(e) => {
+            println!("[Rust] Image task failed: {}", e);
+            return ImageTaskResult {
+                success: false,
+                request_id: Some(request_id),
+                error: Some(e),
+                ..ImageTaskResult::default()
+            };
+        }
+    };
+
+    let mut saved_images = Vec::new();
+    if params.save_to_storage.unwrap_or(true) {
+        for image in &run.images {
+            let Some(data) = image.data.clone() else {
+                continue;
+            };
+            match save_image(
+                app.clone(),
+                data,
+                params.canvas_id.clone(),
+                params.node_id.clone(),
+                Some(params.prompt.clone()),
+                None,
+                Some(ImageType::Generated),
+                Some(GenerationParams {
+                    provider: Some(adapter.id().to_string()),
+                    model: params.model.clone(),
+                    seed: run.seed,
+                    width: params.width,
+                    height: params.height,
+                    extra: Some(serde_json::json!({ "taskId": run.task_id })),
+                    ..GenerationParams::default()
+                }),
+            ) {
+                Ok(info) => saved_images.push(info),
+                Err(e) => println!("[Rust] Failed to save image task output: {}", e),
+            }
+        }
+    }
+
+    ImageTaskResult {
+        success: true,
+        request_id: Some(request_id),
+        task_id: Some(run.task_id),
+        seed: run.seed,
+        images: run.images,
+        saved_images,
+        error: None,
+    }
+}
+
+// Tauri 命令：取消正在轮询的图片任务
+#[tauri::command]
+pub fn image_task_cancel(request_id: String) -> bool {
+    println!(
+        "[Rust] image_task_cancel called, request_id: {}",
+        request_id
+    );
+    request_cancel(&request_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::AtomicUsize;
+
+    // 第三次轮询才完成的模拟服务
+    struct MockAdapter {
+        polls: AtomicUsize,
+    }
+
+    #[async_trait]
+    impl ImageTaskAdapter for MockAdapter {
+        fn id(&self) -> &'static str {
+            "mock"
+        }
+
+        async fn submit(&self, _: &Client, _: &ImageTaskParams) -> Result<SubmittedTask, String> {
+            Ok(SubmittedTask {
+                task_id: "task-1".to_string(),
+                polling_url: None,
+            })
+        }
+
+        async fn poll(
+            &self,
+            _: &Client,
+            _: &ImageTaskParams,
+            _: &SubmittedTask,
+        ) -> Result<TaskPoll, String> {
+            match self.polls.fetch_add(1, Ordering::SeqCst) {
+                0 => Err("网络抖动".to_string()),
+                1 => Ok(TaskPoll::Pending {
+                    status: "running".to_string(),
+                    progress: Some(50),
+                }),
+                _ => Ok(TaskPoll::Failed("内容审核未通过".to_string())),
+            }
+        }
+    }
+
+    fn params() -> ImageTaskParams {
+        ImageTaskParams {
+            prompt: "a cat".to_string(),
+            request_id: Some("req".to_string()),
+            poll_interval_ms: Some(MIN_POLL_INTERVAL_MS),
+            ..ImageTaskParams::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_poll_errors_are_retried_until_terminal_state() {
+        let adapter = MockAdapter {
+            polls: AtomicUsize::new(0),
+        };
+        let events = Mutex::new(Vec::new());
+        let result = run_image_task(&adapter, &params(), &AtomicBool::new(false), &|e| {
+            events.lock().unwrap().push((e.status, e.progress))
+        })
+        .await;
+
+        assert_eq!(result.unwrap_err(), "内容审核未通过");
+        assert_eq!(adapter.polls.load(Ordering::SeqCst), 3);
+        let events = events.into_inner().unwrap();
+        assert_eq!(events[0], ("submitted".to_string(), Some(0)));
+        assert_eq!(events[1], ("running".to_string(), Some(50)));
+    }
+
+    #[tokio::test]
+    async fn test_cancel_stops_polling() {
+        let adapter = MockAdapter {
+            polls: AtomicUsize::new(0),
+        };
+        let result = run_image_task(&adapter, &params(), &AtomicBool::new(true), &|_| {}).await;
+        assert_eq!(result.unwrap_err(), "任务已取消");
+        assert_eq!(adapter.polls.load(Ordering::SeqCst), 0);
+    }
+
+    #[test]
+    fn test_duplicate_request_id_keeps_running_flag() {
+        let id = "test-duplicate-cancel-flag";
+        let flag = register_cancel_flag(id).unwrap();
+        assert!(register_cancel_flag(id).is_err());
+
+        // 锁中毒后仍能继续使用
+        let _ = std::thread::spawn(|| {
+            let _guard = CANCEL_FLAGS.lock().unwrap();
+            panic!("poison");
+        })
+        .join();
+        assert!(request_cancel(id));
+        assert!(flag.load(Ordering::SeqCst));
+
+        remove_cancel_flag(id);
+        assert!(!request_cancel(id));
+        assert!(register_cancel_flag(id).is_ok());
+        remove_cancel_flag(id);
+    }
+}
@@ -0,0 +1,281 @@
+//! 图片格式识别与上传前的规范化
+//!
+//! 所有图片服务共用：识别真实格式、去掉 data URL 前缀，
+//! 对服务不接受的格式（GIF、BMP、TIFF 等）在本地转码为 PNG 后再上传。
+
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use std::borrow::Cow;
+use std::io::Cursor;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImageKind {
+    Png,
+    Jpeg,
+    Webp,
+    Gif,
+    Bmp,
+    Tiff,
+    Heic,
+    Avif,
+}
+
+impl ImageKind {
+    pub fn mime_type(self) -> &'static str {
+        match self {
+            ImageKind::Png => "image/png",
+            ImageKind::Jpeg => "image/jpeg",
+            ImageKind::Webp => "image/webp",
+            ImageKind::Gif => "image/gif",
+            ImageKind::Bmp => "image/bmp",
+            ImageKind::Tiff => "image/tiff",
+            ImageKind::Heic => "image/heic",
+            ImageKind::Avif => "image/avif",
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            ImageKind::Png => "png",
+            ImageKind::Jpeg => "jpg",
+            ImageKind::Webp => "webp",
+            ImageKind::Gif => "gif",
+            ImageKind::Bmp => "bmp",
+            ImageKind::Tiff => "tiff",
+            ImageKind::Heic => "heic",
+            ImageKind::Avif => "avif",
+        }
+    }
+}
+
+// Gemini inlineData 支持的图片格式
+pub const GEMINI_INPUT_FORMATS: &[ImageKind] = &[
+    ImageKind::Png,
+    ImageKind::Jpeg,
+    ImageKind::Webp,
+    ImageKind::Heic,
+];
+
+// OpenAI Images 编辑接口支持的图片格式
+pub const OPENAI_INPUT_FORMATS: &[ImageKind] = &[ImageKind::Png, ImageKind::Jpeg, ImageKind::Webp];
+
+// Claude 支持的图片格式
+pub const CLAUDE_INPUT_FORMATS: &[ImageKind] = &[
+    ImageKind::Png,
+    ImageKind::Jpeg,
+    ImageKind::Gif,
+    ImageKind::Webp,
+];
+
+// 大多数视频服务的参考图只接受 PNG/JPEG
+pub const VIDEO_INPUT_FORMATS: &[ImageKind] = &[ImageKind::Png, ImageKind::Jpeg];
+
+/// 根据文件头识别图片格式
+pub fn sniff_image_kind(bytes: &[u8]) -> Option<ImageKind> {
+    if bytes.starts_with(b"\x89PNG\r\n\x1a\n") {
+        Some(ImageKind::Png)
+    } else if bytes.starts_with(b"\xff\xd8\xff") {
+        Some(ImageKind::Jpeg)
+    } else if bytes.len() >= 12 && &bytes[0..4] == b"RIFF" && &bytes[8..12] == b"WEBP" {
+        Some(ImageKind::Webp)
+    } else if bytes.starts_with(b"GIF87a") || bytes.starts_with(b"GIF89a") {
+        Some(ImageKind::Gif)
+    } else if bytes.starts_with(b"BM") && bytes.len() >= 14 {
+        Some(ImageKind::Bmp)
+    } else if bytes.starts_with(b"II*\0") || bytes.starts_with(b"MM\0*") {
+        Some(ImageKind::Tiff)
+    } else if bytes.len() >= 12 && &bytes[4..8] == b"ftyp" {
+        // ISO BMFF 容器，按 major brand 区分
+        match &bytes[8..12] {
+            b"avif" | b"avis" => Some(ImageKind::Avif),
+            b"heic" | b"heix" | b"hevc" | b"hevx" | b"heim" | b"heis" | b"mif1" | b"msf1" => {
+                Some(ImageKind::Heic)
+            }
+            _ => None,
+        }
+    } else {
+        None
+    }
+}
+
+/// 返回 (MIME 类型, 扩展名)，无法识别时按 PNG 处理
+pub fn guess_image_mime(bytes: &[u8]) -> (&'static str, &'static str) {
+    let kind = sniff_image_kind(bytes).unwrap_or(ImageKind::Png);
+    (kind.mime_type(), kind.extension())
+}
+
+/// 去掉 `data:image/...;base64,` 前缀以及首尾空白
+pub fn strip_data_url_prefix(base64_data: &str) -> &str {
+    let trimmed = base64_data.trim();
+    if trimmed.starts_with("data:") {
+        trimmed
+            .split_once(',')
+            .map(|(_, data)| data)
+            .unwrap_or(trimmed)
+            .trim()
+    } else {
+        trimmed
+    }
+}
+
+/// 解码 base64 图片（兼容 data URL 与换行）
+pub fn decode_base64_image(base64_data: &str) -> Result<Vec<u8>, String> {
+    let data = strip_data_url_prefix(base64_data);
+    let data: Cow<str> = if data.contains(char::is_whitespace) {
+        Cow::Owned(data.chars().filter(|c| !c.is_whitespace()).collect())
+    } else {
+        Cow::Borrowed(data)
+    };
+    BASE64
+        .decode(data.as_bytes())
+        .map_err(|e| format!("图片 base64 解码失败: {}", e))
+}
+
+// 规范化后的图片
+#[derive(Debug, Clone)]
+pub struct NormalizedImage {
+    pub bytes: Vec<u8>,
+    pub kind: ImageKind,
+    pub transcoded: bool,
+}
+
+impl NormalizedImage {
+    pub fn mime_type(&self) -> &'static str {
+        self.kind.mime_type()
+    }
+}
+
+/// 确保图片是服务可接受的格式，不接受时在本地转码为 PNG
+pub fn normalize_image_bytes(
+    bytes: Vec<u8>,
+    accepted: &[ImageKind],
+) -> Result<NormalizedImage, String> {
+    let kind = sniff_image_kind(&bytes);
+    if let Some(kind) = kind.filter(|k| accepted.contains(k)) {
+        return Ok(NormalizedImage {
+            bytes,
+            kind,
+            transcoded: false,
+        });
+    }
+
+    let image = image::load_from_memory(&bytes).map_err(|e| match kind {
+        Some(kind @ (ImageKind::Heic | ImageKind::Avif)) => format!(
+            "暂不支持 {} 格式图片，请先转换为 PNG 或 JPEG",
+            kind.extension().to_uppercase()
+        ),
+        _ => format!("无法识别的图片格式: {}", e),
+    })?;
+
+    let mut output = Vec::new();
+    image
+        .write_to(&mut Cursor::new(&mut output), image::ImageFormat::Png)
+        .map_err(|e| format!("图片转码失败: {}", e))?;
+
+    println!(
+        "[Rust] Transcoded {} image to PNG ({} -> {} bytes)",
+        kind.map(|k| k.extension()).unwrap_or("unknown"),
+        bytes.len(),
+        output.len()
+    );
+
+    Ok(NormalizedImage {
+        bytes: output,
+        kind: ImageKind::Png,
+        transcoded: true,
+    })
+}
+
+/// 解码 base64 并规范化，返回 (MIME 类型, 可直接上传的 base64)
+pub fn normalize_base64_image(
+    base64_data: &str,
+    accepted: &[ImageKind],
+) -> Result<(&'static str, String), String> {
+    let bytes = decode_base64_image(base64_data)?;
+    let normalized = normalize_image_bytes(bytes, accepted)?;
+    let data = if normalized.transcoded {
+        BASE64.encode(&normalized.bytes)
+    } else {
+        // 未转码时沿用原始数据，避免重复编码
+        strip_data_url_prefix(base64_data)
+            .chars()
+            .filter(|c| !c.is_whitespace())
+            .collect()
+    };
+    Ok((normalized.mime_type(), data))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{ImageFormat, Rgba, RgbaImage};
+
+    fn encode(format: ImageFormat) -> Vec<u8> {
+        let image = RgbaImage::from_pixel(4, 4, Rgba([200, 100, 50, 255]));
+        let mut bytes = Vec::new();
+        image::DynamicImage::ImageRgba8(image)
+            .to_rgb8()
+            .write_to(&mut Cursor::new(&mut bytes), format)
+            .unwrap();
+        bytes
+    }
+
+    #[test]
+    fn test_sniff_image_kind() {
+        assert_eq!(
+            sniff_image_kind(&encode(ImageFormat::Png)),
+            Some(ImageKind::Png)
+        );
+        assert_eq!(
+            sniff_image_kind(&encode(ImageFormat::Jpeg)),
+            Some(ImageKind::Jpeg)
+        );
+        assert_eq!(
+            sniff_image_kind(&encode(ImageFormat::Gif)),
+            Some(ImageKind::Gif)
+        );
+        assert_eq!(
+            sniff_image_kind(&encode(ImageFormat::Bmp)),
+            Some(ImageKind::Bmp)
+        );
+        assert_eq!(
+            sniff_image_kind(&encode(ImageFormat::Tiff)),
+            Some(ImageKind::Tiff)
+        );
+        assert_eq!(
+            sniff_image_kind(b"\0\0\0\x18ftypheic\0\0\0\0"),
+            Some(ImageKind::Heic)
+        );
+        assert_eq!(sniff_image_kind(b"not an image"), None);
+    }
+
+    #[test]
+    fn test_strip_data_url_prefix() {
+        assert_eq!(strip_data_url_prefix("data:image/png;base64,QUJD"), "QUJD");
+        assert_eq!(strip_data_url_prefix("  QUJD\n"), "QUJD");
+        assert_eq!(
+            decode_base64_image("data:image/png;base64,QU\nJD").unwrap(),
+            b"ABC"
+        );
+    }
+
+    #[test]
+    fn test_normalize_transcodes_unsupported_formats() {
+        let gif = encode(ImageFormat::Gif);
+        let normalized = normalize_image_bytes(gif, OPENAI_INPUT_FORMATS).unwrap();
+        assert!(normalized.transcoded);
+        assert_eq!(normalized.kind, ImageKind::Png);
+
+        let jpeg = encode(ImageFormat::Jpeg);
+        let normalized = normalize_image_bytes(jpeg.clone(), GEMINI_INPUT_FORMATS).unwrap();
+        assert!(!normalized.transcoded);
+        assert_eq!(normalized.bytes, jpeg);
+
+        let (mime, data) = normalize_base64_image(
+            &format!("data:image/jpeg;base64,{}", BASE64.encode(&jpeg)),
+            GEMINI_INPUT_FORMATS,
+        )
+        .unwrap();
+        assert_eq!(mime, "image/jpeg");
+        assert_eq!(data, BASE64.encode(&jpeg));
+    }
+}
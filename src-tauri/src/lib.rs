@@ -1,16 +1,50 @@
+mod background_removal;
+mod comfyui;
 mod dalle;
+mod describe;
+mod embedded_metadata;
+mod fallback;
+mod framing;
 mod gemini;
+mod gemini_session;
+mod image_ops;
+mod image_preprocess;
+mod image_provider;
+mod image_task;
+mod image_utils;
 mod llm;
+mod outpaint;
+mod sd_webui;
 mod storage;
+#[cfg(test)]
+mod test_support;
 mod text_removal;
+mod upscale;
 mod video;
+mod video_provider;
+mod video_task;
 
+use background_removal::remove_background;
+use comfyui::*;
 use dalle::*;
+use describe::describe_image;
+use fallback::{generate_image_with_fallback, llm_generate_with_fallback};
+use framing::normalize_framing;
 use gemini::*;
+use gemini_session::*;
+use image_ops::apply_image_ops;
+use image_preprocess::preprocess_image_for_provider;
+use image_provider::{compare_generate, generate_image, generate_variations};
+use image_task::{image_task_cancel, image_task_run};
 use llm::*;
+use outpaint::outpaint_image;
+use sd_webui::sd_webui_generate;
 use storage::*;
 use text_removal::*;
+use upscale::upscale_image;
 use video::*;
+use video_provider::{video_generate, video_generate_content, video_generate_status};
+use video_task::{list_video_tasks, resume_video_tasks, video_task_cancel, video_task_start};
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
@@ -19,10 +53,18 @@ pub fn run() {
         .plugin(tauri_plugin_dialog::init())
         .plugin(tauri_plugin_fs::init())
         .plugin(tauri_plugin_store::Builder::default().build())
+        .setup(|app| {
+            // 恢复上次退出时仍在进行的视频任务
+            resume_video_tasks(app.handle());
+            Ok(())
+        })
         .invoke_handler(tauri::generate_handler![
             save_image,
             read_image,
             read_image_metadata,
+            read_embedded_metadata,
+            export_image,
+            export_video,
             delete_image,
             delete_canvas_images,
             get_storage_stats,
@@ -32,6 +74,11 @@ pub fn run() {
             list_canvas_images,
             gemini_generate_content,
             gemini_generate_text,
+            // Gemini 多轮编辑会话
+            gemini_session_create,
+            gemini_session_continue,
+            gemini_session_branch,
+            gemini_session_discard,
             // LLM 代理命令
             openai_chat_completion,
             openai_responses,
@@ -53,6 +100,32 @@ pub fn run() {
             kling_download_video,
             // DALL-E 图片生成命令
             dalle_generate_image,
+            // 统一图片生成入口
+            generate_image,
+            preprocess_image_for_provider,
+            // ComfyUI 本地服务
+            comfyui_generate,
+            comfyui_cancel,
+            sd_webui_generate,
+            image_task_run,
+            image_task_cancel,
+            outpaint_image,
+            apply_image_ops,
+            upscale_image,
+            remove_background,
+            describe_image,
+            compare_generate,
+            list_comparison_groups,
+            generate_image_with_fallback,
+            llm_generate_with_fallback,
+            normalize_framing,
+            generate_variations,
+            video_task_start,
+            video_task_cancel,
+            list_video_tasks,
+            video_generate,
+            video_generate_status,
+            video_generate_content,
             // 文字去除功能（本地化）
             remove_text_from_image,
             detect_text_regions,
@@ -2,10 +2,12 @@ use reqwest::Client;
 use serde::{Deserialize, Serialize};
 use std::time::Duration;
 
+use crate::image_preprocess::{preprocess_base64_image, ImageLimits};
+
 // ==================== 通用数据结构 ====================
 
 // 文件数据结构（用于多模态输入）
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct FileData {
     pub data: String,      // base64 编码的文件数据
@@ -15,7 +17,7 @@ pub struct FileData {
 }
 
 // LLM 请求参数（前端传入）
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct LLMRequestParams {
     pub base_url: String,
@@ -39,6 +41,21 @@ pub struct LLMResult {
     pub error: Option<String>,
 }
 
+// 按服务限制预处理图片文件，返回 (MIME 类型, base64)，失败时沿用原始数据
+async fn prepare_image_file(file: &FileData, provider: &str) -> (String, String) {
+    match preprocess_base64_image(file.data.clone(), ImageLimits::for_provider(provider)).await {
+        Ok(prepared) if prepared.report.changed() => {
+            println!("[Rust] Image file adjusted: {}", prepared.report.summary());
+            (prepared.mime_type().to_string(), prepared.to_base64())
+        }
+        Ok(prepared) => (prepared.mime_type().to_string(), file.data.clone()),
+        Err(e) => {
+            println!("[Rust] Failed to prepare image file: {}", e);
+            (file.mime_type.clone(), file.data.clone())
+        }
+    }
+}
+
 // ==================== OpenAI 协议结构 ====================
 
 #[derive(Debug, Serialize)]
@@ -449,9 +466,10 @@ pub async fn openai_chat_completion(params: LLMRequestParams) -> LLMResult {
             }];
             for file in files {
                 if file.mime_type.starts_with("image/") {
+                    let (mime_type, data) = prepare_image_file(file, "openai").await;
                     parts.push(OpenAIContentPart::ImageUrl {
                         image_url: OpenAIImageUrl {
-                            url: format!("data:{};base64,{}", file.mime_type, file.data),
+                            url: format!("data:{};base64,{}", mime_type, data),
                         },
                     });
                 }
@@ -656,8 +674,9 @@ pub async fn openai_responses(params: LLMRequestParams) -> LLMResult {
     if let Some(files) = &params.files {
         for file in files {
             if file.mime_type.starts_with("image/") {
+                let (mime_type, data) = prepare_image_file(file, "openai").await;
                 content.push(OpenAIResponsesInputContent::InputImage {
-                    image_url: format!("data:{};base64,{}", file.mime_type, file.data),
+                    image_url: format!("data:{};base64,{}", mime_type, data),
                 });
             }
         }
@@ -891,11 +910,12 @@ pub async fn claude_chat_completion(params: LLMRequestParams) -> LLMResult {
             let mut parts: Vec<ClaudeContentPart> = Vec::new();
             for file in files {
                 if file.mime_type.starts_with("image/") {
+                    let (media_type, data) = prepare_image_file(file, "claude").await;
                     parts.push(ClaudeContentPart::Image {
                         source: ClaudeImageSource {
                             source_type: "base64".to_string(),
-                            media_type: file.mime_type.clone(),
-                            data: file.data.clone(),
+                            media_type,
+                            data,
                         },
                     });
                 }
@@ -0,0 +1,492 @@
+//! 扩图（outpainting）
+//!
+//! 把原图按锚点放进更大的画布，自动生成扩展区域的蒙版，
+//! 交给支持蒙版的图片服务（OpenAI 图片编辑或 Gemini 编辑提示词）补全，
+//! 最后把原图像素贴回结果，保证原有内容不被改动。
+
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use image::imageops::FilterType;
+use image::{DynamicImage, GenericImageView, ImageFormat, Rgba, RgbaImage};
+use serde::{Deserialize, Serialize};
+use std::io::Cursor;
+
+use crate::image_provider::{get_provider, run_image_request, ImageGenerationRequest};
+use crate::image_utils::decode_base64_image;
+use crate::storage::{save_image, GenerationParams, ImageInfo, ImageType, InputImageInfo};
+use crate::text_removal::mask_generator::generate_outpaint_mask;
+
+const DEFAULT_OVERLAP: u32 = 16;
+const MAX_CANVAS_DIMENSION: u32 = 8192;
+const OUTPAINT_INSTRUCTION: &str =
+    "自然地向外扩展画面，延续原图的内容、透视、光照和风格，不要出现边框、留白或接缝。";
+
+// 前端调用的参数
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OutpaintParams {
+    pub provider: String, // 需支持蒙版，如 "openai"、"gemini"
+    pub base_url: String,
+    pub api_key: String,
+    pub model: String,
+    pub prompt: Option<String>, // 对扩展内容的描述
+    pub image_data: String,     // base64 原图
+    pub target_width: Option<u32>,
+    pub target_height: Option<u32>,
+    pub aspect_ratio: Option<String>, // 未指定目标尺寸时按比例扩展，如 "16:9"
+    pub anchor: Option<String>,       // 原图位置，默认 "center"，如 "top-left"、"bottom"
+    pub overlap: Option<u32>,         // 原图边缘允许重绘并羽化的像素数
+    pub seed: Option<i64>,
+    pub extras: Option<serde_json::Value>, // 透传给图片服务
+    pub source_path: Option<String>,       // 原图路径，记录到元数据
+    pub canvas_id: Option<String>,
+    pub node_id: Option<String>,
+    pub save_to_storage: Option<bool>, // 默认保存
+}
+
+// 原图在画布中的位置
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OutpaintLayout {
+    pub canvas_width: u32,
+    pub canvas_height: u32,
+    pub offset_x: u32,
+    pub offset_y: u32,
+    pub source_width: u32,
+    pub source_height: u32,
+}
+
+impl OutpaintLayout {
+    fn pads_left(&self) -> bool {
+        self.offset_x > 0
+    }
+
+    fn pads_right(&self) -> bool {
+        self.offset_x + self.source_width < self.canvas_width
+    }
+
+    fn pads_top(&self) -> bool {
+        self.offset_y > 0
+    }
+
+    fn pads_bottom(&self) -> bool {
+        self.offset_y + self.source_height < self.canvas_height
+    }
+
+    /// 实际使用的重叠带宽度，不超过原图短边的四分之一
+    fn effective_overlap(&self, overlap: u32) -> u32 {
+        overlap
+            .min(self.source_width / 4)
+            .min(self.source_height / 4)
+    }
+
+    /// 不需要重绘的区域：原图去掉扩展一侧的重叠带
+    fn keep_rect(&self, overlap: u32) -> (u32, u32, u32, u32) {
+        let overlap = self.effective_overlap(overlap);
+        let left = if self.pads_left() { overlap } else { 0 };
+        let right = if self.pads_right() { overlap } else { 0 };
+        let top = if self.pads_top() { overlap } else { 0 };
+        let bottom = if self.pads_bottom() { overlap } else { 0 };
+        (
+            self.offset_x + left,
+            self.offset_y + top,
+            self.source_width - left - right,
+            self.source_height - top - bottom,
+        )
+    }
+}
+
+// 返回结果
+#[derive(Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OutpaintResult {
+    pub success: bool,
+    pub image_data: Option<String>, // 合成后的 PNG（base64）
+    pub mask_data: Option<String>,  // 黑白蒙版，白色为重绘区域
+    pub layout: Option<OutpaintLayout>,
+    pub saved_image: Option<ImageInfo>,
+    pub error: Option<String>,
+}
+
+impl OutpaintResult {
+    fn failure(error: impl Into<String>) -> Self {
+        Self {
+            success: false,
+            error: Some(error.into()),
+            ..Self::default()
+        }
+    }
+}
+
+/// 解析 "16:9"、"16/9" 或 "1.78" 形式的比例
+pub fn parse_aspect_ratio(text: &str) -> Option<f64> {
+    let text = text.trim();
+    let ratio = match text.split_once([':', '/', 'x']) {
+        Some((w, h)) => w.trim().parse::<f64>().ok()? / h.trim().parse::<f64>().ok()?,
+        None => text.parse::<f64>().ok()?,
+    };
+    (ratio.is_finite() && ratio > 0.0).then_some(ratio)
+}
+
+/// 计算画布尺寸与原图位置
+pub fn compute_layout(
+    source: (u32, u32),
+    target: Option<(u32, u32)>,
+    aspect_ratio: Option<f64>,
+    anchor: &str,
+) -> Result<OutpaintLayout, String> {
+    let (sw, sh) = source;
+    let (cw, ch) = match (target, aspect_ratio) {
+        (Some((w, h)), _) => {
+            if w < sw || h < sh {
+                return Err(format!("目标尺寸 {}x{} 不能小于原图 {}x{}", w, h, sw, sh));
+            }
+            (w, h)
+        }
+        // 保持原图一边不变，扩展另一边到目标比例
+        (None, Some(ratio)) => {
+            if (sw as f64 / sh as f64) < ratio {
+                (((sh as f64) * ratio).round() as u32, sh)
+            } else {
+                (sw, ((sw as f64) / ratio).round() as u32)
+            }
+        }
+        (None, None) => return Err("需要指定目标尺寸或比例".to_string()),
+    };
+    if cw > MAX_CANVAS_DIMENSION || ch > MAX_CANVAS_DIMENSION {
+        return Err(format!("画布尺寸过大: {}x{}", cw, ch));
+    }
+    if (cw, ch) == (sw, sh) {
+        return Err("目标尺寸与原图相同，无需扩展".to_string());
+    }
+
+    let anchor = anchor.trim().to_ascii_lowercase();
+    let offset_x = if anchor.contains("left") {
+        0
+    } else if anchor.contains("right") {
+        cw - sw
+    } else {
+        (cw - sw) / 2
+    };
+    let offset_y = if anchor.contains("top") {
+        0
+    } else if anchor.contains("bottom") {
+        ch - sh
+    } else {
+        (ch - sh) / 2
+    };
+
+    Ok(OutpaintLayout {
+        canvas_width: cw,
+        canvas_height: ch,
+        offset_x,
+        offset_y,
+        source_width: sw,
+        source_height: sh,
+    })
+}
+
+fn encode_png(image: &DynamicImage) -> Result<Vec<u8>, String> {
+    let mut output = Vec::new();
+    image
+        .write_to(&mut Cursor::new(&mut output), ImageFormat::Png)
+        .map_err(|e| format!("PNG 编码失败: {}", e))?;
+    Ok(output)
+}
+
+// 发送给服务的输入（均为 PNG）
+struct OutpaintInputs {
+    canvas: Vec<u8>,
+    provider_mask: Vec<u8>, // 按服务约定编码的蒙版
+    mask: Vec<u8>,          // 黑白蒙版，白色为重绘区域
+}
+
+/// 生成发送给服务的画布与蒙版
+/// OpenAI 的蒙版以透明区域表示可编辑，其余服务使用白色为修改区域的黑白图
+fn build_inputs(
+    source: &DynamicImage,
+    layout: &OutpaintLayout,
+    overlap: u32,
+    alpha_mask: bool,
+) -> Result<OutpaintInputs, String> {
+    let mut canvas = RgbaImage::from_pixel(
+        layout.canvas_width,
+        layout.canvas_height,
+        Rgba([128, 128, 128, 255]),
+    );
+    image::imageops::overlay(
+        &mut canvas,
+        &source.to_rgba8(),
+        layout.offset_x as i64,
+        layout.offset_y as i64,
+    );
+
+    let mask = generate_outpaint_mask(
+        layout.canvas_width,
+        layout.canvas_height,
+        layout.keep_rect(overlap),
+    );
+    let provider_mask = if alpha_mask {
+        let rgba = RgbaImage::from_fn(mask.width(), mask.height(), |x, y| {
+            let editable = mask.get_pixel(x, y)[0] > 0;
+            Rgba([0, 0, 0, if editable { 0 } else { 255 }])
+        });
+        DynamicImage::ImageRgba8(rgba)
+    } else {
+        DynamicImage::ImageLuma8(mask.clone())
+    };
+
+    Ok(OutpaintInputs {
+        canvas: encode_png(&DynamicImage::ImageRgba8(canvas))?,
+        provider_mask: encode_png(&provider_mask)?,
+        mask: encode_png(&DynamicImage::ImageLuma8(mask))?,
+    })
+}
+
+/// 把原图贴回生成结果，扩展一侧的重叠带做线性羽化
+pub fn composite(
+    source: &DynamicImage,
+    generated: &DynamicImage,
+    layout: &OutpaintLayout,
+    overlap: u32,
+) -> RgbaImage {
+    let (cw, ch) = (layout.canvas_width, layout.canvas_height);
+    // 服务返回的比例可能略有不同，按覆盖方式缩放裁切到画布
+    let mut output = if generated.dimensions() == (cw, ch) {
+        generated.to_rgba8()
+    } else {
+        generated
+            .resize_to_fill(cw, ch, FilterType::Lanczos3)
+            .to_rgba8()
+    };
+
+    let source = source.to_rgba8();
+    let (keep_x, keep_y, keep_w, keep_h) = layout.keep_rect(overlap);
+    let feather = layout.effective_overlap(overlap).max(1) as f32;
+
+    for (x, y, pixel) in source.enumerate_pixels() {
+        let (cx, cy) = (x + layout.offset_x, y + layout.offset_y);
+        // 到扩展边的距离，未扩展的一侧不参与计算
+        let mut distance = u32::MAX;
+        if layout.pads_left() {
+            distance = distance.min(cx.saturating_sub(layout.offset_x));
+        }
+        if layout.pads_top() {
+            distance = distance.min(cy.saturating_sub(layout.offset_y));
+        }
+        if layout.pads_right() {
+            distance = distance.min((layout.offset_x + layout.source_width - 1).saturating_sub(cx));
+        }
+        if layout.pads_bottom() {
+            distance =
+                distance.min((layout.offset_y + layout.source_height - 1).saturating_sub(cy));
+        }
+        let inside_keep =
+            cx >= keep_x && cx < keep_x + keep_w && cy >= keep_y && cy < keep_y + keep_h;
+        let weight = if inside_keep {
+            1.0
+        } else {
+            ((distance as f32 + 0.5) / feather).min(1.0)
+        };
+
+        let target = output.get_pixel_mut(cx, cy);
+        for channel in 0..4 {
+            target[channel] = (pixel[channel] as f32 * weight
+                + target[channel] as f32 * (1.0 - weight))
+                .round() as u8;
+        }
+    }
+    output
+}
+
+fn is_alpha_mask_provider(provider: &str) -> bool {
+    get_provider(provider).is_some_and(|p| p.id() == "openai")
+}
+
+// Tauri 命令：扩图
+#[tauri::command]
+pub async fn outpaint_image(app: tauri::AppHandle, params: OutpaintParams) -> OutpaintResult {
+    println!(
+        "[Rust] outpaint_image called, provider: {}, model: {}",
+        params.provider, params.model
+    );
+
+    let source_bytes = match decode_base64_image(&params.image_data) {
+        Ok(bytes) => bytes,
+        Err(e) => return OutpaintResult::failure(e),
+    };
+    let target = params.target_width.zip(params.target_height);
+    let aspect_ratio = params.aspect_ratio.as_deref().and_then(parse_aspect_ratio);
+    let anchor = params
+        .anchor
+        .clone()
+        .unwrap_or_else(|| "center".to_string());
+    let overlap = params.overlap.unwrap_or(DEFAULT_OVERLAP);
+    let alpha_mask = is_alpha_mask_provider(&params.provider);
+
+    // 解码与画布构建放到阻塞线程
+    let prepared = tokio::task::spawn_blocking(move || {
+        let source =
+            image::load_from_memory(&source_bytes).map_err(|e| format!("原图解码失败: {}", e))?;
+        let layout = compute_layout(source.dimensions(), target, aspect_ratio, &anchor)?;
+        let inputs = build_inputs(&source, &layout, overlap, alpha_mask)?;
+        Ok::<_, String>((source, layout, inputs))
+    })
+    .await
+    .map_err(|e| format!("扩图预处理失败: {}", e))
+    .and_then(|result| result);
+    let (source, layout, inputs) = match prepared {
+        Ok(prepared) => prepared,
+        Err(e) => return OutpaintResult::failure(e),
+    };
+    println!(
+        "[Rust] Outpaint canvas: {}x{} -> {}x{} at ({}, {})",
+        layout.source_width,
+        layout.source_height,
+        layout.canvas_width,
+        layout.canvas_height,
+        layout.offset_x,
+        layout.offset_y
+    );
+
+    let prompt = match params.prompt.as_deref().map(str::trim) {
+        Some(prompt) if !prompt.is_empty() => format!("{}\n\n{}", prompt, OUTPAINT_INSTRUCTION),
+        _ => OUTPAINT_INSTRUCTION.to_string(),
+    };
+    let mut extras = params
+        .extras
+        .clone()
+        .unwrap_or_else(|| serde_json::json!({}));
+    if alpha_mask {
+        if let Some(map) = extras.as_object_mut() {
+            map.entry("operation").or_insert("edit".into());
+        }
+    }
+    let request = ImageGenerationRequest {
+        provider: params.provider.clone(),
+        base_url: params.base_url.clone(),
+        api_key: params.api_key.clone(),
+        model: params.model.clone(),
+        prompt: prompt.clone(),
+        input_images: vec![BASE64.encode(&inputs.canvas)],
+        mask_image: Some(BASE64.encode(&inputs.provider_mask)),
+        aspect_ratio: params.aspect_ratio.clone(),
+        n: Some(1),
+        seed: params.seed,
+        extras: Some(extras),
+        ..ImageGenerationRequest::default()
+    };
+    let generated = run_image_request(&request).await;
+    if !generated.success {
+        return OutpaintResult::failure(generated.error.unwrap_or_else(|| "扩图失败".to_string()));
+    }
+    let seed = generated.seed.or(params.seed);
+    let generated_data = match generated.images.into_iter().find_map(|image| image.data) {
+        Some(data) => data,
+        None => return OutpaintResult::failure("图片服务没有返回图片数据"),
+    };
+
+    let composited = tokio::task::spawn_blocking(move || {
+        let bytes = decode_base64_image(&generated_data)?;
+        let generated =
+            image::load_from_memory(&bytes).map_err(|e| format!("生成结果解码失败: {}", e))?;
+        let output = composite(&source, &generated, &layout, overlap);
+        encode_png(&DynamicImage::ImageRgba8(output))
+    })
+    .await
+    .map_err(|e| format!("合成失败: {}", e))
+    .and_then(|result| result);
+    let image_data = match composited {
+        Ok(bytes) => BASE64.encode(bytes),
+        Err(e) => return OutpaintResult::failure(e),
+    };
+
+    let mut saved_image = None;
+    if params.save_to_storage.unwrap_or(true) {
+        let input_images = params.source_path.clone().map(|path| {
+            vec![InputImageInfo {
+                path: Some(path),
+                label: "原图".to_string(),
+            }]
+        });
+        match save_image(
+            app,
+            image_data.clone(),
+            params.canvas_id.clone(),
+            params.node_id.clone(),
+            Some(prompt),
+            input_images,
+            Some(ImageType::Generated),
+            Some(GenerationParams {
+                provider: Some(params.provider.clone()),
+                model: Some(params.model.clone()),
+                seed,
+                width: Some(layout.canvas_width),
+                height: Some(layout.canvas_height),
+                extra: Some(serde_json::json!({
+                    "operation": "outpaint",
+                    "anchor": params.anchor.clone().unwrap_or_else(|| "center".to_string()),
+                    "offsetX": layout.offset_x,
+                    "offsetY": layout.offset_y,
+                    "sourceWidth": layout.source_width,
+                    "sourceHeight": layout.source_height,
+                })),
+                ..GenerationParams::default()
+            }),
+        ) {
+            Ok(info) => saved_image = Some(info),
+            Err(e) => println!("[Rust] Failed to save outpaint result: {}", e),
+        }
+    }
+
+    OutpaintResult {
+        success: true,
+        image_data: Some(image_data),
+        mask_data: Some(BASE64.encode(&inputs.mask)),
+        layout: Some(layout),
+        saved_image,
+        error: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compute_layout_with_aspect_ratio_and_anchor() {
+        let layout = compute_layout((100, 100), None, parse_aspect_ratio("16:9"), "left").unwrap();
+        assert_eq!((layout.canvas_width, layout.canvas_height), (178, 100));
+        assert_eq!((layout.offset_x, layout.offset_y), (0, 0));
+
+        let layout = compute_layout((100, 100), None, Some(0.5), "bottom").unwrap();
+        assert_eq!((layout.canvas_width, layout.canvas_height), (100, 200));
+        assert_eq!(layout.offset_y, 100);
+
+        let layout = compute_layout((100, 50), Some((200, 100)), None, "center").unwrap();
+        assert_eq!((layout.offset_x, layout.offset_y), (50, 25));
+
+        assert!(compute_layout((100, 100), Some((80, 200)), None, "center").is_err());
+    }
+
+    #[test]
+    fn test_keep_rect_only_shrinks_padded_sides() {
+        let layout = compute_layout((100, 100), Some((150, 100)), None, "left").unwrap();
+        assert_eq!(layout.keep_rect(10), (0, 0, 90, 100));
+    }
+
+    #[test]
+    fn test_composite_preserves_original_pixels() {
+        let source =
+            DynamicImage::ImageRgba8(RgbaImage::from_pixel(40, 40, Rgba([255, 0, 0, 255])));
+        let generated =
+            DynamicImage::ImageRgba8(RgbaImage::from_pixel(80, 40, Rgba([0, 0, 255, 255])));
+        let layout = compute_layout((40, 40), Some((80, 40)), None, "left").unwrap();
+        let output = composite(&source, &generated, &layout, 8);
+
+        assert_eq!(output.get_pixel(5, 20), &Rgba([255, 0, 0, 255]));
+        assert_eq!(output.get_pixel(70, 20), &Rgba([0, 0, 255, 255]));
+        // 重叠带内渐变
+        let blended = output.get_pixel(36, 20);
+        assert!(blended[0] > 0 && blended[2] > 0);
+    }
+}
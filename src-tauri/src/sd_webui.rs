@@ -0,0 +1,497 @@
+//! Stable Diffusion WebUI（A1111 / Forge）API
+//!
+//! 调用 `/sdapi/v1/txt2img` 与 `/sdapi/v1/img2img`，有输入图片时走图生图，
+//! 附带蒙版时即为局部重绘。返回的 `info` 会解析成 `GenerationParams`
+//! 随图片一起写入元数据。
+
+use reqwest::{Client, RequestBuilder};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+use std::time::Duration;
+
+use crate::image_preprocess::read_dimensions;
+use crate::image_utils::{decode_base64_image, guess_image_mime, strip_data_url_prefix};
+use crate::storage::{save_image, GenerationParams, ImageInfo, ImageType};
+
+const DEFAULT_TIMEOUT_SECS: u64 = 600;
+
+// 前端调用的参数
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SdWebUIParams {
+    pub base_url: String,
+    pub api_key: Option<String>, // "user:pass" 走 --api-auth 的 Basic 认证，否则作为 Bearer
+    pub model: Option<String>,   // 临时切换的 checkpoint
+    pub prompt: String,
+    pub negative_prompt: Option<String>,
+    pub sampler_name: Option<String>,
+    pub scheduler: Option<String>,
+    pub steps: Option<u32>,
+    pub cfg_scale: Option<f64>,
+    pub seed: Option<i64>, // 为空或 -1 时随机
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    pub batch_size: Option<u32>,
+    pub denoising_strength: Option<f64>,
+    pub init_images: Option<Vec<String>>, // base64，非空时走 img2img
+    pub mask_image: Option<String>,       // 白色为重绘区域
+    pub mask_blur: Option<u32>,
+    pub inpainting_fill: Option<u32>, // 0 fill / 1 original / 2 latent noise / 3 latent nothing
+    pub inpaint_full_res: Option<bool>, // 仅重绘蒙版区域
+    pub inpaint_full_res_padding: Option<u32>,
+    pub inpainting_mask_invert: Option<bool>,
+    pub resize_mode: Option<u32>,
+    pub extras: Option<Value>, // 原样合并进请求体，如 alwayson_scripts
+    pub timeout_secs: Option<u64>,
+    pub canvas_id: Option<String>,
+    pub node_id: Option<String>,
+    pub save_to_storage: Option<bool>, // 默认保存
+}
+
+// 单张输出
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SdWebUIImage {
+    pub data: String, // base64
+    pub mime_type: String,
+    pub generation: GenerationParams,
+    pub infotext: Option<String>,
+}
+
+// 返回结果
+#[derive(Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SdWebUIResult {
+    pub success: bool,
+    pub images: Vec<SdWebUIImage>,
+    pub saved_images: Vec<ImageInfo>,
+    pub info: Option<Value>, // 原始 info
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct SdWebUIResponse {
+    #[serde(default)]
+    images: Vec<String>,
+    info: Option<String>,
+}
+
+fn non_empty_images(params: &SdWebUIParams) -> Vec<String> {
+    params
+        .init_images
+        .iter()
+        .flatten()
+        .filter(|image| !image.trim().is_empty())
+        .map(|image| strip_data_url_prefix(image).to_string())
+        .collect()
+}
+
+fn insert_opt<T: Serialize>(body: &mut Map<String, Value>, key: &str, value: &Option<T>) {
+    if let Some(value) = value {
+        body.insert(key.to_string(), json!(value));
+    }
+}
+
+/// 构建请求体，返回 (接口路径, 请求体)
+fn build_request_body(params: &SdWebUIParams) -> (&'static str, Value) {
+    let mut body = Map::new();
+    body.insert("prompt".to_string(), json!(params.prompt));
+    body.insert(
+        "negative_prompt".to_string(),
+        json!(params.negative_prompt.clone().unwrap_or_default()),
+    );
+    body.insert("seed".to_string(), json!(params.seed.unwrap_or(-1)));
+    body.insert(
+        "batch_size".to_string(),
+        json!(params.batch_size.unwrap_or(1)),
+    );
+    insert_opt(&mut body, "sampler_name", &params.sampler_name);
+    insert_opt(&mut body, "scheduler", &params.scheduler);
+    insert_opt(&mut body, "steps", &params.steps);
+    insert_opt(&mut body, "cfg_scale", &params.cfg_scale);
+    insert_opt(&mut body, "width", &params.width);
+    insert_opt(&mut body, "height", &params.height);
+    insert_opt(&mut body, "denoising_strength", &params.denoising_strength);
+    if let Some(model) = params.model.as_ref().filter(|m| !m.trim().is_empty()) {
+        body.insert(
+            "override_settings".to_string(),
+            json!({ "sd_model_checkpoint": model }),
+        );
+        body.insert(
+            "override_settings_restore_afterwards".to_string(),
+            json!(true),
+        );
+    }
+
+    let init_images = non_empty_images(params);
+    let endpoint = if init_images.is_empty() {
+        "txt2img"
+    } else {
+        body.insert("init_images".to_string(), json!(init_images));
+        insert_opt(&mut body, "resize_mode", &params.resize_mode);
+        if let Some(mask) = params.mask_image.as_ref().filter(|m| !m.trim().is_empty()) {
+            body.insert("mask".to_string(), json!(strip_data_url_prefix(mask)));
+            insert_opt(&mut body, "mask_blur", &params.mask_blur);
+            insert_opt(&mut body, "inpainting_fill", &params.inpainting_fill);
+            insert_opt(&mut body, "inpaint_full_res", &params.inpaint_full_res);
+            insert_opt(
+                &mut body,
+                "inpaint_full_res_padding",
+                &params.inpaint_full_res_padding,
+            );
+            if let Some(invert) = params.inpainting_mask_invert {
+                body.insert(
+                    "inpainting_mask_invert".to_string(),
+                    json!(if invert { 1 } else { 0 }),
+                );
+            }
+        }
+        "img2img"
+    };
+
+    // extras 优先级最高，便于传入脚本等高级参数
+    if let Some(Value::Object(extras)) = &params.extras {
+        for (key, value) in extras {
+            body.insert(key.clone(), value.clone());
+        }
+    }
+
+    (endpoint, Value::Object(body))
+}
+
+fn with_auth(builder: RequestBuilder, api_key: Option<&str>) -> RequestBuilder {
+    match api_key.map(str::trim).filter(|key| !key.is_empty()) {
+        Some(key) => match key.split_once(':') {
+            Some((user, pass)) => builder.basic_auth(user, Some(pass)),
+            None => builder.bearer_auth(key),
+        },
+        None => builder,
+    }
+}
+
+/// 将 info 解析为逐张图片的生成参数，跳过网格图
+/// 返回 (第一张单图的下标, 每张图的参数与 infotext)，条数不少于返回的单图数
+pub fn parse_info(
+    info: &Value,
+    image_count: usize,
+) -> (usize, Vec<(GenerationParams, Option<String>)>) {
+    let first = info
+        .get("index_of_first_image")
+        .and_then(|v| v.as_u64())
+        .unwrap_or(0) as usize;
+    let seeds: Vec<i64> = info
+        .get("all_seeds")
+        .and_then(|v| v.as_array())
+        .map(|seeds| seeds.iter().filter_map(|s| s.as_i64()).collect())
+        .unwrap_or_default();
+    let infotexts: Vec<String> = info
+        .get("infotexts")
+        .and_then(|v| v.as_array())
+        .map(|texts| {
+            texts
+                .iter()
+                .map(|t| t.as_str().unwrap_or_default().to_string())
+                .collect()
+        })
+        .unwrap_or_default();
+
+    let str_field = |key: &str| {
+        info.get(key)
+            .and_then(|v| v.as_str())
+            .filter(|s| !s.is_empty())
+            .map(|s| s.to_string())
+    };
+    let u32_field = |key: &str| info.get(key).and_then(|v| v.as_u64()).map(|v| v as u32);
+
+    let base = GenerationParams {
+        provider: Some("sd-webui".to_string()),
+        model: str_field("sd_model_name"),
+        negative_prompt: str_field("negative_prompt"),
+        seed: info.get("seed").and_then(|v| v.as_i64()),
+        sampler: str_field("sampler_name"),
+        scheduler: str_field("scheduler"),
+        steps: u32_field("steps"),
+        cfg_scale: info.get("cfg_scale").and_then(|v| v.as_f64()),
+        width: u32_field("width"),
+        height: u32_field("height"),
+        denoising_strength: info.get("denoising_strength").and_then(|v| v.as_f64()),
+        extra: None,
+    };
+
+    // infotexts 包含网格图，all_seeds 不包含
+    let count = seeds
+        .len()
+        .max(infotexts.len().saturating_sub(first))
+        .max(image_count.saturating_sub(first))
+        .max(1);
+    let entries = (0..count)
+        .map(|index| {
+            let mut generation = base.clone();
+            if let Some(seed) = seeds.get(index) {
+                generation.seed = Some(*seed);
+            }
+            let infotext = infotexts.get(first + index).cloned();
+            (generation, infotext)
+        })
+        .collect();
+    (first, entries)
+}
+
+/// 执行一次生成，返回图片与解析后的 info
+pub async fn run_sd_webui(params: &SdWebUIParams) -> Result<(Vec<SdWebUIImage>, Value), String> {
+    if params.prompt.trim().is_empty() && non_empty_images(params).is_empty() {
+        return Err("提示词不能为空".to_string());
+    }
+
+    let client = Client::builder()
+        .timeout(Duration::from_secs(
+            params.timeout_secs.unwrap_or(DEFAULT_TIMEOUT_SECS),
+        ))
+        .build()
+        .map_err(|e| format!("创建 HTTP 客户端失败: {}", e))?;
+
+    let (endpoint, body) = build_request_body(params);
+    let url = format!(
+        "{}/sdapi/v1/{}",
+        params.base_url.trim_end_matches('/'),
+        endpoint
+    );
+    println!("[Rust] SD WebUI request: {}", url);
+
+    let response = with_auth(client.post(&url), params.api_key.as_deref())
+        .json(&body)
+        .send()
+        .await
+        .map_err(|e| format!("请求失败: {}", e))?;
+
+    let status = response.status();
+    let text = response
+        .text()
+        .await
+        .map_err(|e| format!("读取响应失败: {}", e))?;
+    if !status.is_success() {
+        let detail = serde_json::from_str::<Value>(&text)
+            .ok()
+            .and_then(|v| {
+                v.get("detail").or_else(|| v.get("error")).map(|d| match d {
+                    Value::String(s) => s.clone(),
+                    other => other.to_string(),
+                })
+            })
+            .unwrap_or(text);
+        return Err(format!("API 错误 ({}): {}", status, detail));
+    }
+
+    let parsed: SdWebUIResponse =
+        serde_json::from_str(&text).map_err(|e| format!("解析响应失败: {}", e))?;
+    // info 是 JSON 字符串，解析失败时仍返回图片
+    let info = parsed
+        .info
+        .as_deref()
+        .and_then(|s| serde_json::from_str::<Value>(s).ok())
+        .unwrap_or(Value::Null);
+    let (first, entries) = parse_info(&info, parsed.images.len());
+
+    let mut images = Vec::new();
+    for (data, (mut generation, infotext)) in parsed.images.into_iter().skip(first).zip(entries) {
+        let bytes = decode_base64_image(&data)?;
+        let (mime_type, _) = guess_image_mime(&bytes);
+        if generation.width.is_none() {
+            if let Some((width, height)) = read_dimensions(&bytes) {
+                generation.width = Some(width);
+                generation.height = Some(height);
+            }
+        }
+        images.push(SdWebUIImage {
+            data: strip_data_url_prefix(&data).to_string(),
+            mime_type: mime_type.to_string(),
+            generation,
+            infotext,
+        });
+    }
+
+    if images.is_empty() {
+        return Err("响应中没有图片数据".to_string());
+    }
+    Ok((images, info))
+}
+
+/// 调用 extras 接口放大单张图片，返回 base64
+pub async fn run_sd_webui_upscale(
+    base_url: &str,
+    api_key: Option<&str>,
+    image: &str,
+    scale: f32,
+    upscaler: Option<&str>,
+) -> Result<String, String> {
+    let client = Client::builder()
+        .timeout(Duration::from_secs(DEFAULT_TIMEOUT_SECS))
+        .build()
+        .map_err(|e| format!("创建 HTTP 客户端失败: {}", e))?;
+    let url = format!(
+        "{}/sdapi/v1/extra-single-image",
+        base_url.trim_end_matches('/')
+    );
+    let body = json!({
+        "image": strip_data_url_prefix(image),
+        "resize_mode": 0,
+        "upscaling_resize": scale,
+        "upscaler_1": upscaler.unwrap_or("R-ESRGAN 4x+"),
+    });
+
+    let response = with_auth(client.post(&url), api_key)
+        .json(&body)
+        .send()
+        .await
+        .map_err(|e| format!("请求失败: {}", e))?;
+    let status = response.status();
+    let text = response
+        .text()
+        .await
+        .map_err(|e| format!("读取响应失败: {}", e))?;
+    if !status.is_success() {
+        return Err(format!("API 错误 ({}): {}", status, text));
+    }
+    let value: Value = serde_json::from_str(&text).map_err(|e| format!("解析响应失败: {}", e))?;
+    value
+        .get("image")
+        .and_then(|image| image.as_str())
+        .filter(|image| !image.is_empty())
+        .map(|image| strip_data_url_prefix(image).to_string())
+        .ok_or_else(|| "响应中没有图片数据".to_string())
+}
+
+// Tauri 命令：调用 SD WebUI 生成图片，默认保存到本地
+#[tauri::command]
+pub async fn sd_webui_generate(app: tauri::AppHandle, params: SdWebUIParams) -> SdWebUIResult {
+    println!("[Rust] sd_webui_generate called");
+    println!("[Rust] base_url: {}", params.base_url);
+
+    let (images, info) = match run_sd_webui(&params).await {
+        Ok(result) => result,
+        Err(e) => {
+            println!("[Rust] SD WebUI failed: {}", e);
+            return SdWebUIResult {
+                success: false,
+                error: Some(e),
+                ..SdWebUIResult::default()
+            };
+        }
+    };
+
+    let mut saved_images = Vec::new();
+    if params.save_to_storage.unwrap_or(true) {
+        for image in &images {
+            match save_image(
+                app.clone(),
+                image.data.clone(),
+                params.canvas_id.clone(),
+                params.node_id.clone(),
+                Some(params.prompt.clone()),
+                None,
+                Some(ImageType::Generated),
+                Some(image.generation.clone()),
+            ) {
+                Ok(info) => saved_images.push(info),
+                Err(e) => println!("[Rust] Failed to save SD WebUI output: {}", e),
+            }
+        }
+    }
+
+    println!("[Rust] SD WebUI returned {} image(s)", images.len());
+    SdWebUIResult {
+        success: true,
+        images,
+        saved_images,
+        info: Some(info),
+        error: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{mock_http, MockResponse};
+    use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+
+    #[test]
+    fn test_build_request_body_switches_endpoint() {
+        let params = SdWebUIParams {
+            prompt: "a cat".to_string(),
+            steps: Some(20),
+            ..SdWebUIParams::default()
+        };
+        let (endpoint, body) = build_request_body(&params);
+        assert_eq!(endpoint, "txt2img");
+        assert_eq!(body["seed"], -1);
+        assert_eq!(body["steps"], 20);
+        assert!(body.get("init_images").is_none());
+
+        let params = SdWebUIParams {
+            prompt: "a cat".to_string(),
+            init_images: Some(vec!["data:image/png;base64,AAAA".to_string()]),
+            mask_image: Some("BBBB".to_string()),
+            inpainting_mask_invert: Some(true),
+            extras: Some(json!({ "steps": 8 })),
+            ..SdWebUIParams::default()
+        };
+        let (endpoint, body) = build_request_body(&params);
+        assert_eq!(endpoint, "img2img");
+        assert_eq!(body["init_images"], json!(["AAAA"]));
+        assert_eq!(body["mask"], "BBBB");
+        assert_eq!(body["inpainting_mask_invert"], 1);
+        assert_eq!(body["steps"], 8);
+    }
+
+    #[test]
+    fn test_parse_info_skips_grid() {
+        let info = json!({
+            "seed": 100,
+            "all_seeds": [100, 101],
+            "sampler_name": "Euler a",
+            "steps": 20,
+            "cfg_scale": 7.0,
+            "width": 512,
+            "height": 768,
+            "sd_model_name": "v1-5",
+            "denoising_strength": null,
+            "index_of_first_image": 1,
+            "infotexts": ["grid", "first", "second"]
+        });
+        let (first, entries) = parse_info(&info, 3);
+        assert_eq!(first, 1);
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[1].0.seed, Some(101));
+        assert_eq!(entries[1].1.as_deref(), Some("second"));
+        assert_eq!(entries[0].0.sampler.as_deref(), Some("Euler a"));
+        assert_eq!(entries[0].0.model.as_deref(), Some("v1-5"));
+        assert_eq!(entries[0].0.height, Some(768));
+        assert_eq!(entries[0].0.denoising_strength, None);
+    }
+
+    #[tokio::test]
+    async fn test_missing_info_keeps_every_image() {
+        let mut png = Vec::new();
+        image::RgbaImage::new(3, 2)
+            .write_to(&mut std::io::Cursor::new(&mut png), image::ImageFormat::Png)
+            .unwrap();
+        let body = json!({ "images": vec![BASE64.encode(&png); 3], "info": null }).to_string();
+        let base_url = mock_http(move |_| {
+            let response = MockResponse::json(body.clone());
+            async move { response }
+        })
+        .await;
+
+        let params = SdWebUIParams {
+            base_url,
+            prompt: "a cat".to_string(),
+            ..SdWebUIParams::default()
+        };
+        let (images, info) = run_sd_webui(&params).await.unwrap();
+        assert_eq!(info, Value::Null);
+        assert_eq!(images.len(), 3);
+        assert!(images
+            .iter()
+            .all(|image| image.generation.width == Some(3) && image.generation.height == Some(2)));
+    }
+}
@@ -1,7 +1,10 @@
+use crate::embedded_metadata::{embed_metadata, extract_metadata};
+use crate::image_utils::{decode_base64_image, guess_image_mime};
 use base64::{engine::general_purpose, Engine as _};
 use serde::{Deserialize, Serialize};
 use std::fs;
-use std::path::PathBuf;
+use std::io::Read;
+use std::path::{Path, PathBuf};
 use tauri::Manager;
 use uuid::Uuid;
 
@@ -14,7 +17,7 @@ pub enum ImageType {
 }
 
 // 图片信息结构
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ImageInfo {
     pub id: String,
     pub filename: String,
@@ -34,6 +37,51 @@ pub struct ImageMetadata {
     pub node_id: Option<String>,
     pub canvas_id: Option<String>,
     pub created_at: i64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub generation: Option<GenerationParams>, // 生成参数（旧数据没有此字段）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<ImageDescription>, // 视觉模型生成的描述，用于搜索和替代文本
+}
+
+// 图片描述（反推提示词）
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+pub struct ImageDescription {
+    pub caption: String,   // 画面描述
+    pub prompt: String,    // 可复现画面的提示词
+    pub tags: Vec<String>, // 关键词标签
+    pub alt_text: String,  // 简短的替代文本
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>, // 生成描述的模型
+    pub created_at: i64,
+}
+
+// 生成参数，各服务按需填写
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+pub struct GenerationParams {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub provider: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub negative_prompt: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub seed: Option<i64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sampler: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scheduler: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub steps: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cfg_scale: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub width: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub height: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub denoising_strength: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub extra: Option<serde_json::Value>, // 其他服务特有参数
 }
 
 // 输入图片信息
@@ -43,6 +91,62 @@ pub struct InputImageInfo {
     pub label: String,
 }
 
+// 对比生成分组：同一提示词交给多个服务/模型生成的结果
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ComparisonGroup {
+    pub id: String,
+    pub prompt: String,
+    pub canvas_id: Option<String>,
+    pub node_id: Option<String>,
+    pub created_at: i64,
+    pub entries: Vec<ComparisonEntry>,
+}
+
+// 对比分组中的一项
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ComparisonEntry {
+    pub label: String,
+    pub provider: String,
+    pub model: String,
+    pub success: bool,
+    pub image_paths: Vec<String>,
+    pub latency_ms: u64,
+    pub cost: Option<f64>,
+    pub error: Option<String>,
+}
+
+// 视频信息结构
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VideoInfo {
+    pub id: String,
+    pub filename: String,
+    pub path: String,
+    pub size: u64,
+    pub created_at: i64,
+    pub canvas_id: Option<String>,
+    pub node_id: Option<String>,
+}
+
+// 视频元数据（{id}_{timestamp}.meta.json）
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct VideoMetadata {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prompt: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub provider: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub task_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source_url: Option<String>, // 服务返回的下载地址
+    pub node_id: Option<String>,
+    pub canvas_id: Option<String>,
+    pub created_at: i64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub extra: Option<serde_json::Value>, // 其他服务特有参数
+}
+
 // 带元数据的图片信息（用于前端）
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ImageInfoWithMetadata {
@@ -90,6 +194,42 @@ fn get_images_dir(app: &tauri::AppHandle) -> Result<PathBuf, String> {
     Ok(images_dir)
 }
 
+// 获取对比分组目录（与图片目录分开，避免被当作画布统计）
+fn get_comparisons_dir(app: &tauri::AppHandle) -> Result<PathBuf, String> {
+    let comparisons_dir = get_app_data_dir(app)?.join("comparisons");
+    if !comparisons_dir.exists() {
+        fs::create_dir_all(&comparisons_dir).map_err(|e| format!("创建对比分组目录失败: {}", e))?;
+    }
+    Ok(comparisons_dir)
+}
+
+// 获取视频存储目录
+fn get_videos_dir(app: &tauri::AppHandle) -> Result<PathBuf, String> {
+    let videos_dir = get_app_data_dir(app)?.join("videos");
+    if !videos_dir.exists() {
+        fs::create_dir_all(&videos_dir).map_err(|e| format!("创建视频目录失败: {}", e))?;
+    }
+    Ok(videos_dir)
+}
+
+// 获取视频下载临时文件目录（videos/.partial），未下载完的视频留在这里等待续传
+pub(crate) fn get_video_partial_dir(app: &tauri::AppHandle) -> Result<PathBuf, String> {
+    let partial_dir = get_videos_dir(app)?.join(".partial");
+    if !partial_dir.exists() {
+        fs::create_dir_all(&partial_dir).map_err(|e| format!("创建下载目录失败: {}", e))?;
+    }
+    Ok(partial_dir)
+}
+
+// 获取视频任务队列文件路径（video_tasks.json）
+pub(crate) fn get_video_tasks_file(app: &tauri::AppHandle) -> Result<PathBuf, String> {
+    let app_data = get_app_data_dir(app)?;
+    if !app_data.exists() {
+        fs::create_dir_all(&app_data).map_err(|e| format!("创建应用数据目录失败: {}", e))?;
+    }
+    Ok(app_data.join("video_tasks.json"))
+}
+
 // 获取缓存目录
 fn get_cache_dir(app: &tauri::AppHandle) -> Result<PathBuf, String> {
     let app_data = get_app_data_dir(app)?;
@@ -100,25 +240,29 @@ fn get_cache_dir(app: &tauri::AppHandle) -> Result<PathBuf, String> {
     Ok(cache_dir)
 }
 
-fn guess_image_extension(image_data: &[u8]) -> &'static str {
-    if image_data.starts_with(b"\x89PNG\r\n\x1a\n") {
-        "png"
-    } else if image_data.starts_with(b"\xff\xd8\xff") {
-        "jpg"
-    } else if image_data.len() >= 12
-        && &image_data[0..4] == b"RIFF"
-        && &image_data[8..12] == b"WEBP"
-    {
-        "webp"
-    } else if image_data.starts_with(b"GIF87a") || image_data.starts_with(b"GIF89a") {
-        "gif"
-    } else {
-        "png"
+// 根据图片路径构造元数据文件路径（{id}_{timestamp}.meta.json）
+fn sidecar_path(image_path: &Path) -> PathBuf {
+    let stem = image_path
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or_default();
+    image_path.with_file_name(format!("{}.meta.json", stem))
+}
+
+// 读取元数据文件（不存在或解析失败时返回 None）
+fn read_sidecar_metadata(image_path: &Path) -> Option<ImageMetadata> {
+    let meta_path = sidecar_path(image_path);
+    if !meta_path.exists() {
+        return None;
     }
+    fs::read_to_string(&meta_path)
+        .ok()
+        .and_then(|content| serde_json::from_str::<ImageMetadata>(&content).ok())
 }
 
 // 保存图片（从 base64）- 同时保存元数据
 #[tauri::command]
+#[allow(clippy::too_many_arguments)]
 pub fn save_image(
     app: tauri::AppHandle,
     base64_data: String,
@@ -126,7 +270,8 @@ pub fn save_image(
     node_id: Option<String>,
     prompt: Option<String>,
     input_images: Option<Vec<InputImageInfo>>,
-    image_type: Option<ImageType>, // 新增：图片类型
+    image_type: Option<ImageType>,        // 新增：图片类型
+    generation: Option<GenerationParams>, // 生成参数，写入元数据
 ) -> Result<ImageInfo, String> {
     let images_dir = get_images_dir(&app)?;
 
@@ -142,32 +287,44 @@ pub fn save_image(
     };
 
     // 解码 base64
-    let image_data = general_purpose::STANDARD
-        .decode(&base64_data)
-        .map_err(|e| format!("Base64 解码失败: {}", e))?;
+    let mut image_data = decode_base64_image(&base64_data)?;
 
     // 生成唯一文件名
     let id = Uuid::new_v4().to_string();
     let timestamp = chrono::Utc::now().timestamp();
-    let ext = guess_image_extension(&image_data);
+    let (_, ext) = guess_image_mime(&image_data);
     let filename = format!("{}_{}.{}", id, timestamp, ext);
     let file_path = target_dir.join(&filename);
 
-    // 写入图片文件
-    fs::write(&file_path, &image_data).map_err(|e| format!("写入文件失败: {}", e))?;
-
-    // 保存元数据文件（如果有提示词或输入图片）
-    if prompt.is_some() || input_images.is_some() {
+    // 确定元数据：有提示词或输入图片时新建，否则尝试沿用导入图片内嵌的元数据
+    let metadata = if prompt.is_some() || input_images.is_some() || generation.is_some() {
         let metadata = ImageMetadata {
             prompt: prompt.clone(),
             input_images: input_images.unwrap_or_default(),
             node_id: node_id.clone(),
             canvas_id: canvas_id.clone(),
             created_at: timestamp,
+            generation,
+            description: None,
         };
 
-        let meta_filename = format!("{}_{}.meta.json", id, timestamp);
-        let meta_path = target_dir.join(&meta_filename);
+        // 将元数据写入图片文件本身，失败时仍保存原图
+        match embed_metadata(&image_data, &metadata) {
+            Ok(embedded) => image_data = embedded,
+            Err(e) => println!("[Rust] 写入内嵌元数据失败，保留原图: {}", e),
+        }
+
+        Some(metadata)
+    } else {
+        extract_metadata(&image_data)
+    };
+
+    // 写入图片文件
+    fs::write(&file_path, &image_data).map_err(|e| format!("写入文件失败: {}", e))?;
+
+    // 保存元数据文件
+    if let Some(metadata) = metadata {
+        let meta_path = sidecar_path(&file_path);
 
         let meta_json = serde_json::to_string_pretty(&metadata)
             .map_err(|e| format!("序列化元数据失败: {}", e))?;
@@ -407,15 +564,7 @@ pub fn list_canvas_images(
                     };
 
                     // 尝试读取对应的元数据文件
-                    let meta_filename = filename.replace(".png", ".meta.json");
-                    let meta_path = canvas_dir.join(&meta_filename);
-                    let metadata = if meta_path.exists() {
-                        fs::read_to_string(&meta_path).ok().and_then(|content| {
-                            serde_json::from_str::<ImageMetadata>(&content).ok()
-                        })
-                    } else {
-                        None
-                    };
+                    let metadata = read_sidecar_metadata(&path);
 
                     // 从元数据中获取 node_id
                     let node_id = metadata.as_ref().and_then(|m| m.node_id.clone());
@@ -453,14 +602,42 @@ pub fn list_canvas_images(
     Ok(images)
 }
 
+// 按 ID 查找已保存的图片（文件名格式: {id}_{timestamp}.{ext}，可能位于画布子目录）
+pub fn find_image_by_id(app: &tauri::AppHandle, image_id: &str) -> Result<PathBuf, String> {
+    let images_dir = get_images_dir(app)?;
+    let prefix = format!("{}_", image_id);
+    let mut dirs = vec![images_dir];
+    while let Some(dir) = dirs.pop() {
+        let Ok(entries) = fs::read_dir(&dir) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path.is_dir() {
+                dirs.push(path);
+                continue;
+            }
+            let filename = path
+                .file_name()
+                .and_then(|n| n.to_str())
+                .unwrap_or_default();
+            if filename.starts_with(&prefix) && !filename.ends_with(".meta.json") {
+                return Ok(path);
+            }
+        }
+    }
+    Err(format!("找不到图片: {}", image_id))
+}
+
 // 读取单个图片的元数据
 #[tauri::command]
 pub fn read_image_metadata(image_path: String) -> Result<Option<ImageMetadata>, String> {
     // 从图片路径构造元数据文件路径
-    let meta_path = image_path.replace(".png", ".meta.json");
+    let meta_path = sidecar_path(Path::new(&image_path));
 
-    if !std::path::Path::new(&meta_path).exists() {
-        return Ok(None);
+    if !meta_path.exists() {
+        // 没有元数据文件时回退到图片内嵌的元数据
+        return read_embedded_metadata(image_path);
     }
 
     let content = fs::read_to_string(&meta_path).map_err(|e| format!("读取元数据失败: {}", e))?;
@@ -471,6 +648,174 @@ pub fn read_image_metadata(image_path: String) -> Result<Option<ImageMetadata>,
     Ok(Some(metadata))
 }
 
+// 更新图片的描述信息：沿用已有元数据（或内嵌元数据），只改写元数据文件
+pub fn write_image_description(
+    image_path: &str,
+    description: ImageDescription,
+) -> Result<ImageMetadata, String> {
+    let path = Path::new(image_path);
+    if !path.exists() {
+        return Err(format!("图片不存在: {}", image_path));
+    }
+    let mut metadata =
+        read_image_metadata(image_path.to_string())?.unwrap_or_else(|| ImageMetadata {
+            prompt: None,
+            input_images: vec![],
+            node_id: None,
+            canvas_id: None,
+            created_at: chrono::Utc::now().timestamp(),
+            generation: None,
+            description: None,
+        });
+    metadata.description = Some(description);
+
+    let meta_json =
+        serde_json::to_string_pretty(&metadata).map_err(|e| format!("序列化元数据失败: {}", e))?;
+    fs::write(sidecar_path(path), meta_json).map_err(|e| format!("写入元数据失败: {}", e))?;
+    Ok(metadata)
+}
+
+// 根据文件头判断视频扩展名，无法识别时按 mp4 处理
+fn guess_video_extension(data: &[u8]) -> &'static str {
+    if data.starts_with(&[0x1A, 0x45, 0xDF, 0xA3]) {
+        "webm"
+    } else if data.len() >= 12 && &data[4..8] == b"ftyp" && &data[8..10] == b"qt" {
+        "mov"
+    } else {
+        "mp4"
+    }
+}
+
+// 把下载完成的临时文件移入视频目录，同时写入元数据文件
+pub fn store_video_file(
+    app: &tauri::AppHandle,
+    temp_path: &Path,
+    canvas_id: Option<String>,
+    node_id: Option<String>,
+    metadata: Option<VideoMetadata>,
+) -> Result<VideoInfo, String> {
+    let mut target_dir = get_videos_dir(app)?;
+    if let Some(cid) = &canvas_id {
+        target_dir = target_dir.join(cid);
+        fs::create_dir_all(&target_dir).map_err(|e| format!("创建画布目录失败: {}", e))?;
+    }
+
+    // 按文件头判断格式
+    let mut header = [0u8; 12];
+    let header_len = fs::File::open(temp_path)
+        .and_then(|mut file| file.read(&mut header))
+        .map_err(|e| format!("读取视频失败: {}", e))?;
+    let size = fs::metadata(temp_path)
+        .map_err(|e| format!("读取视频失败: {}", e))?
+        .len();
+
+    let id = Uuid::new_v4().to_string();
+    let timestamp = chrono::Utc::now().timestamp();
+    let filename = format!(
+        "{}_{}.{}",
+        id,
+        timestamp,
+        guess_video_extension(&header[..header_len])
+    );
+    let file_path = target_dir.join(&filename);
+    fs::rename(temp_path, &file_path).map_err(|e| format!("保存视频失败: {}", e))?;
+
+    if let Some(metadata) = metadata {
+        let meta_json = serde_json::to_string_pretty(&metadata)
+            .map_err(|e| format!("序列化元数据失败: {}", e))?;
+        fs::write(sidecar_path(&file_path), meta_json)
+            .map_err(|e| format!("写入元数据失败: {}", e))?;
+    }
+
+    Ok(VideoInfo {
+        id,
+        filename,
+        path: file_path.to_str().ok_or("路径转换失败")?.to_string(),
+        size,
+        created_at: timestamp,
+        canvas_id,
+        node_id,
+    })
+}
+
+// 校验对比分组 ID：用作文件名，只允许字母、数字、下划线和连字符
+pub fn validate_comparison_id(id: &str) -> Result<(), String> {
+    let valid = !id.is_empty()
+        && id.len() <= 64
+        && id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+    if valid {
+        Ok(())
+    } else {
+        Err("对比分组 ID 只能包含字母、数字、下划线和连字符".to_string())
+    }
+}
+
+// 保存对比分组（comparisons/{id}.json）
+pub fn save_comparison_group(
+    app: &tauri::AppHandle,
+    group: &ComparisonGroup,
+) -> Result<PathBuf, String> {
+    validate_comparison_id(&group.id)?;
+    let path = get_comparisons_dir(app)?.join(format!("{}.json", group.id));
+    let json =
+        serde_json::to_string_pretty(group).map_err(|e| format!("序列化对比分组失败: {}", e))?;
+    fs::write(&path, json).map_err(|e| format!("写入对比分组失败: {}", e))?;
+    Ok(path)
+}
+
+// 列出对比分组，可按画布过滤，按时间倒序
+#[tauri::command]
+pub fn list_comparison_groups(
+    app: tauri::AppHandle,
+    canvas_id: Option<String>,
+) -> Result<Vec<ComparisonGroup>, String> {
+    let dir = get_comparisons_dir(&app)?;
+    let mut groups: Vec<ComparisonGroup> = fs::read_dir(&dir)
+        .map_err(|e| format!("读取对比分组失败: {}", e))?
+        .flatten()
+        .filter(|entry| entry.path().extension().and_then(|e| e.to_str()) == Some("json"))
+        .filter_map(|entry| fs::read_to_string(entry.path()).ok())
+        .filter_map(|content| serde_json::from_str::<ComparisonGroup>(&content).ok())
+        .filter(|group| canvas_id.is_none() || group.canvas_id == canvas_id)
+        .collect();
+    groups.sort_by_key(|group| std::cmp::Reverse(group.created_at));
+    Ok(groups)
+}
+
+// 读取图片文件内嵌的元数据（用于拖入/导入的外部图片）
+#[tauri::command]
+pub fn read_embedded_metadata(image_path: String) -> Result<Option<ImageMetadata>, String> {
+    let data = fs::read(&image_path).map_err(|e| format!("读取文件失败: {}", e))?;
+    Ok(extract_metadata(&data))
+}
+
+// 导出图片：将元数据写入导出的图片文件，导出后仍保留提示词和参数
+#[tauri::command]
+pub fn export_image(source_path: String, target_path: String) -> Result<u64, String> {
+    let source = Path::new(&source_path);
+    let data = fs::read(source).map_err(|e| format!("读取文件失败: {}", e))?;
+
+    let output = match read_sidecar_metadata(source) {
+        Some(metadata) => embed_metadata(&data, &metadata).unwrap_or_else(|e| {
+            println!("[Rust] 导出时写入内嵌元数据失败，按原图导出: {}", e);
+            data
+        }),
+        None => data,
+    };
+
+    fs::write(&target_path, &output).map_err(|e| format!("写入文件失败: {}", e))?;
+
+    Ok(output.len() as u64)
+}
+
+// 导出视频：复制本地保存的视频到指定位置
+#[tauri::command]
+pub fn export_video(source_path: String, target_path: String) -> Result<u64, String> {
+    fs::copy(&source_path, &target_path).map_err(|e| format!("导出视频失败: {}", e))
+}
+
 // 辅助函数：计算目录大小
 fn calculate_dir_size(path: &PathBuf) -> u64 {
     let mut size: u64 = 0;
@@ -488,3 +833,17 @@ fn calculate_dir_size(path: &PathBuf) -> u64 {
 
     size
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_comparison_id() {
+        assert!(validate_comparison_id(&Uuid::new_v4().to_string()).is_ok());
+        assert!(validate_comparison_id("batch_01-a").is_ok());
+        for id in ["", "../../secrets", "a/b", "a.b", "名字", &"x".repeat(65)] {
+            assert!(validate_comparison_id(id).is_err(), "{}", id);
+        }
+    }
+}
@@ -0,0 +1,195 @@
+//! 测试用的本地 HTTP 服务
+//!
+//! 每个连接只处理一个请求，按 handler 的返回值写回响应后关闭连接。
+
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio_tungstenite::WebSocketStream;
+
+pub struct MockRequest {
+    pub method: String,
+    pub path: String,                   // 含查询参数
+    pub headers: Vec<(String, String)>, // 名称已转为小写
+    pub body: Vec<u8>,
+}
+
+impl MockRequest {
+    pub fn header(&self, name: &str) -> Option<&str> {
+        let name = name.to_ascii_lowercase();
+        self.headers
+            .iter()
+            .find(|(key, _)| *key == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    pub fn json(&self) -> serde_json::Value {
+        serde_json::from_slice(&self.body).unwrap()
+    }
+}
+
+pub struct MockResponse {
+    pub status: &'static str,
+    pub headers: Vec<(&'static str, String)>,
+    pub body: Vec<u8>,
+}
+
+impl MockResponse {
+    pub fn new(status: &'static str, content_type: &str, body: impl Into<Vec<u8>>) -> Self {
+        Self {
+            status,
+            headers: vec![("Content-Type", content_type.to_string())],
+            body: body.into(),
+        }
+    }
+
+    pub fn json(body: impl Into<Vec<u8>>) -> Self {
+        Self::new("200 OK", "application/json", body)
+    }
+
+    pub fn header(mut self, name: &'static str, value: impl Into<String>) -> Self {
+        self.headers.push((name, value.into()));
+        self
+    }
+}
+
+type WsHandler = Arc<
+    dyn Fn(WebSocketStream<TcpStream>) -> Pin<Box<dyn Future<Output = ()> + Send>> + Send + Sync,
+>;
+
+/// 启动本地服务，返回 "http://127.0.0.1:端口"
+pub async fn mock_http<F, Fut>(handler: F) -> String
+where
+    F: Fn(MockRequest) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = MockResponse> + Send + 'static,
+{
+    serve(handler, None).await
+}
+
+/// 同 mock_http，请求 ws_path 的连接升级为 WebSocket 交给 ws_handler
+pub async fn mock_http_with_ws<F, Fut, W, WFut>(
+    handler: F,
+    ws_path: &'static str,
+    ws_handler: W,
+) -> String
+where
+    F: Fn(MockRequest) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = MockResponse> + Send + 'static,
+    W: Fn(WebSocketStream<TcpStream>) -> WFut + Send + Sync + 'static,
+    WFut: Future<Output = ()> + Send + 'static,
+{
+    let ws_handler: WsHandler = Arc::new(move |socket| Box::pin(ws_handler(socket)));
+    serve(handler, Some((ws_path, ws_handler))).await
+}
+
+async fn serve<F, Fut>(handler: F, ws: Option<(&'static str, WsHandler)>) -> String
+where
+    F: Fn(MockRequest) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = MockResponse> + Send + 'static,
+{
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap();
+    let handler = Arc::new(handler);
+    tokio::spawn(async move {
+        loop {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let handler = handler.clone();
+            let ws = ws.clone();
+            tokio::spawn(async move {
+                if let Some((ws_path, ws_handler)) = ws {
+                    if request_path(&stream).await.as_deref() == Some(ws_path) {
+                        let socket = tokio_tungstenite::accept_async(stream).await.unwrap();
+                        ws_handler(socket).await;
+                        return;
+                    }
+                }
+                let request = read_request(&mut stream).await;
+                let response = handler(request).await;
+                write_response(&mut stream, response).await;
+            });
+        }
+    });
+    format!("http://{}", address)
+}
+
+// 不消耗数据，只看请求行里的路径（不含查询参数）
+async fn request_path(stream: &TcpStream) -> Option<String> {
+    let mut buffer = [0u8; 1024];
+    loop {
+        let n = stream.peek(&mut buffer).await.ok()?;
+        let head = String::from_utf8_lossy(&buffer[..n]);
+        if let Some((line, _)) = head.split_once("\r\n") {
+            let target = line.split_whitespace().nth(1)?;
+            return target.split('?').next().map(|s| s.to_string());
+        }
+        if n == 0 || n == buffer.len() {
+            return None;
+        }
+        tokio::task::yield_now().await;
+    }
+}
+
+async fn read_request(stream: &mut TcpStream) -> MockRequest {
+    let mut buffer = Vec::new();
+    let mut chunk = [0u8; 8192];
+    let header_end = loop {
+        let n = stream.read(&mut chunk).await.unwrap();
+        assert!(n > 0, "连接在请求头结束前关闭");
+        buffer.extend_from_slice(&chunk[..n]);
+        if let Some(pos) = buffer.windows(4).position(|w| w == b"\r\n\r\n") {
+            break pos + 4;
+        }
+    };
+    let head = String::from_utf8_lossy(&buffer[..header_end]).to_string();
+    let mut lines = head.lines();
+    let mut request_line = lines.next().unwrap_or_default().split_whitespace();
+    let method = request_line.next().unwrap_or_default().to_string();
+    let path = request_line.next().unwrap_or_default().to_string();
+    let headers: Vec<(String, String)> = lines
+        .filter_map(|line| line.split_once(':'))
+        .map(|(key, value)| (key.trim().to_ascii_lowercase(), value.trim().to_string()))
+        .collect();
+
+    let mut body = buffer[header_end..].to_vec();
+    let length = headers
+        .iter()
+        .find(|(key, _)| key == "content-length")
+        .and_then(|(_, value)| value.parse::<usize>().ok());
+    let chunked = headers
+        .iter()
+        .any(|(key, value)| key == "transfer-encoding" && value.eq_ignore_ascii_case("chunked"));
+    if let Some(length) = length {
+        while body.len() < length {
+            let n = stream.read(&mut chunk).await.unwrap();
+            body.extend_from_slice(&chunk[..n]);
+        }
+    } else if chunked {
+        while !body.ends_with(b"0\r\n\r\n") {
+            let n = stream.read(&mut chunk).await.unwrap();
+            body.extend_from_slice(&chunk[..n]);
+        }
+    }
+
+    MockRequest {
+        method,
+        path,
+        headers,
+        body,
+    }
+}
+
+async fn write_response(stream: &mut TcpStream, response: MockResponse) {
+    let mut head = format!("HTTP/1.1 {}\r\n", response.status);
+    for (name, value) in &response.headers {
+        head.push_str(&format!("{}: {}\r\n", name, value));
+    }
+    head.push_str(&format!(
+        "Content-Length: {}\r\nConnection: close\r\n\r\n",
+        response.body.len()
+    ));
+    stream.write_all(head.as_bytes()).await.unwrap();
+    stream.write_all(&response.body).await.unwrap();
+    stream.shutdown().await.ok();
+}
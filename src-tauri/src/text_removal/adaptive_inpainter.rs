@@ -6,11 +6,11 @@ use image::{GrayImage, ImageBuffer, Luma, Rgb, RgbImage};
 use std::cmp::{max, min};
 
 #[derive(Debug, Clone)]
-struct Sample {
-    color: [u8; 3],
+pub(crate) struct Sample {
+    pub(crate) color: [u8; 3],
 }
 
-enum BackgroundStrategy {
+pub(crate) enum BackgroundStrategy {
     Solid { color: [u8; 3] },
     Gradient { fallback_color: [u8; 3] },
 }
@@ -138,7 +138,7 @@ fn collect_border_samples(image: &RgbImage, mask: &GrayImage, border_width: u32)
     samples
 }
 
-fn analyze_background(samples: &[Sample]) -> BackgroundStrategy {
+pub(crate) fn analyze_background(samples: &[Sample]) -> BackgroundStrategy {
     if samples.is_empty() {
         return BackgroundStrategy::Solid {
             color: [255, 255, 255],
@@ -381,7 +381,7 @@ fn lerp_color(a: [f64; 3], b: [f64; 3], t: f64) -> [u8; 3] {
     ]
 }
 
-fn gaussian_blur_mask(mask: &GrayImage, radius: u32) -> Vec<f32> {
+pub(crate) fn gaussian_blur_mask(mask: &GrayImage, radius: u32) -> Vec<f32> {
     let (width, height) = mask.dimensions();
     let size = (width * height) as usize;
     if radius == 0 {
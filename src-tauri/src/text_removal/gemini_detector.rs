@@ -5,6 +5,8 @@ use reqwest::Client;
 use serde::{Deserialize, Serialize};
 use std::time::Duration;
 
+use crate::image_utils::{normalize_base64_image, GEMINI_INPUT_FORMATS};
+
 /// 检测到的文本区域
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TextRegion {
@@ -155,9 +157,11 @@ pub async fn detect_text(
         config.api_key
     );
 
+    let (mime_type, image_base64) = normalize_base64_image(image_base64, GEMINI_INPUT_FORMATS)?;
+
     // 第一轮：自由格式输出（带重试）
     println!("[Rust] Gemini 第一轮检测...");
-    let round1_result = detect_text_round1(&client, &url, image_base64).await?;
+    let round1_result = detect_text_round1(&client, &url, mime_type, &image_base64).await?;
     println!(
         "[Rust] 第一轮结果长度: {} 字符, 有效: {}",
         round1_result.text.len(),
@@ -197,6 +201,8 @@ pub async fn extract_text_styles(
         config.api_key
     );
 
+    let (mime_type, image_base64) = normalize_base64_image(image_base64, GEMINI_INPUT_FORMATS)?;
+
     let mut list_lines = Vec::new();
     for (i, r) in regions.iter().enumerate() {
         let label: String = r.label.chars().take(40).collect();
@@ -248,7 +254,7 @@ pub async fn extract_text_styles(
                 {"text": prompt},
                 {
                     "inlineData": {
-                        "mimeType": "image/png",
+                        "mimeType": mime_type,
                         "data": image_base64
                     }
                 }
@@ -312,6 +318,7 @@ struct Round1Result {
 async fn detect_text_round1(
     client: &Client,
     url: &str,
+    mime_type: &str,
     image_base64: &str,
 ) -> Result<Round1Result, String> {
     let request_body = serde_json::json!({
@@ -320,7 +327,7 @@ async fn detect_text_round1(
                 {"text": ROUND1_PROMPT},
                 {
                     "inlineData": {
-                        "mimeType": "image/png",
+                        "mimeType": mime_type,
                         "data": image_base64
                     }
                 }
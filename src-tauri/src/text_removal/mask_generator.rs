@@ -54,6 +54,19 @@ pub fn generate_mask(regions: &[TextRegion], width: u32, height: u32, dilation:
     }
 }
 
+/// 生成扩图掩码：保留区域为黑色，其余为白色
+/// keep 为像素坐标 (x, y, width, height)
+pub fn generate_outpaint_mask(width: u32, height: u32, keep: (u32, u32, u32, u32)) -> GrayImage {
+    let (kx, ky, kw, kh) = keep;
+    ImageBuffer::from_fn(width, height, |x, y| {
+        if x >= kx && x < kx + kw && y >= ky && y < ky + kh {
+            Luma([0u8])
+        } else {
+            Luma([255u8])
+        }
+    })
+}
+
 /// 简单多边形填充（扫描线算法）
 fn fill_polygon(mask: &mut GrayImage, points: &[(i32, i32)]) {
     if points.is_empty() {
@@ -198,4 +211,13 @@ mod tests {
         // 检查掩码外部是否为黑色
         assert_eq!(mask.get_pixel(50, 50)[0], 0);
     }
+
+    #[test]
+    fn test_generate_outpaint_mask() {
+        let mask = generate_outpaint_mask(10, 6, (2, 1, 4, 3));
+        assert_eq!(mask.get_pixel(2, 1)[0], 0);
+        assert_eq!(mask.get_pixel(5, 3)[0], 0);
+        assert_eq!(mask.get_pixel(6, 3)[0], 255);
+        assert_eq!(mask.get_pixel(0, 0)[0], 255);
+    }
 }
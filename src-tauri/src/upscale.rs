@@ -0,0 +1,577 @@
+//! 图片放大
+//!
+//! 本地默认使用 Lanczos 重采样加 USM 锐化；启用 `onnx-upscale` 特性后
+//! 可加载用户提供的 ONNX 超分模型；也可以交给 SD WebUI 的 extras 接口处理。
+//! 支持批量放大整个画布，结果保留原图的提示词与生成参数并记录来源。
+
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use image::imageops::FilterType;
+use image::{DynamicImage, GenericImageView, ImageFormat};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::io::Cursor;
+use tauri::Emitter;
+
+use crate::image_utils::decode_base64_image;
+use crate::sd_webui::run_sd_webui_upscale;
+use crate::storage::{
+    list_canvas_images, read_image_metadata, save_image, GenerationParams, ImageInfo, ImageType,
+    InputImageInfo,
+};
+
+const DEFAULT_SCALE: f32 = 2.0;
+const MAX_OUTPUT_DIMENSION: u32 = 16384;
+const DEFAULT_SHARPEN_SIGMA: f32 = 0.8;
+const DEFAULT_SHARPEN_THRESHOLD: i32 = 2;
+
+// 前端调用的参数
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UpscaleParams {
+    #[serde(default)]
+    pub image_paths: Vec<String>, // 本地图片路径
+    pub image_data: Option<String>,    // 单张 base64 图片
+    pub canvas_id: Option<String>,     // 未提供图片时放大该画布的全部图片
+    pub scale: Option<f32>,            // 默认 2 倍
+    pub target_long_edge: Option<u32>, // 指定长边像素，优先于 scale，如 3840
+    pub method: Option<String>,        // "lanczos"（默认）| "onnx" | "sd-webui"
+    pub sharpen_sigma: Option<f32>,    // 0 关闭锐化
+    pub sharpen_threshold: Option<i32>,
+    pub model_path: Option<String>,   // ONNX 超分模型
+    pub runtime_path: Option<String>, // onnxruntime 动态库路径，未指定时读取 ORT_DYLIB_PATH
+    pub tile_size: Option<u32>,       // ONNX 分块大小
+    pub base_url: Option<String>,     // SD WebUI 地址
+    pub api_key: Option<String>,
+    pub upscaler: Option<String>, // SD WebUI 放大算法名
+    pub node_id: Option<String>,
+    pub request_id: Option<String>,    // 批量进度事件中用于区分请求
+    pub save_to_storage: Option<bool>, // 默认保存
+}
+
+// 单张图片的放大结果
+#[derive(Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UpscaleItem {
+    pub source_path: Option<String>,
+    pub success: bool,
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    pub image_data: Option<String>, // 未保存时返回 PNG（base64）
+    pub saved_image: Option<ImageInfo>,
+    pub error: Option<String>,
+}
+
+// 批量进度事件（upscale-progress）
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UpscaleProgressEvent {
+    pub request_id: Option<String>,
+    pub completed: usize,
+    pub total: usize,
+    pub source_path: Option<String>,
+    pub success: bool,
+}
+
+// 返回结果
+#[derive(Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UpscaleResult {
+    pub success: bool,
+    pub items: Vec<UpscaleItem>,
+    pub error: Option<String>,
+}
+
+/// 计算放大后的尺寸
+pub fn target_size(
+    width: u32,
+    height: u32,
+    scale: Option<f32>,
+    target_long_edge: Option<u32>,
+) -> Result<(u32, u32), String> {
+    let factor = match target_long_edge {
+        Some(edge) if edge > 0 => edge as f64 / width.max(height) as f64,
+        _ => scale.unwrap_or(DEFAULT_SCALE) as f64,
+    };
+    if !factor.is_finite() || factor <= 0.0 {
+        return Err("放大倍数无效".to_string());
+    }
+    let size = (
+        ((width as f64) * factor).round().max(1.0) as u32,
+        ((height as f64) * factor).round().max(1.0) as u32,
+    );
+    if size.0 > MAX_OUTPUT_DIMENSION || size.1 > MAX_OUTPUT_DIMENSION {
+        return Err(format!("放大后尺寸过大: {}x{}", size.0, size.1));
+    }
+    Ok(size)
+}
+
+/// Lanczos 重采样后做 USM 锐化
+pub fn upscale_lanczos(
+    image: &DynamicImage,
+    width: u32,
+    height: u32,
+    sharpen_sigma: f32,
+    sharpen_threshold: i32,
+) -> DynamicImage {
+    let resized = image.resize_exact(width, height, FilterType::Lanczos3);
+    if sharpen_sigma > 0.0 {
+        resized.unsharpen(sharpen_sigma, sharpen_threshold)
+    } else {
+        resized
+    }
+}
+
+#[cfg(feature = "onnx-upscale")]
+mod onnx {
+    use image::imageops::FilterType;
+    use image::{DynamicImage, GenericImageView, RgbImage};
+    use ort::session::Session;
+    use ort::value::Tensor;
+    use std::sync::Mutex;
+
+    // 分块之间的重叠像素，避免接缝
+    const TILE_PADDING: u32 = 16;
+
+    lazy_static::lazy_static! {
+        // 缓存最近一次加载的模型
+        static ref SESSION: Mutex<Option<(String, Session)>> = Mutex::new(None);
+    }
+
+    /// 使用超分模型放大（模型输入输出均为 NCHW、0-1 的 RGB）
+    pub fn upscale(
+        image: &DynamicImage,
+        model_path: &str,
+        runtime_path: Option<&str>,
+        tile_size: u32,
+    ) -> Result<DynamicImage, String> {
+        if let Some(runtime_path) = runtime_path {
+            ort::init_from(runtime_path)
+                .commit()
+                .map_err(|e| format!("加载 onnxruntime 失败: {}", e))?;
+        }
+
+        // 推理中途 panic 会使锁中毒，缓存的会话不再可信，丢弃后重新加载
+        let mut guard = match SESSION.lock() {
+            Ok(guard) => guard,
+            Err(e) => {
+                let mut guard = e.into_inner();
+                *guard = None;
+                SESSION.clear_poison();
+                guard
+            }
+        };
+        if guard
+            .as_ref()
+            .map(|(path, _)| path != model_path)
+            .unwrap_or(true)
+        {
+            let session = Session::builder()
+                .and_then(|builder| builder.commit_from_file(model_path))
+                .map_err(|e| format!("加载 ONNX 模型失败: {}", e))?;
+            *guard = Some((model_path.to_string(), session));
+        }
+        let session = &mut guard.as_mut().unwrap().1;
+
+        let source = image.to_rgb8();
+        let (width, height) = source.dimensions();
+        let tile = tile_size.max(64);
+        let mut output: Option<RgbImage> = None;
+        let mut factor = 0u32;
+
+        for tile_y in (0..height).step_by(tile as usize) {
+            for tile_x in (0..width).step_by(tile as usize) {
+                let x0 = tile_x.saturating_sub(TILE_PADDING);
+                let y0 = tile_y.saturating_sub(TILE_PADDING);
+                let x1 = (tile_x + tile + TILE_PADDING).min(width);
+                let y1 = (tile_y + tile + TILE_PADDING).min(height);
+                let (tw, th) = ((x1 - x0) as usize, (y1 - y0) as usize);
+
+                let mut input = vec![0f32; 3 * tw * th];
+                for y in 0..th {
+                    for x in 0..tw {
+                        let pixel = source.get_pixel(x0 + x as u32, y0 + y as u32);
+                        for channel in 0..3 {
+                            input[channel * tw * th + y * tw + x] = pixel[channel] as f32 / 255.0;
+                        }
+                    }
+                }
+                let tensor = Tensor::from_array(([1usize, 3, th, tw], input))
+                    .map_err(|e| format!("创建输入张量失败: {}", e))?;
+                let outputs = session
+                    .run(ort::inputs![tensor])
+                    .map_err(|e| format!("模型推理失败: {}", e))?;
+                let (shape, values) = outputs[0]
+                    .try_extract_tensor::<f32>()
+                    .map_err(|e| format!("读取模型输出失败: {}", e))?;
+                if shape.len() != 4 || shape[1] != 3 {
+                    return Err(format!("不支持的模型输出形状: {:?}", &shape[..]));
+                }
+                let (ow, oh) = (shape[3] as usize, shape[2] as usize);
+                if factor == 0 {
+                    factor = (ow / tw).max(1) as u32;
+                }
+                let canvas =
+                    output.get_or_insert_with(|| RgbImage::new(width * factor, height * factor));
+
+                // 只写回去掉重叠部分的区域
+                let keep_x = (tile_x - x0) * factor..((tile_x + tile).min(width) - x0) * factor;
+                let keep_y = (tile_y - y0) * factor..((tile_y + tile).min(height) - y0) * factor;
+                for y in keep_y.clone() {
+                    for x in keep_x.clone() {
+                        let (ux, uy) = (x as usize, y as usize);
+                        if ux >= ow || uy >= oh {
+                            continue;
+                        }
+                        let mut pixel = [0u8; 3];
+                        for (channel, value) in pixel.iter_mut().enumerate() {
+                            let v = values[channel * ow * oh + uy * ow + ux];
+                            *value = (v.clamp(0.0, 1.0) * 255.0).round() as u8;
+                        }
+                        canvas.put_pixel(x0 * factor + x, y0 * factor + y, image::Rgb(pixel));
+                    }
+                }
+            }
+        }
+
+        let output = DynamicImage::ImageRgb8(output.ok_or("图片为空")?);
+        // 模型只处理 RGB，透明通道单独放大后合并
+        if image.color().has_alpha() {
+            let (ow, oh) = output.dimensions();
+            let alpha = image.resize_exact(ow, oh, FilterType::Lanczos3).to_rgba8();
+            let mut rgba = output.to_rgba8();
+            for (pixel, alpha) in rgba.pixels_mut().zip(alpha.pixels()) {
+                pixel[3] = alpha[3];
+            }
+            return Ok(DynamicImage::ImageRgba8(rgba));
+        }
+        Ok(output)
+    }
+}
+
+fn upscale_onnx(
+    image: &DynamicImage,
+    params: &UpscaleParams,
+    width: u32,
+    height: u32,
+) -> Result<DynamicImage, String> {
+    let model_path = params
+        .model_path
+        .as_deref()
+        .filter(|p| !p.trim().is_empty())
+        .ok_or("ONNX 放大需要指定模型路径")?;
+    let upscaled = run_onnx_model(
+        image,
+        model_path,
+        params.runtime_path.as_deref(),
+        params.tile_size.unwrap_or(256),
+    )?;
+    // 模型倍数固定，再缩放到目标尺寸
+    if upscaled.dimensions() == (width, height) {
+        Ok(upscaled)
+    } else {
+        Ok(upscaled.resize_exact(width, height, FilterType::Lanczos3))
+    }
+}
+
+#[cfg(feature = "onnx-upscale")]
+fn run_onnx_model(
+    image: &DynamicImage,
+    model_path: &str,
+    runtime_path: Option<&str>,
+    tile_size: u32,
+) -> Result<DynamicImage, String> {
+    onnx::upscale(image, model_path, runtime_path, tile_size)
+}
+
+#[cfg(not(feature = "onnx-upscale"))]
+fn run_onnx_model(
+    _image: &DynamicImage,
+    _model_path: &str,
+    _runtime_path: Option<&str>,
+    _tile_size: u32,
+) -> Result<DynamicImage, String> {
+    Err("当前版本未启用 ONNX 超分（需要 onnx-upscale 特性）".to_string())
+}
+
+fn encode_png(image: &DynamicImage) -> Result<Vec<u8>, String> {
+    let mut output = Vec::new();
+    image
+        .write_to(&mut Cursor::new(&mut output), ImageFormat::Png)
+        .map_err(|e| format!("PNG 编码失败: {}", e))?;
+    Ok(output)
+}
+
+/// 放大单张图片，返回 PNG 数据与尺寸
+async fn upscale_bytes(
+    bytes: Vec<u8>,
+    params: &UpscaleParams,
+) -> Result<(Vec<u8>, u32, u32), String> {
+    let method = params
+        .method
+        .clone()
+        .unwrap_or_else(|| "lanczos".to_string());
+
+    if method == "sd-webui" {
+        let base_url = params
+            .base_url
+            .as_deref()
+            .filter(|url| !url.trim().is_empty())
+            .ok_or("SD WebUI 放大需要提供服务地址")?;
+        let (width, height) = image::ImageReader::new(Cursor::new(&bytes))
+            .with_guessed_format()
+            .map_err(|e| format!("图片解码失败: {}", e))?
+            .into_dimensions()
+            .map_err(|e| format!("图片解码失败: {}", e))?;
+        let (tw, _) = target_size(width, height, params.scale, params.target_long_edge)?;
+        let data = run_sd_webui_upscale(
+            base_url,
+            params.api_key.as_deref(),
+            &BASE64.encode(&bytes),
+            tw as f32 / width as f32,
+            params.upscaler.as_deref(),
+        )
+        .await?;
+        let output = decode_base64_image(&data)?;
+        let image =
+            image::load_from_memory(&output).map_err(|e| format!("放大结果解码失败: {}", e))?;
+        let (width, height) = image.dimensions();
+        return Ok((output, width, height));
+    }
+
+    let params = params.clone();
+    tokio::task::spawn_blocking(move || {
+        let image = image::load_from_memory(&bytes).map_err(|e| format!("图片解码失败: {}", e))?;
+        let (width, height) = image.dimensions();
+        let (tw, th) = target_size(width, height, params.scale, params.target_long_edge)?;
+        let output = match method.as_str() {
+            "lanczos" => upscale_lanczos(
+                &image,
+                tw,
+                th,
+                params.sharpen_sigma.unwrap_or(DEFAULT_SHARPEN_SIGMA),
+                params
+                    .sharpen_threshold
+                    .unwrap_or(DEFAULT_SHARPEN_THRESHOLD),
+            ),
+            "onnx" => upscale_onnx(&image, &params, tw, th)?,
+            other => return Err(format!("不支持的放大方式: {}", other)),
+        };
+        Ok((encode_png(&output)?, tw, th))
+    })
+    .await
+    .map_err(|e| format!("放大任务失败: {}", e))?
+}
+
+/// 沿用原图元数据，补充放大信息
+fn upscaled_generation(
+    source: Option<GenerationParams>,
+    method: &str,
+    width: u32,
+    height: u32,
+) -> GenerationParams {
+    let mut generation = source.unwrap_or_default();
+    let mut extra = match generation.extra.take() {
+        Some(Value::Object(map)) => map,
+        _ => serde_json::Map::new(),
+    };
+    extra.insert(
+        "upscale".to_string(),
+        json!({
+            "method": method,
+            "sourceWidth": generation.width,
+            "sourceHeight": generation.height,
+        }),
+    );
+    generation.width = Some(width);
+    generation.height = Some(height);
+    generation.extra = Some(Value::Object(extra));
+    generation
+}
+
+// Tauri 命令：放大图片，支持批量与整个画布
+#[tauri::command]
+pub async fn upscale_image(app: tauri::AppHandle, params: UpscaleParams) -> UpscaleResult {
+    println!(
+        "[Rust] upscale_image called, method: {}",
+        params.method.as_deref().unwrap_or("lanczos")
+    );
+
+    // 确定要处理的图片：base64、路径列表或整个画布
+    let mut sources: Vec<Option<String>> = params.image_paths.iter().cloned().map(Some).collect();
+    if sources.is_empty() && params.image_data.is_none() {
+        if let Some(canvas_id) = &params.canvas_id {
+            match list_canvas_images(app.clone(), canvas_id.clone()) {
+                Ok(images) => sources = images.into_iter().map(|image| Some(image.path)).collect(),
+                Err(e) => {
+                    return UpscaleResult {
+                        success: false,
+                        error: Some(e),
+                        ..UpscaleResult::default()
+                    }
+                }
+            }
+        }
+    }
+    if params.image_data.is_some() {
+        sources.insert(0, None);
+    }
+    if sources.is_empty() {
+        return UpscaleResult {
+            success: false,
+            error: Some("没有需要放大的图片".to_string()),
+            ..UpscaleResult::default()
+        };
+    }
+
+    let method = params
+        .method
+        .clone()
+        .unwrap_or_else(|| "lanczos".to_string());
+    let save = params.save_to_storage.unwrap_or(true);
+    let total = sources.len();
+    let mut items = Vec::new();
+
+    for (index, source_path) in sources.into_iter().enumerate() {
+        let bytes = match &source_path {
+            Some(path) => std::fs::read(path).map_err(|e| format!("读取图片失败: {}", e)),
+            None => decode_base64_image(params.image_data.as_deref().unwrap_or_default()),
+        };
+        let upscaled = match bytes {
+            Ok(bytes) => upscale_bytes(bytes, &params).await,
+            Err(e) => Err(e),
+        };
+
+        let item = match upscaled {
+            Ok((bytes, width, height)) => {
+                let data = BASE64.encode(&bytes);
+                let source_metadata = source_path
+                    .clone()
+                    .and_then(|path| read_image_metadata(path).ok().flatten());
+                if save {
+                    let (prompt, generation, canvas_id, node_id) = match source_metadata {
+                        Some(metadata) => (
+                            metadata.prompt,
+                            metadata.generation,
+                            metadata.canvas_id,
+                            metadata.node_id,
+                        ),
+                        None => (None, None, None, None),
+                    };
+                    let input_images = source_path.clone().map(|path| {
+                        vec![InputImageInfo {
+                            path: Some(path),
+                            label: "原图".to_string(),
+                        }]
+                    });
+                    match save_image(
+                        app.clone(),
+                        data,
+                        params.canvas_id.clone().or(canvas_id),
+                        params.node_id.clone().or(node_id),
+                        prompt,
+                        input_images,
+                        Some(ImageType::Generated),
+                        Some(upscaled_generation(generation, &method, width, height)),
+                    ) {
+                        Ok(info) => UpscaleItem {
+                            source_path: source_path.clone(),
+                            success: true,
+                            width: Some(width),
+                            height: Some(height),
+                            saved_image: Some(info),
+                            ..UpscaleItem::default()
+                        },
+                        Err(e) => UpscaleItem {
+                            source_path: source_path.clone(),
+                            error: Some(format!("保存图片失败: {}", e)),
+                            ..UpscaleItem::default()
+                        },
+                    }
+                } else {
+                    UpscaleItem {
+                        source_path: source_path.clone(),
+                        success: true,
+                        width: Some(width),
+                        height: Some(height),
+                        image_data: Some(data),
+                        ..UpscaleItem::default()
+                    }
+                }
+            }
+            Err(e) => {
+                println!("[Rust] Upscale failed for {:?}: {}", source_path, e);
+                UpscaleItem {
+                    source_path: source_path.clone(),
+                    error: Some(e),
+                    ..UpscaleItem::default()
+                }
+            }
+        };
+
+        let _ = app.emit(
+            "upscale-progress",
+            UpscaleProgressEvent {
+                request_id: params.request_id.clone(),
+                completed: index + 1,
+                total,
+                source_path,
+                success: item.success,
+            },
+        );
+        items.push(item);
+    }
+
+    let succeeded = items.iter().filter(|item| item.success).count();
+    println!("[Rust] upscale_image finished: {}/{}", succeeded, total);
+    UpscaleResult {
+        success: succeeded > 0,
+        error: if succeeded == 0 {
+            items.first().and_then(|item| item.error.clone())
+        } else {
+            None
+        },
+        items,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{Rgba, RgbaImage};
+
+    #[test]
+    fn test_target_size() {
+        assert_eq!(target_size(1024, 768, None, None).unwrap(), (2048, 1536));
+        assert_eq!(
+            target_size(1024, 576, None, Some(3840)).unwrap(),
+            (3840, 2160)
+        );
+        assert_eq!(target_size(100, 50, Some(1.5), None).unwrap(), (150, 75));
+        assert!(target_size(10000, 10000, Some(4.0), None).is_err());
+    }
+
+    #[test]
+    fn test_upscale_lanczos_keeps_alpha() {
+        let image = DynamicImage::ImageRgba8(RgbaImage::from_pixel(8, 4, Rgba([10, 20, 30, 0])));
+        let output = upscale_lanczos(&image, 16, 8, DEFAULT_SHARPEN_SIGMA, 2);
+        assert_eq!(output.dimensions(), (16, 8));
+        assert_eq!(output.to_rgba8().get_pixel(5, 5)[3], 0);
+    }
+
+    #[test]
+    fn test_upscaled_generation_merges_extra() {
+        let source = GenerationParams {
+            provider: Some("sd-webui".to_string()),
+            width: Some(512),
+            height: Some(512),
+            extra: Some(json!({ "Version": "v1" })),
+            ..GenerationParams::default()
+        };
+        let generation = upscaled_generation(Some(source), "lanczos", 1024, 1024);
+        assert_eq!(generation.provider.as_deref(), Some("sd-webui"));
+        assert_eq!(generation.width, Some(1024));
+        let extra = generation.extra.unwrap();
+        assert_eq!(extra["Version"], "v1");
+        assert_eq!(extra["upscale"]["sourceWidth"], 512);
+    }
+}
@@ -1,8 +1,12 @@
-use base64::{engine::general_purpose::STANDARD as BASE64, Engine as _};
-use reqwest::Client;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
-use std::time::Duration;
+
+use crate::storage::{VideoInfo, VideoMetadata};
+use crate::video_provider::{
+    content_result, create_video_task, get_video_source, get_video_task_status, store_task_video,
+    store_video_url, task_key, task_metadata, VideoDownloadTarget, VideoGenerationRequest,
+    VideoReference, VideoTaskHandle, VideoTaskState,
+};
 
 // ==================== 视频服务数据结构 ====================
 
@@ -20,7 +24,7 @@ pub struct VideoCreateParams {
 }
 
 // 视频任务响应
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Default, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct VideoTaskResult {
     pub success: bool,
@@ -30,6 +34,9 @@ pub struct VideoTaskResult {
     pub status: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub progress: Option<i32>,
+    // 统一后的状态，status / progress 保留服务返回的原值
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub state: Option<VideoTaskState>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub video_url: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -48,7 +55,7 @@ pub struct VideoTaskResult {
 pub struct VideoContentResult {
     pub success: bool,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub video_data: Option<String>, // base64 编码的视频数据
+    pub video: Option<VideoInfo>, // 保存到本地的视频
     #[serde(skip_serializing_if = "Option::is_none")]
     pub error: Option<String>,
 }
@@ -60,6 +67,12 @@ pub struct VideoStatusParams {
     pub base_url: String,
     pub api_key: String,
     pub task_id: String,
+    // 以下仅用于获取视频内容：保存位置、进度事件的 request_id 以及写入元数据的提示词和模型
+    pub canvas_id: Option<String>,
+    pub node_id: Option<String>,
+    pub request_id: Option<String>,
+    pub prompt: Option<String>,
+    pub model: Option<String>,
 }
 
 // new-api 通用视频创建任务参数
@@ -92,54 +105,13 @@ pub struct NewApiVideoCreateParams {
     pub metadata: Option<Value>,
 }
 
-#[derive(Debug, Serialize)]
-pub struct NewApiVideoRequest {
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub model: Option<String>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub prompt: Option<String>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub image: Option<String>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub duration: Option<f64>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub width: Option<i32>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub height: Option<i32>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub fps: Option<i32>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub seed: Option<i64>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub n: Option<i32>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub response_format: Option<String>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub user: Option<String>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub metadata: Option<Value>,
-}
-
-// API 响应结构
-#[derive(Debug, Deserialize)]
-struct VideoApiResponse {
-    id: Option<String>,
-    status: Option<String>,
-    progress: Option<i32>,
-    error: Option<VideoApiError>,
-}
-
-#[derive(Debug, Deserialize)]
-struct VideoApiError {
-    message: Option<String>,
-}
-
-fn empty_video_task_result(error: Option<String>) -> VideoTaskResult {
+pub(crate) fn empty_video_task_result(error: Option<String>) -> VideoTaskResult {
     VideoTaskResult {
         success: false,
         task_id: None,
         status: None,
         progress: None,
+        state: None,
         video_url: None,
         format: None,
         metadata: None,
@@ -148,13 +120,15 @@ fn empty_video_task_result(error: Option<String>) -> VideoTaskResult {
     }
 }
 
-fn parse_task_response(
+/// 解析各家任务响应：任务 ID 取 task_id 或 id，视频地址取 url 或 video_url
+pub(crate) fn parse_task_response(
     response_text: &str,
     fallback_task_id: Option<String>,
 ) -> Result<VideoTaskResult, String> {
     let raw: Value =
         serde_json::from_str(response_text).map_err(|e| format!("解析响应失败: {}", e))?;
 
+    // Kling 在成功的响应里也会带上 {"code": 0, "message": ""}，不算错误
     let error_message = raw.get("error").and_then(|error| {
         if error.is_string() {
             error.as_str().map(|s| s.to_string())
@@ -162,8 +136,14 @@ fn parse_task_response(
             error
                 .get("message")
                 .and_then(|message| message.as_str())
+                .filter(|message| !message.is_empty())
                 .map(|s| s.to_string())
-                .or_else(|| error.get("code").map(|code| code.to_string()))
+                .or_else(|| {
+                    error
+                        .get("code")
+                        .filter(|code| !code.is_null() && code.as_i64() != Some(0))
+                        .map(|code| code.to_string())
+                })
         }
     });
 
@@ -202,6 +182,7 @@ fn parse_task_response(
         task_id,
         status,
         progress,
+        state: None,
         video_url,
         format,
         metadata,
@@ -213,14 +194,14 @@ fn parse_task_response(
 // ==================== Veo 视频服务数据结构 ====================
 
 // Veo 参考图片
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 #[serde(rename_all = "camelCase")]
 pub struct VeoReferenceImage {
     pub image: VeoImageData,
     pub reference_type: String,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 #[serde(rename_all = "camelCase")]
 pub struct VeoImageData {
     pub bytes_base64_encoded: String,
@@ -229,7 +210,7 @@ pub struct VeoImageData {
 }
 
 // Veo metadata 参数
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct VeoMetadata {
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -241,6 +222,10 @@ pub struct VeoMetadata {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub person_generation: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
+    pub resolution: Option<String>, // "720p" / "1080p"
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub generate_audio: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub reference_images: Option<Vec<VeoReferenceImage>>,
 }
 
@@ -256,214 +241,222 @@ pub struct VeoCreateParams {
     pub metadata: Option<VeoMetadata>,
 }
 
-// Veo API 请求体
-#[derive(Debug, Serialize)]
-pub struct VeoApiRequest {
-    pub model: String,
-    pub prompt: String,
+// ==================== Kling 视频服务数据结构 ====================
+
+// Kling metadata 扩展参数
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub struct KlingMetadata {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub negative_prompt: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub images: Option<Vec<String>>,
+    pub style: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub metadata: Option<VeoMetadata>,
+    pub quality_level: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cfg_scale: Option<f64>, // 0-1，越大越贴合提示词
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub camera_control: Option<KlingCameraControl>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub static_mask: Option<String>, // 运动笔刷：静止区域蒙版
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dynamic_masks: Option<Vec<KlingDynamicMask>>, // 运动笔刷：运动区域及轨迹
 }
 
-// ==================== 创建视频任务 ====================
-
-#[tauri::command]
-pub async fn video_create_task(params: VideoCreateParams) -> VideoTaskResult {
-    println!("[Rust] video_create_task called");
-    println!("[Rust] base_url: {}", params.base_url);
-    println!("[Rust] model: {}", params.model);
-
-    // 创建 HTTP 客户端
-    let client = match Client::builder().timeout(Duration::from_secs(60)).build() {
-        Ok(c) => c,
-        Err(e) => {
-            return VideoTaskResult {
-                success: false,
-                task_id: None,
-                status: None,
-                progress: None,
-                video_url: None,
-                format: None,
-                metadata: None,
-                raw: None,
-                error: Some(format!("创建 HTTP 客户端失败: {}", e)),
-            }
-        }
-    };
+// Kling 镜头控制
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub struct KlingCameraControl {
+    // "simple" 为自定义运镜，其余为预设：down_back / forward_up / right_turn_forward / left_turn_forward
+    #[serde(rename = "type")]
+    pub kind: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub config: Option<KlingCameraConfig>, // 仅 simple 使用
+}
 
-    // 构建 multipart form
-    let mut form = reqwest::multipart::Form::new()
-        .text("model", params.model.clone())
-        .text("prompt", params.prompt.clone());
+// 自定义运镜，取值 -10 到 10，只能设置其中一项
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct KlingCameraConfig {
+    #[serde(default)]
+    pub horizontal: f64,
+    #[serde(default)]
+    pub vertical: f64,
+    #[serde(default)]
+    pub pan: f64,
+    #[serde(default)]
+    pub tilt: f64,
+    #[serde(default)]
+    pub roll: f64,
+    #[serde(default)]
+    pub zoom: f64,
+}
 
-    if let Some(seconds) = params.seconds {
-        form = form.text("seconds", seconds);
-    }
+// 运动笔刷的一个运动区域
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct KlingDynamicMask {
+    pub mask: String,
+    pub trajectories: Vec<KlingTrajectoryPoint>,
+}
 
-    if let Some(size) = params.size {
-        form = form.text("size", size);
-    }
+// 运动轨迹坐标，以图片左下角为原点
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct KlingTrajectoryPoint {
+    pub x: i32,
+    pub y: i32,
+}
 
-    // 添加参考图片
-    if let Some(image_base64) = params.input_image {
-        match BASE64.decode(&image_base64) {
-            Ok(image_bytes) => {
-                let part = reqwest::multipart::Part::bytes(image_bytes)
-                    .file_name("reference.png")
-                    .mime_str("image/png")
-                    .unwrap_or_else(|_| reqwest::multipart::Part::bytes(vec![]));
-                form = form.part("input_reference", part);
-            }
-            Err(e) => {
-                println!("[Rust] Failed to decode input image: {}", e);
-            }
-        }
-    }
+// Kling 创建任务参数
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct KlingCreateParams {
+    pub base_url: String,
+    pub api_key: String,
+    pub model: String,
+    pub prompt: String,
+    pub mode: String, // "text2video" / "image2video" / "multi-image2video"
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub image: Option<String>, // base64 编码的图片或 URL
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub image_tail: Option<String>, // 尾帧图片，仅 image2video
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub image_list: Option<Vec<String>>, // 多图参考，仅 multi-image2video
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub duration: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub width: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub height: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fps: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub seed: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub n: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<KlingMetadata>,
+}
 
-    // 构建 URL
-    let url = format!("{}/v1/videos", params.base_url.trim_end_matches('/'));
-    println!("[Rust] Request URL: {}", url);
+// Kling 获取任务状态参数
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct KlingStatusParams {
+    pub base_url: String,
+    pub api_key: String,
+    pub task_id: String,
+    pub mode: String, // "text2video" / "image2video" / "multi-image2video"
+}
 
-    // 发送请求
-    println!("[Rust] Sending video create request...");
-    let start_time = std::time::Instant::now();
+// Kling 视频内容结果（包含 URL）
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct KlingContentResult {
+    pub success: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub video_url: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub video_data: Option<String>, // base64 编码的视频数据
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
 
-    let response = match client
-        .post(&url)
-        .header("Authorization", format!("Bearer {}", params.api_key))
-        .multipart(form)
-        .send()
-        .await
-    {
-        Ok(r) => {
-            println!("[Rust] Response received in {:?}", start_time.elapsed());
-            r
-        }
-        Err(e) => {
-            println!("[Rust] Request failed: {}", e);
-            let error_msg = if e.is_timeout() {
-                "请求超时，请稍后重试".to_string()
-            } else if e.is_connect() {
-                "无法连接到服务器，请检查网络".to_string()
-            } else {
-                format!("请求失败: {}", e)
-            };
-            return VideoTaskResult {
-                success: false,
-                task_id: None,
-                status: None,
-                progress: None,
-                video_url: None,
-                format: None,
-                metadata: None,
-                raw: None,
-                error: Some(error_msg),
-            };
-        }
-    };
+// Kling 下载参数
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct KlingDownloadParams {
+    pub video_url: String,
+    pub task_id: Option<String>, // 同一任务重复下载时可续传
+    pub canvas_id: Option<String>,
+    pub node_id: Option<String>,
+    pub request_id: Option<String>,
+    pub metadata: Option<Value>, // 创建任务时返回的 metadata，记录到保存的视频中
+}
 
-    // 检查 HTTP 状态码
-    let status = response.status();
-    let response_text = match response.text().await {
-        Ok(t) => t,
-        Err(e) => {
-            return VideoTaskResult {
-                success: false,
-                task_id: None,
-                status: None,
-                progress: None,
-                video_url: None,
-                format: None,
-                metadata: None,
-                raw: None,
-                error: Some(format!("获取响应失败: {}", e)),
-            };
-        }
-    };
+// ==================== 各服务的视频命令 ====================
+// 保留前端原有的参数格式，请求统一交给 video_provider 中对应的适配器处理
 
-    if !status.is_success() {
-        println!("[Rust] Error response: {}", response_text);
-        return VideoTaskResult {
-            success: false,
-            task_id: None,
-            status: None,
-            progress: None,
-            video_url: None,
-            format: None,
-            metadata: None,
-            raw: None,
-            error: Some(format!("API 返回错误 ({}): {}", status, response_text)),
-        };
+fn status_handle(provider: &str, params: &VideoStatusParams) -> VideoTaskHandle {
+    VideoTaskHandle {
+        provider: provider.to_string(),
+        base_url: params.base_url.clone(),
+        api_key: params.api_key.clone(),
+        task_id: params.task_id.clone(),
+        mode: None,
     }
+}
 
-    // 解析响应
-
-    let api_response: VideoApiResponse = match serde_json::from_str(&response_text) {
-        Ok(r) => r,
-        Err(e) => {
-            println!("[Rust] Failed to parse JSON: {}", e);
-            return VideoTaskResult {
-                success: false,
-                task_id: None,
-                status: None,
-                progress: None,
-                video_url: None,
-                format: None,
-                metadata: None,
-                raw: None,
-                error: Some(format!("解析响应失败: {}", e)),
-            };
-        }
+/// 下载任务视频并保存到本地
+async fn store_content(
+    app: &tauri::AppHandle,
+    provider: &str,
+    params: VideoStatusParams,
+) -> VideoContentResult {
+    let handle = status_handle(provider, &params);
+    let mut metadata = task_metadata(&handle);
+    metadata.prompt = params.prompt.or(metadata.prompt);
+    metadata.model = params.model.or(metadata.model);
+    let target = VideoDownloadTarget {
+        request_id: params.request_id,
+        task_id: Some(task_key(provider, &params.task_id)),
+        canvas_id: params.canvas_id,
+        node_id: params.node_id,
+        metadata: Some(metadata),
     };
+    content_result(
+        store_task_video(app, &handle, target)
+            .await
+            .map(|(video, _)| video),
+    )
+}
 
-    // 检查 API 错误
-    if let Some(err) = api_response.error {
-        return VideoTaskResult {
-            success: false,
-            task_id: None,
-            status: None,
-            progress: None,
-            video_url: None,
-            format: None,
-            metadata: None,
-            raw: None,
-            error: err.message,
-        };
+fn kling_handle(params: KlingStatusParams) -> VideoTaskHandle {
+    VideoTaskHandle {
+        provider: "kling".to_string(),
+        base_url: params.base_url,
+        api_key: params.api_key,
+        task_id: params.task_id,
+        mode: Some(params.mode),
     }
+}
 
-    let task_id = api_response.id;
-    if task_id.is_none() {
-        return VideoTaskResult {
-            success: false,
-            task_id: None,
-            status: None,
-            progress: None,
-            video_url: None,
-            format: None,
-            metadata: None,
-            raw: None,
-            error: Some("API 未返回任务 ID".to_string()),
-        };
+/// 把宽高写成 "1280x720" 形式的分辨率
+fn resolution_from(width: Option<i32>, height: Option<i32>) -> Option<String> {
+    match (width, height) {
+        (Some(width), Some(height)) => Some(format!("{}x{}", width, height)),
+        _ => None,
     }
+}
 
-    println!("[Rust] Video task created: {:?}", task_id);
-
-    VideoTaskResult {
-        success: true,
-        task_id,
-        status: api_response.status,
-        progress: api_response.progress,
-        video_url: None,
-        format: None,
-        metadata: None,
-        raw: None,
-        error: None,
+/// 解析前端传入的时长，留空表示使用服务默认值
+fn parse_seconds(seconds: Option<&str>) -> Result<Option<f64>, String> {
+    let Some(seconds) = seconds.map(str::trim).filter(|s| !s.is_empty()) else {
+        return Ok(None);
+    };
+    match seconds.parse::<f64>() {
+        Ok(value) if value.is_finite() && value > 0.0 => Ok(Some(value)),
+        _ => Err(format!("无效的视频时长: {}", seconds)),
     }
 }
 
-// ==================== 获取视频任务状态 ====================
+#[tauri::command]
+pub async fn video_create_task(params: VideoCreateParams) -> VideoTaskResult {
+    println!("[Rust] video_create_task called, model: {}", params.model);
+    let duration = match parse_seconds(params.seconds.as_deref()) {
+        Ok(duration) => duration,
+        Err(e) => return empty_video_task_result(Some(e)),
+    };
+    create_video_task(&VideoGenerationRequest {
+        provider: "openai".to_string(),
+        base_url: params.base_url,
+        api_key: params.api_key,
+        model: params.model,
+        prompt: params.prompt,
+        first_frame: params.input_image,
+        duration,
+        resolution: params.size,
+        ..VideoGenerationRequest::default()
+    })
+    .await
+}
 
 #[tauri::command]
 pub async fn video_get_status(params: VideoStatusParams) -> VideoTaskResult {
@@ -471,222 +464,47 @@ pub async fn video_get_status(params: VideoStatusParams) -> VideoTaskResult {
         "[Rust] video_get_status called, task_id: {}",
         params.task_id
     );
+    get_video_task_status(&status_handle("openai", &params)).await
+}
 
-    // 创建 HTTP 客户端
-    let client = match Client::builder().timeout(Duration::from_secs(30)).build() {
-        Ok(c) => c,
-        Err(e) => {
-            return VideoTaskResult {
-                success: false,
-                task_id: None,
-                status: None,
-                progress: None,
-                video_url: None,
-                format: None,
-                metadata: None,
-                raw: None,
-                error: Some(format!("创建 HTTP 客户端失败: {}", e)),
-            }
-        }
-    };
-
-    // 构建 URL
-    let url = format!(
-        "{}/v1/videos/{}",
-        params.base_url.trim_end_matches('/'),
+#[tauri::command]
+pub async fn video_get_content(
+    app: tauri::AppHandle,
+    params: VideoStatusParams,
+) -> VideoContentResult {
+    println!(
+        "[Rust] video_get_content called, task_id: {}",
         params.task_id
     );
-
-    // 发送请求
-    let response = match client
-        .get(&url)
-        .header("Authorization", format!("Bearer {}", params.api_key))
-        .send()
-        .await
-    {
-        Ok(r) => r,
-        Err(e) => {
-            let error_msg = if e.is_timeout() {
-                "请求超时".to_string()
-            } else if e.is_connect() {
-                "无法连接到服务器".to_string()
-            } else {
-                format!("请求失败: {}", e)
-            };
-            return VideoTaskResult {
-                success: false,
-                task_id: None,
-                status: None,
-                progress: None,
-                video_url: None,
-                format: None,
-                metadata: None,
-                raw: None,
-                error: Some(error_msg),
-            };
-        }
-    };
-
-    // 检查 HTTP 状态码
-    let status = response.status();
-    let response_text = match response.text().await {
-        Ok(t) => t,
-        Err(e) => {
-            return VideoTaskResult {
-                success: false,
-                task_id: None,
-                status: None,
-                progress: None,
-                video_url: None,
-                format: None,
-                metadata: None,
-                raw: None,
-                error: Some(format!("获取响应失败: {}", e)),
-            };
-        }
-    };
-
-    if !status.is_success() {
-        return VideoTaskResult {
-            success: false,
-            task_id: None,
-            status: None,
-            progress: None,
-            video_url: None,
-            format: None,
-            metadata: None,
-            raw: None,
-            error: Some(format!("API 返回错误 ({}): {}", status, response_text)),
-        };
-    }
-
-    // 解析响应
-    let api_response: VideoApiResponse = match serde_json::from_str(&response_text) {
-        Ok(r) => r,
-        Err(e) => {
-            return VideoTaskResult {
-                success: false,
-                task_id: None,
-                status: None,
-                progress: None,
-                video_url: None,
-                format: None,
-                metadata: None,
-                raw: None,
-                error: Some(format!("解析响应失败: {}", e)),
-            };
-        }
-    };
-
-    // 检查 API 错误
-    if let Some(err) = api_response.error {
-        return VideoTaskResult {
-            success: false,
-            task_id: Some(params.task_id),
-            status: api_response.status,
-            progress: api_response.progress,
-            video_url: None,
-            format: None,
-            metadata: None,
-            raw: None,
-            error: err.message,
-        };
-    }
-
-    VideoTaskResult {
-        success: true,
-        task_id: Some(params.task_id),
-        status: api_response.status,
-        progress: api_response.progress,
-        video_url: None,
-        format: None,
-        metadata: None,
-        raw: None,
-        error: None,
-    }
+    store_content(&app, "openai", params).await
 }
 
-// ==================== 获取视频内容 ====================
-
-// ==================== new-api 通用视频任务 ====================
-
 #[tauri::command]
 pub async fn newapi_video_create_task(params: NewApiVideoCreateParams) -> VideoTaskResult {
-    println!("[Rust] newapi_video_create_task called");
-    println!("[Rust] base_url: {}", params.base_url);
-    println!("[Rust] model: {}", params.model);
-
-    let client = match Client::builder().timeout(Duration::from_secs(120)).build() {
-        Ok(c) => c,
-        Err(e) => return empty_video_task_result(Some(format!("创建 HTTP 客户端失败: {}", e))),
-    };
-
-    let request_body = NewApiVideoRequest {
-        model: Some(params.model.clone()),
-        prompt: Some(params.prompt.clone()),
-        image: params.image.clone(),
+    println!(
+        "[Rust] newapi_video_create_task called, model: {}",
+        params.model
+    );
+    create_video_task(&VideoGenerationRequest {
+        provider: "newapi".to_string(),
+        base_url: params.base_url,
+        api_key: params.api_key,
+        model: params.model,
+        prompt: params.prompt,
+        first_frame: params.image,
         duration: params.duration,
-        width: params.width,
-        height: params.height,
+        resolution: resolution_from(params.width, params.height),
         fps: params.fps,
         seed: params.seed,
         n: params.n,
-        response_format: params.response_format.clone(),
-        user: params.user.clone(),
-        metadata: params.metadata.clone(),
-    };
-
-    let url = format!(
-        "{}/v1/video/generations",
-        params.base_url.trim_end_matches('/')
-    );
-    println!("[Rust] Request URL: {}", url);
-
-    let response = match client
-        .post(&url)
-        .header("Authorization", format!("Bearer {}", params.api_key))
-        .header("Content-Type", "application/json")
-        .json(&request_body)
-        .send()
-        .await
-    {
-        Ok(r) => r,
-        Err(e) => {
-            let error_msg = if e.is_timeout() {
-                "请求超时，请稍后重试".to_string()
-            } else if e.is_connect() {
-                "无法连接到服务器，请检查网络".to_string()
-            } else {
-                format!("请求失败: {}", e)
-            };
-            return empty_video_task_result(Some(error_msg));
-        }
-    };
-
-    let status = response.status();
-    let response_text = match response.text().await {
-        Ok(t) => t,
-        Err(e) => return empty_video_task_result(Some(format!("获取响应失败: {}", e))),
-    };
-
-    if !status.is_success() {
-        println!("[Rust] Error response: {}", response_text);
-        return empty_video_task_result(Some(format!(
-            "API 返回错误 ({}): {}",
-            status, response_text
-        )));
-    }
-
-    match parse_task_response(&response_text, None) {
-        Ok(mut result) => {
-            if result.task_id.is_none() {
-                result.success = false;
-                result.error = Some("API 未返回任务 ID".to_string());
-            }
-            result
-        }
-        Err(e) => empty_video_task_result(Some(e)),
-    }
+        extras: Some(serde_json::json!({
+            "responseFormat": params.response_format,
+            "user": params.user,
+            "metadata": params.metadata,
+        })),
+        ..VideoGenerationRequest::default()
+    })
+    .await
 }
 
 #[tauri::command]
@@ -695,1280 +513,169 @@ pub async fn newapi_video_get_status(params: VideoStatusParams) -> VideoTaskResu
         "[Rust] newapi_video_get_status called, task_id: {}",
         params.task_id
     );
+    get_video_task_status(&status_handle("newapi", &params)).await
+}
 
-    let client = match Client::builder().timeout(Duration::from_secs(30)).build() {
-        Ok(c) => c,
-        Err(e) => return empty_video_task_result(Some(format!("创建 HTTP 客户端失败: {}", e))),
-    };
+#[tauri::command]
+pub async fn veo_create_task(params: VeoCreateParams) -> VideoTaskResult {
+    println!("[Rust] veo_create_task called, model: {}", params.model);
+    // images 依次为首帧、尾帧
+    let mut frames = params.images.unwrap_or_default().into_iter();
+    let metadata = params.metadata.unwrap_or_default();
+    create_video_task(&VideoGenerationRequest {
+        provider: "veo".to_string(),
+        base_url: params.base_url,
+        api_key: params.api_key,
+        model: params.model,
+        prompt: params.prompt,
+        negative_prompt: metadata.negative_prompt,
+        first_frame: frames.next(),
+        last_frame: frames.next(),
+        references: metadata
+            .reference_images
+            .unwrap_or_default()
+            .into_iter()
+            .map(|reference| VideoReference {
+                image: reference.image.bytes_base64_encoded,
+                kind: Some(reference.reference_type),
+            })
+            .collect(),
+        duration: metadata.duration_seconds.map(f64::from),
+        resolution: metadata.resolution,
+        aspect_ratio: metadata.aspect_ratio,
+        audio: metadata.generate_audio,
+        extras: Some(serde_json::json!({
+            "personGeneration": metadata.person_generation,
+        })),
+        ..VideoGenerationRequest::default()
+    })
+    .await
+}
 
-    let url = format!(
-        "{}/v1/video/generations/{}",
-        params.base_url.trim_end_matches('/'),
-        params.task_id
-    );
+#[tauri::command]
+pub async fn veo_get_status(params: VideoStatusParams) -> VideoTaskResult {
+    println!("[Rust] veo_get_status called, task_id: {}", params.task_id);
+    get_video_task_status(&status_handle("veo", &params)).await
+}
 
-    let response = match client
-        .get(&url)
-        .header("Authorization", format!("Bearer {}", params.api_key))
-        .send()
-        .await
-    {
-        Ok(r) => r,
-        Err(e) => {
-            let error_msg = if e.is_timeout() {
-                "请求超时".to_string()
-            } else if e.is_connect() {
-                "无法连接到服务器".to_string()
-            } else {
-                format!("请求失败: {}", e)
-            };
-            return empty_video_task_result(Some(error_msg));
-        }
-    };
+#[tauri::command]
+pub async fn veo_get_content(
+    app: tauri::AppHandle,
+    params: VideoStatusParams,
+) -> VideoContentResult {
+    println!("[Rust] veo_get_content called, task_id: {}", params.task_id);
+    store_content(&app, "veo", params).await
+}
 
-    let status = response.status();
-    let response_text = match response.text().await {
-        Ok(t) => t,
-        Err(e) => return empty_video_task_result(Some(format!("获取响应失败: {}", e))),
-    };
+#[tauri::command]
+pub async fn kling_create_task(params: KlingCreateParams) -> VideoTaskResult {
+    println!(
+        "[Rust] kling_create_task called, model: {}, mode: {}",
+        params.model, params.mode
+    );
+    let metadata = params.metadata.unwrap_or_default();
+    create_video_task(&VideoGenerationRequest {
+        provider: "kling".to_string(),
+        base_url: params.base_url,
+        api_key: params.api_key,
+        model: params.model,
+        prompt: params.prompt,
+        negative_prompt: metadata.negative_prompt,
+        first_frame: params.image,
+        last_frame: params.image_tail,
+        references: params
+            .image_list
+            .unwrap_or_default()
+            .into_iter()
+            .map(|image| VideoReference { image, kind: None })
+            .collect(),
+        duration: params.duration,
+        resolution: resolution_from(params.width, params.height),
+        fps: params.fps,
+        seed: params.seed,
+        n: params.n,
+        extras: Some(serde_json::json!({
+            "mode": params.mode,
+            "style": metadata.style,
+            "qualityLevel": metadata.quality_level,
+            "cfgScale": metadata.cfg_scale,
+            "cameraControl": metadata.camera_control,
+            "staticMask": metadata.static_mask,
+            "dynamicMasks": metadata.dynamic_masks,
+        })),
+        ..VideoGenerationRequest::default()
+    })
+    .await
+}
 
-    if !status.is_success() {
-        return empty_video_task_result(Some(format!(
-            "API 返回错误 ({}): {}",
-            status, response_text
-        )));
-    }
+#[tauri::command]
+pub async fn kling_get_status(params: KlingStatusParams) -> VideoTaskResult {
+    println!(
+        "[Rust] kling_get_status called, task_id: {}, mode: {}",
+        params.task_id, params.mode
+    );
+    get_video_task_status(&kling_handle(params)).await
+}
 
-    match parse_task_response(&response_text, Some(params.task_id)) {
-        Ok(result) => result,
-        Err(e) => empty_video_task_result(Some(e)),
+#[tauri::command]
+pub async fn kling_get_content(params: KlingStatusParams) -> KlingContentResult {
+    println!(
+        "[Rust] kling_get_content called, task_id: {}, mode: {}",
+        params.task_id, params.mode
+    );
+    match get_video_source(&kling_handle(params)).await {
+        Ok(source) => KlingContentResult {
+            success: true,
+            video_url: Some(source.url),
+            video_data: None,
+            error: None,
+        },
+        Err(e) => KlingContentResult {
+            success: false,
+            video_url: None,
+            video_data: None,
+            error: Some(e),
+        },
     }
 }
 
 #[tauri::command]
-pub async fn video_get_content(params: VideoStatusParams) -> VideoContentResult {
+pub async fn kling_download_video(
+    app: tauri::AppHandle,
+    params: KlingDownloadParams,
+) -> VideoContentResult {
     println!(
-        "[Rust] video_get_content called, task_id: {}",
-        params.task_id
+        "[Rust] kling_download_video called, url: {}",
+        params.video_url
     );
-
-    // 创建 HTTP 客户端（视频下载可能需要更长时间）
-    let client = match Client::builder().timeout(Duration::from_secs(300)).build() {
-        Ok(c) => c,
-        Err(e) => {
-            return VideoContentResult {
-                success: false,
-                video_data: None,
-                error: Some(format!("创建 HTTP 客户端失败: {}", e)),
-            }
-        }
-    };
-
-    // 构建 URL
-    let url = format!(
-        "{}/v1/videos/{}/content",
-        params.base_url.trim_end_matches('/'),
-        params.task_id
-    );
-    println!("[Rust] Fetching video content from: {}", url);
-
-    // 发送请求
-    let start_time = std::time::Instant::now();
-    let response = match client
-        .get(&url)
-        .header("Authorization", format!("Bearer {}", params.api_key))
-        .send()
-        .await
-    {
-        Ok(r) => {
-            println!(
-                "[Rust] Response headers received in {:?}",
-                start_time.elapsed()
-            );
-            r
-        }
-        Err(e) => {
-            let error_msg = if e.is_timeout() {
-                "下载超时，请稍后重试".to_string()
-            } else if e.is_connect() {
-                "无法连接到服务器".to_string()
-            } else {
-                format!("请求失败: {}", e)
-            };
-            return VideoContentResult {
-                success: false,
-                video_data: None,
-                error: Some(error_msg),
-            };
-        }
-    };
-
-    // 检查 HTTP 状态码
-    let status = response.status();
-    if !status.is_success() {
-        let error_text = response.text().await.unwrap_or_default();
-        return VideoContentResult {
-            success: false,
-            video_data: None,
-            error: Some(format!("获取视频失败 ({}): {}", status, error_text)),
-        };
-    }
-
-    // 获取视频数据
-    let video_bytes = match response.bytes().await {
-        Ok(b) => b,
-        Err(e) => {
-            return VideoContentResult {
-                success: false,
-                video_data: None,
-                error: Some(format!("下载视频失败: {}", e)),
-            };
-        }
-    };
-
-    println!(
-        "[Rust] Video downloaded: {} bytes in {:?}",
-        video_bytes.len(),
-        start_time.elapsed()
-    );
-
-    // 转换为 base64
-    let video_base64 = BASE64.encode(&video_bytes);
-
-    VideoContentResult {
-        success: true,
-        video_data: Some(video_base64),
-        error: None,
-    }
-}
-
-// ==================== Veo 创建视频任务 ====================
-
-#[tauri::command]
-pub async fn veo_create_task(params: VeoCreateParams) -> VideoTaskResult {
-    println!("[Rust] veo_create_task called");
-    println!("[Rust] base_url: {}", params.base_url);
-    println!("[Rust] model: {}", params.model);
-
-    // 创建 HTTP 客户端
-    let client = match Client::builder()
-        .timeout(Duration::from_secs(120)) // Veo 可能需要更长时间
-        .build()
-    {
-        Ok(c) => c,
-        Err(e) => {
-            return VideoTaskResult {
-                success: false,
-                task_id: None,
-                status: None,
-                progress: None,
-                video_url: None,
-                format: None,
-                metadata: None,
-                raw: None,
-                error: Some(format!("创建 HTTP 客户端失败: {}", e)),
-            }
-        }
-    };
-
-    // 构建请求体
-    let request_body = VeoApiRequest {
-        model: params.model.clone(),
-        prompt: params.prompt.clone(),
-        images: params.images.clone(),
-        metadata: params.metadata,
-    };
-
-    // 构建 URL
-    let url = format!("{}/v1/videos", params.base_url.trim_end_matches('/'));
-    println!("[Rust] Request URL: {}", url);
-
-    // 发送请求
-    println!("[Rust] Sending Veo create request...");
-    let start_time = std::time::Instant::now();
-
-    let response = match client
-        .post(&url)
-        .header("Authorization", format!("Bearer {}", params.api_key))
-        .header("Content-Type", "application/json")
-        .json(&request_body)
-        .send()
-        .await
-    {
-        Ok(r) => {
-            println!("[Rust] Response received in {:?}", start_time.elapsed());
-            r
-        }
-        Err(e) => {
-            println!("[Rust] Request failed: {}", e);
-            let error_msg = if e.is_timeout() {
-                "请求超时，请稍后重试".to_string()
-            } else if e.is_connect() {
-                "无法连接到服务器，请检查网络".to_string()
-            } else {
-                format!("请求失败: {}", e)
-            };
-            return VideoTaskResult {
-                success: false,
-                task_id: None,
-                status: None,
-                progress: None,
-                video_url: None,
-                format: None,
-                metadata: None,
-                raw: None,
-                error: Some(error_msg),
-            };
-        }
-    };
-
-    // 检查 HTTP 状态码
-    let status = response.status();
-    let response_text = match response.text().await {
-        Ok(t) => t,
-        Err(e) => {
-            return VideoTaskResult {
-                success: false,
-                task_id: None,
-                status: None,
-                progress: None,
-                video_url: None,
-                format: None,
-                metadata: None,
-                raw: None,
-                error: Some(format!("获取响应失败: {}", e)),
-            };
-        }
-    };
-
-    if !status.is_success() {
-        println!("[Rust] Error response: {}", response_text);
-        return VideoTaskResult {
-            success: false,
-            task_id: None,
-            status: None,
-            progress: None,
-            video_url: None,
-            format: None,
-            metadata: None,
-            raw: None,
-            error: Some(format!("API 返回错误 ({}): {}", status, response_text)),
-        };
-    }
-
-    // 解析响应（Veo 响应格式中 task_id 字段可能是 id 或 task_id）
-    #[derive(Debug, Deserialize)]
-    struct VeoApiResponse {
-        id: Option<String>,
-        task_id: Option<String>,
-        status: Option<String>,
-        progress: Option<i32>,
-        error: Option<VideoApiError>,
-    }
-
-    let api_response: VeoApiResponse = match serde_json::from_str(&response_text) {
-        Ok(r) => r,
-        Err(e) => {
-            println!("[Rust] Failed to parse JSON: {}", e);
-            println!("[Rust] Response text: {}", response_text);
-            return VideoTaskResult {
-                success: false,
-                task_id: None,
-                status: None,
-                progress: None,
-                video_url: None,
-                format: None,
-                metadata: None,
-                raw: None,
-                error: Some(format!("解析响应失败: {}", e)),
-            };
-        }
-    };
-
-    // 检查 API 错误
-    if let Some(err) = api_response.error {
-        return VideoTaskResult {
-            success: false,
-            task_id: None,
-            status: None,
-            progress: None,
-            video_url: None,
-            format: None,
-            metadata: None,
-            raw: None,
-            error: err.message,
-        };
-    }
-
-    // 优先使用 task_id，否则使用 id
-    let task_id = api_response.task_id.or(api_response.id);
-    if task_id.is_none() {
-        return VideoTaskResult {
-            success: false,
-            task_id: None,
-            status: None,
-            progress: None,
-            video_url: None,
-            format: None,
-            metadata: None,
-            raw: None,
-            error: Some("API 未返回任务 ID".to_string()),
-        };
-    }
-
-    println!("[Rust] Veo task created: {:?}", task_id);
-
-    VideoTaskResult {
-        success: true,
-        task_id,
-        status: api_response.status,
-        progress: api_response.progress,
-        video_url: None,
-        format: None,
-        metadata: None,
-        raw: None,
-        error: None,
-    }
-}
-
-// ==================== Veo 获取视频任务状态 ====================
-
-#[tauri::command]
-pub async fn veo_get_status(params: VideoStatusParams) -> VideoTaskResult {
-    println!("[Rust] veo_get_status called, task_id: {}", params.task_id);
-
-    // 创建 HTTP 客户端
-    let client = match Client::builder().timeout(Duration::from_secs(30)).build() {
-        Ok(c) => c,
-        Err(e) => {
-            return VideoTaskResult {
-                success: false,
-                task_id: None,
-                status: None,
-                progress: None,
-                video_url: None,
-                format: None,
-                metadata: None,
-                raw: None,
-                error: Some(format!("创建 HTTP 客户端失败: {}", e)),
-            }
-        }
-    };
-
-    // 构建 URL
-    let url = format!(
-        "{}/v1/videos/{}",
-        params.base_url.trim_end_matches('/'),
-        params.task_id
-    );
-
-    // 发送请求
-    let response = match client
-        .get(&url)
-        .header("Authorization", format!("Bearer {}", params.api_key))
-        .send()
-        .await
-    {
-        Ok(r) => r,
-        Err(e) => {
-            let error_msg = if e.is_timeout() {
-                "请求超时".to_string()
-            } else if e.is_connect() {
-                "无法连接到服务器".to_string()
-            } else {
-                format!("请求失败: {}", e)
-            };
-            return VideoTaskResult {
-                success: false,
-                task_id: None,
-                status: None,
-                progress: None,
-                video_url: None,
-                format: None,
-                metadata: None,
-                raw: None,
-                error: Some(error_msg),
-            };
-        }
-    };
-
-    // 检查 HTTP 状态码
-    let status = response.status();
-    let response_text = match response.text().await {
-        Ok(t) => t,
-        Err(e) => {
-            return VideoTaskResult {
-                success: false,
-                task_id: None,
-                status: None,
-                progress: None,
-                video_url: None,
-                format: None,
-                metadata: None,
-                raw: None,
-                error: Some(format!("获取响应失败: {}", e)),
-            };
-        }
-    };
-
-    if !status.is_success() {
-        return VideoTaskResult {
-            success: false,
-            task_id: None,
-            status: None,
-            progress: None,
-            video_url: None,
-            format: None,
-            metadata: None,
-            raw: None,
-            error: Some(format!("API 返回错误 ({}): {}", status, response_text)),
-        };
-    }
-
-    // 解析响应
-    let api_response: VideoApiResponse = match serde_json::from_str(&response_text) {
-        Ok(r) => r,
-        Err(e) => {
-            return VideoTaskResult {
-                success: false,
-                task_id: None,
-                status: None,
-                progress: None,
-                video_url: None,
-                format: None,
-                metadata: None,
-                raw: None,
-                error: Some(format!("解析响应失败: {}", e)),
-            };
-        }
-    };
-
-    // 检查 API 错误
-    if let Some(err) = api_response.error {
-        return VideoTaskResult {
-            success: false,
-            task_id: Some(params.task_id),
-            status: api_response.status,
-            progress: api_response.progress,
-            video_url: None,
-            format: None,
-            metadata: None,
-            raw: None,
-            error: err.message,
-        };
-    }
-
-    VideoTaskResult {
-        success: true,
-        task_id: Some(params.task_id),
-        status: api_response.status,
-        progress: api_response.progress,
-        video_url: None,
-        format: None,
-        metadata: None,
-        raw: None,
-        error: None,
-    }
-}
-
-// ==================== Veo 获取视频内容 ====================
-
-#[tauri::command]
-pub async fn veo_get_content(params: VideoStatusParams) -> VideoContentResult {
-    println!("[Rust] veo_get_content called, task_id: {}", params.task_id);
-
-    // 创建 HTTP 客户端（视频下载可能需要更长时间）
-    let client = match Client::builder().timeout(Duration::from_secs(300)).build() {
-        Ok(c) => c,
-        Err(e) => {
-            return VideoContentResult {
-                success: false,
-                video_data: None,
-                error: Some(format!("创建 HTTP 客户端失败: {}", e)),
-            }
-        }
-    };
-
-    // 构建 URL
-    let url = format!(
-        "{}/v1/videos/{}/content",
-        params.base_url.trim_end_matches('/'),
-        params.task_id
-    );
-    println!("[Rust] Fetching Veo video content from: {}", url);
-
-    // 发送请求
-    let start_time = std::time::Instant::now();
-    let response = match client
-        .get(&url)
-        .header("Authorization", format!("Bearer {}", params.api_key))
-        .send()
-        .await
-    {
-        Ok(r) => {
-            println!(
-                "[Rust] Response headers received in {:?}",
-                start_time.elapsed()
-            );
-            r
-        }
-        Err(e) => {
-            let error_msg = if e.is_timeout() {
-                "下载超时，请稍后重试".to_string()
-            } else if e.is_connect() {
-                "无法连接到服务器".to_string()
-            } else {
-                format!("请求失败: {}", e)
-            };
-            return VideoContentResult {
-                success: false,
-                video_data: None,
-                error: Some(error_msg),
-            };
-        }
-    };
-
-    // 检查 HTTP 状态码
-    let status = response.status();
-    if !status.is_success() {
-        let error_text = response.text().await.unwrap_or_default();
-        return VideoContentResult {
-            success: false,
-            video_data: None,
-            error: Some(format!("获取视频失败 ({}): {}", status, error_text)),
-        };
-    }
-
-    // 获取视频数据
-    let video_bytes = match response.bytes().await {
-        Ok(b) => b,
-        Err(e) => {
-            return VideoContentResult {
-                success: false,
-                video_data: None,
-                error: Some(format!("下载视频失败: {}", e)),
-            };
-        }
-    };
-
-    println!(
-        "[Rust] Veo video downloaded: {} bytes in {:?}",
-        video_bytes.len(),
-        start_time.elapsed()
-    );
-
-    // 转换为 base64
-    let video_base64 = BASE64.encode(&video_bytes);
-
-    VideoContentResult {
-        success: true,
-        video_data: Some(video_base64),
-        error: None,
-    }
-}
-
-// ==================== Kling 视频服务数据结构 ====================
-
-// Kling metadata 扩展参数
-#[derive(Debug, Serialize, Deserialize)]
-#[serde(rename_all = "snake_case")]
-pub struct KlingMetadata {
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub negative_prompt: Option<String>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub style: Option<String>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub quality_level: Option<String>,
-}
-
-// Kling 创建任务参数
-#[derive(Debug, Deserialize)]
-#[serde(rename_all = "camelCase")]
-pub struct KlingCreateParams {
-    pub base_url: String,
-    pub api_key: String,
-    pub model: String,
-    pub prompt: String,
-    pub mode: String, // "text2video" 或 "image2video"
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub image: Option<String>, // base64 编码的图片或 URL
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub duration: Option<f64>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub width: Option<i32>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub height: Option<i32>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub fps: Option<i32>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub seed: Option<i64>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub n: Option<i32>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub metadata: Option<KlingMetadata>,
-}
-
-// Kling 获取任务状态参数
-#[derive(Debug, Deserialize)]
-#[serde(rename_all = "camelCase")]
-pub struct KlingStatusParams {
-    pub base_url: String,
-    pub api_key: String,
-    pub task_id: String,
-    pub mode: String, // "text2video" 或 "image2video"
-}
-
-// Kling API 请求体
-#[derive(Debug, Serialize)]
-pub struct KlingApiRequest {
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub model: Option<String>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub prompt: Option<String>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub image: Option<String>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub duration: Option<f64>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub width: Option<i32>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub height: Option<i32>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub fps: Option<i32>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub seed: Option<i64>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub n: Option<i32>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub metadata: Option<KlingMetadata>,
-}
-
-// Kling 视频内容结果（包含 URL）
-#[derive(Debug, Serialize)]
-#[serde(rename_all = "camelCase")]
-pub struct KlingContentResult {
-    pub success: bool,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub video_url: Option<String>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub video_data: Option<String>, // base64 编码的视频数据
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub error: Option<String>,
-}
-
-// Kling 下载参数
-#[derive(Debug, Deserialize)]
-#[serde(rename_all = "camelCase")]
-pub struct KlingDownloadParams {
-    pub video_url: String,
-}
-
-// ==================== Kling 创建视频任务 ====================
-
-#[tauri::command]
-pub async fn kling_create_task(params: KlingCreateParams) -> VideoTaskResult {
-    println!("[Rust] kling_create_task called");
-    println!("[Rust] base_url: {}", params.base_url);
-    println!("[Rust] model: {}", params.model);
-    println!("[Rust] mode: {}", params.mode);
-
-    // 创建 HTTP 客户端
-    let client = match Client::builder().timeout(Duration::from_secs(120)).build() {
-        Ok(c) => c,
-        Err(e) => {
-            return VideoTaskResult {
-                success: false,
-                task_id: None,
-                status: None,
-                progress: None,
-                video_url: None,
-                format: None,
-                metadata: None,
-                raw: None,
-                error: Some(format!("创建 HTTP 客户端失败: {}", e)),
-            }
-        }
-    };
-
-    // 构建请求体
-    let request_body = KlingApiRequest {
-        model: Some(params.model.clone()),
-        prompt: Some(params.prompt.clone()),
-        image: params.image.clone(),
-        duration: params.duration,
-        width: params.width,
-        height: params.height,
-        fps: params.fps,
-        seed: params.seed,
-        n: params.n,
-        metadata: params.metadata,
-    };
-
-    // 构建 URL（根据模式选择端点）
-    let endpoint = if params.mode == "image2video" {
-        "image2video"
-    } else {
-        "text2video"
-    };
-
-    let url = format!(
-        "{}/kling/v1/videos/{}",
-        params.base_url.trim_end_matches('/'),
-        endpoint
-    );
-    println!("[Rust] Request URL: {}", url);
-
-    // 发送请求
-    println!("[Rust] Sending Kling create request...");
-    let start_time = std::time::Instant::now();
-
-    let response = match client
-        .post(&url)
-        .header("Authorization", format!("Bearer {}", params.api_key))
-        .header("Content-Type", "application/json")
-        .json(&request_body)
-        .send()
-        .await
-    {
-        Ok(r) => {
-            println!("[Rust] Response received in {:?}", start_time.elapsed());
-            r
-        }
-        Err(e) => {
-            println!("[Rust] Request failed: {}", e);
-            let error_msg = if e.is_timeout() {
-                "请求超时，请稍后重试".to_string()
-            } else if e.is_connect() {
-                "无法连接到服务器，请检查网络".to_string()
-            } else {
-                format!("请求失败: {}", e)
-            };
-            return VideoTaskResult {
-                success: false,
-                task_id: None,
-                status: None,
-                progress: None,
-                video_url: None,
-                format: None,
-                metadata: None,
-                raw: None,
-                error: Some(error_msg),
-            };
-        }
-    };
-
-    // 检查 HTTP 状态码
-    let status = response.status();
-    let response_text = match response.text().await {
-        Ok(t) => t,
-        Err(e) => {
-            return VideoTaskResult {
-                success: false,
-                task_id: None,
-                status: None,
-                progress: None,
-                video_url: None,
-                format: None,
-                metadata: None,
-                raw: None,
-                error: Some(format!("获取响应失败: {}", e)),
-            };
-        }
-    };
-
-    println!("[Rust] Kling response: {}", response_text);
-
-    if !status.is_success() {
-        println!("[Rust] Error response: {}", response_text);
-        // 尝试解析错误信息
-        #[derive(Debug, Deserialize)]
-        struct KlingErrorResponse {
-            error: Option<KlingApiError>,
-        }
-        #[allow(dead_code)]
-        #[derive(Debug, Deserialize)]
-        struct KlingApiError {
-            message: Option<String>,
-            #[serde(rename = "type")]
-            error_type: Option<String>,
-            code: Option<String>,
-        }
-
-        if let Ok(err_resp) = serde_json::from_str::<KlingErrorResponse>(&response_text) {
-            if let Some(err) = err_resp.error {
-                return VideoTaskResult {
-                    success: false,
-                    task_id: None,
-                    status: None,
-                    progress: None,
-                    video_url: None,
-                    format: None,
-                    metadata: None,
-                    raw: None,
-                    error: err.message.or(Some(format!("API 错误: {}", status))),
-                };
-            }
-        }
-
-        return VideoTaskResult {
-            success: false,
-            task_id: None,
-            status: None,
-            progress: None,
-            video_url: None,
-            format: None,
-            metadata: None,
-            raw: None,
-            error: Some(format!("API 返回错误 ({}): {}", status, response_text)),
-        };
-    }
-
-    // 解析响应
-    #[derive(Debug, Deserialize)]
-    struct KlingCreateResponse {
-        task_id: Option<String>,
-        status: Option<String>,
-    }
-
-    let api_response: KlingCreateResponse = match serde_json::from_str(&response_text) {
-        Ok(r) => r,
-        Err(e) => {
-            println!("[Rust] Failed to parse JSON: {}", e);
-            println!("[Rust] Response text: {}", response_text);
-            return VideoTaskResult {
-                success: false,
-                task_id: None,
-                status: None,
-                progress: None,
-                video_url: None,
-                format: None,
-                metadata: None,
-                raw: None,
-                error: Some(format!("解析响应失败: {}", e)),
-            };
-        }
-    };
-
-    let task_id = api_response.task_id;
-    if task_id.is_none() {
-        return VideoTaskResult {
-            success: false,
-            task_id: None,
-            status: None,
-            progress: None,
-            video_url: None,
-            format: None,
-            metadata: None,
-            raw: None,
-            error: Some("API 未返回任务 ID".to_string()),
-        };
-    }
-
-    println!("[Rust] Kling task created: {:?}", task_id);
-
-    VideoTaskResult {
-        success: true,
-        task_id,
-        status: api_response.status,
-        progress: None,
-        video_url: None,
-        format: None,
-        metadata: None,
-        raw: None,
-        error: None,
-    }
-}
-
-// ==================== Kling 获取视频任务状态 ====================
-
-#[tauri::command]
-pub async fn kling_get_status(params: KlingStatusParams) -> VideoTaskResult {
-    println!(
-        "[Rust] kling_get_status called, task_id: {}, mode: {}",
-        params.task_id, params.mode
-    );
-
-    // 创建 HTTP 客户端
-    let client = match Client::builder().timeout(Duration::from_secs(30)).build() {
-        Ok(c) => c,
-        Err(e) => {
-            return VideoTaskResult {
-                success: false,
-                task_id: None,
-                status: None,
-                progress: None,
-                video_url: None,
-                format: None,
-                metadata: None,
-                raw: None,
-                error: Some(format!("创建 HTTP 客户端失败: {}", e)),
-            }
-        }
-    };
-
-    // 构建 URL（根据模式选择端点）
-    let endpoint = if params.mode == "image2video" {
-        "image2video"
-    } else {
-        "text2video"
-    };
-
-    let url = format!(
-        "{}/kling/v1/videos/{}/{}",
-        params.base_url.trim_end_matches('/'),
-        endpoint,
-        params.task_id
-    );
-    println!("[Rust] Status URL: {}", url);
-
-    // 发送请求
-    let response = match client
-        .get(&url)
-        .header("Authorization", format!("Bearer {}", params.api_key))
-        .send()
-        .await
-    {
-        Ok(r) => r,
-        Err(e) => {
-            let error_msg = if e.is_timeout() {
-                "请求超时".to_string()
-            } else if e.is_connect() {
-                "无法连接到服务器".to_string()
-            } else {
-                format!("请求失败: {}", e)
-            };
-            return VideoTaskResult {
-                success: false,
-                task_id: None,
-                status: None,
-                progress: None,
-                video_url: None,
-                format: None,
-                metadata: None,
-                raw: None,
-                error: Some(error_msg),
-            };
-        }
-    };
-
-    // 检查 HTTP 状态码
-    let status = response.status();
-    let response_text = match response.text().await {
-        Ok(t) => t,
-        Err(e) => {
-            return VideoTaskResult {
-                success: false,
-                task_id: None,
-                status: None,
-                progress: None,
-                video_url: None,
-                format: None,
-                metadata: None,
-                raw: None,
-                error: Some(format!("获取响应失败: {}", e)),
-            };
-        }
-    };
-
-    println!("[Rust] Kling status response: {}", response_text);
-
-    if !status.is_success() {
-        return VideoTaskResult {
-            success: false,
-            task_id: None,
-            status: None,
-            progress: None,
-            video_url: None,
-            format: None,
-            metadata: None,
-            raw: None,
-            error: Some(format!("API 返回错误 ({}): {}", status, response_text)),
-        };
-    }
-
-    // 解析响应
-    #[allow(dead_code)]
-    #[derive(Debug, Deserialize)]
-    struct KlingStatusResponse {
-        task_id: Option<String>,
-        status: Option<String>,
-        url: Option<String>,
-        format: Option<String>,
-        metadata: Option<serde_json::Value>,
-        error: Option<KlingStatusError>,
-    }
-
-    #[derive(Debug, Deserialize)]
-    struct KlingStatusError {
-        code: Option<i32>,
-        message: Option<String>,
-    }
-
-    let api_response: KlingStatusResponse = match serde_json::from_str(&response_text) {
-        Ok(r) => r,
-        Err(e) => {
-            return VideoTaskResult {
-                success: false,
-                task_id: None,
-                status: None,
-                progress: None,
-                video_url: None,
-                format: None,
-                metadata: None,
-                raw: None,
-                error: Some(format!("解析响应失败: {}", e)),
-            };
-        }
-    };
-
-    // 检查 API 错误
-    if let Some(err) = api_response.error {
-        if err.message.is_some() && err.code.unwrap_or(0) != 0 {
-            return VideoTaskResult {
-                success: false,
-                task_id: Some(params.task_id),
-                status: api_response.status,
-                progress: None,
-                video_url: None,
-                format: None,
-                metadata: None,
-                raw: None,
-                error: err.message,
-            };
-        }
-    }
-
-    VideoTaskResult {
-        success: true,
-        task_id: Some(params.task_id),
-        status: api_response.status,
-        progress: None,
-        video_url: None,
-        format: None,
-        metadata: None,
-        raw: None,
-        error: None,
-    }
-}
-
-// ==================== Kling 获取视频内容（URL 或下载） ====================
-
-#[tauri::command]
-pub async fn kling_get_content(params: KlingStatusParams) -> KlingContentResult {
-    println!(
-        "[Rust] kling_get_content called, task_id: {}, mode: {}",
-        params.task_id, params.mode
-    );
-
-    // 创建 HTTP 客户端
-    let client = match Client::builder().timeout(Duration::from_secs(30)).build() {
-        Ok(c) => c,
-        Err(e) => {
-            return KlingContentResult {
-                success: false,
-                video_url: None,
-                video_data: None,
-                error: Some(format!("创建 HTTP 客户端失败: {}", e)),
-            }
-        }
-    };
-
-    // 构建 URL（根据模式选择端点）
-    let endpoint = if params.mode == "image2video" {
-        "image2video"
-    } else {
-        "text2video"
-    };
-
-    let url = format!(
-        "{}/kling/v1/videos/{}/{}",
-        params.base_url.trim_end_matches('/'),
-        endpoint,
-        params.task_id
-    );
-
-    // 发送请求获取状态（包含视频 URL）
-    let response = match client
-        .get(&url)
-        .header("Authorization", format!("Bearer {}", params.api_key))
-        .send()
-        .await
-    {
-        Ok(r) => r,
-        Err(e) => {
-            let error_msg = if e.is_timeout() {
-                "请求超时".to_string()
-            } else if e.is_connect() {
-                "无法连接到服务器".to_string()
-            } else {
-                format!("请求失败: {}", e)
-            };
-            return KlingContentResult {
-                success: false,
-                video_url: None,
-                video_data: None,
-                error: Some(error_msg),
-            };
-        }
-    };
-
-    // 检查 HTTP 状态码
-    let status = response.status();
-    let response_text = match response.text().await {
-        Ok(t) => t,
-        Err(e) => {
-            return KlingContentResult {
-                success: false,
-                video_url: None,
-                video_data: None,
-                error: Some(format!("获取响应失败: {}", e)),
-            };
-        }
-    };
-
-    if !status.is_success() {
-        return KlingContentResult {
-            success: false,
-            video_url: None,
-            video_data: None,
-            error: Some(format!("API 返回错误 ({}): {}", status, response_text)),
-        };
-    }
-
-    // 解析响应获取视频 URL
-    #[derive(Debug, Deserialize)]
-    struct KlingContentResponse {
-        status: Option<String>,
-        url: Option<String>,
-    }
-
-    let api_response: KlingContentResponse = match serde_json::from_str(&response_text) {
-        Ok(r) => r,
-        Err(e) => {
-            return KlingContentResult {
-                success: false,
-                video_url: None,
-                video_data: None,
-                error: Some(format!("解析响应失败: {}", e)),
-            };
-        }
-    };
-
-    // 检查任务是否完成
-    if api_response.status.as_deref() != Some("completed") {
-        return KlingContentResult {
-            success: false,
-            video_url: None,
-            video_data: None,
-            error: Some(format!("任务尚未完成，当前状态: {:?}", api_response.status)),
-        };
-    }
-
-    // 获取视频 URL
-    let video_url = match api_response.url {
-        Some(u) => u,
-        None => {
-            return KlingContentResult {
-                success: false,
-                video_url: None,
-                video_data: None,
-                error: Some("API 未返回视频 URL".to_string()),
-            };
-        }
-    };
-
-    println!("[Rust] Kling video URL: {}", video_url);
-
-    KlingContentResult {
-        success: true,
-        video_url: Some(video_url),
-        video_data: None,
-        error: None,
-    }
-}
-
-// ==================== Kling 下载视频 ====================
-
-#[tauri::command]
-pub async fn kling_download_video(params: KlingDownloadParams) -> VideoContentResult {
-    println!(
-        "[Rust] kling_download_video called, url: {}",
-        params.video_url
-    );
-
-    // 创建 HTTP 客户端（视频下载可能需要更长时间）
-    let client = match Client::builder().timeout(Duration::from_secs(300)).build() {
-        Ok(c) => c,
-        Err(e) => {
-            return VideoContentResult {
-                success: false,
-                video_data: None,
-                error: Some(format!("创建 HTTP 客户端失败: {}", e)),
-            }
-        }
-    };
-
-    // 发送请求下载视频
-    let start_time = std::time::Instant::now();
-    let response = match client.get(&params.video_url).send().await {
-        Ok(r) => {
-            println!(
-                "[Rust] Response headers received in {:?}",
-                start_time.elapsed()
-            );
-            r
-        }
-        Err(e) => {
-            let error_msg = if e.is_timeout() {
-                "下载超时，请稍后重试".to_string()
-            } else if e.is_connect() {
-                "无法连接到服务器".to_string()
-            } else {
-                format!("请求失败: {}", e)
-            };
-            return VideoContentResult {
-                success: false,
-                video_data: None,
-                error: Some(error_msg),
-            };
-        }
-    };
-
-    // 检查 HTTP 状态码
-    let status = response.status();
-    if !status.is_success() {
-        let error_text = response.text().await.unwrap_or_default();
-        return VideoContentResult {
-            success: false,
-            video_data: None,
-            error: Some(format!("下载视频失败 ({}): {}", status, error_text)),
-        };
-    }
-
-    // 获取视频数据
-    let video_bytes = match response.bytes().await {
-        Ok(b) => b,
-        Err(e) => {
-            return VideoContentResult {
-                success: false,
-                video_data: None,
-                error: Some(format!("下载视频失败: {}", e)),
-            };
-        }
-    };
-
-    println!(
-        "[Rust] Kling video downloaded: {} bytes in {:?}",
-        video_bytes.len(),
-        start_time.elapsed()
-    );
-
-    // 转换为 base64
-    let video_base64 = BASE64.encode(&video_bytes);
-
-    VideoContentResult {
-        success: true,
-        video_data: Some(video_base64),
-        error: None,
+    let target = VideoDownloadTarget {
+        request_id: params.request_id,
+        task_id: params.task_id.map(|id| format!("kling_{}", id)),
+        canvas_id: params.canvas_id,
+        node_id: params.node_id,
+        metadata: Some(VideoMetadata {
+            provider: Some("kling".to_string()),
+            source_url: Some(params.video_url.clone()),
+            created_at: chrono::Utc::now().timestamp(),
+            extra: params.metadata,
+            ..VideoMetadata::default()
+        }),
+    };
+    content_result(store_video_url(&app, &params.video_url, target).await)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_seconds() {
+        assert_eq!(parse_seconds(None), Ok(None));
+        assert_eq!(parse_seconds(Some("  ")), Ok(None));
+        assert_eq!(parse_seconds(Some(" 8 ")), Ok(Some(8.0)));
+        assert_eq!(parse_seconds(Some("4.5")), Ok(Some(4.5)));
+        assert!(parse_seconds(Some("8s")).is_err());
+        assert!(parse_seconds(Some("0")).is_err());
+        assert!(parse_seconds(Some("NaN")).is_err());
     }
 }
@@ -0,0 +1,426 @@
+use futures_util::StreamExt;
+use reqwest::header::{CONTENT_RANGE, RANGE};
+use reqwest::{Client, StatusCode};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tauri::Emitter;
+use tokio::io::AsyncWriteExt;
+
+use super::VideoSource;
+use crate::image_task::request_error;
+use crate::storage::{get_video_partial_dir, store_video_file, VideoInfo, VideoMetadata};
+
+const MAX_ATTEMPTS: usize = 4;
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(500);
+
+lazy_static::lazy_static! {
+    // 正在写入的临时文件，同一任务的并发下载排队，避免交错写入同一个文件
+    static ref PARTIAL_LOCKS: Mutex<HashMap<PathBuf, Arc<tokio::sync::Mutex<()>>>> =
+        Mutex::new(HashMap::new());
+}
+
+// 下载进度事件（video-download-progress）
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VideoDownloadProgressEvent {
+    pub request_id: Option<String>,
+    pub task_id: Option<String>,
+    pub downloaded: u64,
+    pub total: Option<u64>,
+    pub resumed: bool, // 本次请求是从断点续传
+}
+
+// 下载保存的位置与元数据
+#[derive(Debug, Clone, Default)]
+pub struct VideoDownloadTarget {
+    pub request_id: Option<String>, // 用于区分进度事件
+    pub task_id: Option<String>,    // 同一任务重复下载时复用未完成的临时文件
+    pub canvas_id: Option<String>,
+    pub node_id: Option<String>,
+    pub metadata: Option<VideoMetadata>,
+}
+
+enum DownloadError {
+    Interrupted(String), // 可以续传
+    Fatal(String),
+}
+
+/// 临时文件名：有任务 ID 时按任务区分，否则按地址
+fn partial_name(source: &VideoSource, task_id: Option<&str>) -> String {
+    match task_id.filter(|id| !id.trim().is_empty()) {
+        Some(id) => {
+            let safe: String = id
+                .chars()
+                .map(|c| {
+                    if c.is_ascii_alphanumeric() || c == '-' || c == '_' {
+                        c
+                    } else {
+                        '_'
+                    }
+                })
+                .collect();
+            format!("{}.part", safe)
+        }
+        None => format!("{:08x}.part", crc32fast::hash(source.url.as_bytes())),
+    }
+}
+
+/// 从 "bytes 100-199/1000" 中解析起始位置和总大小
+fn parse_content_range(value: &str) -> (Option<u64>, Option<u64>) {
+    let Some(range) = value.trim().strip_prefix("bytes ") else {
+        return (None, None);
+    };
+    let (span, total) = range.split_once('/').unwrap_or((range, "*"));
+    let start = span
+        .split_once('-')
+        .and_then(|(start, _)| start.parse().ok());
+    (start, total.parse().ok())
+}
+
+async fn remove_partial(path: &Path) {
+    let _ = tokio::fs::remove_file(path).await;
+}
+
+/// 下载一次，已有临时文件时用 Range 续传；返回下载完成后的文件大小
+async fn download_once(
+    client: &Client,
+    source: &VideoSource,
+    api_key: &str,
+    path: &Path,
+    on_progress: &mut (dyn FnMut(u64, Option<u64>, bool) + Send),
+) -> Result<u64, DownloadError> {
+    let existing = tokio::fs::metadata(path)
+        .await
+        .map(|m| m.len())
+        .unwrap_or(0);
+    let mut builder = client.get(&source.url);
+    if source.authorized {
+        builder = builder.header("Authorization", format!("Bearer {}", api_key));
+    }
+    if existing > 0 {
+        builder = builder.header(RANGE, format!("bytes={}-", existing));
+    }
+    let response = builder
+        .send()
+        .await
+        .map_err(|e| DownloadError::Interrupted(request_error(e)))?;
+
+    let status = response.status();
+    let content_range = response
+        .headers()
+        .get(CONTENT_RANGE)
+        .and_then(|value| value.to_str().ok())
+        .map(parse_content_range)
+        .unwrap_or((None, None));
+
+    let (append, mut total) = match status {
+        StatusCode::PARTIAL_CONTENT => {
+            if content_range.0 != Some(existing) {
+                remove_partial(path).await;
+                return Err(DownloadError::Interrupted(
+                    "续传位置不一致，重新下载".to_string(),
+                ));
+            }
+            (true, content_range.1)
+        }
+        // 临时文件可能已经完整
+        StatusCode::RANGE_NOT_SATISFIABLE if existing > 0 => {
+            if content_range.1 == Some(existing) {
+                return Ok(existing);
+            }
+            remove_partial(path).await;
+            return Err(DownloadError::Interrupted(
+                "续传位置无效，重新下载".to_string(),
+            ));
+        }
+        // 服务器不支持 Range 时返回完整内容，从头写入
+        status if status.is_success() => (false, response.content_length()),
+        status => {
+            let text = response.text().await.unwrap_or_default();
+            let message = format!("下载视频失败 ({}): {}", status, text);
+            return Err(if status.is_server_error() {
+                DownloadError::Interrupted(message)
+            } else {
+                DownloadError::Fatal(message)
+            });
+        }
+    };
+    if append {
+        total = total.or_else(|| response.content_length().map(|len| len + existing));
+    }
+
+    let mut file = tokio::fs::OpenOptions::new()
+        .create(true)
+        .write(true)
+        .append(append)
+        .truncate(!append)
+        .open(path)
+        .await
+        .map_err(|e| DownloadError::Fatal(format!("创建临时文件失败: {}", e)))?;
+    let mut downloaded = if append { existing } else { 0 };
+    on_progress(downloaded, total, append);
+
+    let mut last_report = Instant::now();
+    let mut stream = response.bytes_stream();
+    while let Some(chunk) = stream.next().await {
+        let chunk = match chunk {
+            Ok(chunk) => chunk,
+            Err(e) => {
+                let _ = file.flush().await;
+                return Err(DownloadError::Interrupted(format!("下载中断: {}", e)));
+            }
+        };
+        file.write_all(&chunk)
+            .await
+            .map_err(|e| DownloadError::Fatal(format!("写入视频失败: {}", e)))?;
+        downloaded += chunk.len() as u64;
+        if last_report.elapsed() >= PROGRESS_INTERVAL {
+            last_report = Instant::now();
+            on_progress(downloaded, total, append);
+        }
+    }
+    file.flush()
+        .await
+        .map_err(|e| DownloadError::Fatal(format!("写入视频失败: {}", e)))?;
+    on_progress(downloaded, total, append);
+
+    // 校验大小
+    match total {
+        Some(total) if downloaded < total => Err(DownloadError::Interrupted(format!(
+            "下载不完整: {}/{} 字节",
+            downloaded, total
+        ))),
+        Some(total) if downloaded > total => {
+            remove_partial(path).await;
+            Err(DownloadError::Fatal(format!(
+                "视频大小校验失败: 收到 {} 字节，应为 {} 字节",
+                downloaded, total
+            )))
+        }
+        _ if downloaded == 0 => {
+            remove_partial(path).await;
+            Err(DownloadError::Fatal("下载的视频为空".to_string()))
+        }
+        _ => Ok(downloaded),
+    }
+}
+
+/// 下载到临时文件，中断后自动续传，返回临时文件路径
+async fn download_to_partial(
+    client: &Client,
+    source: &VideoSource,
+    api_key: &str,
+    path: PathBuf,
+    on_progress: &mut (dyn FnMut(u64, Option<u64>, bool) + Send),
+) -> Result<PathBuf, String> {
+    let start_time = Instant::now();
+    let mut last_error = String::new();
+    for attempt in 1..=MAX_ATTEMPTS {
+        match download_once(client, source, api_key, &path, on_progress).await {
+            Ok(size) => {
+                println!(
+                    "[Rust] Video downloaded: {} bytes in {:?}",
+                    size,
+                    start_time.elapsed()
+                );
+                return Ok(path);
+            }
+            Err(DownloadError::Fatal(e)) => return Err(e),
+            Err(DownloadError::Interrupted(e)) => {
+                println!(
+                    "[Rust] Video download attempt {}/{} interrupted: {}",
+                    attempt, MAX_ATTEMPTS, e
+                );
+                last_error = e;
+                if attempt < MAX_ATTEMPTS {
+                    tokio::time::sleep(Duration::from_secs(attempt as u64)).await;
+                }
+            }
+        }
+    }
+    Err(last_error)
+}
+
+fn partial_lock(path: &Path) -> Arc<tokio::sync::Mutex<()>> {
+    PARTIAL_LOCKS
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .entry(path.to_path_buf())
+        .or_default()
+        .clone()
+}
+
+/// 释放临时文件的锁，没有其他下载在等待时移除记录
+fn release_partial_lock(path: &Path, lock: Arc<tokio::sync::Mutex<()>>) {
+    let mut locks = PARTIAL_LOCKS.lock().unwrap_or_else(|e| e.into_inner());
+    // 只剩表中和这里两个引用
+    if Arc::strong_count(&lock) <= 2 {
+        locks.remove(path);
+    }
+}
+
+/// 流式下载视频并保存到本地，通过 `video-download-progress` 事件上报进度
+pub async fn download_to_storage(
+    app: &tauri::AppHandle,
+    client: &Client,
+    source: &VideoSource,
+    api_key: &str,
+    target: VideoDownloadTarget,
+) -> Result<VideoInfo, String> {
+    let path = get_video_partial_dir(app)?.join(partial_name(source, target.task_id.as_deref()));
+    let mut on_progress = |downloaded: u64, total: Option<u64>, resumed: bool| {
+        let _ = app.emit(
+            "video-download-progress",
+            VideoDownloadProgressEvent {
+                request_id: target.request_id.clone(),
+                task_id: target.task_id.clone(),
+                downloaded,
+                total,
+                resumed,
+            },
+        );
+    };
+    let lock = partial_lock(&path);
+    let result = {
+        let _guard = lock.lock().await;
+        match download_to_partial(client, source, api_key, path.clone(), &mut on_progress).await {
+            Ok(path) => store_video_file(
+                app,
+                &path,
+                target.canvas_id.clone(),
+                target.node_id.clone(),
+                target.metadata,
+            ),
+            Err(e) => Err(e),
+        }
+    };
+    release_partial_lock(&path, lock);
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{mock_http, MockRequest, MockResponse};
+
+    #[test]
+    fn test_parse_content_range() {
+        assert_eq!(
+            parse_content_range("bytes 100-199/1000"),
+            (Some(100), Some(1000))
+        );
+        assert_eq!(parse_content_range("bytes */1000"), (None, Some(1000)));
+        assert_eq!(parse_content_range("bytes 0-99/*"), (Some(0), None));
+        assert_eq!(parse_content_range("items 0-1/2"), (None, None));
+    }
+
+    #[test]
+    fn test_partial_name() {
+        let source = VideoSource {
+            url: "https://cdn/v.mp4".to_string(),
+            authorized: false,
+        };
+        assert_eq!(
+            partial_name(&source, Some("video/abc:1")),
+            "video_abc_1.part"
+        );
+        assert!(partial_name(&source, None).ends_with(".part"));
+        assert_eq!(
+            partial_name(&source, None),
+            partial_name(&source, Some(" "))
+        );
+    }
+
+    // 按路径返回不同状态的本地服务：/206 续传，/200 忽略 Range，/416 已完整
+    async fn start_mock_server(ranges: Arc<Mutex<Vec<Option<String>>>>) -> String {
+        mock_http(move |request: MockRequest| {
+            ranges
+                .lock()
+                .unwrap()
+                .push(request.header("range").map(|v| v.to_string()));
+            let response = match request.path.as_str() {
+                "/206" => MockResponse::new("206 Partial Content", "video/mp4", b"world".to_vec())
+                    .header("Content-Range", "bytes 6-10/11"),
+                "/416" => MockResponse::new("416 Range Not Satisfiable", "video/mp4", Vec::new())
+                    .header("Content-Range", "bytes */11"),
+                _ => MockResponse::new("200 OK", "video/mp4", b"hello world".to_vec()),
+            };
+            async move { response }
+        })
+        .await
+    }
+
+    #[tokio::test]
+    async fn test_resume_against_mock_server() {
+        let ranges = Arc::new(Mutex::new(Vec::new()));
+        let base_url = start_mock_server(ranges.clone()).await;
+        let dir = std::env::temp_dir().join(format!("video-resume-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let client = Client::new();
+
+        let run = |name: &'static str, existing: &'static [u8]| {
+            let path = dir.join(format!("{}.part", name));
+            std::fs::write(&path, existing).unwrap();
+            let source = VideoSource {
+                url: format!("{}/{}", base_url, name),
+                authorized: false,
+            };
+            let client = client.clone();
+            async move {
+                let mut resumed = Vec::new();
+                let size =
+                    download_to_partial(&client, &source, "", path.clone(), &mut |_, _, r| {
+                        resumed.push(r)
+                    })
+                    .await
+                    .map(|_| std::fs::read(&path).unwrap());
+                (size, resumed)
+            }
+        };
+
+        // 206：追加到已有内容之后
+        let (content, resumed) = run("206", b"hello ").await;
+        assert_eq!(content.unwrap(), b"hello world");
+        assert!(resumed.iter().all(|r| *r));
+
+        // 200：服务器不支持 Range，从头写入
+        let (content, resumed) = run("200", b"stale").await;
+        assert_eq!(content.unwrap(), b"hello world");
+        assert!(resumed.iter().all(|r| !*r));
+
+        // 416：临时文件已经完整
+        let (content, resumed) = run("416", b"hello world").await;
+        assert_eq!(content.unwrap(), b"hello world");
+        assert!(resumed.is_empty());
+
+        assert_eq!(
+            *ranges.lock().unwrap(),
+            vec![
+                Some("bytes=6-".to_string()),
+                Some("bytes=5-".to_string()),
+                Some("bytes=11-".to_string()),
+            ]
+        );
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[tokio::test]
+    async fn test_partial_lock_is_shared_and_released() {
+        let path = PathBuf::from("/tmp/lock-test.part");
+        let first = partial_lock(&path);
+        let second = partial_lock(&path);
+        assert!(Arc::ptr_eq(&first, &second));
+
+        let guard = first.lock().await;
+        assert!(second.try_lock().is_err());
+        drop(guard);
+
+        release_partial_lock(&path, second);
+        assert!(PARTIAL_LOCKS.lock().unwrap().contains_key(&path));
+        release_partial_lock(&path, first);
+        assert!(!PARTIAL_LOCKS.lock().unwrap().contains_key(&path));
+    }
+}
@@ -0,0 +1,472 @@
+use async_trait::async_trait;
+use reqwest::{Client, RequestBuilder};
+use serde::Serialize;
+use serde_json::Value;
+
+use super::{
+    api_url, parse_created, prepare_video_image, VideoGenerationRequest, VideoProvider,
+    VideoSource, VideoTaskHandle,
+};
+use crate::image_task::request_error;
+use crate::image_utils::strip_data_url_prefix;
+use crate::video::{
+    parse_task_response, KlingCameraControl, KlingDynamicMask, KlingMetadata, VideoTaskResult,
+};
+
+// Kling（经 `/kling/v1/videos` 中转），有首帧图片时走 image2video，只有参考图时走 multi-image2video
+pub struct KlingVideoProvider;
+
+const MAX_REFERENCE_IMAGES: usize = 4;
+const MAX_DYNAMIC_MASKS: usize = 6;
+const CAMERA_PRESETS: [&str; 4] = [
+    "down_back",
+    "forward_up",
+    "right_turn_forward",
+    "left_turn_forward",
+];
+
+#[derive(Debug, Serialize)]
+struct KlingApiRequest {
+    model: String,
+    prompt: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    image: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    image_tail: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    image_list: Option<Vec<KlingImageItem>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    duration: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    width: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    height: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    fps: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    seed: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    n: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    metadata: Option<KlingMetadata>,
+}
+
+#[derive(Debug, Serialize)]
+struct KlingImageItem {
+    image: String,
+}
+
+/// 请求使用的端点，extras 中的 mode 优先
+fn create_mode(request: &VideoGenerationRequest) -> &'static str {
+    match request.extra_str("mode").as_deref() {
+        Some("image2video") => "image2video",
+        Some("text2video") => "text2video",
+        Some("multi-image2video") => "multi-image2video",
+        _ if request.non_empty_first_frame().is_some() => "image2video",
+        _ if !reference_images(request).is_empty() => "multi-image2video",
+        _ => "text2video",
+    }
+}
+
+fn task_mode(task: &VideoTaskHandle) -> &'static str {
+    match task.mode.as_deref() {
+        Some("image2video") => "image2video",
+        Some("multi-image2video") => "multi-image2video",
+        _ => "text2video",
+    }
+}
+
+/// 非空的参考图
+fn reference_images(request: &VideoGenerationRequest) -> Vec<String> {
+    request
+        .references
+        .iter()
+        .map(|reference| reference.image.clone())
+        .filter(|image| !image.trim().is_empty())
+        .collect()
+}
+
+/// 蒙版需要与首帧尺寸一致，不做预处理，只去掉 data URL 前缀
+fn mask_data(mask: &str) -> String {
+    strip_data_url_prefix(mask).to_string()
+}
+
+fn build_metadata(request: &VideoGenerationRequest) -> Result<Option<KlingMetadata>, String> {
+    let dynamic_masks: Option<Vec<KlingDynamicMask>> = request.extra_as("dynamicMasks")?;
+    let metadata = KlingMetadata {
+        negative_prompt: request.negative_prompt.clone(),
+        style: request.extra_str("style"),
+        quality_level: request.extra_str("qualityLevel"),
+        cfg_scale: request.extra_as("cfgScale")?,
+        camera_control: request.extra_as("cameraControl")?,
+        static_mask: request
+            .extra_str("staticMask")
+            .filter(|mask| !mask.trim().is_empty())
+            .map(|mask| mask_data(&mask)),
+        dynamic_masks: dynamic_masks
+            .filter(|masks| !masks.is_empty())
+            .map(|masks| {
+                masks
+                    .into_iter()
+                    .map(|m| KlingDynamicMask {
+                        mask: mask_data(&m.mask),
+                        ..m
+                    })
+                    .collect()
+            }),
+    };
+    Ok((metadata != KlingMetadata::default()).then_some(metadata))
+}
+
+/// simple 为自定义运镜，必须且只能设置一项；预设运镜不带 config
+fn validate_camera_control(control: &KlingCameraControl) -> Result<(), String> {
+    match (control.kind.as_str(), &control.config) {
+        ("simple", Some(config)) => {
+            let values = [
+                config.horizontal,
+                config.vertical,
+                config.pan,
+                config.tilt,
+                config.roll,
+                config.zoom,
+            ];
+            if values.iter().any(|v| !(-10.0..=10.0).contains(v)) {
+                return Err("自定义运镜的取值范围为 -10 到 10".to_string());
+            }
+            if values.iter().filter(|v| **v != 0.0).count() != 1 {
+                return Err("自定义运镜只能设置一项非零参数".to_string());
+            }
+            Ok(())
+        }
+        ("simple", None) => Err("自定义运镜需要提供 config".to_string()),
+        (kind, None) if CAMERA_PRESETS.contains(&kind) => Ok(()),
+        (kind, Some(_)) if CAMERA_PRESETS.contains(&kind) => {
+            Err(format!("预设运镜 {} 不支持 config", kind))
+        }
+        (kind, _) => Err(format!("不支持的运镜类型: {}", kind)),
+    }
+}
+
+/// 按端点检查高级参数：尾帧、运动笔刷只用于图生视频，且与镜头控制互斥；多图参考只用于 multi-image2video
+fn validate_controls(
+    mode: &str,
+    request: &VideoGenerationRequest,
+    metadata: Option<&KlingMetadata>,
+) -> Result<(), String> {
+    let empty = KlingMetadata::default();
+    let metadata = metadata.unwrap_or(&empty);
+    let has_tail = request.non_empty_last_frame().is_some();
+    let has_brush = metadata.static_mask.is_some() || metadata.dynamic_masks.is_some();
+    let has_camera = metadata.camera_control.is_some();
+    let reference_count = reference_images(request).len();
+
+    if let Some(cfg_scale) = metadata.cfg_scale {
+        if !(0.0..=1.0).contains(&cfg_scale) {
+            return Err("cfg_scale 的取值范围为 0 到 1".to_string());
+        }
+    }
+    if let Some(control) = &metadata.camera_control {
+        validate_camera_control(control)?;
+    }
+    if let Some(masks) = &metadata.dynamic_masks {
+        if masks.len() > MAX_DYNAMIC_MASKS {
+            return Err(format!("运动笔刷最多 {} 个运动区域", MAX_DYNAMIC_MASKS));
+        }
+        if masks
+            .iter()
+            .any(|m| m.mask.trim().is_empty() || m.trajectories.len() < 2)
+        {
+            return Err("每个运动区域需要蒙版和至少 2 个轨迹点".to_string());
+        }
+    }
+
+    match mode {
+        "image2video" => {
+            if request.non_empty_first_frame().is_none() {
+                return Err("图生视频需要提供首帧图片".to_string());
+            }
+            if reference_count > 0 {
+                return Err("图生视频不支持多图参考，请使用 multi-image2video".to_string());
+            }
+            if [has_tail, has_brush, has_camera]
+                .iter()
+                .filter(|x| **x)
+                .count()
+                > 1
+            {
+                return Err("尾帧、运动笔刷和镜头控制只能选择其一".to_string());
+            }
+        }
+        "multi-image2video" => {
+            if reference_count == 0 || reference_count > MAX_REFERENCE_IMAGES {
+                return Err(format!("多图参考需要 1 到 {} 张图片", MAX_REFERENCE_IMAGES));
+            }
+            if has_tail || has_brush || has_camera {
+                return Err("多图参考不支持尾帧、运动笔刷和镜头控制".to_string());
+            }
+        }
+        _ => {
+            if has_tail || has_brush {
+                return Err("文生视频不支持尾帧和运动笔刷".to_string());
+            }
+            if reference_count > 0 {
+                return Err("文生视频不支持多图参考，请使用 multi-image2video".to_string());
+            }
+        }
+    }
+    Ok(())
+}
+
+/// 任务 metadata：查询状态需要的端点，以及使用的高级参数（不含图片数据），保存视频时一并记录
+fn task_metadata(
+    mode: &str,
+    request: &VideoGenerationRequest,
+    metadata: Option<&KlingMetadata>,
+) -> Value {
+    let mut summary = serde_json::json!({ "mode": mode });
+    if let Some(metadata) = metadata {
+        if let Some(cfg_scale) = metadata.cfg_scale {
+            summary["cfgScale"] = cfg_scale.into();
+        }
+        if let Some(control) = &metadata.camera_control {
+            summary["cameraControl"] = serde_json::to_value(control).unwrap_or_default();
+        }
+        if metadata.static_mask.is_some() || metadata.dynamic_masks.is_some() {
+            summary["motionBrush"] = serde_json::json!({
+                "staticMask": metadata.static_mask.is_some(),
+                "dynamicMasks": metadata.dynamic_masks.as_ref().map_or(0, Vec::len),
+            });
+        }
+    }
+    if request.non_empty_last_frame().is_some() {
+        summary["endFrame"] = true.into();
+    }
+    let reference_count = reference_images(request).len();
+    if reference_count > 0 {
+        summary["referenceImages"] = reference_count.into();
+    }
+    summary
+}
+
+/// 读取失败响应中的 error.message
+fn error_message(text: &str) -> Option<String> {
+    let raw: Value = serde_json::from_str(text).ok()?;
+    raw.get("error")?
+        .get("message")?
+        .as_str()
+        .map(|s| s.to_string())
+}
+
+/// 发送请求，Kling 的失败响应优先返回 error.message
+async fn send(builder: RequestBuilder, api_key: &str) -> Result<String, String> {
+    let response = builder
+        .header("Authorization", format!("Bearer {}", api_key))
+        .send()
+        .await
+        .map_err(request_error)?;
+    let status = response.status();
+    let text = response
+        .text()
+        .await
+        .map_err(|e| format!("读取响应失败: {}", e))?;
+    if !status.is_success() {
+        println!("[Rust] Kling error response: {}", text);
+        return Err(
+            error_message(&text).unwrap_or_else(|| format!("API 错误 ({}): {}", status, text))
+        );
+    }
+    Ok(text)
+}
+
+#[async_trait]
+impl VideoProvider for KlingVideoProvider {
+    fn id(&self) -> &'static str {
+        "kling"
+    }
+
+    async fn create(
+        &self,
+        client: &Client,
+        request: &VideoGenerationRequest,
+    ) -> Result<VideoTaskResult, String> {
+        let mode = create_mode(request);
+        let metadata = build_metadata(request)?;
+        validate_controls(mode, request, metadata.as_ref())?;
+        let task_metadata = task_metadata(mode, request, metadata.as_ref());
+
+        let mut image_list = Vec::new();
+        for image in reference_images(request) {
+            image_list.extend(
+                prepare_video_image(Some(image))
+                    .await
+                    .map(|image| KlingImageItem { image }),
+            );
+        }
+        let (width, height) = request.dimensions();
+        let body = KlingApiRequest {
+            model: request.model.clone(),
+            prompt: request.prompt.clone(),
+            image: prepare_video_image(request.non_empty_first_frame()).await,
+            image_tail: prepare_video_image(request.non_empty_last_frame()).await,
+            image_list: (!image_list.is_empty()).then_some(image_list),
+            duration: request.duration,
+            width,
+            height,
+            fps: request.fps,
+            seed: request.seed,
+            n: request.n,
+            metadata,
+        };
+
+        let url = api_url(&request.base_url, &format!("/kling/v1/videos/{}", mode));
+        println!("[Rust] Request URL: {}", url);
+        let text = send(client.post(&url).json(&body), &request.api_key).await?;
+        let mut result = parse_created(&text)?;
+        result.metadata = Some(task_metadata);
+        Ok(result)
+    }
+
+    async fn status(
+        &self,
+        client: &Client,
+        task: &VideoTaskHandle,
+    ) -> Result<VideoTaskResult, String> {
+        let url = api_url(
+            &task.base_url,
+            &format!("/kling/v1/videos/{}/{}", task_mode(task), task.task_id),
+        );
+        let text = send(client.get(&url), &task.api_key).await?;
+        parse_task_response(&text, Some(task.task_id.clone()))
+    }
+
+    async fn content(
+        &self,
+        client: &Client,
+        task: &VideoTaskHandle,
+    ) -> Result<VideoSource, String> {
+        let result = self.status(client, task).await?;
+        if result.status.as_deref() != Some("completed") {
+            return Err(format!("任务尚未完成，当前状态: {:?}", result.status));
+        }
+        let url = result.video_url.ok_or("API 未返回视频 URL")?;
+        println!("[Rust] Kling video URL: {}", url);
+        Ok(VideoSource {
+            url,
+            authorized: false,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::video_provider::VideoReference;
+    use serde_json::json;
+
+    #[test]
+    fn test_create_mode() {
+        let mut request = VideoGenerationRequest::default();
+        assert_eq!(create_mode(&request), "text2video");
+        request.first_frame = Some("aGVsbG8=".to_string());
+        assert_eq!(create_mode(&request), "image2video");
+        request.extras = Some(json!({ "mode": "text2video" }));
+        assert_eq!(create_mode(&request), "text2video");
+
+        let request = VideoGenerationRequest {
+            references: vec![VideoReference {
+                image: "aGVsbG8=".to_string(),
+                kind: None,
+            }],
+            ..VideoGenerationRequest::default()
+        };
+        assert_eq!(create_mode(&request), "multi-image2video");
+    }
+
+    #[test]
+    fn test_camera_control() {
+        let camera = |value: serde_json::Value| -> KlingCameraControl {
+            serde_json::from_value(value).unwrap()
+        };
+        assert!(validate_camera_control(&camera(json!({ "type": "forward_up" }))).is_ok());
+        assert!(validate_camera_control(&camera(
+            json!({ "type": "simple", "config": { "zoom": 5 } })
+        ))
+        .is_ok());
+        assert!(validate_camera_control(&camera(
+            json!({ "type": "simple", "config": { "pan": 2, "tilt": 3 } })
+        ))
+        .is_err());
+        assert!(validate_camera_control(&camera(
+            json!({ "type": "simple", "config": { "roll": 11 } })
+        ))
+        .is_err());
+        assert!(validate_camera_control(&camera(json!({ "type": "simple" }))).is_err());
+        assert!(validate_camera_control(&camera(json!({ "type": "orbit" }))).is_err());
+    }
+
+    #[test]
+    fn test_validate_controls() {
+        let request = |extras: serde_json::Value| VideoGenerationRequest {
+            first_frame: Some("aGVsbG8=".to_string()),
+            extras: Some(extras),
+            ..VideoGenerationRequest::default()
+        };
+        let check = |mode: &str, request: &VideoGenerationRequest| {
+            let metadata = build_metadata(request)?;
+            validate_controls(mode, request, metadata.as_ref())
+        };
+
+        let camera = request(json!({ "cameraControl": { "type": "down_back" }, "cfgScale": 0.5 }));
+        assert!(check("image2video", &camera).is_ok());
+        assert!(check("text2video", &camera).is_ok());
+
+        let mut tail_and_camera = camera.clone();
+        tail_and_camera.last_frame = Some("aGVsbG8=".to_string());
+        assert!(check("image2video", &tail_and_camera).is_err());
+
+        let brush = request(json!({
+            "dynamicMasks": [{ "mask": "data:image/png;base64,bWFzaw==", "trajectories": [{ "x": 1, "y": 2 }, { "x": 3, "y": 4 }] }]
+        }));
+        assert!(check("image2video", &brush).is_ok());
+        assert!(check("text2video", &brush).is_err());
+        assert_eq!(
+            build_metadata(&brush)
+                .unwrap()
+                .unwrap()
+                .dynamic_masks
+                .unwrap()[0]
+                .mask,
+            "bWFzaw=="
+        );
+
+        assert!(check("image2video", &request(json!({ "cfgScale": 1.5 }))).is_err());
+        assert!(check("image2video", &request(json!({ "cameraControl": "zoom" }))).is_err());
+        assert!(check("multi-image2video", &request(json!({}))).is_err());
+    }
+
+    #[test]
+    fn test_task_metadata() {
+        let request = VideoGenerationRequest {
+            first_frame: Some("aGVsbG8=".to_string()),
+            last_frame: Some("aGVsbG8=".to_string()),
+            extras: Some(json!({ "cfgScale": 0.7 })),
+            ..VideoGenerationRequest::default()
+        };
+        let metadata = build_metadata(&request).unwrap();
+        assert_eq!(
+            task_metadata("image2video", &request, metadata.as_ref()),
+            json!({ "mode": "image2video", "cfgScale": 0.7, "endFrame": true })
+        );
+    }
+
+    #[test]
+    fn test_status_ignores_zero_error_code() {
+        let result = parse_task_response(
+            r#"{"task_id":"k1","status":"completed","url":"https://cdn/v.mp4","error":{"code":0,"message":""}}"#,
+            None,
+        )
+        .unwrap();
+        assert!(result.success);
+        assert_eq!(result.video_url.as_deref(), Some("https://cdn/v.mp4"));
+    }
+}
@@ -0,0 +1,528 @@
+//! 统一的视频生成抽象
+//!
+//! 各家视频服务（OpenAI `/v1/videos`、new-api `/v1/video/generations`、Veo、Kling）
+//! 都是「创建任务 → 查询状态 → 获取内容」的流程，通过 `VideoProvider` 接入。
+//! 请求使用统一的参数模型，由各适配器换算成服务自己的格式，
+//! 新增服务只需要实现一个适配器。
+
+mod download;
+mod kling;
+mod newapi;
+mod openai;
+mod status;
+mod veo;
+
+use async_trait::async_trait;
+use reqwest::{Client, RequestBuilder};
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use crate::framing;
+use crate::image_preprocess::{preprocess_image_field, ImageLimits};
+use crate::image_task::{read_response_text, request_error};
+use crate::storage::{VideoInfo, VideoMetadata};
+use crate::video::{
+    empty_video_task_result, parse_task_response, VideoContentResult, VideoTaskResult,
+};
+
+pub use download::VideoDownloadTarget;
+pub use kling::KlingVideoProvider;
+pub use newapi::NewApiVideoProvider;
+pub use openai::OpenAIVideoProvider;
+pub use status::{map_vendor_status, VideoTaskState};
+pub use veo::VeoVideoProvider;
+
+const CREATE_TIMEOUT_SECS: u64 = 120;
+const STATUS_TIMEOUT_SECS: u64 = 30;
+const DOWNLOAD_CONNECT_TIMEOUT_SECS: u64 = 30;
+const DOWNLOAD_READ_TIMEOUT_SECS: u64 = 60;
+
+lazy_static::lazy_static! {
+    // 本次运行中创建的任务的请求信息，下载视频时写入元数据
+    static ref CREATED_TASKS: Mutex<HashMap<String, VideoMetadata>> = Mutex::new(HashMap::new());
+}
+
+// 参考图片
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VideoReference {
+    pub image: String,        // base64 图片数据或 URL
+    pub kind: Option<String>, // 参考类型，如 Veo 的 "asset" / "style"
+}
+
+// 统一的视频生成请求
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VideoGenerationRequest {
+    pub provider: String,
+    pub base_url: String,
+    pub api_key: String,
+    pub model: String,
+    pub prompt: String,
+    pub negative_prompt: Option<String>,
+    pub first_frame: Option<String>, // 首帧图片，base64 或 URL
+    pub last_frame: Option<String>,  // 尾帧图片
+    #[serde(default)]
+    pub references: Vec<VideoReference>,
+    pub duration: Option<f64>,        // 秒
+    pub resolution: Option<String>,   // "1280x720" 或 "720p"
+    pub aspect_ratio: Option<String>, // "16:9"
+    pub fps: Option<i32>,
+    pub seed: Option<i64>,
+    pub audio: Option<bool>, // 是否同时生成音频
+    pub n: Option<i32>,
+    // 各服务特有的参数，原样交给对应的适配器解析
+    pub extras: Option<Value>,
+}
+
+impl VideoGenerationRequest {
+    /// 读取 extras 中的参数
+    pub fn extra(&self, key: &str) -> Option<&Value> {
+        self.extras
+            .as_ref()
+            .and_then(|extras| extras.get(key))
+            .filter(|value| !value.is_null())
+    }
+
+    /// 读取 extras 中的字符串参数
+    pub fn extra_str(&self, key: &str) -> Option<String> {
+        self.extra(key)
+            .and_then(|value| value.as_str())
+            .map(|value| value.to_string())
+    }
+
+    /// 按类型解析 extras 中的参数，格式不对时返回错误
+    pub fn extra_as<T: DeserializeOwned>(&self, key: &str) -> Result<Option<T>, String> {
+        self.extra(key)
+            .map(|value| {
+                serde_json::from_value(value.clone())
+                    .map_err(|e| format!("参数 {} 格式错误: {}", key, e))
+            })
+            .transpose()
+    }
+
+    /// 非空的首帧图片
+    pub fn non_empty_first_frame(&self) -> Option<String> {
+        self.first_frame
+            .as_ref()
+            .filter(|image| !image.trim().is_empty())
+            .cloned()
+    }
+
+    /// 非空的尾帧图片
+    pub fn non_empty_last_frame(&self) -> Option<String> {
+        self.last_frame
+            .as_ref()
+            .filter(|image| !image.trim().is_empty())
+            .cloned()
+    }
+
+    /// 从 "1280x720" 形式的分辨率中解析宽高，"720p" 等写法返回 None
+    pub fn dimensions(&self) -> (Option<i32>, Option<i32>) {
+        self.resolution
+            .as_deref()
+            .and_then(|s| s.split_once('x'))
+            .map(|(w, h)| (w.trim().parse().ok(), h.trim().parse().ok()))
+            .unwrap_or((None, None))
+    }
+}
+
+// 已创建任务的引用，查询状态和获取内容时使用
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VideoTaskHandle {
+    pub provider: String,
+    pub base_url: String,
+    pub api_key: String,
+    pub task_id: String,
+    pub mode: Option<String>, // Kling 的 "text2video" / "image2video"，创建结果的 metadata 中会返回
+}
+
+// 完成视频的下载位置
+#[derive(Debug, Clone, PartialEq)]
+pub struct VideoSource {
+    pub url: String,
+    pub authorized: bool, // 下载时是否需要带上 API Key
+}
+
+#[async_trait]
+pub trait VideoProvider: Send + Sync {
+    /// provider 标识，与请求中的 `provider` 字段对应
+    fn id(&self) -> &'static str;
+
+    /// 创建生成任务，返回的结果中带有任务 ID
+    async fn create(
+        &self,
+        client: &Client,
+        request: &VideoGenerationRequest,
+    ) -> Result<VideoTaskResult, String>;
+
+    /// 查询任务状态
+    async fn status(
+        &self,
+        client: &Client,
+        task: &VideoTaskHandle,
+    ) -> Result<VideoTaskResult, String>;
+
+    /// 把服务返回的状态映射到统一状态，查询本身失败时返回 None
+    fn map_status(&self, result: &VideoTaskResult) -> Option<VideoTaskState> {
+        map_vendor_status(result)
+    }
+
+    /// 任务完成后视频的下载位置
+    async fn content(&self, client: &Client, task: &VideoTaskHandle)
+        -> Result<VideoSource, String>;
+}
+
+/// 按标识查找 provider
+pub fn get_video_provider(id: &str) -> Option<Box<dyn VideoProvider>> {
+    match id.trim().to_ascii_lowercase().as_str() {
+        "openai" | "sora" => Some(Box::new(OpenAIVideoProvider)),
+        "newapi" | "new-api" => Some(Box::new(NewApiVideoProvider)),
+        "veo" => Some(Box::new(VeoVideoProvider)),
+        "kling" => Some(Box::new(KlingVideoProvider)),
+        _ => None,
+    }
+}
+
+fn find_provider(id: &str) -> Result<Box<dyn VideoProvider>, String> {
+    get_video_provider(id).ok_or_else(|| format!("不支持的视频服务: {}", id))
+}
+
+fn http_client(timeout_secs: u64) -> Result<Client, String> {
+    Client::builder()
+        .timeout(Duration::from_secs(timeout_secs))
+        .build()
+        .map_err(|e| format!("创建 HTTP 客户端失败: {}", e))
+}
+
+pub(crate) fn api_url(base_url: &str, path: &str) -> String {
+    format!("{}{}", base_url.trim_end_matches('/'), path)
+}
+
+/// 发送带鉴权的请求并读取响应文本
+pub(crate) async fn send_request(builder: RequestBuilder, api_key: &str) -> Result<String, String> {
+    let response = builder
+        .header("Authorization", format!("Bearer {}", api_key))
+        .send()
+        .await
+        .map_err(request_error)?;
+    read_response_text(response).await
+}
+
+/// 解析创建任务的响应，出错或没有任务 ID 时返回错误
+pub(crate) fn parse_created(text: &str) -> Result<VideoTaskResult, String> {
+    let result = parse_task_response(text, None)?;
+    if !result.success {
+        return Err(result.error.unwrap_or_else(|| "创建任务失败".to_string()));
+    }
+    if result.task_id.is_none() {
+        return Err("API 未返回任务 ID".to_string());
+    }
+    Ok(result)
+}
+
+// 按视频服务限制预处理图片字段（URL 原样保留），失败时沿用原图
+pub(crate) async fn prepare_video_image(image: Option<String>) -> Option<String> {
+    let image = image?;
+    match preprocess_image_field(image.clone(), ImageLimits::for_provider("video")).await {
+        Ok((data, report)) => {
+            if let Some(report) = report.filter(|r| r.changed()) {
+                println!("[Rust] Input image adjusted: {}", report.summary());
+            }
+            Some(data)
+        }
+        Err(e) => {
+            println!("[Rust] Failed to prepare input image: {}", e);
+            Some(image)
+        }
+    }
+}
+
+fn download_client() -> Result<Client, String> {
+    // 视频可能很大，不限制总时长，只限制连接和两次读取之间的间隔
+    Client::builder()
+        .connect_timeout(Duration::from_secs(DOWNLOAD_CONNECT_TIMEOUT_SECS))
+        .read_timeout(Duration::from_secs(DOWNLOAD_READ_TIMEOUT_SECS))
+        .build()
+        .map_err(|e| format!("创建 HTTP 客户端失败: {}", e))
+}
+
+// ==================== 统一入口 ====================
+
+/// 创建视频任务
+pub async fn create_video_task(request: &VideoGenerationRequest) -> VideoTaskResult {
+    let result = async {
+        let provider = find_provider(&request.provider)?;
+        let client = http_client(CREATE_TIMEOUT_SECS)?;
+        println!(
+            "[Rust] Creating {} video task, model: {}",
+            provider.id(),
+            request.model
+        );
+        // 按服务规格调整画面参数，避免请求不支持的宽高比或分辨率
+        let mut request = request.clone();
+        framing::apply_to_video_request(&mut request, provider.id());
+        let mut result = provider.create(&client, &request).await?;
+        if let Some(task_id) = &result.task_id {
+            remember_task(provider.id(), task_id, &request);
+        }
+        // 刚创建的任务至少处于排队状态
+        result.state = provider
+            .map_status(&result)
+            .filter(|state| !state.is_terminal())
+            .or(Some(VideoTaskState::Queued));
+        Ok(result)
+    }
+    .await;
+    match result {
+        Ok(result) => {
+            println!("[Rust] Video task created: {:?}", result.task_id);
+            result
+        }
+        Err(e) => {
+            println!("[Rust] Failed to create video task: {}", e);
+            empty_video_task_result(Some(e))
+        }
+    }
+}
+
+/// 查询视频任务状态
+pub async fn get_video_task_status(task: &VideoTaskHandle) -> VideoTaskResult {
+    let result = async {
+        let provider = find_provider(&task.provider)?;
+        let mut result = provider
+            .status(&http_client(STATUS_TIMEOUT_SECS)?, task)
+            .await?;
+        result.state = provider.map_status(&result);
+        Ok(result)
+    }
+    .await;
+    result.unwrap_or_else(|e| empty_video_task_result(Some(e)))
+}
+
+/// 查询完成视频的下载位置
+pub async fn get_video_source(task: &VideoTaskHandle) -> Result<VideoSource, String> {
+    let provider = find_provider(&task.provider)?;
+    provider
+        .content(&http_client(STATUS_TIMEOUT_SECS)?, task)
+        .await
+}
+
+/// 任务的唯一标识，用于临时文件名和请求信息记录；provider 按适配器标识归一
+pub(crate) fn task_key(provider: &str, task_id: &str) -> String {
+    let provider = get_video_provider(provider)
+        .map(|p| p.id())
+        .unwrap_or(provider);
+    format!("{}_{}", provider, task_id)
+}
+
+/// 记录创建任务时的请求信息
+fn remember_task(provider_id: &str, task_id: &str, request: &VideoGenerationRequest) {
+    let metadata = VideoMetadata {
+        prompt: Some(request.prompt.clone()).filter(|p| !p.trim().is_empty()),
+        provider: Some(request.provider.clone()),
+        model: Some(request.model.clone()).filter(|m| !m.trim().is_empty()),
+        task_id: Some(task_id.to_string()),
+        extra: Some(serde_json::json!({
+            "negativePrompt": request.negative_prompt,
+            "duration": request.duration,
+            "resolution": request.resolution,
+            "aspectRatio": request.aspect_ratio,
+            "seed": request.seed,
+        })),
+        ..VideoMetadata::default()
+    };
+    if let Ok(mut tasks) = CREATED_TASKS.lock() {
+        tasks.insert(task_key(provider_id, task_id), metadata);
+    }
+}
+
+/// 保存下载视频时写入的元数据，本次运行中创建的任务带上提示词和生成参数
+pub(crate) fn task_metadata(task: &VideoTaskHandle) -> VideoMetadata {
+    let remembered = CREATED_TASKS
+        .lock()
+        .ok()
+        .and_then(|tasks| tasks.get(&task_key(&task.provider, &task.task_id)).cloned());
+    VideoMetadata {
+        created_at: chrono::Utc::now().timestamp(),
+        ..remembered.unwrap_or_else(|| VideoMetadata {
+            provider: Some(task.provider.clone()),
+            task_id: Some(task.task_id.clone()),
+            ..VideoMetadata::default()
+        })
+    }
+}
+
+/// 下载完成的视频并保存到本地，target 未指定元数据时按任务信息生成
+pub async fn store_task_video(
+    app: &tauri::AppHandle,
+    task: &VideoTaskHandle,
+    mut target: VideoDownloadTarget,
+) -> Result<(VideoInfo, VideoSource), String> {
+    let source = get_video_source(task).await?;
+    target
+        .task_id
+        .get_or_insert_with(|| task_key(&task.provider, &task.task_id));
+    let metadata = target.metadata.get_or_insert_with(|| task_metadata(task));
+    // 需要鉴权的地址不记录
+    if metadata.source_url.is_none() && !source.authorized {
+        metadata.source_url = Some(source.url.clone());
+    }
+    let info =
+        download::download_to_storage(app, &download_client()?, &source, &task.api_key, target)
+            .await?;
+    Ok((info, source))
+}
+
+/// 下载任意地址的视频（不带鉴权）并保存到本地
+pub async fn store_video_url(
+    app: &tauri::AppHandle,
+    url: &str,
+    target: VideoDownloadTarget,
+) -> Result<VideoInfo, String> {
+    let source = VideoSource {
+        url: url.to_string(),
+        authorized: false,
+    };
+    download::download_to_storage(app, &download_client()?, &source, "", target).await
+}
+
+pub(crate) fn content_result(video: Result<VideoInfo, String>) -> VideoContentResult {
+    match video {
+        Ok(video) => VideoContentResult {
+            success: true,
+            video: Some(video),
+            error: None,
+        },
+        Err(e) => VideoContentResult {
+            success: false,
+            video: None,
+            error: Some(e),
+        },
+    }
+}
+
+// Tauri 命令：统一的视频任务创建入口
+#[tauri::command]
+pub async fn video_generate(request: VideoGenerationRequest) -> VideoTaskResult {
+    println!(
+        "[Rust] video_generate called, provider: {}, model: {}",
+        request.provider, request.model
+    );
+    create_video_task(&request).await
+}
+
+// Tauri 命令：查询视频任务状态
+#[tauri::command]
+pub async fn video_generate_status(task: VideoTaskHandle) -> VideoTaskResult {
+    println!(
+        "[Rust] video_generate_status called, provider: {}, task_id: {}",
+        task.provider, task.task_id
+    );
+    get_video_task_status(&task).await
+}
+
+// Tauri 命令：下载完成的视频并保存到本地，返回保存的视频信息
+#[tauri::command]
+pub async fn video_generate_content(
+    app: tauri::AppHandle,
+    task: VideoTaskHandle,
+    canvas_id: Option<String>,
+    node_id: Option<String>,
+    request_id: Option<String>,
+) -> VideoContentResult {
+    println!(
+        "[Rust] video_generate_content called, provider: {}, task_id: {}",
+        task.provider, task.task_id
+    );
+    let target = VideoDownloadTarget {
+        request_id,
+        canvas_id,
+        node_id,
+        ..VideoDownloadTarget::default()
+    };
+    content_result(
+        store_task_video(&app, &task, target)
+            .await
+            .map(|(video, _)| video),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_get_video_provider_aliases() {
+        assert_eq!(get_video_provider("Sora").unwrap().id(), "openai");
+        assert_eq!(get_video_provider("new-api").unwrap().id(), "newapi");
+        assert_eq!(get_video_provider("kling").unwrap().id(), "kling");
+        assert!(get_video_provider("runway").is_none());
+    }
+
+    #[test]
+    fn test_parse_created_requires_task_id() {
+        let created = parse_created(r#"{"id":"video_1","status":"queued"}"#).unwrap();
+        assert_eq!(created.task_id.as_deref(), Some("video_1"));
+        assert_eq!(
+            parse_created(r#"{"status":"queued"}"#).unwrap_err(),
+            "API 未返回任务 ID"
+        );
+        assert_eq!(
+            parse_created(r#"{"error":{"message":"quota exceeded"}}"#).unwrap_err(),
+            "quota exceeded"
+        );
+    }
+
+    #[test]
+    fn test_created_task_metadata_is_used_for_download() {
+        let request = VideoGenerationRequest {
+            provider: "sora".to_string(),
+            model: "sora-2".to_string(),
+            prompt: "a paper boat in the rain".to_string(),
+            resolution: Some("1280x720".to_string()),
+            ..VideoGenerationRequest::default()
+        };
+        remember_task("openai", "video_meta_1", &request);
+
+        let handle = VideoTaskHandle {
+            provider: "openai".to_string(),
+            task_id: "video_meta_1".to_string(),
+            ..VideoTaskHandle::default()
+        };
+        assert_eq!(task_key("Sora", "video_meta_1"), "openai_video_meta_1");
+        let metadata = task_metadata(&handle);
+        assert_eq!(metadata.prompt.as_deref(), Some("a paper boat in the rain"));
+        assert_eq!(metadata.model.as_deref(), Some("sora-2"));
+        assert_eq!(metadata.extra.unwrap()["resolution"], "1280x720");
+
+        let unknown = VideoTaskHandle {
+            provider: "veo".to_string(),
+            task_id: "operations/unknown".to_string(),
+            ..VideoTaskHandle::default()
+        };
+        let metadata = task_metadata(&unknown);
+        assert_eq!(metadata.provider.as_deref(), Some("veo"));
+        assert_eq!(metadata.task_id.as_deref(), Some("operations/unknown"));
+        assert_eq!(metadata.prompt, None);
+    }
+
+    #[test]
+    fn test_request_dimensions() {
+        let request = VideoGenerationRequest {
+            resolution: Some("1280x720".to_string()),
+            ..VideoGenerationRequest::default()
+        };
+        assert_eq!(request.dimensions(), (Some(1280), Some(720)));
+        let request = VideoGenerationRequest {
+            resolution: Some("720p".to_string()),
+            ..VideoGenerationRequest::default()
+        };
+        assert_eq!(request.dimensions(), (None, None));
+    }
+}
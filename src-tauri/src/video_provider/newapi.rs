@@ -0,0 +1,142 @@
+use async_trait::async_trait;
+use reqwest::Client;
+use serde::Serialize;
+use serde_json::Value;
+
+use super::{
+    api_url, parse_created, prepare_video_image, send_request, VideoGenerationRequest,
+    VideoProvider, VideoSource, VideoTaskHandle,
+};
+use crate::video::{parse_task_response, VideoTaskResult};
+
+// new-api 通用视频任务 `/v1/video/generations`，完成后返回视频地址
+pub struct NewApiVideoProvider;
+
+#[derive(Debug, Serialize)]
+struct NewApiVideoRequest {
+    model: String,
+    prompt: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    image: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    duration: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    width: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    height: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    fps: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    seed: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    n: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    response_format: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    user: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    metadata: Option<Value>,
+}
+
+/// 把请求使用的种子记入任务元数据，服务已返回种子时以服务为准
+fn record_seed(metadata: Option<Value>, seed: Option<i64>) -> Option<Value> {
+    let Some(seed) = seed else {
+        return metadata;
+    };
+    match metadata {
+        Some(Value::Object(mut map)) => {
+            map.entry("seed").or_insert_with(|| Value::from(seed));
+            Some(Value::Object(map))
+        }
+        None | Some(Value::Null) => Some(serde_json::json!({ "seed": seed })),
+        other => other,
+    }
+}
+
+#[async_trait]
+impl VideoProvider for NewApiVideoProvider {
+    fn id(&self) -> &'static str {
+        "newapi"
+    }
+
+    async fn create(
+        &self,
+        client: &Client,
+        request: &VideoGenerationRequest,
+    ) -> Result<VideoTaskResult, String> {
+        let (width, height) = request.dimensions();
+        let body = NewApiVideoRequest {
+            model: request.model.clone(),
+            prompt: request.prompt.clone(),
+            image: prepare_video_image(request.non_empty_first_frame()).await,
+            duration: request.duration,
+            width,
+            height,
+            fps: request.fps,
+            seed: request.seed,
+            n: request.n,
+            response_format: request.extra_str("responseFormat"),
+            user: request.extra_str("user"),
+            metadata: request.extra("metadata").cloned(),
+        };
+
+        let url = api_url(&request.base_url, "/v1/video/generations");
+        println!("[Rust] Request URL: {}", url);
+        let text = send_request(client.post(&url).json(&body), &request.api_key).await?;
+        let mut result = parse_created(&text)?;
+        result.metadata = record_seed(result.metadata, request.seed);
+        Ok(result)
+    }
+
+    async fn status(
+        &self,
+        client: &Client,
+        task: &VideoTaskHandle,
+    ) -> Result<VideoTaskResult, String> {
+        let url = api_url(
+            &task.base_url,
+            &format!("/v1/video/generations/{}", task.task_id),
+        );
+        let text = send_request(client.get(&url), &task.api_key).await?;
+        parse_task_response(&text, Some(task.task_id.clone()))
+    }
+
+    async fn content(
+        &self,
+        client: &Client,
+        task: &VideoTaskHandle,
+    ) -> Result<VideoSource, String> {
+        let result = self.status(client, task).await?;
+        if !result.success {
+            return Err(result
+                .error
+                .unwrap_or_else(|| "查询任务状态失败".to_string()));
+        }
+        let url = result.video_url.ok_or_else(|| {
+            format!(
+                "任务完成但没有返回视频地址，当前状态: {}",
+                result.status.unwrap_or_default()
+            )
+        })?;
+        Ok(VideoSource {
+            url,
+            authorized: false,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_record_seed_keeps_service_value() {
+        assert_eq!(record_seed(None, Some(7)), Some(json!({ "seed": 7 })));
+        assert_eq!(
+            record_seed(Some(json!({ "seed": 3, "fps": 24 })), Some(7)),
+            Some(json!({ "seed": 3, "fps": 24 }))
+        );
+        assert_eq!(record_seed(None, None), None);
+    }
+}
@@ -0,0 +1,120 @@
+use async_trait::async_trait;
+use reqwest::multipart::{Form, Part};
+use reqwest::Client;
+
+use super::{
+    api_url, parse_created, send_request, VideoGenerationRequest, VideoProvider, VideoSource,
+    VideoTaskHandle,
+};
+use crate::image_preprocess::{preprocess_base64_image, ImageLimits};
+use crate::video::{parse_task_response, VideoTaskResult};
+
+// OpenAI 兼容的 `/v1/videos`（Sora），multipart 提交，首帧作为 input_reference
+pub struct OpenAIVideoProvider;
+
+/// 时长写成整数秒，OpenAI 只接受 "4" / "8" 这样的字符串
+pub(super) fn format_seconds(duration: f64) -> String {
+    if duration.fract() == 0.0 {
+        format!("{}", duration as i64)
+    } else {
+        duration.to_string()
+    }
+}
+
+/// `/v1/videos/{id}` 查询状态，Veo 中转也使用同一接口
+pub(super) async fn get_status(
+    client: &Client,
+    task: &VideoTaskHandle,
+) -> Result<VideoTaskResult, String> {
+    let url = api_url(&task.base_url, &format!("/v1/videos/{}", task.task_id));
+    let text = send_request(client.get(&url), &task.api_key).await?;
+    parse_task_response(&text, Some(task.task_id.clone()))
+}
+
+/// `/v1/videos/{id}/content` 下载视频，需要鉴权
+pub(super) fn content_source(task: &VideoTaskHandle) -> VideoSource {
+    VideoSource {
+        url: api_url(
+            &task.base_url,
+            &format!("/v1/videos/{}/content", task.task_id),
+        ),
+        authorized: true,
+    }
+}
+
+#[async_trait]
+impl VideoProvider for OpenAIVideoProvider {
+    fn id(&self) -> &'static str {
+        "openai"
+    }
+
+    async fn create(
+        &self,
+        client: &Client,
+        request: &VideoGenerationRequest,
+    ) -> Result<VideoTaskResult, String> {
+        let mut form = Form::new()
+            .text("model", request.model.clone())
+            .text("prompt", request.prompt.clone());
+        if let Some(duration) = request.duration {
+            form = form.text("seconds", format_seconds(duration));
+        }
+        if let Some(size) = request.resolution.clone() {
+            form = form.text("size", size);
+        }
+
+        // 首帧图片作为参考图上传
+        if let Some(image_base64) = request.non_empty_first_frame() {
+            match preprocess_base64_image(image_base64, ImageLimits::for_provider("video")).await {
+                Ok(image) => {
+                    if image.report.changed() {
+                        println!(
+                            "[Rust] Reference image adjusted: {}",
+                            image.report.summary()
+                        );
+                    }
+                    let file_name = format!("reference.{}", image.extension());
+                    let mime = image.mime_type();
+                    let part = Part::bytes(image.bytes)
+                        .file_name(file_name)
+                        .mime_str(mime)
+                        .map_err(|e| format!("构建参考图失败: {}", e))?;
+                    form = form.part("input_reference", part);
+                }
+                Err(e) => println!("[Rust] Failed to prepare input image: {}", e),
+            }
+        }
+
+        let url = api_url(&request.base_url, "/v1/videos");
+        println!("[Rust] Request URL: {}", url);
+        let text = send_request(client.post(&url).multipart(form), &request.api_key).await?;
+        parse_created(&text)
+    }
+
+    async fn status(
+        &self,
+        client: &Client,
+        task: &VideoTaskHandle,
+    ) -> Result<VideoTaskResult, String> {
+        get_status(client, task).await
+    }
+
+    async fn content(
+        &self,
+        _client: &Client,
+        task: &VideoTaskHandle,
+    ) -> Result<VideoSource, String> {
+        Ok(content_source(task))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_seconds() {
+        assert_eq!(format_seconds(8.0), "8");
+        assert_eq!(format_seconds(2.5), "2.5");
+    }
+}
@@ -0,0 +1,194 @@
+use serde::{Deserialize, Serialize};
+
+use crate::video::VideoTaskResult;
+
+// 完成任务产出的视频
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VideoAsset {
+    pub url: Option<String>,
+    pub format: Option<String>,
+}
+
+// 统一的视频任务状态，各服务的状态值都映射到这里
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "state", rename_all = "camelCase")]
+pub enum VideoTaskState {
+    #[default]
+    Queued,
+    Running {
+        progress: Option<i32>, // 0-100
+    },
+    Succeeded {
+        assets: Vec<VideoAsset>,
+    },
+    Failed {
+        reason: String,
+    },
+    Cancelled,
+    Expired,
+}
+
+impl VideoTaskState {
+    /// 状态名称，与序列化后的 `state` 字段一致
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::Queued => "queued",
+            Self::Running { .. } => "running",
+            Self::Succeeded { .. } => "succeeded",
+            Self::Failed { .. } => "failed",
+            Self::Cancelled => "cancelled",
+            Self::Expired => "expired",
+        }
+    }
+
+    /// 是否已结束
+    pub fn is_terminal(&self) -> bool {
+        !matches!(self, Self::Queued | Self::Running { .. })
+    }
+
+    /// 是否允许转到 next：已结束的状态不再变化，运行中不会回到排队，进度不会倒退
+    pub fn can_transition_to(&self, next: &Self) -> bool {
+        match (self, next) {
+            (current, next) if current.is_terminal() => current == next,
+            (Self::Running { .. }, Self::Queued) => false,
+            (Self::Running { progress: Some(a) }, Self::Running { progress: Some(b) }) => b >= a,
+            _ => true,
+        }
+    }
+
+    /// 转到 next，返回状态是否有变化；不允许的转换保持原状态并返回错误
+    pub fn transition(&mut self, next: Self) -> Result<bool, String> {
+        if !self.can_transition_to(&next) {
+            return Err(format!(
+                "忽略无效的状态变化: {} -> {}",
+                self.label(),
+                next.label()
+            ));
+        }
+        // 服务偶尔不返回进度，沿用上一次的值
+        let next = match (&*self, next) {
+            (Self::Running { progress: Some(p) }, Self::Running { progress: None }) => {
+                Self::Running { progress: Some(*p) }
+            }
+            (_, next) => next,
+        };
+        let changed = *self != next;
+        *self = next;
+        Ok(changed)
+    }
+}
+
+/// 把常见的状态值映射到统一状态；没有状态的失败响应视为本次查询失败，返回 None
+pub fn map_vendor_status(result: &VideoTaskResult) -> Option<VideoTaskState> {
+    let status = result
+        .status
+        .as_deref()
+        .unwrap_or_default()
+        .trim()
+        .to_ascii_lowercase();
+    let progress = result.progress.map(|p| p.clamp(0, 100));
+    let state = match status.as_str() {
+        "queued" | "pending" | "submitted" | "not_start" | "waiting" => VideoTaskState::Queued,
+        "completed" | "succeeded" | "succeed" | "success" => VideoTaskState::Succeeded {
+            assets: result
+                .video_url
+                .iter()
+                .map(|url| VideoAsset {
+                    url: Some(url.clone()),
+                    format: result.format.clone(),
+                })
+                .collect(),
+        },
+        "failed" | "failure" | "error" => VideoTaskState::Failed {
+            reason: result
+                .error
+                .clone()
+                .unwrap_or_else(|| format!("任务失败，状态: {}", status)),
+        },
+        "cancelled" | "canceled" => VideoTaskState::Cancelled,
+        "expired" => VideoTaskState::Expired,
+        "" if !result.success => return None,
+        "" if progress.unwrap_or(0) == 0 => VideoTaskState::Queued,
+        // in_progress / processing / running 以及其他未知的中间状态
+        _ => VideoTaskState::Running { progress },
+    };
+    Some(state)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn result(success: bool, status: Option<&str>, progress: Option<i32>) -> VideoTaskResult {
+        VideoTaskResult {
+            success,
+            task_id: Some("task-1".to_string()),
+            status: status.map(|s| s.to_string()),
+            progress,
+            video_url: Some("https://cdn/v.mp4".to_string()),
+            ..VideoTaskResult::default()
+        }
+    }
+
+    #[test]
+    fn test_map_vendor_status() {
+        assert_eq!(
+            map_vendor_status(&result(true, Some("in_progress"), Some(140))),
+            Some(VideoTaskState::Running {
+                progress: Some(100)
+            })
+        );
+        assert_eq!(
+            map_vendor_status(&result(true, Some("SUBMITTED"), None)),
+            Some(VideoTaskState::Queued)
+        );
+        assert_eq!(
+            map_vendor_status(&result(true, Some("succeed"), None)),
+            Some(VideoTaskState::Succeeded {
+                assets: vec![VideoAsset {
+                    url: Some("https://cdn/v.mp4".to_string()),
+                    format: None,
+                }]
+            })
+        );
+        assert!(matches!(
+            map_vendor_status(&result(false, Some("failed"), None)),
+            Some(VideoTaskState::Failed { .. })
+        ));
+        assert_eq!(map_vendor_status(&result(false, None, None)), None);
+    }
+
+    #[test]
+    fn test_transitions() {
+        let mut state = VideoTaskState::Queued;
+        assert_eq!(
+            state.transition(VideoTaskState::Running { progress: Some(30) }),
+            Ok(true)
+        );
+        assert!(state
+            .transition(VideoTaskState::Running { progress: Some(10) })
+            .is_err());
+        assert!(state.transition(VideoTaskState::Queued).is_err());
+        assert_eq!(
+            state.transition(VideoTaskState::Running { progress: None }),
+            Ok(false)
+        );
+        assert_eq!(state, VideoTaskState::Running { progress: Some(30) });
+
+        assert_eq!(state.transition(VideoTaskState::Expired), Ok(true));
+        assert!(state
+            .transition(VideoTaskState::Running { progress: None })
+            .is_err());
+        assert_eq!(state.transition(VideoTaskState::Expired), Ok(false));
+    }
+
+    #[test]
+    fn test_state_serialization() {
+        let value = serde_json::to_value(VideoTaskState::Running { progress: Some(5) }).unwrap();
+        assert_eq!(
+            value,
+            serde_json::json!({ "state": "running", "progress": 5 })
+        );
+    }
+}
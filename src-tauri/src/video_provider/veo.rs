@@ -0,0 +1,143 @@
+use async_trait::async_trait;
+use reqwest::Client;
+use serde::Serialize;
+
+use super::{
+    api_url, openai, parse_created, prepare_video_image, send_request, VideoGenerationRequest,
+    VideoProvider, VideoSource, VideoTaskHandle,
+};
+use crate::image_preprocess::{preprocess_base64_image, ImageLimits};
+use crate::video::{VeoImageData, VeoMetadata, VeoReferenceImage, VideoTaskResult};
+
+// Veo（经 `/v1/videos` 中转），首尾帧放在 images，其余参数放在 metadata
+pub struct VeoVideoProvider;
+
+#[derive(Debug, Serialize)]
+struct VeoApiRequest {
+    model: String,
+    prompt: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    images: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    metadata: Option<VeoMetadata>,
+}
+
+/// 参考图按 Veo 的限制预处理，失败时沿用原图
+async fn prepare_reference(image: String, kind: Option<String>) -> VeoReferenceImage {
+    let (bytes_base64_encoded, mime_type) =
+        match preprocess_base64_image(image.clone(), ImageLimits::for_provider("veo")).await {
+            Ok(prepared) if prepared.report.changed() => {
+                (prepared.to_base64(), Some(prepared.mime_type().to_string()))
+            }
+            Ok(_) => (image, None),
+            Err(e) => {
+                println!("[Rust] Failed to prepare reference image: {}", e);
+                (image, None)
+            }
+        };
+    VeoReferenceImage {
+        image: VeoImageData {
+            bytes_base64_encoded,
+            mime_type,
+        },
+        reference_type: kind.unwrap_or_else(|| "asset".to_string()),
+    }
+}
+
+fn build_metadata(
+    request: &VideoGenerationRequest,
+    reference_images: Vec<VeoReferenceImage>,
+) -> Option<VeoMetadata> {
+    let metadata = VeoMetadata {
+        aspect_ratio: request.aspect_ratio.clone(),
+        duration_seconds: request.duration.map(|d| d.round() as i32),
+        negative_prompt: request.negative_prompt.clone(),
+        person_generation: request.extra_str("personGeneration"),
+        resolution: request.resolution.clone(),
+        generate_audio: request.audio,
+        reference_images: (!reference_images.is_empty()).then_some(reference_images),
+    };
+    (metadata != VeoMetadata::default()).then_some(metadata)
+}
+
+#[async_trait]
+impl VideoProvider for VeoVideoProvider {
+    fn id(&self) -> &'static str {
+        "veo"
+    }
+
+    async fn create(
+        &self,
+        client: &Client,
+        request: &VideoGenerationRequest,
+    ) -> Result<VideoTaskResult, String> {
+        let mut images = Vec::new();
+        for frame in [
+            request.non_empty_first_frame(),
+            request.non_empty_last_frame(),
+        ]
+        .into_iter()
+        .flatten()
+        {
+            images.extend(prepare_video_image(Some(frame)).await);
+        }
+        let mut references = Vec::with_capacity(request.references.len());
+        for reference in &request.references {
+            references
+                .push(prepare_reference(reference.image.clone(), reference.kind.clone()).await);
+        }
+
+        let body = VeoApiRequest {
+            model: request.model.clone(),
+            prompt: request.prompt.clone(),
+            images: (!images.is_empty()).then_some(images),
+            metadata: build_metadata(request, references),
+        };
+        let url = api_url(&request.base_url, "/v1/videos");
+        println!("[Rust] Request URL: {}", url);
+        let text = send_request(client.post(&url).json(&body), &request.api_key).await?;
+        parse_created(&text)
+    }
+
+    async fn status(
+        &self,
+        client: &Client,
+        task: &VideoTaskHandle,
+    ) -> Result<VideoTaskResult, String> {
+        openai::get_status(client, task).await
+    }
+
+    async fn content(
+        &self,
+        _client: &Client,
+        task: &VideoTaskHandle,
+    ) -> Result<VideoSource, String> {
+        Ok(openai::content_source(task))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_build_metadata() {
+        assert!(build_metadata(&VideoGenerationRequest::default(), Vec::new()).is_none());
+
+        let request = VideoGenerationRequest {
+            aspect_ratio: Some("9:16".to_string()),
+            duration: Some(7.6),
+            audio: Some(true),
+            ..VideoGenerationRequest::default()
+        };
+        let metadata = serde_json::to_value(build_metadata(&request, Vec::new())).unwrap();
+        assert_eq!(
+            metadata,
+            serde_json::json!({
+                "aspectRatio": "9:16",
+                "durationSeconds": 8,
+                "generateAudio": true,
+            })
+        );
+    }
+}
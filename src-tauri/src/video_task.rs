@@ -0,0 +1,834 @@
+//! 后端视频任务轮询
+//!
+//! 前端创建视频任务后把任务 ID 交给这里，由后端按自适应间隔轮询状态，
+//! 通过 `video-task-progress` 事件上报进度；完成后下载视频保存到本地
+//! （下载进度见同一 request_id 的 `video-download-progress` 事件），
+//! 再发出 `video-task-complete` 事件。轮询不依赖页面，刷新 webview 后任务仍会继续。
+//!
+//! 任务记录保存在 `video_tasks.json` 中，应用重启后自动恢复未结束的任务，
+//! 历史记录通过 `list_video_tasks` 查询。
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::AtomicBool;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tauri::Emitter;
+use tauri_plugin_store::StoreExt;
+use uuid::Uuid;
+
+use crate::image_task::{
+    register_cancel_flag, remove_cancel_flag, request_cancel, sleep_unless_cancelled,
+};
+use crate::storage::{get_video_tasks_file, VideoInfo, VideoMetadata};
+use crate::video_provider::{
+    get_video_provider, get_video_task_status, store_task_video, store_video_url,
+    VideoDownloadTarget, VideoTaskHandle, VideoTaskState,
+};
+
+const DEFAULT_MAX_WAIT_SECS: u64 = 30 * 60;
+const MIN_POLL_INTERVAL: Duration = Duration::from_secs(3);
+const MAX_POLL_INTERVAL: Duration = Duration::from_secs(20);
+const MAX_FINISHED_RECORDS: usize = 200;
+// 状态没有变化时，累计轮询时长最多隔这么久写回一次
+const PROGRESS_SAVE_INTERVAL: Duration = Duration::from_secs(60);
+// 前端设置所在的 tauri-plugin-store 文件和键（见 src/utils/tauriStorage.ts、settingsStore.ts）
+const SETTINGS_STORE: &str = "app-data.json";
+const SETTINGS_KEY: &str = "next-creator-settings";
+
+// 前端调用的参数：已创建的任务
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VideoTaskWatchParams {
+    pub provider: String,        // "openai" | "newapi" | "veo" | "kling"
+    pub profile: Option<String>, // 前端的服务配置名称，用于历史面板显示
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub provider_id: Option<String>, // 前端供应商配置的 ID，恢复轮询时据此读取 API Key
+    pub base_url: String,
+    #[serde(default, skip_serializing)]
+    pub api_key: String, // 只在内存中使用，不写入任务文件
+    pub task_id: String,
+    pub mode: Option<String>, // Kling 的 "text2video" / "image2video" / "multi-image2video"
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<Value>, // 创建任务时返回的 metadata，记录到保存的视频中
+    pub model: Option<String>,
+    pub prompt: Option<String>,
+    pub request_id: Option<String>, // 用于区分事件和取消任务
+    pub max_wait_secs: Option<u64>, // 默认 30 分钟
+    pub canvas_id: Option<String>,
+    pub node_id: Option<String>,
+    pub save_to_storage: Option<bool>, // 默认下载保存，否则只返回视频地址
+}
+
+// 启动结果
+#[derive(Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VideoTaskStartResult {
+    pub success: bool,
+    pub request_id: Option<String>,
+    pub error: Option<String>,
+}
+
+// 进度事件（video-task-progress）
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VideoTaskProgressEvent {
+    pub request_id: String,
+    pub provider: String,
+    pub task_id: String,
+    pub state: VideoTaskState,
+    pub vendor_status: Option<String>, // 服务返回的原始状态
+    pub downloading: bool,             // 任务已完成，正在下载视频
+    pub elapsed_secs: u64,
+}
+
+// 完成事件（video-task-complete），失败、超时和取消也会发出
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VideoTaskCompleteEvent {
+    pub request_id: String,
+    pub provider: String,
+    pub task_id: String,
+    pub success: bool,
+    pub cancelled: bool, // 用户停止跟踪
+    pub state: VideoTaskState,
+    pub video_url: Option<String>,
+    pub video: Option<VideoInfo>, // 保存到本地的视频
+    pub error: Option<String>,
+}
+
+// 持久化的任务记录，应用重启后据此恢复轮询
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VideoTaskRecord {
+    #[serde(flatten)]
+    pub params: VideoTaskWatchParams,
+    #[serde(default)]
+    pub state: VideoTaskState,
+    pub vendor_status: Option<String>,
+    #[serde(default)]
+    pub downloading: bool,
+    #[serde(default)]
+    pub polled_secs: u64, // 实际轮询的累计时长，应用关闭期间不计入
+    pub finished: bool,
+    pub created_at: i64, // 秒
+    pub updated_at: i64,
+    pub video_url: Option<String>,
+    pub video: Option<VideoInfo>,
+    pub error: Option<String>,
+}
+
+impl VideoTaskRecord {
+    fn request_id(&self) -> &str {
+        self.params.request_id.as_deref().unwrap_or_default()
+    }
+}
+
+// ==================== 任务队列持久化 ====================
+
+lazy_static::lazy_static! {
+    // 串行化队列文件的读写
+    static ref QUEUE_LOCK: Mutex<()> = Mutex::new(());
+}
+
+/// 读取队列文件；文件不存在时为空，读取或解析失败时返回错误，避免用空队列覆盖原文件
+fn load_records(path: &Path) -> Result<Vec<VideoTaskRecord>, String> {
+    let content = match fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(format!("读取任务队列失败: {}", e)),
+    };
+    if content.trim().is_empty() {
+        return Ok(Vec::new());
+    }
+    serde_json::from_str(&content).map_err(|e| {
+        format!(
+            "任务队列文件已损坏，不会覆盖（可从 {} 恢复）: {}",
+            backup_path(path).display(),
+            e
+        )
+    })
+}
+
+fn backup_path(path: &Path) -> PathBuf {
+    path.with_extension("json.bak")
+}
+
+fn store_records(path: &Path, records: &[VideoTaskRecord]) -> Result<(), String> {
+    let json =
+        serde_json::to_string_pretty(records).map_err(|e| format!("序列化任务队列失败: {}", e))?;
+    // 先写临时文件再替换，避免退出时写坏队列；替换前把上一版保留为 .bak
+    let temp_path = path.with_extension("json.tmp");
+    fs::write(&temp_path, json).map_err(|e| format!("写入任务队列失败: {}", e))?;
+    if path.exists() {
+        if let Err(e) = fs::copy(path, backup_path(path)) {
+            println!("[Rust] Failed to back up video task queue: {}", e);
+        }
+    }
+    fs::rename(&temp_path, path).map_err(|e| format!("写入任务队列失败: {}", e))
+}
+
+/// 未结束的任务全部保留，已结束的只保留最近的若干条
+fn prune_finished(records: &mut Vec<VideoTaskRecord>) {
+    records.sort_by_key(|record| std::cmp::Reverse(record.created_at));
+    let mut finished = 0;
+    records.retain(|record| {
+        if record.finished {
+            finished += 1;
+        }
+        !record.finished || finished <= MAX_FINISHED_RECORDS
+    });
+}
+
+/// 在锁内读取、修改并写回队列文件
+fn update_queue<T>(
+    app: &tauri::AppHandle,
+    f: impl FnOnce(&mut Vec<VideoTaskRecord>) -> T,
+) -> Result<T, String> {
+    let _guard = QUEUE_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let path = get_video_tasks_file(app)?;
+    let mut records = load_records(&path)?;
+    let result = f(&mut records);
+    prune_finished(&mut records);
+    store_records(&path, &records)?;
+    Ok(result)
+}
+
+/// 修改单条任务记录，写入失败只记录日志
+fn update_record(app: &tauri::AppHandle, request_id: &str, f: impl FnOnce(&mut VideoTaskRecord)) {
+    let result = update_queue(app, |records| {
+        if let Some(record) = records
+            .iter_mut()
+            .find(|record| record.request_id() == request_id)
+        {
+            f(record);
+            record.updated_at = chrono::Utc::now().timestamp();
+        }
+    });
+    if let Err(e) = result {
+        println!("[Rust] Failed to update video task queue: {}", e);
+    }
+}
+
+// ==================== 轮询 ====================
+
+/// 上次写回记录时的进度；每次写回都会重写整个队列文件，没有变化的轮询不写
+struct SavedProgress {
+    state: VideoTaskState,
+    vendor_status: Option<String>,
+    downloading: bool,
+    saved_at: Instant,
+}
+
+impl SavedProgress {
+    fn new(record: &VideoTaskRecord) -> Self {
+        Self {
+            state: record.state.clone(),
+            vendor_status: record.vendor_status.clone(),
+            downloading: record.downloading,
+            saved_at: Instant::now(),
+        }
+    }
+
+    fn changed(
+        &self,
+        state: &VideoTaskState,
+        vendor_status: &Option<String>,
+        downloading: bool,
+    ) -> bool {
+        self.state != *state
+            || self.vendor_status != *vendor_status
+            || self.downloading != downloading
+    }
+
+    fn due(&self) -> bool {
+        self.saved_at.elapsed() >= PROGRESS_SAVE_INTERVAL
+    }
+}
+
+/// 下一次轮询的间隔：进度有变化时回到最短间隔，否则逐步放慢
+fn next_interval(current: Duration, progressed: bool) -> Duration {
+    if progressed {
+        MIN_POLL_INTERVAL
+    } else {
+        current.mul_f64(1.5).min(MAX_POLL_INTERVAL)
+    }
+}
+
+/// 是否已用完等待时间；本次运行还没查询过时不算超时，恢复的任务至少查询一次
+fn wait_exhausted(polled_this_run: bool, polled_secs: u64, max_wait: u64) -> bool {
+    polled_this_run && polled_secs >= max_wait
+}
+
+fn task_handle(params: &VideoTaskWatchParams) -> VideoTaskHandle {
+    VideoTaskHandle {
+        provider: params.provider.clone(),
+        base_url: params.base_url.clone(),
+        api_key: params.api_key.clone(),
+        task_id: params.task_id.clone(),
+        mode: params.mode.clone(),
+    }
+}
+
+/// 保存视频时记录的额外信息：创建任务返回的 metadata 加上 mode
+fn video_extra(params: &VideoTaskWatchParams) -> Option<Value> {
+    let mut extra = match &params.metadata {
+        Some(Value::Object(map)) => map.clone(),
+        _ => serde_json::Map::new(),
+    };
+    if let Some(mode) = &params.mode {
+        extra.insert("mode".to_string(), Value::String(mode.clone()));
+    }
+    (!extra.is_empty()).then_some(Value::Object(extra))
+}
+
+/// 查询一次任务状态，返回统一状态和服务的原始状态
+async fn poll_once(
+    params: &VideoTaskWatchParams,
+) -> Result<(VideoTaskState, Option<String>), String> {
+    let result = get_video_task_status(&task_handle(params)).await;
+    match result.state {
+        Some(state) => Ok((state, result.status)),
+        None => Err(result
+            .error
+            .unwrap_or_else(|| "查询任务状态失败".to_string())),
+    }
+}
+
+/// 下载完成的视频并保存，返回保存的视频和可公开的下载地址
+async fn store_video(
+    app: &tauri::AppHandle,
+    params: &VideoTaskWatchParams,
+    target: VideoDownloadTarget,
+    video_url: Option<String>,
+) -> Result<(VideoInfo, Option<String>), String> {
+    match store_task_video(app, &task_handle(params), target.clone()).await {
+        // 需要鉴权的地址不对外返回
+        Ok((video, source)) => Ok((video, (!source.authorized).then_some(source.url))),
+        // 适配器取不到地址时，退回状态查询中带回的地址
+        Err(e) => {
+            let Some(url) = video_url else {
+                return Err(e);
+            };
+            let mut target = target;
+            if let Some(metadata) = target.metadata.as_mut() {
+                metadata.source_url = Some(url.clone());
+            }
+            store_video_url(app, &url, target)
+                .await
+                .map(|video| (video, Some(url)))
+        }
+    }
+}
+
+/// 轮询到任务结束并下载、保存视频
+async fn watch_task(
+    app: &tauri::AppHandle,
+    record: &VideoTaskRecord,
+    cancel: &AtomicBool,
+) -> VideoTaskCompleteEvent {
+    let params = &record.params;
+    let request_id = record.request_id();
+    // 最长等待时间按实际轮询的时长累计，应用关闭期间不计入；恢复的任务至少查询一次
+    let max_wait = params.max_wait_secs.unwrap_or(DEFAULT_MAX_WAIT_SECS);
+    let started = Instant::now();
+    let polled_secs = || record.polled_secs + started.elapsed().as_secs();
+    let mut event = VideoTaskCompleteEvent {
+        request_id: request_id.to_string(),
+        provider: params.provider.clone(),
+        task_id: params.task_id.clone(),
+        ..VideoTaskCompleteEvent::default()
+    };
+    let saved = Mutex::new(SavedProgress::new(record));
+    let report = |state: &VideoTaskState, vendor_status: Option<String>, downloading: bool| {
+        let mut saved = saved.lock().unwrap_or_else(|e| e.into_inner());
+        if saved.changed(state, &vendor_status, downloading) || saved.due() {
+            update_record(app, request_id, |record| {
+                record.state = state.clone();
+                record.vendor_status = vendor_status.clone();
+                record.downloading = downloading;
+                record.polled_secs = polled_secs();
+            });
+            *saved = SavedProgress {
+                state: state.clone(),
+                vendor_status: vendor_status.clone(),
+                downloading,
+                saved_at: Instant::now(),
+            };
+        }
+        drop(saved);
+        let _ = app.emit(
+            "video-task-progress",
+            VideoTaskProgressEvent {
+                request_id: request_id.to_string(),
+                provider: params.provider.clone(),
+                task_id: params.task_id.clone(),
+                state: state.clone(),
+                vendor_status,
+                downloading,
+                elapsed_secs: (chrono::Utc::now().timestamp() - record.created_at).max(0) as u64,
+            },
+        );
+    };
+
+    let save_polled = || {
+        let mut saved = saved.lock().unwrap_or_else(|e| e.into_inner());
+        if saved.due() {
+            update_record(app, request_id, |record| record.polled_secs = polled_secs());
+            saved.saved_at = Instant::now();
+        }
+    };
+
+    // 从记录中的状态继续，重启恢复后同样只允许向前推进
+    let mut state = record.state.clone();
+    let mut interval = MIN_POLL_INTERVAL;
+    let mut polled = false;
+    while !state.is_terminal() {
+        if wait_exhausted(polled, polled_secs(), max_wait) {
+            state = VideoTaskState::Expired;
+            event.error = Some(format!("等待超时（{}）", params.task_id));
+            break;
+        }
+        polled = true;
+        match poll_once(params).await {
+            Ok((next, vendor_status)) => match state.transition(next) {
+                Ok(changed) => {
+                    interval = next_interval(interval, changed);
+                    if !state.is_terminal() {
+                        report(&state, vendor_status, false);
+                    }
+                }
+                Err(e) => {
+                    println!("[Rust] Video task {}: {}", params.task_id, e);
+                    interval = next_interval(interval, false);
+                    save_polled();
+                }
+            },
+            // 单次查询失败不终止任务，放慢后继续
+            Err(e) => {
+                println!("[Rust] Video task poll failed: {}", e);
+                interval = next_interval(interval, false);
+                save_polled();
+            }
+        }
+        if !state.is_terminal() && !sleep_unless_cancelled(interval, cancel).await {
+            state = VideoTaskState::Cancelled;
+            event.cancelled = true;
+            event.error = Some("已停止跟踪任务".to_string());
+        }
+    }
+    event.state = state.clone();
+
+    let video_url = match &state {
+        VideoTaskState::Succeeded { assets } => assets.iter().find_map(|asset| asset.url.clone()),
+        VideoTaskState::Failed { reason } => {
+            event.error = Some(reason.clone());
+            return event;
+        }
+        VideoTaskState::Cancelled if !event.cancelled => {
+            event.error = Some("任务已被服务取消".to_string());
+            return event;
+        }
+        VideoTaskState::Expired if event.error.is_none() => {
+            event.error = Some("任务已过期".to_string());
+            return event;
+        }
+        _ => return event,
+    };
+
+    // 不保存时只返回视频地址
+    if !params.save_to_storage.unwrap_or(true) {
+        event.video_url = video_url;
+        event.success = true;
+        return event;
+    }
+
+    report(&state, None, true);
+    let target = VideoDownloadTarget {
+        request_id: Some(request_id.to_string()),
+        canvas_id: params.canvas_id.clone(),
+        node_id: params.node_id.clone(),
+        metadata: Some(VideoMetadata {
+            prompt: params.prompt.clone(),
+            provider: Some(params.provider.clone()),
+            model: params.model.clone(),
+            task_id: Some(params.task_id.clone()),
+            node_id: params.node_id.clone(),
+            canvas_id: params.canvas_id.clone(),
+            created_at: chrono::Utc::now().timestamp(),
+            extra: video_extra(params),
+            ..VideoMetadata::default()
+        }),
+        ..VideoDownloadTarget::default()
+    };
+    match store_video(app, params, target, video_url).await {
+        Ok((video, video_url)) => {
+            event.video = Some(video);
+            event.video_url = video_url;
+        }
+        Err(e) => {
+            event.error = Some(e);
+            return event;
+        }
+    }
+    event.success = true;
+    event
+}
+
+/// 在后台跟踪任务，结束后写回记录并发出完成事件
+fn spawn_watch(app: tauri::AppHandle, record: VideoTaskRecord, cancel: Arc<AtomicBool>) {
+    let request_id = record.request_id().to_string();
+    tauri::async_runtime::spawn(async move {
+        let event = watch_task(&app, &record, &cancel).await;
+        remove_cancel_flag(&request_id);
+        println!(
+            "[Rust] Video task {} finished: success={}, error={:?}",
+            record.params.task_id, event.success, event.error
+        );
+        update_record(&app, &request_id, |record| {
+            record.state = event.state.clone();
+            record.downloading = false;
+            record.finished = true;
+            record.video_url = event.video_url.clone();
+            record.video = event.video.clone();
+            record.error = event.error.clone();
+        });
+        let _ = app.emit("video-task-complete", event);
+    });
+}
+
+/// 在前端设置中按供应商 ID 查找 API Key；设置经 zustand persist 存为 JSON 字符串
+fn find_provider_api_key(settings: &Value, provider_id: &str) -> Option<String> {
+    let parsed;
+    let settings = match settings {
+        Value::String(json) => {
+            parsed = serde_json::from_str::<Value>(json).ok()?;
+            &parsed
+        }
+        value => value,
+    };
+    settings
+        .pointer("/state/settings/providers")?
+        .as_array()?
+        .iter()
+        .find(|provider| provider.get("id").and_then(Value::as_str) == Some(provider_id))?
+        .get("apiKey")?
+        .as_str()
+        .filter(|key| !key.is_empty())
+        .map(|key| key.to_string())
+}
+
+/// 恢复的任务没有保存 API Key，从供应商配置中读取
+fn provider_api_key(
+    app: &tauri::AppHandle,
+    params: &VideoTaskWatchParams,
+) -> Result<String, String> {
+    let provider_id = params
+        .provider_id
+        .as_deref()
+        .ok_or("任务没有关联供应商配置，无法恢复")?;
+    // 与前端的加载选项保持一致，不开启自动保存
+    let store = app
+        .store_builder(SETTINGS_STORE)
+        .disable_auto_save()
+        .build()
+        .map_err(|e| format!("读取供应商设置失败: {}", e))?;
+    let settings = store.get(SETTINGS_KEY).ok_or("未找到供应商设置")?;
+    find_provider_api_key(&settings, provider_id)
+        .ok_or_else(|| format!("供应商配置 {} 不存在或没有 API Key", provider_id))
+}
+
+/// 无法恢复的任务直接结束并发出完成事件
+fn finish_unresumable(app: &tauri::AppHandle, record: &VideoTaskRecord, error: String) {
+    println!(
+        "[Rust] Cannot resume video task {}: {}",
+        record.params.task_id, error
+    );
+    update_record(app, record.request_id(), |record| {
+        record.finished = true;
+        record.error = Some(error.clone());
+    });
+    let _ = app.emit(
+        "video-task-complete",
+        VideoTaskCompleteEvent {
+            request_id: record.request_id().to_string(),
+            provider: record.params.provider.clone(),
+            task_id: record.params.task_id.clone(),
+            state: record.state.clone(),
+            error: Some(error),
+            ..VideoTaskCompleteEvent::default()
+        },
+    );
+}
+
+/// 启动时恢复上次未结束的任务
+pub fn resume_video_tasks(app: &tauri::AppHandle) {
+    let pending = match update_queue(app, |records| {
+        records
+            .iter()
+            .filter(|record| !record.finished && !record.request_id().is_empty())
+            .cloned()
+            .collect::<Vec<_>>()
+    }) {
+        Ok(pending) => pending,
+        Err(e) => {
+            println!("[Rust] Failed to load video task queue: {}", e);
+            return;
+        }
+    };
+    if !pending.is_empty() {
+        println!("[Rust] Resuming {} video task(s)", pending.len());
+    }
+    for mut record in pending {
+        // 旧版本的记录里可能还带着 API Key，下次写入时会被去掉
+        if record.params.api_key.is_empty() {
+            match provider_api_key(app, &record.params) {
+                Ok(api_key) => record.params.api_key = api_key,
+                Err(e) => {
+                    finish_unresumable(app, &record, e);
+                    continue;
+                }
+            }
+        }
+        match register_cancel_flag(record.request_id()) {
+            Ok(cancel) => spawn_watch(app.clone(), record, cancel),
+            Err(e) => println!("[Rust] Skip resuming video task: {}", e),
+        }
+    }
+}
+
+// Tauri 命令：在后端跟踪已创建的视频任务，立即返回 request_id
+#[tauri::command]
+pub fn video_task_start(
+    app: tauri::AppHandle,
+    mut params: VideoTaskWatchParams,
+) -> VideoTaskStartResult {
+    println!(
+        "[Rust] video_task_start called, provider: {}, task_id: {}",
+        params.provider, params.task_id
+    );
+    if params.task_id.trim().is_empty() {
+        return VideoTaskStartResult {
+            success: false,
+            error: Some("任务 ID 不能为空".to_string()),
+            ..VideoTaskStartResult::default()
+        };
+    }
+    params.provider = params.provider.trim().to_ascii_lowercase();
+    if get_video_provider(&params.provider).is_none() {
+        return VideoTaskStartResult {
+            success: false,
+            error: Some(format!("不支持的视频服务: {}", params.provider)),
+            ..VideoTaskStartResult::default()
+        };
+    }
+
+    let request_id = params
+        .request_id
+        .get_or_insert_with(|| Uuid::new_v4().to_string())
+        .clone();
+    // 同一 request_id 仍在跟踪时不能覆盖它的记录和取消信号
+    let cancel = match register_cancel_flag(&request_id) {
+        Ok(cancel) => cancel,
+        Err(e) => {
+            return VideoTaskStartResult {
+                success: false,
+                request_id: Some(request_id),
+                error: Some(e),
+            }
+        }
+    };
+    let now = chrono::Utc::now().timestamp();
+    let record = VideoTaskRecord {
+        params,
+        created_at: now,
+        updated_at: now,
+        ..VideoTaskRecord::default()
+    };
+    // 先落盘再开始轮询，应用在此之后退出也能恢复
+    let persisted = update_queue(&app, |records| {
+        records.retain(|existing| existing.request_id() != request_id);
+        records.push(record.clone());
+    });
+    if let Err(e) = persisted {
+        println!("[Rust] Failed to persist video task: {}", e);
+    }
+    spawn_watch(app, record, cancel);
+
+    VideoTaskStartResult {
+        success: true,
+        request_id: Some(request_id),
+        error: None,
+    }
+}
+
+// Tauri 命令：视频任务历史（最新的在前）
+#[tauri::command]
+pub fn list_video_tasks(
+    app: tauri::AppHandle,
+    canvas_id: Option<String>,
+) -> Result<Vec<VideoTaskRecord>, String> {
+    let records = {
+        let _guard = QUEUE_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        load_records(&get_video_tasks_file(&app)?)?
+    };
+    let mut records: Vec<VideoTaskRecord> = records
+        .into_iter()
+        .filter(|record| canvas_id.is_none() || record.params.canvas_id == canvas_id)
+        .collect();
+    records.sort_by_key(|record| std::cmp::Reverse(record.created_at));
+    Ok(records)
+}
+
+// Tauri 命令：停止跟踪视频任务（服务端的任务不受影响）
+#[tauri::command]
+pub fn video_task_cancel(request_id: String) -> bool {
+    println!(
+        "[Rust] video_task_cancel called, request_id: {}",
+        request_id
+    );
+    request_cancel(&request_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_prune_keeps_unfinished_records() {
+        let mut records: Vec<VideoTaskRecord> = (0..MAX_FINISHED_RECORDS as i64 + 5)
+            .map(|i| VideoTaskRecord {
+                finished: i != 0,
+                created_at: i,
+                ..VideoTaskRecord::default()
+            })
+            .collect();
+        prune_finished(&mut records);
+        assert_eq!(records.len(), MAX_FINISHED_RECORDS + 1);
+        assert!(records.iter().any(|record| !record.finished));
+        assert_eq!(records[0].created_at, MAX_FINISHED_RECORDS as i64 + 4);
+    }
+
+    #[test]
+    fn test_corrupt_queue_is_not_overwritten() {
+        let dir = std::env::temp_dir().join(format!("video_tasks_{}", Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("video_tasks.json");
+
+        assert!(load_records(&path).unwrap().is_empty());
+        let record = VideoTaskRecord {
+            created_at: 1,
+            ..VideoTaskRecord::default()
+        };
+        store_records(&path, std::slice::from_ref(&record)).unwrap();
+        store_records(&path, &[record.clone(), record]).unwrap();
+        assert_eq!(load_records(&path).unwrap().len(), 2);
+        assert_eq!(load_records(&backup_path(&path)).unwrap().len(), 1);
+
+        fs::write(&path, "[{\"provider\":").unwrap();
+        assert!(load_records(&path).is_err());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_unchanged_progress_is_not_saved_every_poll() {
+        let record = VideoTaskRecord {
+            state: VideoTaskState::Running { progress: Some(10) },
+            vendor_status: Some("processing".to_string()),
+            ..VideoTaskRecord::default()
+        };
+        let mut saved = SavedProgress::new(&record);
+        assert!(!saved.changed(&record.state, &record.vendor_status, false));
+        assert!(!saved.due());
+        assert!(saved.changed(
+            &VideoTaskState::Running { progress: Some(20) },
+            &record.vendor_status,
+            false
+        ));
+        assert!(saved.changed(&record.state, &record.vendor_status, true));
+        saved.saved_at = Instant::now() - PROGRESS_SAVE_INTERVAL;
+        assert!(saved.due());
+    }
+
+    #[test]
+    fn test_next_interval_backs_off_until_progress() {
+        let slower = next_interval(MIN_POLL_INTERVAL, false);
+        assert!(slower > MIN_POLL_INTERVAL);
+        assert_eq!(next_interval(MAX_POLL_INTERVAL, false), MAX_POLL_INTERVAL);
+        assert_eq!(next_interval(slower, true), MIN_POLL_INTERVAL);
+    }
+
+    #[test]
+    fn test_resumed_task_polls_before_expiring() {
+        assert!(!wait_exhausted(
+            false,
+            DEFAULT_MAX_WAIT_SECS + 60,
+            DEFAULT_MAX_WAIT_SECS
+        ));
+        assert!(!wait_exhausted(true, 10, DEFAULT_MAX_WAIT_SECS));
+        assert!(wait_exhausted(
+            true,
+            DEFAULT_MAX_WAIT_SECS,
+            DEFAULT_MAX_WAIT_SECS
+        ));
+
+        // 旧记录没有 polledSecs，按 0 处理
+        let record: VideoTaskRecord = serde_json::from_str(
+            r#"{"provider":"veo","baseUrl":"https://api","taskId":"t1","finished":false,"createdAt":0,"updatedAt":0}"#,
+        )
+        .unwrap();
+        assert_eq!(record.polled_secs, 0);
+    }
+
+    #[test]
+    fn test_api_key_is_not_persisted() {
+        let record = VideoTaskRecord {
+            params: VideoTaskWatchParams {
+                provider_id: Some("p1".to_string()),
+                api_key: "sk-secret".to_string(),
+                ..VideoTaskWatchParams::default()
+            },
+            ..VideoTaskRecord::default()
+        };
+        let json = serde_json::to_string(&record).unwrap();
+        assert!(!json.contains("sk-secret"));
+        assert!(json.contains("\"providerId\":\"p1\""));
+    }
+
+    #[test]
+    fn test_find_provider_api_key() {
+        let settings = serde_json::json!({
+            "state": { "settings": { "providers": [
+                { "id": "p1", "apiKey": "sk-1" },
+                { "id": "p2", "apiKey": "" },
+            ] } },
+            "version": 0,
+        });
+        assert_eq!(
+            find_provider_api_key(&settings, "p1").as_deref(),
+            Some("sk-1")
+        );
+        assert_eq!(find_provider_api_key(&settings, "p2"), None);
+        assert_eq!(find_provider_api_key(&settings, "p3"), None);
+        // zustand persist 存的是 JSON 字符串
+        let stored = Value::String(settings.to_string());
+        assert_eq!(
+            find_provider_api_key(&stored, "p1").as_deref(),
+            Some("sk-1")
+        );
+    }
+
+    #[test]
+    fn test_video_extra_keeps_task_metadata() {
+        let mut params = VideoTaskWatchParams::default();
+        assert_eq!(video_extra(&params), None);
+        params.mode = Some("image2video".to_string());
+        params.metadata = Some(serde_json::json!({ "mode": "text2video", "endFrame": true }));
+        assert_eq!(
+            video_extra(&params),
+            Some(serde_json::json!({ "mode": "image2video", "endFrame": true }))
+        );
+    }
+}
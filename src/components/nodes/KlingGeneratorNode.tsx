@@ -375,29 +375,23 @@ export const KlingGeneratorNode = memo(({ id, data, selected }: NodeProps<KlingG
 
     try {
       // 从 URL 下载视频
-      const result = await invoke<{ success: boolean; videoData?: string; error?: string }>("kling_download_video", {
+      // 后端流式下载到本地存储
+      const result = await invoke<{ success: boolean; video?: { path: string }; error?: string }>("kling_download_video", {
         params: {
           videoUrl: videoUrlToDownload,
+          canvasId: activeCanvasId,
+          nodeId: id,
         },
       });
 
-      if (!result.success || !result.videoData) {
+      if (!result.success || !result.video) {
         console.error("[KlingNode] 下载失败:", result.error);
         setIsDownloading(false);
         return;
       }
 
-      // 将 base64 转换为 Uint8Array
-      const byteCharacters = atob(result.videoData);
-      const byteNumbers = new Array(byteCharacters.length);
-      for (let i = 0; i < byteCharacters.length; i++) {
-        byteNumbers[i] = byteCharacters.charCodeAt(i);
-      }
-      const bytes = new Uint8Array(byteNumbers);
-
       // 使用 Tauri 保存对话框
       const { save } = await import("@tauri-apps/plugin-dialog");
-      const { writeFile } = await import("@tauri-apps/plugin-fs");
 
       const filePath = await save({
         defaultPath: `kling-video-${Date.now()}.mp4`,
@@ -405,14 +399,14 @@ export const KlingGeneratorNode = memo(({ id, data, selected }: NodeProps<KlingG
       });
 
       if (filePath) {
-        await writeFile(filePath, bytes);
+        await invoke("export_video", { sourcePath: result.video.path, targetPath: filePath });
       }
     } catch (error) {
       console.error("[KlingNode] 下载失败:", error);
     }
 
     setIsDownloading(false);
-  }, [data.videoUrl, previewUrl, isDownloading]);
+  }, [data.videoUrl, previewUrl, isDownloading, activeCanvasId, id]);
 
   // 获取当前阶段配置
   const currentStage = data.taskStage ? stageConfig[data.taskStage] : null;
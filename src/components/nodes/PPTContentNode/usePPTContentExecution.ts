@@ -408,7 +408,11 @@ export function usePPTContentExecution({
             const imageInfo = await saveImage(
               response.imageData,
               saveCanvasId,
-              `${nodeId}-page-${page.pageNumber}`
+              `${nodeId}-page-${page.pageNumber}`,
+              prompt,
+              undefined,
+              "generated",
+              response.generation
             );
             imagePath = imageInfo.path;
           } catch (saveError) {
@@ -29,7 +29,7 @@ import { useNodeConnectionStatus } from "@/hooks/useNodeConnectionStatus";
 import { ErrorDetailModal } from "@/components/ui/ErrorDetailModal";
 import { useCustomModelStore } from "@/stores/customModelStore";
 import type { ErrorDetails } from "@/types";
-import { invoke } from "@tauri-apps/api/core";
+import { convertFileSrc, invoke } from "@tauri-apps/api/core";
 import { useSettingsStore } from "@/stores/settingsStore";
 import type {
   VeoAspectRatio,
@@ -128,15 +128,6 @@ export const VeoGeneratorNode = memo(({ id, data, selected }: NodeProps<VeoGener
     return preset ? preset.label : currentModel;
   };
 
-  // 清理函数 - 清理预览 URL
-  useEffect(() => {
-    return () => {
-      if (previewUrl) {
-        URL.revokeObjectURL(previewUrl);
-      }
-    };
-  }, [previewUrl]);
-
   // 打开预览
   const handleOpenPreview = useCallback(async () => {
     if (!data.taskId || previewState === "loading") return;
@@ -156,7 +147,7 @@ export const VeoGeneratorNode = memo(({ id, data, selected }: NodeProps<VeoGener
     }
 
     try {
-      const result = await invoke<{ success: boolean; videoData?: string; error?: string }>("veo_get_content", {
+      const result = await invoke<{ success: boolean; video?: { path: string }; error?: string }>("veo_get_content", {
         params: {
           baseUrl: provider.baseUrl,
           apiKey: provider.apiKey,
@@ -164,17 +155,9 @@ export const VeoGeneratorNode = memo(({ id, data, selected }: NodeProps<VeoGener
         },
       });
 
-      if (result.success && result.videoData) {
-        // 将 base64 转换为 Blob URL
-        const byteCharacters = atob(result.videoData);
-        const byteNumbers = new Array(byteCharacters.length);
-        for (let i = 0; i < byteCharacters.length; i++) {
-          byteNumbers[i] = byteCharacters.charCodeAt(i);
-        }
-        const byteArray = new Uint8Array(byteNumbers);
-        const blob = new Blob([byteArray], { type: "video/mp4" });
-        const url = URL.createObjectURL(blob);
-        setPreviewUrl(url);
+      if (result.success && result.video) {
+        // 视频已保存到本地，通过 asset 协议播放
+        setPreviewUrl(convertFileSrc(result.video.path));
         setPreviewState("ready");
       } else {
         setPreviewError(result.error || "加载视频失败");
@@ -188,13 +171,10 @@ export const VeoGeneratorNode = memo(({ id, data, selected }: NodeProps<VeoGener
 
   // 关闭预览
   const handleClosePreview = useCallback(() => {
-    if (previewUrl) {
-      URL.revokeObjectURL(previewUrl);
-    }
     setPreviewUrl(null);
     setPreviewState("idle");
     setPreviewError(null);
-  }, [previewUrl]);
+  }, []);
 
   // 生成视频
   const handleGenerate = useCallback(async () => {
@@ -429,7 +409,7 @@ export const VeoGeneratorNode = memo(({ id, data, selected }: NodeProps<VeoGener
       }
 
       // 直接调用 Veo 后端获取视频内容
-      const result = await invoke<{ success: boolean; videoData?: string; error?: string }>("veo_get_content", {
+      const result = await invoke<{ success: boolean; video?: { path: string }; error?: string }>("veo_get_content", {
         params: {
           baseUrl: provider.baseUrl,
           apiKey: provider.apiKey,
@@ -437,23 +417,15 @@ export const VeoGeneratorNode = memo(({ id, data, selected }: NodeProps<VeoGener
         },
       });
 
-      if (!result.success || !result.videoData) {
+      if (!result.success || !result.video) {
         console.error("[VeoNode] 下载失败:", result.error);
         setIsDownloading(false);
         return;
       }
-
-      // 将 base64 转换为 Uint8Array
-      const byteCharacters = atob(result.videoData);
-      const byteNumbers = new Array(byteCharacters.length);
-      for (let i = 0; i < byteCharacters.length; i++) {
-        byteNumbers[i] = byteCharacters.charCodeAt(i);
-      }
-      const bytes = new Uint8Array(byteNumbers);
+      const sourcePath = result.video.path;
 
       // 使用 Tauri 保存对话框
       const { save } = await import("@tauri-apps/plugin-dialog");
-      const { writeFile } = await import("@tauri-apps/plugin-fs");
 
       const filePath = await save({
         defaultPath: `veo-video-${Date.now()}.mp4`,
@@ -461,7 +433,7 @@ export const VeoGeneratorNode = memo(({ id, data, selected }: NodeProps<VeoGener
       });
 
       if (filePath) {
-        await writeFile(filePath, bytes);
+        await invoke("export_video", { sourcePath, targetPath: filePath });
       }
     } catch (error) {
       console.error("[VeoNode] 下载失败:", error);
@@ -1173,7 +1145,7 @@ function VeoPreviewModal({ videoUrl, taskId, onClose }: VeoPreviewModalProps) {
       }
 
       // 直接调用 Veo 后端获取视频内容
-      const result = await invoke<{ success: boolean; videoData?: string; error?: string }>("veo_get_content", {
+      const result = await invoke<{ success: boolean; video?: { path: string }; error?: string }>("veo_get_content", {
         params: {
           baseUrl: provider.baseUrl,
           apiKey: provider.apiKey,
@@ -1181,23 +1153,15 @@ function VeoPreviewModal({ videoUrl, taskId, onClose }: VeoPreviewModalProps) {
         },
       });
 
-      if (!result.success || !result.videoData) {
+      if (!result.success || !result.video) {
         console.error("[VeoPreview] 下载失败:", result.error);
         setIsDownloading(false);
         return;
       }
-
-      // 将 base64 转换为 Uint8Array
-      const byteCharacters = atob(result.videoData);
-      const byteNumbers = new Array(byteCharacters.length);
-      for (let i = 0; i < byteCharacters.length; i++) {
-        byteNumbers[i] = byteCharacters.charCodeAt(i);
-      }
-      const bytes = new Uint8Array(byteNumbers);
+      const sourcePath = result.video.path;
 
       // 使用 Tauri 保存对话框
       const { save } = await import("@tauri-apps/plugin-dialog");
-      const { writeFile } = await import("@tauri-apps/plugin-fs");
 
       const filePath = await save({
         defaultPath: `veo-video-${Date.now()}.mp4`,
@@ -1205,7 +1169,7 @@ function VeoPreviewModal({ videoUrl, taskId, onClose }: VeoPreviewModalProps) {
       });
 
       if (filePath) {
-        await writeFile(filePath, bytes);
+        await invoke("export_video", { sourcePath, targetPath: filePath });
       }
     } catch (error) {
       console.error("[VeoPreview] 下载失败:", error);
@@ -248,7 +248,8 @@ export function useImageGeneratorExecution(id: string, data: ImageGeneratorNodeD
                   id,
                   resolvedPrompt,
                   inputImagesMetadata.length > 0 ? inputImagesMetadata : undefined,
-                  "generated"
+                  "generated",
+                  response.generation
                 )
               )
             );
@@ -15,6 +15,22 @@ export interface InputImageInfo {
   label: string;
 }
 
+// 生成参数（字段与后端 GenerationParams 一致），生成变体时据此重建请求
+export interface GenerationParams {
+  provider?: string;
+  model?: string;
+  negative_prompt?: string;
+  seed?: number;
+  sampler?: string;
+  scheduler?: string;
+  steps?: number;
+  cfg_scale?: number;
+  width?: number;
+  height?: number;
+  denoising_strength?: number;
+  extra?: Record<string, unknown>;
+}
+
 // 图片元数据结构
 export interface ImageMetadata {
   prompt?: string;
@@ -22,6 +38,7 @@ export interface ImageMetadata {
   node_id?: string;
   canvas_id?: string;
   created_at: number;
+  generation?: GenerationParams;
 }
 
 // 图片信息类型
@@ -63,6 +80,7 @@ export interface CanvasImageStats {
  * @param prompt - 可选的生成提示词
  * @param inputImages - 可选的输入图片信息
  * @param imageType - 可选的图片类型（input/generated）
+ * @param generation - 可选的生成参数（服务、模型、种子等）
  * @returns 图片信息
  */
 export async function saveImage(
@@ -71,7 +89,8 @@ export async function saveImage(
   nodeId?: string,
   prompt?: string,
   inputImages?: InputImageInfo[],
-  imageType?: ImageType
+  imageType?: ImageType,
+  generation?: GenerationParams
 ): Promise<ImageInfo> {
   return await invoke<ImageInfo>("save_image", {
     base64Data,
@@ -80,6 +99,7 @@ export async function saveImage(
     prompt,
     inputImages,
     imageType,
+    generation,
   });
 }
 
@@ -13,6 +13,7 @@ import type {
   ImageGenerationCapability,
 } from "../types";
 import type { ErrorDetails } from "@/types";
+import type { GenerationParams } from "@/services/fileStorageService";
 
 // Tauri 后端响应类型
 interface TauriDalleResult {
@@ -20,6 +21,8 @@ interface TauriDalleResult {
   imageData?: string;
   imageUrl?: string;
   revisedPrompt?: string;
+  seed?: number;
+  generation?: GenerationParams;
   error?: string;
 }
 
@@ -194,8 +197,10 @@ export class DalleImageProvider implements ImageGenerationProvider {
       imageData: result.imageData,
       metadata: {
         model: request.model,
+        seed: result.seed,
         revisedPrompt: result.revisedPrompt,
       },
+      generation: result.generation,
     };
   }
 
@@ -13,6 +13,7 @@ import type {
   ImageGenerationCapability,
 } from "../types";
 import type { ErrorDetails } from "@/types";
+import type { GenerationParams } from "@/services/fileStorageService";
 
 // Tauri 后端响应类型（复用 DALL-E 的响应格式）
 interface TauriDalleResult {
@@ -20,6 +21,8 @@ interface TauriDalleResult {
   imageData?: string;
   imageUrl?: string;
   revisedPrompt?: string;
+  seed?: number;
+  generation?: GenerationParams;
   error?: string;
 }
 
@@ -171,7 +174,8 @@ export class FluxImageProvider implements ImageGenerationProvider {
 
     return {
       imageData: result.imageData,
-      metadata: { model: request.model },
+      metadata: { model: request.model, seed: result.seed },
+      generation: result.generation,
     };
   }
 
@@ -15,6 +15,7 @@ import type {
   ImageGenerationCapability,
 } from "../types";
 import type { ErrorDetails } from "@/types";
+import type { GenerationParams } from "@/services/fileStorageService";
 import { extractBase64ImageFromText, normalizeImageInput } from "@/services/imageDataUtils";
 
 // Tauri 后端响应类型
@@ -22,6 +23,8 @@ interface TauriGeminiResult {
   success: boolean;
   imageData?: string;
   text?: string;
+  seed?: number;
+  generation?: GenerationParams;
   error?: string;
 }
 
@@ -212,7 +215,9 @@ export class GeminiImageProvider implements ImageGenerationProvider {
       text: result.text,
       metadata: {
         model: request.model,
+        seed: result.seed,
       },
+      generation: result.generation,
     };
   }
 
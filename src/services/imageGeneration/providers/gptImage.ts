@@ -15,6 +15,7 @@ import type {
   ImageGenerationCapability,
 } from "../types";
 import type { ErrorDetails } from "@/types";
+import type { GenerationParams } from "@/services/fileStorageService";
 
 interface TauriOpenAIImageResult {
   success: boolean;
@@ -23,6 +24,8 @@ interface TauriOpenAIImageResult {
   imageUrl?: string;
   imageUrls?: string[];
   revisedPrompt?: string;
+  seed?: number;
+  generation?: GenerationParams;
   error?: string;
 }
 
@@ -236,8 +239,10 @@ export class GptImageProvider implements ImageGenerationProvider {
       imageDataList: result.imageDataList?.length ? result.imageDataList : [result.imageData],
       metadata: {
         model: request.model,
+        seed: result.seed,
         revisedPrompt: result.revisedPrompt,
       },
+      generation: result.generation,
     };
   }
 
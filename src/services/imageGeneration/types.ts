@@ -4,6 +4,7 @@
 
 import type { ProviderProtocol, ErrorDetails } from "@/types";
 import type { ImageApiProtocol } from "@/components/nodes/imageGeneratorConfig";
+import type { GenerationParams } from "@/services/fileStorageService";
 
 /**
  * 图片生成能力枚举
@@ -58,6 +59,7 @@ export interface ImageGenerationResponse {
     seed?: number;
     revisedPrompt?: string; // 修正后的提示词（DALL-E 特性）
   };
+  generation?: GenerationParams; // 后端返回的生成参数，保存图片时写入元数据
 }
 
 /**
@@ -1,5 +1,6 @@
 import { invoke } from "@tauri-apps/api/core";
 import type { ImageGenerationParams, ImageEditParams, GenerationResponse, ErrorDetails } from "@/types";
+import type { GenerationParams } from "@/services/fileStorageService";
 import { useSettingsStore } from "@/stores/settingsStore";
 import { extractBase64ImageFromText, normalizeImageInput } from "@/services/imageDataUtils";
 
@@ -43,6 +44,7 @@ interface TauriGeminiResult {
   success: boolean;
   imageData?: string;
   text?: string;
+  generation?: GenerationParams;
   error?: string;
 }
 
@@ -140,6 +142,7 @@ async function invokeGemini(
     return {
       imageData: result.imageData || fallbackImage || undefined,
       text: result.text,
+      generation: result.generation,
     };
   } catch (error) {
     console.error("[imageService] Tauri invoke error:", error);
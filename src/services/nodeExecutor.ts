@@ -503,7 +503,17 @@ async function executeImageGeneratorNode(
           ? response.imageDataList
           : [response.imageData];
         const savedImages = await Promise.all(
-          imagesToSave.map((imageData) => saveImage(imageData, canvasId, node.id))
+          imagesToSave.map((imageData) =>
+            saveImage(
+              imageData,
+              canvasId,
+              node.id,
+              resolvedPrompt,
+              undefined,
+              "generated",
+              response.generation
+            )
+          )
         );
         imagePaths = savedImages.map((image) => image.path);
         imagePath = imagePaths[0];
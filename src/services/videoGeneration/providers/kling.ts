@@ -3,7 +3,8 @@
  *
  * 支持 Kling API 格式的视频生成
  * - 文生视频（Text-to-Video）
- * - 图生视频（Image-to-Video）
+ * - 图生视频（Image-to-Video），可选尾帧、运动笔刷
+ * - 多图参考生视频（Multi-Image-to-Video）
  */
 
 import { invoke } from "@tauri-apps/api/core";
@@ -20,7 +21,7 @@ import type { ErrorDetails } from "@/types";
 export type KlingModel = "kling-v1" | "kling-v1-5" | string;
 
 // Kling 生成模式
-export type KlingMode = "text2video" | "image2video";
+export type KlingMode = "text2video" | "image2video" | "multi-image2video";
 
 // Kling 视频尺寸预设
 export interface KlingVideoSize {
@@ -38,11 +39,34 @@ export const KLING_VIDEO_SIZES: KlingVideoSize[] = [
   { width: 1024, height: 1024, label: "1024x1024 方形" },
 ];
 
+// Kling 镜头控制：simple 为自定义运镜（config 中只能设置一项，取值 -10 到 10），其余为预设
+export interface KlingCameraControl {
+  type: "simple" | "down_back" | "forward_up" | "right_turn_forward" | "left_turn_forward";
+  config?: {
+    horizontal?: number;
+    vertical?: number;
+    pan?: number;
+    tilt?: number;
+    roll?: number;
+    zoom?: number;
+  };
+}
+
+// Kling 运动笔刷的运动区域
+export interface KlingDynamicMask {
+  mask: string;  // base64 蒙版，与首帧尺寸一致
+  trajectories: { x: number; y: number }[];  // 至少 2 个点
+}
+
 // Kling metadata 扩展参数
 export interface KlingMetadata {
   negativePrompt?: string;
   style?: string;
   qualityLevel?: string;
+  cfgScale?: number;  // 0-1
+  cameraControl?: KlingCameraControl;  // 与尾帧、运动笔刷互斥
+  staticMask?: string;  // 运动笔刷：静止区域（仅图生视频）
+  dynamicMasks?: KlingDynamicMask[];  // 运动笔刷：运动区域（仅图生视频，最多 6 个）
 }
 
 // Kling 生成请求参数
@@ -51,6 +75,8 @@ export interface KlingGenerationRequest {
   model: KlingModel;
   mode: KlingMode;
   image?: string;  // base64 编码的图片或 URL（仅图生视频模式）
+  imageTail?: string;  // 尾帧图片（仅图生视频模式）
+  imageList?: string[];  // 参考图片，最多 4 张（仅多图参考模式）
   duration?: number;
   width?: number;
   height?: number;
@@ -68,6 +94,8 @@ interface TauriKlingCreateParams {
   prompt: string;
   mode: string;
   image?: string;
+  imageTail?: string;
+  imageList?: string[];
   duration?: number;
   width?: number;
   height?: number;
@@ -78,6 +106,10 @@ interface TauriKlingCreateParams {
     negative_prompt?: string;
     style?: string;
     quality_level?: string;
+    cfg_scale?: number;
+    camera_control?: KlingCameraControl;
+    static_mask?: string;
+    dynamic_masks?: KlingDynamicMask[];
   };
 }
 
@@ -90,6 +122,7 @@ interface TauriKlingStatusParams {
 
 interface TauriKlingDownloadParams {
   videoUrl: string;
+  metadata?: Record<string, unknown>;  // 创建任务时返回的 metadata，记录到保存的视频中
 }
 
 // Tauri 后端响应
@@ -108,9 +141,16 @@ interface TauriKlingContentResult {
   error?: string;
 }
 
+interface StoredVideoInfo {
+  id: string;
+  filename: string;
+  path: string; // 本地文件路径
+  size: number;
+}
+
 interface TauriKlingDownloadResult {
   success: boolean;
-  videoData?: string;  // base64
+  video?: StoredVideoInfo;  // 已下载到本地的视频
   error?: string;
 }
 
@@ -154,6 +194,12 @@ export class KlingVideoProvider implements VideoGenerationProvider {
       return { valid: false, error: "图生视频模式需要提供图片" };
     }
 
+    // 多图参考模式需要 1-4 张图片
+    const imageCount = klingRequest.imageList?.length ?? 0;
+    if (klingRequest.mode === "multi-image2video" && (imageCount === 0 || imageCount > 4)) {
+      return { valid: false, error: "多图参考模式需要 1 到 4 张图片" };
+    }
+
     return { valid: true };
   }
 
@@ -177,6 +223,12 @@ export class KlingVideoProvider implements VideoGenerationProvider {
     if (klingRequest.image) {
       params.image = klingRequest.image;
     }
+    if (klingRequest.imageTail) {
+      params.imageTail = klingRequest.imageTail;
+    }
+    if (klingRequest.imageList?.length) {
+      params.imageList = klingRequest.imageList;
+    }
 
     // 添加可选参数
     if (klingRequest.duration !== undefined) {
@@ -210,6 +262,18 @@ export class KlingVideoProvider implements VideoGenerationProvider {
       if (klingRequest.metadata.qualityLevel) {
         params.metadata.quality_level = klingRequest.metadata.qualityLevel;
       }
+      if (klingRequest.metadata.cfgScale !== undefined) {
+        params.metadata.cfg_scale = klingRequest.metadata.cfgScale;
+      }
+      if (klingRequest.metadata.cameraControl) {
+        params.metadata.camera_control = klingRequest.metadata.cameraControl;
+      }
+      if (klingRequest.metadata.staticMask) {
+        params.metadata.static_mask = klingRequest.metadata.staticMask;
+      }
+      if (klingRequest.metadata.dynamicMasks?.length) {
+        params.metadata.dynamic_masks = klingRequest.metadata.dynamicMasks;
+      }
     }
 
     return params;
@@ -309,8 +373,11 @@ export class KlingVideoProvider implements VideoGenerationProvider {
     return {
       prompt: request.prompt,
       model: request.model,
-      mode: maybeKling.mode || (image ? "image2video" : "text2video"),
+      mode: maybeKling.mode
+        || (image ? "image2video" : maybeKling.imageList?.length ? "multi-image2video" : "text2video"),
       image,
+      imageTail: maybeKling.imageTail,
+      imageList: maybeKling.imageList,
       duration: request.duration,
       width: request.width,
       height: request.height,
@@ -395,14 +462,16 @@ export class KlingVideoProvider implements VideoGenerationProvider {
   }
 
   /**
-   * 下载视频（从 URL 下载并转换为 base64）
+   * 下载视频（从 URL 下载并保存到本地）
    */
   async downloadVideo(
-    videoUrl: string
-  ): Promise<{ videoData?: string; error?: string }> {
+    videoUrl: string,
+    metadata?: Record<string, unknown>
+  ): Promise<{ videoPath?: string; error?: string }> {
     try {
       const tauriParams: TauriKlingDownloadParams = {
         videoUrl,
+        metadata,
       };
 
       console.log("[KlingProvider] Downloading video via Tauri backend...");
@@ -410,11 +479,11 @@ export class KlingVideoProvider implements VideoGenerationProvider {
         params: tauriParams,
       });
 
-      if (!result.success || !result.videoData) {
+      if (!result.success || !result.video) {
         return { error: result.error || "下载视频失败" };
       }
 
-      return { videoData: result.videoData };
+      return { videoPath: result.video.path };
     } catch (error) {
       const message =
         error instanceof Error ? error.message : "下载视频失败";
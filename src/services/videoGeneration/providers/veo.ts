@@ -80,9 +80,16 @@ interface TauriVeoTaskResult {
   error?: string;
 }
 
+interface StoredVideoInfo {
+  id: string;
+  filename: string;
+  path: string; // 本地文件路径
+  size: number;
+}
+
 interface TauriVeoContentResult {
   success: boolean;
-  videoData?: string; // base64
+  video?: StoredVideoInfo; // 已下载到本地的视频
   error?: string;
 }
 
@@ -324,7 +331,7 @@ export class VeoVideoProvider implements VideoGenerationProvider {
   async getVideoContent(
     taskId: string,
     config: VideoProviderConfig
-  ): Promise<{ videoData?: string; error?: string }> {
+  ): Promise<{ videoPath?: string; error?: string }> {
     try {
       const tauriParams: TauriVeoStatusParams = {
         baseUrl: config.baseUrl.replace(/\/+$/, ""),
@@ -337,11 +344,11 @@ export class VeoVideoProvider implements VideoGenerationProvider {
         params: tauriParams,
       });
 
-      if (!result.success || !result.videoData) {
+      if (!result.success || !result.video) {
         return { error: result.error || "获取视频失败" };
       }
 
-      return { videoData: result.videoData };
+      return { videoPath: result.video.path };
     } catch (error) {
       const message =
         error instanceof Error ? error.message : "获取视频内容失败";
@@ -158,7 +158,7 @@ export interface VideoGenerationProvider {
   getVideoContent(
     taskId: string,
     config: VideoProviderConfig
-  ): Promise<{ videoData?: string; videoUrl?: string; error?: string }>;
+  ): Promise<{ videoData?: string; videoUrl?: string; videoPath?: string; error?: string }>;
 
   /**
    * 验证请求参数
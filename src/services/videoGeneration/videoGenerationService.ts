@@ -2,6 +2,7 @@
  * 视频生成框架 - 统一服务入口
  */
 
+import { convertFileSrc, invoke } from "@tauri-apps/api/core";
 import { videoGenerationRegistry } from "./registry";
 import { newApiVideoProvider, soraVideoProvider, veoVideoProvider } from "./providers";
 import { useSettingsStore } from "@/stores/settingsStore";
@@ -163,6 +164,11 @@ export async function getVideoContentBlobUrl(
       return { url: result.videoUrl };
     }
 
+    // 后端已保存到本地，直接通过 asset 协议访问
+    if (result.videoPath) {
+      return { url: convertFileSrc(result.videoPath) };
+    }
+
     if (result.error || !result.videoData) {
       return { error: result.error || "获取视频失败" };
     }
@@ -213,6 +219,21 @@ export async function downloadVideo(
       return { success: true };
     }
 
+    // 后端已保存到本地，复制到用户选择的位置
+    if (result.videoPath) {
+      const { save } = await import("@tauri-apps/plugin-dialog");
+      const filePath = await save({
+        defaultPath: defaultFileName,
+        filters: [{ name: "视频", extensions: ["mp4", "webm", "mov"] }],
+      });
+      if (!filePath) {
+        return { success: false };
+      }
+      await invoke("export_video", { sourcePath: result.videoPath, targetPath: filePath });
+      toast.success(`视频已保存到: ${filePath.split("/").pop()}`);
+      return { success: true };
+    }
+
     if (result.error || !result.videoData) {
       const errorMsg = result.error || "下载视频失败";
       toast.error(`下载失败: ${errorMsg}`);
@@ -2,6 +2,7 @@ import type { Node, Edge } from "@xyflow/react";
 import type { ImageGeneratorNodeData } from "@/components/nodes/imageGeneratorConfig";
 import type { VideoGeneratorNodeData } from "@/components/nodes/videoGeneratorConfig";
 import type { LLMContentNodeData } from "@/components/nodes/llmContentConfig";
+import type { GenerationParams } from "@/services/fileStorageService";
 
 // 详细错误信息结构
 export interface ErrorDetails {
@@ -91,6 +92,7 @@ export interface GenerationResponse {
   text?: string;
   error?: string;
   errorDetails?: ErrorDetails;  // 详细错误信息
+  generation?: GenerationParams; // 生成参数，保存图片时写入元数据
 }
 
 // 节点数据类型 - 添加索引签名以满足 React Flow 的 Record<string, unknown> 约束